@@ -0,0 +1,104 @@
+use serde_json::Value;
+
+use crate::{BusType, Source};
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AuditEntry {
+  /// Milliseconds since the unix epoch
+  pub timestamp: u64,
+  /// Id from `consoles` of the desk the change was made on, or its address for tools pointed
+  /// straight at one. Empty in entries written before consoles were told apart.
+  #[serde(default)]
+  pub console: String,
+  pub source: Source,
+  pub bus: BusType,
+  pub index: u16,
+  pub parameter: String,
+  pub oldValue: Value,
+  pub newValue: Value,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct AuditQuery {
+  pub from: Option<u64>,
+  pub to: Option<u64>,
+  pub console: Option<String>,
+  pub source: Option<Source>,
+  pub bus: Option<BusType>,
+  pub index: Option<u16>,
+  pub parameter: Option<String>,
+  /// Only return the most recent `limit` matching entries
+  pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+  pub fn matches(&self, entry: &AuditEntry) -> bool {
+    if self.from.is_some_and(|from| entry.timestamp < from) { return false; }
+    if self.to.is_some_and(|to| entry.timestamp > to) { return false; }
+    if self.console.as_ref().is_some_and(|console| &entry.console != console) { return false; }
+    if self.source.is_some_and(|source| entry.source != source) { return false; }
+    if self.bus.is_some_and(|bus| entry.bus != bus) { return false; }
+    if self.index.is_some_and(|index| entry.index != index) { return false; }
+    if self.parameter.as_ref().is_some_and(|parameter| &entry.parameter != parameter) { return false; }
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn entry() -> AuditEntry {
+    AuditEntry {
+      timestamp: 1_000,
+      console: String::from("studio-a"),
+      source: Source::API,
+      bus: BusType::FADER,
+      index: 3,
+      parameter: String::from("level"),
+      oldValue: Value::from(0),
+      newValue: Value::from(800),
+    }
+  }
+
+  #[test]
+  fn empty_query_matches_everything() {
+    assert!(AuditQuery::default().matches(&entry()));
+  }
+
+  #[test]
+  fn time_range_is_inclusive() {
+    let query = |from, to| AuditQuery { from, to, ..AuditQuery::default() };
+    assert!(query(Some(1_000), Some(1_000)).matches(&entry()));
+    assert!(!query(Some(1_001), None).matches(&entry()));
+    assert!(!query(None, Some(999)).matches(&entry()));
+  }
+
+  #[test]
+  fn every_field_filters() {
+    let entry = entry();
+    let matching = AuditQuery {
+      from: Some(0),
+      to: Some(2_000),
+      console: Some(String::from("studio-a")),
+      source: Some(Source::API),
+      bus: Some(BusType::FADER),
+      index: Some(3),
+      parameter: Some(String::from("level")),
+      limit: None,
+    };
+    assert!(matching.matches(&entry));
+
+    let misses = [
+      AuditQuery { console: Some(String::from("studio-b")), ..matching.clone() },
+      AuditQuery { source: Some(Source::UI), ..matching.clone() },
+      AuditQuery { bus: Some(BusType::MAIN), ..matching.clone() },
+      AuditQuery { index: Some(4), ..matching.clone() },
+      AuditQuery { parameter: Some(String::from("cut")), ..matching.clone() },
+    ];
+    for query in misses {
+      assert!(!query.matches(&entry), "{:?}", query);
+    }
+  }
+}
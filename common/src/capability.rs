@@ -0,0 +1,61 @@
+/// Command bytes the client knows, shared by sets, queries and the reports answering them
+pub const FADER_LEVEL: u8 = 0x00;
+pub const FADER_CUT: u8 = 0x01;
+pub const MAIN_LEVEL: u8 = 0x02;
+pub const FADER_PFL: u8 = 0x05;
+pub const DESK_NAME: u8 = 0x07;
+pub const DESK_INFO: u8 = 0x08;
+pub const FADER_LABEL: u8 = 0x0B;
+pub const MAIN_PFL: u8 = 0x0C;
+pub const MAIN_LABEL: u8 = 0x0D;
+pub const AUX_AVAILABILITY: u8 = 0x10;
+pub const FADER_FORMAT: u8 = 0x11;
+pub const AUX_LEVEL: u8 = 0x13;
+pub const MAIN_AVAILABILITY: u8 = 0x14;
+pub const ROUTING: u8 = 0x16;
+
+/// How the console answered a query sent while probing
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum ProbeResult {
+  /// ACKed and answered with a report
+  REPLY,
+  /// ACKed without a report
+  ACK,
+  ERR,
+  /// Neither ACKed nor rejected in time
+  TIMEOUT,
+}
+
+impl ProbeResult {
+  pub fn is_supported(&self) -> bool {
+    matches!(self, ProbeResult::REPLY | ProbeResult::ACK)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Capability {
+  pub command: u8,
+  pub result: ProbeResult,
+}
+
+/// Short name for a command byte, for logs and the UI
+pub fn command_name(command: u8) -> String {
+  let name = match command {
+    FADER_LEVEL => "fader level",
+    FADER_CUT => "fader cut",
+    MAIN_LEVEL => "main level",
+    FADER_PFL => "fader PFL",
+    DESK_NAME => "desk name",
+    DESK_INFO => "desk info",
+    FADER_LABEL => "fader label",
+    MAIN_PFL => "main PFL",
+    MAIN_LABEL => "main label",
+    AUX_AVAILABILITY => "aux availability",
+    FADER_FORMAT => "fader format",
+    AUX_LEVEL => "aux level",
+    MAIN_AVAILABILITY => "main availability",
+    ROUTING => "routing",
+    _ => return format!("{:#04x}", command),
+  };
+  name.to_string()
+}
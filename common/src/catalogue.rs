@@ -0,0 +1,31 @@
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Coverage {
+  /// The client ignores the command
+  UNKNOWN,
+  /// The client decodes some of the command or is guessing at it
+  PARTIAL,
+}
+
+/// One distinct message seen for a catalogued command
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CatalogueSample {
+  /// Milliseconds since the unix epoch, when this payload was last seen
+  pub timestamp: u64,
+  pub flags: u8,
+  /// Bytes between the command byte and the checksum
+  pub payload: Vec<u8>,
+  /// Decoded frames on the console link just before it, oldest first
+  pub context: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CatalogueEntry {
+  pub command: u8,
+  pub coverage: Coverage,
+  /// What the client does know about the command
+  pub note: String,
+  pub count: u64,
+  pub firstSeen: u64,
+  pub lastSeen: u64,
+  pub samples: Vec<CatalogueSample>,
+}
@@ -0,0 +1,7 @@
+/// Payload of an event about one of several consoles, the console's own fields flattened next to its id
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct OnConsole<T> {
+  pub console: String,
+  #[serde(flatten)]
+  pub data: T,
+}
@@ -0,0 +1,32 @@
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Direction {
+  /// Read from the console
+  RX,
+  /// Written to the console
+  TX,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum FrameKind {
+  MSG,
+  ACK,
+  ERR,
+}
+
+/// A raw frame seen on the console link, as captured by the protocol inspector
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CapturedFrame {
+  /// Increases by one for every captured frame, also across clears
+  pub id: u64,
+  /// Milliseconds since the unix epoch
+  pub timestamp: u64,
+  pub direction: Direction,
+  pub kind: FrameKind,
+  pub bytes: Vec<u8>,
+  /// Command byte of a MSG frame
+  pub command: Option<u8>,
+  /// Human readable meaning of the frame
+  pub description: String,
+  /// For an ACK or ERR, the id of the written frame it answers
+  pub replyTo: Option<u64>,
+}
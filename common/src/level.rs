@@ -0,0 +1,53 @@
+//! Conversion between the console's raw 0-1023 fader level and dB.
+//!
+//! The law is a piecewise linear approximation of the desk's fader scale, good
+//! enough for remote control but not for metering.
+
+pub const MAX_LEVEL: u16 = 1023;
+
+/// Reported for a fully closed fader, and anything at or below it closes the fader
+pub const MIN_DB: f32 = -100.0;
+
+const LAW: [(u16, f32); 9] = [
+  (0, MIN_DB),
+  (96, -60.0),
+  (224, -40.0),
+  (352, -30.0),
+  (480, -20.0),
+  (608, -10.0),
+  (704, -5.0),
+  (800, 0.0),
+  (MAX_LEVEL, 10.0),
+];
+
+pub fn level_to_db(level: u16) -> f32 {
+  let level = level.min(MAX_LEVEL);
+
+  for pair in LAW.windows(2) {
+    let (lowLevel, lowDb) = pair[0];
+    let (highLevel, highDb) = pair[1];
+    if level <= highLevel {
+      let ratio = (level - lowLevel) as f32 / (highLevel - lowLevel) as f32;
+      return lowDb + ratio * (highDb - lowDb);
+    }
+  }
+
+  LAW[LAW.len() - 1].1
+}
+
+pub fn db_to_level(db: f32) -> u16 {
+  if db.is_nan() || db <= MIN_DB {
+    return 0;
+  }
+
+  for pair in LAW.windows(2) {
+    let (lowLevel, lowDb) = pair[0];
+    let (highLevel, highDb) = pair[1];
+    if db <= highDb {
+      let ratio = (db - lowDb) / (highDb - lowDb);
+      return (lowLevel as f32 + ratio * (highLevel - lowLevel) as f32).round() as u16;
+    }
+  }
+
+  MAX_LEVEL
+}
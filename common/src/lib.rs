@@ -4,6 +4,22 @@ use std::sync::Arc;
 use num_derive::FromPrimitive;
 use slab::Slab;
 
+pub mod audit;
+pub mod capability;
+pub mod catalogue;
+pub mod console;
+pub mod inspector;
+pub mod level;
+pub mod link;
+pub mod midi;
+pub mod onair;
+pub mod rules;
+pub mod scene;
+pub mod schedule;
+pub mod scripts;
+pub mod template;
+pub mod timecode;
+
 #[derive(Debug, Clone, Copy, PartialEq, FromPrimitive, serde::Serialize, serde::Deserialize)]
 pub enum AudioType {
   U,
@@ -40,11 +56,40 @@ pub struct Fader {
   pub isPfl: bool,
   pub pathType: AudioType,
   pub format: AudioWidth,
+  /// Whether the fader is routed to a main, `None` until the console reports routing
+  #[serde(default)]
+  pub isRouted: Option<bool>,
 }
 
 impl Fader {
   pub fn new(index: u16) -> Fader {
-    Fader { index, label: String::from(""), level: 0, isCut: false, isPfl: false, pathType: AudioType::U, format: AudioWidth::NP }
+    Fader { index, label: String::from(""), level: 0, isCut: false, isPfl: false, pathType: AudioType::U, format: AudioWidth::NP, isRouted: None }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Main {
+  pub index: u16,
+  pub label: String,
+  pub level: u16,
+  pub isPfl: bool,
+}
+
+impl Main {
+  pub fn new(index: u16) -> Main {
+    Main { index, label: String::from(""), level: 0, isPfl: false }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Aux {
+  pub index: u16,
+  pub level: u16,
+}
+
+impl Aux {
+  pub fn new(index: u16) -> Aux {
+    Aux { index, level: 0 }
   }
 }
 
@@ -54,11 +99,20 @@ pub struct DeskInfo {
   pub numFaders: u16,
   pub numMains: u16,
   pub name: String,
+  /// What the console answered to each query probed after connecting, empty until probed
+  #[serde(default)]
+  pub capabilities: Vec<capability::Capability>,
 }
 
 impl DeskInfo {
   pub fn default() -> DeskInfo {
-    DeskInfo { cscpVersion:  0, numFaders: 0, numMains: 0, name: String::new() }
+    DeskInfo { cscpVersion:  0, numFaders: 0, numMains: 0, name: String::new(), capabilities: vec![] }
+  }
+
+  /// Commands are assumed to be supported until probing shows otherwise
+  pub fn supports(&self, command: u8) -> bool {
+    !self.capabilities.iter()
+      .any(|capability| capability.command == command && !capability.result.is_supported())
   }
 }
 
@@ -66,6 +120,12 @@ impl DeskInfo {
 pub struct DB {
   pub deskInfo: DeskInfo,
   pub faders: Vec<Fader>,
+  #[serde(default)]
+  pub mains: Vec<Main>,
+  #[serde(default)]
+  pub auxes: Vec<Aux>,
+  #[serde(default)]
+  pub link: link::LinkStatus,
 }
 
 impl DB {
@@ -73,6 +133,25 @@ impl DB {
     DB {
       deskInfo: DeskInfo::default(),
       faders: vec![],
+      mains: vec![],
+      auxes: vec![],
+      link: link::LinkStatus::default(),
     }
   }
 }
+
+/// Where a change to the console state originated.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Source {
+  CONSOLE,
+  UI,
+  API,
+  AUTOMATION,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum BusType {
+  FADER,
+  MAIN,
+  AUX,
+}
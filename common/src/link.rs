@@ -0,0 +1,79 @@
+/// State of the connection to the console
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LinkStatus {
+  /// Endpoint in use, or last used while disconnected
+  pub endpoint: String,
+  /// Position of the endpoint in the configured list, 0 is the primary
+  pub index: usize,
+  pub connected: bool,
+  /// Milliseconds since the unix epoch at which `connected` last changed
+  pub since: u64,
+  /// Times the client moved to another endpoint
+  pub failovers: u32,
+  pub health: LinkHealth,
+  /// Heartbeats missed in a row
+  pub missed: u32,
+  pub roundTrip: RoundTrip,
+}
+
+/// How well the console answers heartbeats
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum LinkHealth {
+  /// Heartbeats are answered in time, or none has been sent yet
+  GOOD,
+  /// Some heartbeats in a row went unanswered
+  DEGRADED,
+  /// Too many heartbeats went unanswered and the link was dropped
+  LOST,
+}
+
+/// Round trip times of the latest heartbeats in milliseconds
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RoundTrip {
+  pub last: f32,
+  pub min: f32,
+  pub max: f32,
+  pub average: f32,
+  /// Heartbeats the figures are taken from
+  pub samples: u32,
+}
+
+impl RoundTrip {
+  pub fn from_samples(samples: &[f32]) -> RoundTrip {
+    if samples.is_empty() {
+      return RoundTrip::default();
+    }
+    RoundTrip {
+      last: samples[samples.len() - 1],
+      min: samples.iter().cloned().fold(f32::INFINITY, f32::min),
+      max: samples.iter().cloned().fold(0.0, f32::max),
+      average: samples.iter().sum::<f32>() / samples.len() as f32,
+      samples: samples.len() as u32,
+    }
+  }
+}
+
+impl Default for LinkStatus {
+  fn default() -> Self {
+    LinkStatus {
+      endpoint: String::new(),
+      index: 0,
+      connected: false,
+      since: 0,
+      failovers: 0,
+      health: LinkHealth::GOOD,
+      missed: 0,
+      roundTrip: RoundTrip::default(),
+    }
+  }
+}
+
+impl LinkStatus {
+  /// Human readable role of the endpoint, "primary" or "backup N"
+  pub fn role(&self) -> String {
+    match self.index {
+      0 => String::from("primary"),
+      index => format!("backup {}", index),
+    }
+  }
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+
+/// A control on a MIDI surface. Channels are 1-16.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type")]
+pub enum MidiControl {
+  /// A 7-bit control change
+  CC { channel: u8, controller: u8 },
+  /// A 14-bit control change, `controller` (0-31) carrying the high bits and `controller + 32` the low ones
+  CC14 { channel: u8, controller: u8 },
+  /// A 14-bit non-registered parameter (0-16383)
+  NRPN { channel: u8, parameter: u16 },
+  PITCH_BEND { channel: u8 },
+  /// A button sending note on when pressed and note off, or note on at 0, when released
+  NOTE { channel: u8, note: u8 },
+}
+
+impl MidiControl {
+  pub fn channel(&self) -> u8 {
+    match self {
+      MidiControl::CC { channel, .. }
+      | MidiControl::CC14 { channel, .. }
+      | MidiControl::NRPN { channel, .. }
+      | MidiControl::PITCH_BEND { channel }
+      | MidiControl::NOTE { channel, .. } => *channel,
+    }
+  }
+
+  /// Highest value the control sends
+  pub fn max_value(&self) -> u16 {
+    match self {
+      MidiControl::CC { .. } | MidiControl::NOTE { .. } => 127,
+      MidiControl::CC14 { .. } | MidiControl::NRPN { .. } | MidiControl::PITCH_BEND { .. } => 16383,
+    }
+  }
+}
+
+impl fmt::Display for MidiControl {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      MidiControl::CC { channel, controller } => write!(f, "CC {} ch {}", controller, channel),
+      MidiControl::CC14 { channel, controller } => write!(f, "CC {}/{} ch {}", controller, controller + 32, channel),
+      MidiControl::NRPN { channel, parameter } => write!(f, "NRPN {} ch {}", parameter, channel),
+      MidiControl::PITCH_BEND { channel } => write!(f, "pitch bend ch {}", channel),
+      MidiControl::NOTE { channel, note } => write!(f, "note {} ch {}", note, channel),
+    }
+  }
+}
+
+/// What a control moves. `fader` takes a number or a label selector, acting on every matching fader.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "target")]
+pub enum MidiTarget {
+  FADER_LEVEL { fader: String },
+  FADER_CUT { fader: String },
+  FADER_PFL { fader: String },
+}
+
+impl MidiTarget {
+  pub fn fader(&self) -> &str {
+    match self {
+      MidiTarget::FADER_LEVEL { fader } | MidiTarget::FADER_CUT { fader } | MidiTarget::FADER_PFL { fader } => fader,
+    }
+  }
+}
+
+impl fmt::Display for MidiTarget {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      MidiTarget::FADER_LEVEL { fader } => write!(f, "fader {} level", fader),
+      MidiTarget::FADER_CUT { fader } => write!(f, "fader {} cut", fader),
+      MidiTarget::FADER_PFL { fader } => write!(f, "fader {} PFL", fader),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MidiMapping {
+  pub control: MidiControl,
+  pub target: MidiTarget,
+  /// Cuts and PFLs follow the button while it is held instead of toggling on each press
+  #[serde(default)]
+  pub momentary: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MidiStatus {
+  /// Names of the open ports
+  pub input: Option<String>,
+  pub output: Option<String>,
+  /// Why a port isn't open
+  pub error: Option<String>,
+  /// What the next control moved will be mapped to
+  pub learning: Option<MidiTarget>,
+}
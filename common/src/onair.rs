@@ -0,0 +1,26 @@
+/// A period during which a fader was on air. `end` is `None` while it is still open.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct OnAirInterval {
+  pub index: u16,
+  pub label: String,
+  /// Milliseconds since the unix epoch
+  pub start: u64,
+  pub end: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct OnAirChannelReport {
+  pub index: u16,
+  pub label: String,
+  /// Total time on air within the report window, in milliseconds
+  pub duration: u64,
+  /// Number of times the fader went on air within the report window
+  pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct OnAirReport {
+  pub from: u64,
+  pub to: u64,
+  pub channels: Vec<OnAirChannelReport>,
+}
@@ -0,0 +1,91 @@
+/// "When `when` starts to hold for `holdFor` ms, do `then`, and `otherwise` once it stops"
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Rule {
+  pub name: String,
+  #[serde(default = "enabled")]
+  pub enabled: bool,
+  pub when: Condition,
+  /// How long the condition has to hold before the rule fires, in milliseconds
+  #[serde(default)]
+  pub holdFor: u64,
+  /// Shortest time between two firings of the rule, in milliseconds
+  #[serde(default = "cooldown")]
+  pub cooldown: u64,
+  pub then: Vec<RuleAction>,
+  #[serde(default)]
+  pub otherwise: Vec<RuleAction>,
+}
+
+fn enabled() -> bool {
+  true
+}
+
+fn cooldown() -> u64 {
+  500
+}
+
+/// State of one fader, main or aux. Set exactly one of `fader`, `main` and `aux`;
+/// `fader` takes a number or a label selector, and any matching fader counts.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Condition {
+  #[serde(default)]
+  pub fader: Option<String>,
+  #[serde(default)]
+  pub main: Option<u16>,
+  #[serde(default)]
+  pub aux: Option<u16>,
+  pub is: BusState,
+  /// Level in dB above which the bus counts as open, fully closed when left out
+  #[serde(default)]
+  pub db: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum BusState {
+  OPEN,
+  CLOSED,
+  CUT,
+  UNCUT,
+  PFL,
+  NO_PFL,
+}
+
+/// Fader numbers and label selectors act on every matching fader.
+/// Levels are `level` (0-1023) or `db`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "action")]
+pub enum RuleAction {
+  SET_FADER_LEVEL { fader: String, #[serde(default)] level: Option<u16>, #[serde(default)] db: Option<f32> },
+  SET_FADER_CUT { fader: String, isCut: bool },
+  SET_FADER_PFL { fader: String, isPfl: bool },
+  SET_MAIN_LEVEL { main: u16, #[serde(default)] level: Option<u16>, #[serde(default)] db: Option<f32> },
+  SET_MAIN_PFL { main: u16, isPfl: bool },
+  SET_AUX_LEVEL { aux: u16, #[serde(default)] level: Option<u16>, #[serde(default)] db: Option<f32> },
+  /// Shows `message` in the UI
+  ALERT { message: String },
+  /// Starts the script of that name from the scripts folder
+  RUN_SCRIPT { script: String },
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RuleStatus {
+  pub name: String,
+  pub enabled: bool,
+  /// Whether the condition holds now
+  pub active: bool,
+  /// Stopped after firing too often, until the rules are saved again
+  pub suspended: bool,
+  /// Why the rule can't run
+  pub error: Option<String>,
+  pub firings: u64,
+  /// Milliseconds since the unix epoch
+  pub lastFired: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RuleAlert {
+  pub rule: String,
+  pub message: String,
+  /// Milliseconds since the unix epoch
+  pub timestamp: u64,
+}
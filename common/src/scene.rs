@@ -0,0 +1,36 @@
+/// Levels and switches to apply to the desk. Anything left out is untouched, and
+/// extra fields are ignored so a saved `DB` can be recalled as a scene.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Scene {
+  pub faders: Vec<SceneFader>,
+  pub mains: Vec<SceneMain>,
+  pub auxes: Vec<SceneAux>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SceneFader {
+  pub index: u16,
+  #[serde(default)]
+  pub level: Option<u16>,
+  #[serde(default)]
+  pub isCut: Option<bool>,
+  #[serde(default)]
+  pub isPfl: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SceneMain {
+  pub index: u16,
+  #[serde(default)]
+  pub level: Option<u16>,
+  #[serde(default)]
+  pub isPfl: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SceneAux {
+  pub index: u16,
+  #[serde(default)]
+  pub level: Option<u16>,
+}
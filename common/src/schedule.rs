@@ -0,0 +1,84 @@
+/// Actions run at set times of day, in the machine's local time
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ScheduleEntry {
+  pub name: String,
+  #[serde(default = "enabled")]
+  pub enabled: bool,
+  pub when: When,
+  /// Run in order, each fade finishing before the next action
+  pub actions: Vec<ScheduleAction>,
+  /// What to do about runs missed while the app wasn't running
+  #[serde(default)]
+  pub missed: MissedPolicy,
+}
+
+fn enabled() -> bool {
+  true
+}
+
+/// Times are `HH:MM` or `HH:MM:SS`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type")]
+pub enum When {
+  /// Once, at `at` written as `2024-05-01T06:00`
+  ONCE { at: String },
+  /// Every day at `time`, or only on `days` when given
+  DAILY { time: String, #[serde(default)] days: Vec<Weekday> },
+  /// Every `minutes` minutes counted from midnight, between `from` and `to` when given
+  EVERY { minutes: u32, #[serde(default)] from: Option<String>, #[serde(default)] to: Option<String> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Weekday {
+  MON,
+  TUE,
+  WED,
+  THU,
+  FRI,
+  SAT,
+  SUN,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum MissedPolicy {
+  /// Runs missed while the app wasn't running are dropped
+  #[default]
+  SKIP,
+  /// The last missed run happens as soon as the app is back
+  RUN_LATEST,
+}
+
+/// Fader numbers and label selectors act on every matching fader.
+/// Levels are `level` (0-1023) or `db`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "action")]
+pub enum ScheduleAction {
+  /// Applies a scene file, relative paths are inside the app config dir
+  RECALL_SCENE { scene: String },
+  /// Moves faders to `db` over `duration` milliseconds
+  FADE { fader: String, db: f32, duration: u64 },
+  SET_FADER_LEVEL { fader: String, #[serde(default)] level: Option<u16>, #[serde(default)] db: Option<f32> },
+  SET_FADER_CUT { fader: String, isCut: bool },
+  /// Starts the script of that name from the scripts folder
+  RUN_SCRIPT { script: String },
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ScheduleStatus {
+  pub name: String,
+  pub enabled: bool,
+  /// Milliseconds since the unix epoch
+  pub next: Option<u64>,
+  pub lastRun: Option<u64>,
+  /// Why the entry can't run, or what went wrong the last time it did
+  pub error: Option<String>,
+}
+
+/// A run coming up and what it would do to the desk as it is now
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ScheduledRun {
+  pub name: String,
+  /// Milliseconds since the unix epoch
+  pub at: u64,
+  pub steps: Vec<String>,
+}
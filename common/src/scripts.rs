@@ -0,0 +1,32 @@
+/// A script in the scripts folder
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ScriptInfo {
+  /// File name without the `.rhai` extension
+  pub name: String,
+  /// Key that runs the script in the UI, e.g. `Ctrl+F5`
+  pub hotkey: Option<String>,
+  pub running: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum OutputKind {
+  STARTED,
+  /// From `print` and `debug`
+  PRINT,
+  /// From `alert`, shown prominently
+  ALERT,
+  ERROR,
+  FINISHED,
+}
+
+/// One line of output from a script run
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ScriptOutput {
+  /// Counts up from 1 for every run since the backend started
+  pub run: u64,
+  pub script: String,
+  pub kind: OutputKind,
+  pub message: String,
+  /// Milliseconds since the unix epoch
+  pub timestamp: u64,
+}
@@ -0,0 +1,150 @@
+use crate::{AudioType, AudioWidth, DB, level::{db_to_level, level_to_db}};
+
+/// How the desk should be set up for a show. Anything left out of a fader isn't checked.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ShowTemplate {
+  pub name: String,
+  pub faders: Vec<FaderTemplate>,
+  /// How far in dB a fader may be from its starting level
+  pub levelTolerance: f32,
+}
+
+impl Default for ShowTemplate {
+  fn default() -> Self {
+    Self { name: String::new(), faders: vec![], levelTolerance: 1.0 }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct FaderTemplate {
+  pub index: u16,
+  #[serde(default)]
+  pub label: Option<String>,
+  #[serde(default)]
+  pub pathType: Option<AudioType>,
+  #[serde(default)]
+  pub format: Option<AudioWidth>,
+  /// Whether the fader should be routed to a main
+  #[serde(default)]
+  pub isRouted: Option<bool>,
+  /// Starting level, 0-1023
+  #[serde(default)]
+  pub level: Option<u16>,
+  /// Starting level in dB, ignored when `level` is given
+  #[serde(default)]
+  pub db: Option<f32>,
+}
+
+impl FaderTemplate {
+  pub fn level(&self) -> Option<u16> {
+    self.level.or_else(|| self.db.map(db_to_level))
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum CheckResult {
+  PASS,
+  MISMATCH,
+  /// The console hasn't reported the fader
+  MISSING,
+  /// The console hasn't reported the value yet
+  UNKNOWN,
+}
+
+/// One expected value of one fader against the desk
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TemplateCheck {
+  pub index: u16,
+  /// Label on the desk, empty when the fader is missing
+  pub label: String,
+  pub field: String,
+  pub expected: String,
+  pub actual: String,
+  pub result: CheckResult,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TemplateReport {
+  pub name: String,
+  pub checks: Vec<TemplateCheck>,
+}
+
+impl TemplateReport {
+  pub fn count(&self, result: CheckResult) -> usize {
+    self.checks.iter().filter(|check| check.result == result).count()
+  }
+
+  /// Whether nothing on the desk differs from the template. Values the console
+  /// hasn't reported don't count against it.
+  pub fn passed(&self) -> bool {
+    self.count(CheckResult::MISMATCH) == 0 && self.count(CheckResult::MISSING) == 0
+  }
+}
+
+/// Compares the desk with the template, fader by fader in template order
+pub fn verify(template: &ShowTemplate, db: &DB) -> TemplateReport {
+  let mut checks = vec![];
+
+  for expected in template.faders.iter() {
+    let fader = match db.faders.iter().find(|fader| fader.index == expected.index) {
+      Some(fader) => fader,
+      None => {
+        checks.push(TemplateCheck {
+          index: expected.index,
+          label: String::new(),
+          field: String::from("fader"),
+          expected: String::from("present"),
+          actual: String::from("missing"),
+          result: CheckResult::MISSING,
+        });
+        continue;
+      }
+    };
+
+    let check = |field: &str, expected: String, actual: String, result: CheckResult| TemplateCheck {
+      index: fader.index,
+      label: fader.label.trim().to_string(),
+      field: String::from(field),
+      expected,
+      actual,
+      result,
+    };
+    let compare = |same: bool| if same { CheckResult::PASS } else { CheckResult::MISMATCH };
+
+    if let Some(label) = &expected.label {
+      checks.push(check("label", label.clone(), fader.label.trim().to_string(), compare(label.trim() == fader.label.trim())));
+    }
+    if let Some(pathType) = expected.pathType {
+      checks.push(check("pathType", format!("{:?}", pathType), format!("{:?}", fader.pathType), compare(pathType == fader.pathType)));
+    }
+    if let Some(format) = expected.format {
+      checks.push(check("format", format!("{:?}", format), format!("{:?}", fader.format), compare(format == fader.format)));
+    }
+    if let Some(isRouted) = expected.isRouted {
+      let result = match fader.isRouted {
+        Some(routed) => compare(routed == isRouted),
+        None => CheckResult::UNKNOWN,
+      };
+      checks.push(check("routed", routed_text(Some(isRouted)), routed_text(fader.isRouted), result));
+    }
+    if let Some(level) = expected.level() {
+      let same = (level_to_db(level) - level_to_db(fader.level)).abs() <= template.levelTolerance;
+      checks.push(check("level", level_text(level), level_text(fader.level), compare(same)));
+    }
+  }
+
+  TemplateReport { name: template.name.clone(), checks }
+}
+
+fn routed_text(isRouted: Option<bool>) -> String {
+  match isRouted {
+    Some(true) => String::from("routed"),
+    Some(false) => String::from("not routed"),
+    None => String::from("not reported"),
+  }
+}
+
+fn level_text(level: u16) -> String {
+  format!("{:.1} dB", level_to_db(level))
+}
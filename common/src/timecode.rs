@@ -0,0 +1,219 @@
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum FrameRate {
+  FPS_24,
+  #[default]
+  FPS_25,
+  /// 29.97 drop-frame, written with `;` before the frames
+  FPS_29_97_DF,
+  FPS_30,
+}
+
+impl FrameRate {
+  /// Frames counted per second in the timecode
+  pub fn nominal(&self) -> u32 {
+    match self {
+      FrameRate::FPS_24 => 24,
+      FrameRate::FPS_25 => 25,
+      FrameRate::FPS_29_97_DF | FrameRate::FPS_30 => 30,
+    }
+  }
+
+  /// Frames actually played per second
+  pub fn fps(&self) -> f64 {
+    match self {
+      FrameRate::FPS_29_97_DF => 30000.0 / 1001.0,
+      rate => rate.nominal() as f64,
+    }
+  }
+
+  pub fn is_drop(&self) -> bool {
+    matches!(self, FrameRate::FPS_29_97_DF)
+  }
+
+  /// Frames in 24 hours, where timecode wraps
+  pub fn frames_per_day(&self) -> u32 {
+    match self {
+      FrameRate::FPS_29_97_DF => 2_589_408,
+      rate => rate.nominal() * 86_400,
+    }
+  }
+}
+
+/// A position written `HH:MM:SS:FF`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Timecode {
+  pub hours: u8,
+  pub minutes: u8,
+  pub seconds: u8,
+  pub frames: u8,
+}
+
+impl Timecode {
+  pub fn new(hours: u8, minutes: u8, seconds: u8, frames: u8) -> Timecode {
+    Timecode { hours, minutes, seconds, frames }
+  }
+
+  /// Whether the position exists at `rate`. Drop-frame skips frames 0 and 1 of every minute but each tenth.
+  pub fn is_valid(&self, rate: FrameRate) -> bool {
+    let dropped = rate.is_drop() && self.seconds == 0 && self.frames < 2 && self.minutes % 10 != 0;
+    self.hours < 24 && self.minutes < 60 && self.seconds < 60 && (self.frames as u32) < rate.nominal() && !dropped
+  }
+
+  /// Frames since midnight
+  pub fn to_frames(&self, rate: FrameRate) -> u32 {
+    let nominal = rate.nominal();
+    let seconds = self.hours as u32 * 3600 + self.minutes as u32 * 60 + self.seconds as u32;
+    let frames = seconds * nominal + self.frames as u32;
+    if rate.is_drop() {
+      let minutes = self.hours as u32 * 60 + self.minutes as u32;
+      frames - 2 * (minutes - minutes / 10)
+    } else {
+      frames
+    }
+  }
+
+  /// The position `frames` after midnight, wrapping at 24 hours
+  pub fn from_frames(frames: u32, rate: FrameRate) -> Timecode {
+    let mut frames = frames % rate.frames_per_day();
+    if rate.is_drop() {
+      // Put back the frame numbers skipped before this one
+      let (tens, rest) = (frames / 17_982, frames % 17_982);
+      frames += 18 * tens + if rest < 2 { 0 } else { 2 * ((rest - 2) / 1798) };
+    }
+    let nominal = rate.nominal();
+    let seconds = frames / nominal;
+    Timecode {
+      hours: (seconds / 3600) as u8,
+      minutes: (seconds / 60 % 60) as u8,
+      seconds: (seconds % 60) as u8,
+      frames: (frames % nominal) as u8,
+    }
+  }
+
+  /// Milliseconds since midnight at the start of the frame
+  pub fn to_millis(&self, rate: FrameRate) -> f64 {
+    self.to_frames(rate) as f64 * 1000.0 / rate.fps()
+  }
+
+  /// The frame playing `millis` after midnight
+  pub fn from_millis(millis: f64, rate: FrameRate) -> Timecode {
+    Timecode::from_frames((millis.max(0.0) * rate.fps() / 1000.0 + 1e-6) as u32, rate)
+  }
+
+  /// Written as timecode at `rate`, with `;` before the frames for drop-frame
+  pub fn text(&self, rate: FrameRate) -> String {
+    let separator = if rate.is_drop() { ';' } else { ':' };
+    format!("{:02}:{:02}:{:02}{}{:02}", self.hours, self.minutes, self.seconds, separator, self.frames)
+  }
+}
+
+impl fmt::Display for Timecode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{:02}:{:02}:{:02}:{:02}", self.hours, self.minutes, self.seconds, self.frames)
+  }
+}
+
+impl FromStr for Timecode {
+  type Err = String;
+
+  /// `HH:MM:SS:FF`, with `;` or `.` allowed before the frames
+  fn from_str(text: &str) -> Result<Self, Self::Err> {
+    let bad = || format!("\"{}\" isn't timecode like 01:00:00:00", text);
+    let parts: Vec<u8> = text.trim()
+      .split([':', ';', '.'])
+      .map(|part| part.parse::<u8>().map_err(|_| bad()))
+      .collect::<Result<Vec<u8>, String>>()?;
+    match parts.as_slice() {
+      [hours, minutes, seconds, frames] => Ok(Timecode::new(*hours, *minutes, *seconds, *frames)),
+      _ => Err(bad()),
+    }
+  }
+}
+
+/// Where the timecode comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum TimecodeSource {
+  /// MIDI Timecode from a MIDI input
+  MTC,
+  /// Linear timecode decoded from an audio input or a WAV file
+  LTC,
+  /// A clock in the app, run from the UI
+  #[default]
+  LOCAL,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ChaseState {
+  /// No timecode coming in, cues wait where it stopped
+  STOPPED,
+  /// Following the incoming timecode
+  LOCKED,
+  /// Timecode dropped out, carrying on at the same speed for a while
+  FREEWHEEL,
+}
+
+/// A cue fired when the timecode reaches `at`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TimecodeCue {
+  pub name: String,
+  #[serde(default = "enabled")]
+  pub enabled: bool,
+  /// Timecode at the configured frame rate
+  pub at: String,
+  pub actions: Vec<CueAction>,
+}
+
+fn enabled() -> bool {
+  true
+}
+
+/// Fader numbers and label selectors act on every matching fader.
+/// Levels are `level` (0-1023) or `db`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "action")]
+pub enum CueAction {
+  /// Applies a scene file, relative paths are inside the app config dir
+  RECALL_SCENE { scene: String },
+  /// Moves faders to `db` over `duration` milliseconds of programme time, from `from` dB
+  /// or wherever they are when the cue fires
+  FADE { fader: String, db: f32, duration: u64, #[serde(default)] from: Option<f32> },
+  SET_FADER_LEVEL { fader: String, #[serde(default)] level: Option<u16>, #[serde(default)] db: Option<f32> },
+  SET_FADER_CUT { fader: String, isCut: bool },
+  /// Starts the script of that name from the scripts folder. Not run again when chasing to a later position.
+  RUN_SCRIPT { script: String },
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CueStatus {
+  pub name: String,
+  pub at: String,
+  pub enabled: bool,
+  /// Whether the timecode is past it
+  pub passed: bool,
+  /// Why the cue can't run, or what went wrong the last time it did
+  pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TimecodeStatus {
+  pub source: TimecodeSource,
+  pub rate: FrameRate,
+  pub state: ChaseState,
+  /// Where the cues stand, as timecode at `rate`
+  pub position: Option<String>,
+  /// Why the source isn't giving timecode
+  pub error: Option<String>,
+  pub cues: Vec<CueStatus>,
+}
+
+/// Moves the LOCAL clock
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type")]
+pub enum Transport {
+  PLAY,
+  STOP,
+  /// Jumps to `position`, written as timecode
+  LOCATE { position: String },
+}
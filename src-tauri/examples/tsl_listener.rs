@@ -0,0 +1,94 @@
+//! Prints TSL UMD messages received on 127.0.0.1:8900 over UDP and TCP, for
+//! checking the TSL output locally.
+//!
+//! cargo run --example tsl_listener
+use tokio::{io::AsyncReadExt, net::{TcpListener, UdpSocket}};
+
+const ADDRESS: &str = "127.0.0.1:8900";
+
+fn print_packet(packet: &[u8]) {
+  if packet.len() == 18 && packet[0] & 0x80 != 0 {
+    // v3.1
+    let address = packet[0] - 0x80;
+    let tally = packet[1] & 0x0F;
+    let brightness = packet[1] >> 4 & 0x03;
+    let text = String::from_utf8_lossy(&packet[2..]);
+    println!("v3.1 address={} tally={:04b} brightness={} text={:?}", address, tally, brightness, text);
+    return;
+  }
+
+  if packet.len() >= 12 {
+    // v5.0
+    let flags = packet[3];
+    let screen = u16::from_le_bytes([packet[4], packet[5]]);
+    let index = u16::from_le_bytes([packet[6], packet[7]]);
+    let control = u16::from_le_bytes([packet[8], packet[9]]);
+    let length = u16::from_le_bytes([packet[10], packet[11]]) as usize;
+    let bytes = &packet[12..(12 + length).min(packet.len())];
+    let text = if flags & 0x01 != 0 {
+      let chars: Vec<u16> = bytes.chunks(2).map(|c| u16::from_le_bytes([c[0], *c.get(1).unwrap_or(&0)])).collect();
+      String::from_utf16_lossy(&chars)
+    } else {
+      String::from_utf8_lossy(bytes).to_string()
+    };
+    println!(
+      "v5.0 screen={} index={} rh={} text_tally={} lh={} brightness={} text={:?}",
+      screen, index, control & 0x03, control >> 2 & 0x03, control >> 4 & 0x03, control >> 6 & 0x03, text
+    );
+    return;
+  }
+
+  println!("Unknown packet {:?}", packet);
+}
+
+/// Strips the DLE/STX framing used by v5.0 over TCP
+fn unwrap_tcp(buffer: &[u8]) -> Vec<Vec<u8>> {
+  let mut packets = vec![];
+  let mut current: Option<Vec<u8>> = None;
+  let mut i = 0;
+  while i < buffer.len() {
+    if buffer[i] == 0xFE && buffer.get(i + 1) == Some(&0x02) {
+      if let Some(packet) = current.take() { packets.push(packet); }
+      current = Some(vec![]);
+      i += 2;
+      continue;
+    }
+    if buffer[i] == 0xFE && buffer.get(i + 1) == Some(&0xFE) {
+      i += 1;
+    }
+    if let Some(packet) = current.as_mut() { packet.push(buffer[i]); }
+    i += 1;
+  }
+  if let Some(packet) = current { packets.push(packet); }
+  packets
+}
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+  let socket = UdpSocket::bind(ADDRESS).await?;
+  let listener = TcpListener::bind(ADDRESS).await?;
+  println!("Listening for TSL on {} (UDP and TCP)", ADDRESS);
+
+  tokio::spawn(async move {
+    loop {
+      let (mut stream, peer) = listener.accept().await.unwrap();
+      println!("TCP connection from {}", peer);
+      tokio::spawn(async move {
+        let mut buffer = [0u8; 1024];
+        loop {
+          match stream.read(&mut buffer).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) if buffer[0] == 0xFE => unwrap_tcp(&buffer[..n]).iter().for_each(|packet| print_packet(packet)),
+            Ok(n) => buffer[..n].chunks(18).for_each(print_packet),
+          }
+        }
+      });
+    }
+  });
+
+  let mut buffer = [0u8; 2048];
+  loop {
+    let (n, _) = socket.recv_from(&mut buffer).await?;
+    print_packet(&buffer[..n]);
+  }
+}
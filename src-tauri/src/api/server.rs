@@ -0,0 +1,251 @@
+#![allow(non_snake_case)]
+use std::net::SocketAddr;
+use axum::{
+  extract::{ws::{Message as WsMessage, WebSocket, WebSocketUpgrade}, Path, Query, State},
+  http::StatusCode,
+  response::Response,
+  routing::get,
+  Json, Router,
+};
+use common::{Aux, Fader, Main, Source, DB, link::LinkStatus, capability::command_name, level::{db_to_level, MAX_LEVEL}};
+use tokio::sync::{broadcast, mpsc};
+
+use crate::{config::HttpConfig, cscp::{
+  events::ConsoleEvent,
+  requests::{get_db, get_link, Request, SetAuxLevel, SetFaderCut, SetFaderLevel, SetFaderPfl, SetMainLevel, SetMainPfl},
+  selector::{FaderRef, FaderSelector, SelectError},
+}};
+
+type ApiResult<T> = Result<T, (StatusCode, String)>;
+
+#[derive(Clone)]
+struct ApiState {
+  input_tx: mpsc::Sender<Request>,
+  event_tx: broadcast::Sender<ConsoleEvent>,
+}
+
+/// Any combination of fields can be set at once. `db` is ignored when `level` is given, and `level`
+/// is clamped to 1023.
+#[derive(Debug, serde::Deserialize)]
+struct Update {
+  level: Option<u16>,
+  db: Option<f32>,
+  isCut: Option<bool>,
+  isPfl: Option<bool>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct FaderFilter {
+  label: Option<String>,
+}
+
+impl Update {
+  fn level(&self) -> Option<u16> {
+    self.level.map(|level| level.min(MAX_LEVEL)).or_else(|| self.db.map(db_to_level))
+  }
+}
+
+/// REST and WebSocket API. Fader, main and aux numbers in paths start at 1.
+/// Faders can also be addressed by label, see `FaderSelector`.
+///
+/// - `GET /api/db`
+/// - `GET /api/faders`, `GET /api/faders?label={selector}`, `GET`/`PUT /api/faders/{n or selector}`
+/// - `GET /api/mains`, `GET`/`PUT /api/mains/{n}`
+/// - `GET /api/auxes`, `GET`/`PUT /api/auxes/{n}`
+/// - `GET /api/events` upgrades to a WebSocket streaming every console change as JSON
+pub async fn run(config: HttpConfig, input_tx: mpsc::Sender<Request>, event_tx: broadcast::Sender<ConsoleEvent>) -> Result<(), String> {
+  let address: SocketAddr = config.bind.parse().map_err(|e| format!("Bad HTTP bind address {} :: {}", config.bind, e))?;
+
+  let app = Router::new()
+    .route("/api/db", get(get_database))
+    .route("/api/link", get(get_link_status))
+    .route("/api/faders", get(list_faders))
+    .route("/api/faders/:fader", get(get_fader).put(put_fader))
+    .route("/api/mains", get(list_mains))
+    .route("/api/mains/:number", get(get_main).put(put_main))
+    .route("/api/auxes", get(list_auxes))
+    .route("/api/auxes/:number", get(get_aux).put(put_aux))
+    .route("/api/events", get(events))
+    .with_state(ApiState { input_tx, event_tx });
+
+  eprintln!("HTTP :: listening on {}", address);
+  axum::Server::bind(&address)
+    .serve(app.into_make_service())
+    .await
+    .map_err(|e| e.to_string())
+}
+
+async fn load_db(state: &ApiState) -> ApiResult<DB> {
+  get_db(&state.input_tx).await.map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e))
+}
+
+async fn send(state: &ApiState, requests: Vec<Request>) -> ApiResult<StatusCode> {
+  if requests.is_empty() {
+    return Err((StatusCode::BAD_REQUEST, String::from("Nothing to update")));
+  }
+
+  let deskInfo = load_db(state).await?.deskInfo;
+  if let Some(command) = requests.iter().filter_map(Request::command).find(|command| !deskInfo.supports(*command)) {
+    return Err((StatusCode::NOT_IMPLEMENTED, format!("The console doesn't support {}", command_name(command))));
+  }
+
+  for request in requests {
+    state.input_tx.send(request).await.map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e.to_string()))?;
+  }
+
+  // Changes are applied once the console reports them back
+  Ok(StatusCode::ACCEPTED)
+}
+
+fn to_index(number: u16) -> ApiResult<u16> {
+  number.checked_sub(1).ok_or((StatusCode::BAD_REQUEST, String::from("Numbers start at 1")))
+}
+
+fn not_found(kind: &str, number: u16) -> (StatusCode, String) {
+  (StatusCode::NOT_FOUND, format!("No {} {}", kind, number))
+}
+
+fn select_error(error: SelectError) -> (StatusCode, String) {
+  let status = match error {
+    SelectError::INVALID(_) => StatusCode::BAD_REQUEST,
+    SelectError::NOT_FOUND(_) => StatusCode::NOT_FOUND,
+    SelectError::AMBIGUOUS(..) => StatusCode::CONFLICT,
+  };
+  (status, error.to_string())
+}
+
+async fn get_database(State(state): State<ApiState>) -> ApiResult<Json<DB>> {
+  Ok(Json(load_db(&state).await?))
+}
+
+async fn get_link_status(State(state): State<ApiState>) -> ApiResult<Json<LinkStatus>> {
+  get_link(&state.input_tx).await.map(Json).map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e))
+}
+
+async fn list_faders(State(state): State<ApiState>, Query(filter): Query<FaderFilter>) -> ApiResult<Json<Vec<Fader>>> {
+  let faders = load_db(&state).await?.faders;
+  match filter.label {
+    Some(label) => {
+      let selector: FaderSelector = label.parse().map_err(select_error)?;
+      Ok(Json(selector.select(&faders).into_iter().cloned().collect()))
+    }
+    None => Ok(Json(faders)),
+  }
+}
+
+/// Labels are resolved against the faders as the console reports them now
+async fn get_fader(State(state): State<ApiState>, Path(fader): Path<String>) -> ApiResult<Json<Fader>> {
+  let fader: FaderRef = fader.parse().map_err(select_error)?;
+  let faders = load_db(&state).await?.faders;
+  let index = fader.index(&faders).map_err(select_error)?;
+  faders.into_iter()
+    .find(|fader| fader.index == index)
+    .map(Json)
+    .ok_or_else(|| not_found("fader", index + 1))
+}
+
+async fn put_fader(State(state): State<ApiState>, Path(fader): Path<String>, Json(update): Json<Update>) -> ApiResult<StatusCode> {
+  let index = get_fader(State(state.clone()), Path(fader)).await?.index;
+  let source = Source::API;
+
+  let mut requests = vec![];
+  if let Some(level) = update.level() {
+    requests.push(Request::SET_FADER_LEVEL(SetFaderLevel { index, level, source }));
+  }
+  if let Some(isCut) = update.isCut {
+    requests.push(Request::SET_FADER_CUT(SetFaderCut { index, isCut, source }));
+  }
+  if let Some(isPfl) = update.isPfl {
+    requests.push(Request::SET_FADER_PFL(SetFaderPfl { index, isPfl, source }));
+  }
+
+  send(&state, requests).await
+}
+
+async fn list_mains(State(state): State<ApiState>) -> ApiResult<Json<Vec<Main>>> {
+  Ok(Json(load_db(&state).await?.mains))
+}
+
+async fn get_main(State(state): State<ApiState>, Path(number): Path<u16>) -> ApiResult<Json<Main>> {
+  let index = to_index(number)?;
+  load_db(&state).await?.mains.into_iter()
+    .find(|main| main.index == index)
+    .map(Json)
+    .ok_or_else(|| not_found("main", number))
+}
+
+async fn put_main(State(state): State<ApiState>, Path(number): Path<u16>, Json(update): Json<Update>) -> ApiResult<StatusCode> {
+  let index = get_main(State(state.clone()), Path(number)).await?.index;
+  let source = Source::API;
+
+  if update.isCut.is_some() {
+    return Err((StatusCode::BAD_REQUEST, String::from("Mains can't be cut")));
+  }
+
+  let mut requests = vec![];
+  if let Some(level) = update.level() {
+    requests.push(Request::SET_MAIN_LEVEL(SetMainLevel { index, level, source }));
+  }
+  if let Some(isPfl) = update.isPfl {
+    requests.push(Request::SET_MAIN_PFL(SetMainPfl { index, isPfl, source }));
+  }
+
+  send(&state, requests).await
+}
+
+async fn list_auxes(State(state): State<ApiState>) -> ApiResult<Json<Vec<Aux>>> {
+  Ok(Json(load_db(&state).await?.auxes))
+}
+
+async fn get_aux(State(state): State<ApiState>, Path(number): Path<u16>) -> ApiResult<Json<Aux>> {
+  let index = to_index(number)?;
+  load_db(&state).await?.auxes.into_iter()
+    .find(|aux| aux.index == index)
+    .map(Json)
+    .ok_or_else(|| not_found("aux", number))
+}
+
+async fn put_aux(State(state): State<ApiState>, Path(number): Path<u16>, Json(update): Json<Update>) -> ApiResult<StatusCode> {
+  let index = get_aux(State(state.clone()), Path(number)).await?.index;
+
+  if update.isCut.is_some() || update.isPfl.is_some() {
+    return Err((StatusCode::BAD_REQUEST, String::from("Auxes only have a level")));
+  }
+
+  let mut requests = vec![];
+  if let Some(level) = update.level() {
+    requests.push(Request::SET_AUX_LEVEL(SetAuxLevel { index, level, source: Source::API }));
+  }
+
+  send(&state, requests).await
+}
+
+async fn events(ws: WebSocketUpgrade, State(state): State<ApiState>) -> Response {
+  let event_rx = state.event_tx.subscribe();
+  ws.on_upgrade(move |socket| stream_events(socket, event_rx))
+}
+
+async fn stream_events(mut socket: WebSocket, mut event_rx: broadcast::Receiver<ConsoleEvent>) {
+  loop {
+    tokio::select! {
+      event = event_rx.recv() => {
+        let event = match event {
+          Ok(event) => event,
+          Err(broadcast::error::RecvError::Lagged(_)) => continue,
+          Err(broadcast::error::RecvError::Closed) => break,
+        };
+        let text = serde_json::to_string(&event).unwrap();
+        if socket.send(WsMessage::Text(text)).await.is_err() {
+          break;
+        }
+      }
+      received = socket.recv() => {
+        // Nothing is expected from clients, only watch for them going away
+        match received {
+          Some(Ok(WsMessage::Close(_))) | Some(Err(_)) | None => break,
+          Some(Ok(_)) => {}
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,33 @@
+use std::fs;
+use common::audit::{AuditEntry, AuditQuery};
+
+use super::log::{AuditLogStorage, to_csv};
+
+pub struct AuditLogState {
+  pub inner: AuditLogStorage,
+}
+
+#[tauri::command]
+pub async fn queryAuditLog(
+    query: AuditQuery,
+    state: tauri::State<'_, AuditLogState>,
+) -> Result<Vec<AuditEntry>, String> {
+    eprintln!("queryAuditLog {:?}", query);
+    let audit = state.inner.lock().await;
+    audit.query(&query).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn exportAuditLog(
+    query: AuditQuery,
+    path: String,
+    state: tauri::State<'_, AuditLogState>,
+) -> Result<(), String> {
+    eprintln!("exportAuditLog path={} {:?}", path, query);
+    let entries = {
+      let audit = state.inner.lock().await;
+      audit.query(&query).map_err(|e| e.to_string())?
+    };
+
+    fs::write(path, to_csv(&entries)).map_err(|e| e.to_string())
+}
@@ -0,0 +1,181 @@
+#![allow(non_snake_case)]
+use std::{fs::{self, File, OpenOptions}, io::{self, Write}, path::PathBuf, sync::Arc};
+use common::{audit::{AuditEntry, AuditQuery}, BusType, Source};
+use futures_util::lock::Mutex;
+use serde_json::Value;
+
+use crate::{clock::now_millis, csv};
+
+pub type AuditLogStorage = Arc<Mutex<AuditLog>>;
+
+/// The audit log as one console's client writes to it, every entry tagged with the console
+#[derive(Clone)]
+pub struct ConsoleAudit {
+  pub log: AuditLogStorage,
+  pub console: String,
+}
+
+impl ConsoleAudit {
+  pub fn new(log: AuditLogStorage, console: &str) -> ConsoleAudit {
+    ConsoleAudit { log, console: console.to_string() }
+  }
+}
+
+/// Append-only JSON-lines log of console changes, rotated by size.
+///
+/// The current file is `audit.jsonl`, older files are `audit.1.jsonl` (newest)
+/// up to `audit.{maxFiles}.jsonl` (oldest).
+pub struct AuditLog {
+  directory: PathBuf,
+  maxFileSize: u64,
+  maxFiles: usize,
+  file: Option<File>,
+  enabled: bool,
+}
+
+impl AuditLog {
+  pub fn new(directory: PathBuf, maxFileSize: u64, maxFiles: usize) -> AuditLog {
+    AuditLog { directory, maxFileSize, maxFiles, file: None, enabled: true }
+  }
+
+  /// A log that drops every entry, for tools that shouldn't leave files behind
+  pub fn disabled() -> AuditLog {
+    AuditLog { directory: PathBuf::new(), maxFileSize: 0, maxFiles: 0, file: None, enabled: false }
+  }
+
+  pub fn append(&mut self, entry: &AuditEntry) -> io::Result<()> {
+    if !self.enabled {
+      return Ok(());
+    }
+
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+
+    self.rotate_if_needed(line.len() as u64)?;
+
+    if self.file.is_none() {
+      fs::create_dir_all(&self.directory)?;
+      self.file = Some(OpenOptions::new().create(true).append(true).open(self.current_path())?);
+    }
+
+    let file = self.file.as_mut().unwrap();
+    file.write_all(line.as_bytes())?;
+    file.flush()
+  }
+
+  /// Matching entries across all files, oldest first
+  pub fn query(&self, query: &AuditQuery) -> io::Result<Vec<AuditEntry>> {
+    if !self.enabled {
+      return Ok(vec![]);
+    }
+
+    let mut paths: Vec<PathBuf> = (1..=self.maxFiles).rev().map(|n| self.rotated_path(n)).collect();
+    paths.push(self.current_path());
+
+    let mut entries = vec![];
+    for path in paths {
+      let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+        Err(e) => return Err(e),
+      };
+
+      for line in contents.lines() {
+        match serde_json::from_str::<AuditEntry>(line) {
+          Ok(entry) if query.matches(&entry) => entries.push(entry),
+          Ok(_) => {}
+          Err(e) => eprintln!("Skipping bad audit line in {} :: {}", path.display(), e),
+        }
+      }
+    }
+
+    if let Some(limit) = query.limit {
+      let skip = entries.len().saturating_sub(limit);
+      entries.drain(..skip);
+    }
+
+    Ok(entries)
+  }
+
+  fn rotate_if_needed(&mut self, incoming: u64) -> io::Result<()> {
+    let size = fs::metadata(self.current_path()).map(|meta| meta.len()).unwrap_or(0);
+    if size == 0 || size + incoming <= self.maxFileSize {
+      return Ok(());
+    }
+
+    self.file = None;
+
+    if self.maxFiles == 0 {
+      return fs::remove_file(self.current_path());
+    }
+
+    let _ = fs::remove_file(self.rotated_path(self.maxFiles));
+    for n in (1..self.maxFiles).rev() {
+      let from = self.rotated_path(n);
+      if from.exists() {
+        fs::rename(from, self.rotated_path(n + 1))?;
+      }
+    }
+    fs::rename(self.current_path(), self.rotated_path(1))
+  }
+
+  fn current_path(&self) -> PathBuf {
+    self.directory.join("audit.jsonl")
+  }
+
+  fn rotated_path(&self, n: usize) -> PathBuf {
+    self.directory.join(format!("audit.{}.jsonl", n))
+  }
+}
+
+pub async fn record<O: Into<Value>, N: Into<Value>>(
+  audit: &ConsoleAudit,
+  source: Source,
+  bus: BusType,
+  index: u16,
+  parameter: &str,
+  oldValue: O,
+  newValue: N,
+) {
+  let entry = AuditEntry {
+    timestamp: now_millis(),
+    console: audit.console.clone(),
+    source,
+    bus,
+    index,
+    parameter: parameter.to_string(),
+    oldValue: oldValue.into(),
+    newValue: newValue.into(),
+  };
+
+  if let Err(e) = audit.log.lock().await.append(&entry) {
+    eprintln!("Failed to write audit entry {:?} :: {}", entry, e);
+  }
+}
+
+pub fn to_csv(entries: &[AuditEntry]) -> String {
+  let mut contents = String::from("timestamp,console,source,bus,index,parameter,oldValue,newValue\n");
+
+  for entry in entries {
+    contents.push_str(&csv::row(&[
+      entry.timestamp.to_string(),
+      entry.console.clone(),
+      format!("{:?}", entry.source),
+      format!("{:?}", entry.bus),
+      entry.index.to_string(),
+      entry.parameter.clone(),
+      csv_value(&entry.oldValue),
+      csv_value(&entry.newValue),
+    ]));
+  }
+
+  contents
+}
+
+fn csv_value(value: &Value) -> String {
+  match value {
+    Value::Null => String::new(),
+    Value::String(value) => value.clone(),
+    value => value.to_string(),
+  }
+}
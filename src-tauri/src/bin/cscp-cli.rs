@@ -0,0 +1,558 @@
+#![allow(non_snake_case, non_camel_case_types)]
+//! Headless CSCP client for scripting the desk without the Tauri app.
+//!
+//! Exit codes: 0 when the console ACKs (or the query succeeds), 1 when it NAKs,
+//! 2 for bad usage or a label matching several faders, 3 when the console can't be reached
+//! or doesn't answer in time, 4 when the fader, main or aux doesn't exist and 5 when the desk
+//! doesn't match a show template.
+//!
+//! Faders can be given by number or by label, see `FaderSelector`.
+
+use std::{path::PathBuf, process, sync::Arc, time::Duration};
+use clap::{Parser, Subcommand, ValueEnum};
+use common::{Aux, DB, DeskInfo, Fader, Main, Source, capability::command_name, link::LinkStatus, level::{db_to_level, level_to_db, MAX_LEVEL}, scene::Scene, template::{verify, CheckResult, TemplateCheck, TemplateReport}};
+use futures_util::lock::Mutex;
+use tokio::{sync::{broadcast, mpsc}, task::JoinHandle, time::{self, Instant}};
+
+use rust_cscp_controller::{
+  audit::log::{AuditLog, ConsoleAudit},
+  config::Config,
+  cscp::{
+    client::CSCPClient,
+    events::ConsoleEvent,
+    frame::Frame,
+    requests::{confirm, get_db, Request, NOT_CONNECTED, UNSUPPORTED, SetFaderCut, SetFaderLevel, SetFaderPfl, SetMainLevel, SetMainPfl, SetAuxLevel},
+    selector::{FaderRef, SelectError},
+  },
+  scene,
+  session::{file, recorder::record, replay},
+  template,
+};
+
+const EXIT_OK: i32 = 0;
+const EXIT_NAK: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_CONNECTION: i32 = 3;
+const EXIT_NOT_FOUND: i32 = 4;
+const EXIT_MISMATCH: i32 = 5;
+
+/// How long the console has to stay quiet before its state is considered complete
+const SETTLE: Duration = Duration::from_millis(200);
+
+#[derive(Parser)]
+#[command(name = "cscp-cli", about = "Control a CSCP console from the command line")]
+struct Cli {
+  /// Console address as `ip:port`, defaults to the one in the config file
+  #[arg(long, short)]
+  address: Option<String>,
+  /// App config file to take the console address and audit log directory from
+  #[arg(long, short)]
+  config: Option<PathBuf>,
+  /// Console from the config's `consoles` list, defaults to the first one
+  #[arg(long, conflicts_with = "address")]
+  console: Option<String>,
+  /// Seconds to wait for the console to connect and answer
+  #[arg(long, default_value_t = 5)]
+  timeout: u64,
+  /// Print JSON instead of text
+  #[arg(long)]
+  json: bool,
+  /// Record the session to a capture file
+  #[arg(long)]
+  record: Option<PathBuf>,
+  /// Play a capture file instead of connecting to the console, as fast as possible.
+  /// The replayed console doesn't answer sets.
+  #[arg(long, conflicts_with = "address")]
+  replay: Option<PathBuf>,
+  #[command(subcommand)]
+  command: Command,
+}
+
+#[derive(Subcommand)]
+#[command(rename_all = "lower")]
+enum Command {
+  /// Desk name, CSCP version and fader and main counts
+  INFO,
+  /// Everything the console has reported
+  DUMP,
+  /// One fader, main or aux
+  GET {
+    bus: Bus,
+    #[arg(value_name = "NUMBER|LABEL")]
+    number: FaderRef,
+  },
+  /// Sets a level in dB or as a raw 0-1023 value
+  SET {
+    bus: Bus,
+    #[arg(value_name = "NUMBER|LABEL")]
+    number: FaderRef,
+    #[arg(long, allow_negative_numbers = true, conflicts_with = "level", required_unless_present = "level")]
+    db: Option<f32>,
+    #[arg(long, value_parser = clap::value_parser!(u16).range(..=MAX_LEVEL as i64))]
+    level: Option<u16>,
+  },
+  /// Cuts a fader
+  CUT {
+    #[arg(value_name = "NUMBER|LABEL")]
+    number: FaderRef,
+  },
+  /// Uncuts a fader
+  UNCUT {
+    #[arg(value_name = "NUMBER|LABEL")]
+    number: FaderRef,
+  },
+  /// Turns PFL on for a fader or main, or off with --off
+  PFL {
+    bus: Bus,
+    #[arg(value_name = "NUMBER|LABEL")]
+    number: FaderRef,
+    #[arg(long)]
+    off: bool,
+  },
+  /// Prints every change until interrupted
+  WATCH,
+  /// Applies a scene file, such as the output of `dump --json`
+  RECALL { file: PathBuf },
+  /// Checks the desk against a show template and lists every difference
+  VERIFY { file: PathBuf },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+#[value(rename_all = "lower")]
+enum Bus {
+  FADER,
+  MAIN,
+  AUX,
+}
+
+struct Session {
+  input_tx: mpsc::Sender<Request>,
+  event_rx: broadcast::Receiver<ConsoleEvent>,
+  client: JoinHandle<Result<CSCPClient, std::io::Error>>,
+  timeout: Duration,
+  json: bool,
+}
+
+#[tokio::main]
+async fn main() {
+  let cli = Cli::parse();
+  process::exit(run(cli).await);
+}
+
+async fn run(cli: Cli) -> i32 {
+  let config = cli.config.as_deref().map(Config::load).unwrap_or_default();
+  let address = match &cli.replay {
+    Some(path) => {
+      let records = match file::read(path) {
+        Ok(records) => records,
+        Err(e) => {
+          eprintln!("Could not read capture {} :: {}", path.display(), e);
+          return EXIT_USAGE;
+        }
+      };
+      match replay::serve(records, 0.0, false).await {
+        Ok(address) => address.to_string(),
+        Err(e) => {
+          eprintln!("Could not start replay :: {}", e);
+          return EXIT_CONNECTION;
+        }
+      }
+    }
+    None => match &cli.address {
+      Some(address) => address.clone(),
+      None => match config.console_address(cli.console.as_deref()) {
+        Ok(address) => address,
+        Err(e) => {
+          eprintln!("{}", e);
+          return EXIT_USAGE;
+        }
+      },
+    },
+  };
+  // Entries name the console picked from the config, or the address when given one
+  let console = cli.console.clone().or_else(|| cli.address.clone()).unwrap_or_else(|| config.consoles()[0].id.clone());
+  // Only log to a directory that was asked for, the app's default one depends on its install
+  let audit = match config.audit.directory {
+    Some(directory) => AuditLog::new(directory, config.audit.maxFileSize, config.audit.maxFiles),
+    None => AuditLog::disabled(),
+  };
+  let audit = ConsoleAudit::new(Arc::new(Mutex::new(audit)), &console);
+
+  let (input_tx, input_rx) = mpsc::channel(1);
+  let (event_tx, event_rx) = broadcast::channel(256);
+  let (traffic_tx, _) = broadcast::channel(256);
+  if let Some(path) = cli.record.clone() {
+    let traffic_rx = traffic_tx.subscribe();
+    tokio::spawn(async move {
+      if let Err(e) = record(path, traffic_rx).await {
+        eprintln!("Recording stopped :: {}", e);
+      }
+    });
+  }
+  let client = tokio::spawn(CSCPClient::connect(address.clone(), input_rx, event_tx, traffic_tx, audit));
+
+  let mut session = Session { input_tx, event_rx, client, timeout: Duration::from_secs(cli.timeout), json: cli.json };
+  let db = match session.connect(&address).await {
+    Ok(db) => db,
+    Err(code) => return code,
+  };
+
+  // Sets wait for probing so ones the console doesn't support aren't sent
+  if matches!(cli.command, Command::SET { .. } | Command::CUT { .. } | Command::UNCUT { .. } | Command::PFL { .. } | Command::RECALL { .. }) {
+    session.probed(db.deskInfo.clone()).await;
+  }
+
+  match cli.command {
+    Command::INFO => {
+      let info = session.probed(db.deskInfo).await;
+      if session.json {
+        print_json(&info);
+      } else {
+        println!("Name:         {}", info.name);
+        println!("CSCP version: {}", info.cscpVersion);
+        println!("Faders:       {}", info.numFaders);
+        println!("Mains:        {}", info.numMains);
+        for capability in info.capabilities.iter() {
+          println!("{:<13} {:?}", format!("{}:", command_name(capability.command)), capability.result);
+        }
+      }
+      EXIT_OK
+    }
+    Command::DUMP => {
+      if session.json {
+        print_json(&db);
+      } else {
+        db.faders.iter().for_each(|fader| println!("{}", fader_line(fader)));
+        db.mains.iter().for_each(|main| println!("{}", main_line(main)));
+        db.auxes.iter().for_each(|aux| println!("{}", aux_line(aux)));
+      }
+      EXIT_OK
+    }
+    Command::GET { bus, ref number } => session.get(db, bus, number).await,
+    Command::SET { bus, ref number, db: gain, level } => {
+      let index = match to_index(bus, number, &db) {
+        Ok(index) => index,
+        Err(code) => return code,
+      };
+      let level = level.unwrap_or_else(|| db_to_level(gain.unwrap_or_default()));
+      let source = Source::API;
+      let request = match bus {
+        Bus::FADER => Request::SET_FADER_LEVEL(SetFaderLevel { index, level, source }),
+        Bus::MAIN => Request::SET_MAIN_LEVEL(SetMainLevel { index, level, source }),
+        Bus::AUX => Request::SET_AUX_LEVEL(SetAuxLevel { index, level, source }),
+      };
+      session.send(request).await
+    }
+    Command::CUT { ref number } | Command::UNCUT { ref number } => {
+      let isCut = matches!(cli.command, Command::CUT { .. });
+      match to_index(Bus::FADER, number, &db) {
+        Ok(index) => session.send(Request::SET_FADER_CUT(SetFaderCut { index, isCut, source: Source::API })).await,
+        Err(code) => code,
+      }
+    }
+    Command::PFL { bus, ref number, off } => {
+      let index = match to_index(bus, number, &db) {
+        Ok(index) => index,
+        Err(code) => return code,
+      };
+      let isPfl = !off;
+      let source = Source::API;
+      let request = match bus {
+        Bus::FADER => Request::SET_FADER_PFL(SetFaderPfl { index, isPfl, source }),
+        Bus::MAIN => Request::SET_MAIN_PFL(SetMainPfl { index, isPfl, source }),
+        Bus::AUX => {
+          eprintln!("Auxes have no PFL");
+          return EXIT_USAGE;
+        }
+      };
+      session.send(request).await
+    }
+    Command::WATCH => session.watch().await,
+    Command::RECALL { file } => {
+      let scene = match std::fs::read_to_string(&file).map_err(|e| e.to_string()).and_then(|contents| serde_json::from_str::<Scene>(&contents).map_err(|e| e.to_string())) {
+        Ok(scene) => scene,
+        Err(e) => {
+          eprintln!("Could not read scene {} :: {}", file.display(), e);
+          return EXIT_USAGE;
+        }
+      };
+
+      let mut code = EXIT_OK;
+      for request in scene::requests(&scene, Source::API) {
+        match session.send(request).await {
+          EXIT_OK => {}
+          EXIT_NAK => code = EXIT_NAK,
+          failed => return failed,
+        }
+      }
+      code
+    }
+    Command::VERIFY { file } => {
+      let template = match template::load(&file) {
+        Ok(template) => template,
+        Err(e) => {
+          eprintln!("{}", e);
+          return EXIT_USAGE;
+        }
+      };
+
+      let report = verify(&template, &db);
+      if session.json {
+        print_json(&report);
+      } else {
+        report.checks.iter().for_each(|check| println!("{}", check_line(check)));
+        println!("{}", report_line(&report));
+      }
+      if report.passed() { EXIT_OK } else { EXIT_MISMATCH }
+    }
+  }
+}
+
+impl Session {
+  /// Waits for the desk info and for the console to finish sending its state
+  async fn connect(&mut self, address: &str) -> Result<DB, i32> {
+    let deadline = Instant::now() + self.timeout;
+
+    loop {
+      if self.client.is_finished() {
+        match (&mut self.client).await {
+          Ok(Err(e)) => eprintln!("Could not connect to {} :: {}", address, e),
+          _ => eprintln!("Lost connection to {}", address),
+        }
+        return Err(EXIT_CONNECTION);
+      }
+      if let Ok(db) = get_db(&self.input_tx).await {
+        if db.deskInfo.numFaders > 0 {
+          break;
+        }
+      }
+      if Instant::now() >= deadline {
+        eprintln!("Timed out waiting for {}", address);
+        return Err(EXIT_CONNECTION);
+      }
+      time::sleep(Duration::from_millis(50)).await;
+    }
+
+    self.settle(deadline).await;
+    get_db(&self.input_tx).await.map_err(|e| {
+      eprintln!("Lost connection to {} :: {}", address, e);
+      EXIT_CONNECTION
+    })
+  }
+
+  /// Waits for the client to finish probing what the console supports, up to the timeout
+  async fn probed(&mut self, info: DeskInfo) -> DeskInfo {
+    if !info.capabilities.is_empty() {
+      return info;
+    }
+    let deadline = Instant::now() + self.timeout;
+    loop {
+      match time::timeout_at(deadline, self.event_rx.recv()).await {
+        Ok(Ok(ConsoleEvent::DESK(info))) if !info.capabilities.is_empty() => return info,
+        Ok(Ok(_)) | Ok(Err(broadcast::error::RecvError::Lagged(_))) => {}
+        _ => {
+          eprintln!("The console wasn't probed in time");
+          return info;
+        }
+      }
+    }
+  }
+
+  async fn settle(&mut self, deadline: Instant) {
+    loop {
+      let quiet = (Instant::now() + SETTLE).min(deadline);
+      match time::timeout_at(quiet, self.event_rx.recv()).await {
+        Ok(Ok(_)) | Ok(Err(broadcast::error::RecvError::Lagged(_))) => {}
+        _ => return,
+      }
+    }
+  }
+
+  /// Sends a request and turns the console's answer into an exit code
+  async fn send(&mut self, request: Request) -> i32 {
+    match time::timeout(self.timeout, confirm(&self.input_tx, request)).await {
+      Ok(Ok(Ok(()))) => EXIT_OK,
+      Ok(Ok(Err(UNSUPPORTED))) => {
+        eprintln!("The console doesn't support this command");
+        EXIT_NAK
+      }
+      Ok(Ok(Err(NOT_CONNECTED))) => {
+        eprintln!("The console isn't connected");
+        EXIT_CONNECTION
+      }
+      Ok(Ok(Err(error))) => {
+        eprintln!("Console rejected the command with error {:#04x}", error);
+        EXIT_NAK
+      }
+      Ok(Err(e)) => {
+        eprintln!("Lost connection to the console :: {}", e);
+        EXIT_CONNECTION
+      }
+      Err(_) => {
+        eprintln!("Timed out waiting for the console to answer");
+        EXIT_CONNECTION
+      }
+    }
+  }
+
+  async fn get(&mut self, mut db: DB, bus: Bus, number: &FaderRef) -> i32 {
+    let index = match to_index(bus, number, &db) {
+      Ok(index) => index,
+      Err(code) => return code,
+    };
+
+    // Faders the console hasn't reported yet can be asked for directly
+    if let Bus::FADER = bus {
+      if !db.faders.iter().any(|fader| fader.index == index) {
+        for frame in [Frame::get_fader_label(index), Frame::get_fader_level(index), Frame::get_fader_cut(index), Frame::get_fader_pfl(index)] {
+          match self.send(Request::SEND_FRAME(frame)).await {
+            EXIT_OK => {}
+            EXIT_NAK => {
+              eprintln!("No fader {}", number);
+              return EXIT_NOT_FOUND;
+            }
+            failed => return failed,
+          }
+        }
+        self.settle(Instant::now() + self.timeout).await;
+        db = match get_db(&self.input_tx).await {
+          Ok(db) => db,
+          Err(e) => {
+            eprintln!("Lost connection to the console :: {}", e);
+            return EXIT_CONNECTION;
+          }
+        };
+      }
+    }
+
+    let found = match bus {
+      Bus::FADER => db.faders.iter().find(|fader| fader.index == index).map(|fader| if self.json { to_json(fader) } else { fader_line(fader) }),
+      Bus::MAIN => db.mains.iter().find(|main| main.index == index).map(|main| if self.json { to_json(main) } else { main_line(main) }),
+      Bus::AUX => db.auxes.iter().find(|aux| aux.index == index).map(|aux| if self.json { to_json(aux) } else { aux_line(aux) }),
+    };
+
+    match found {
+      Some(text) => {
+        println!("{}", text);
+        EXIT_OK
+      }
+      None => {
+        eprintln!("No {:?} {}", bus, number);
+        EXIT_NOT_FOUND
+      }
+    }
+  }
+
+  async fn watch(&mut self) -> i32 {
+    loop {
+      match self.event_rx.recv().await {
+        Ok(event) => {
+          if self.json {
+            println!("{}", serde_json::to_string(&event).unwrap_or_default());
+          } else {
+            match &event {
+              ConsoleEvent::FADER(fader) => println!("{}", fader_line(fader)),
+              ConsoleEvent::MAIN(main) => println!("{}", main_line(main)),
+              ConsoleEvent::AUX(aux) => println!("{}", aux_line(aux)),
+              ConsoleEvent::DESK(info) => println!("{}", desk_line(info)),
+              ConsoleEvent::LINK(link) => println!("{}", link_line(link)),
+            }
+          }
+        }
+        Err(broadcast::error::RecvError::Lagged(n)) => eprintln!("Missed {} changes", n),
+        Err(broadcast::error::RecvError::Closed) => {
+          eprintln!("Lost connection to the console");
+          return EXIT_CONNECTION;
+        }
+      }
+    }
+  }
+}
+
+/// Numbers on the command line start at 1, as on the desk. Fader labels are looked up in `db`.
+fn to_index(bus: Bus, number: &FaderRef, db: &DB) -> Result<u16, i32> {
+  if number.is_label() && !matches!(bus, Bus::FADER) {
+    eprintln!("Only faders can be given by label");
+    return Err(EXIT_USAGE);
+  }
+  number.index(&db.faders).map_err(|e| {
+    eprintln!("{}", e);
+    match e {
+      SelectError::NOT_FOUND(_) => EXIT_NOT_FOUND,
+      _ => EXIT_USAGE,
+    }
+  })
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> String {
+  serde_json::to_string_pretty(value).unwrap_or_default()
+}
+
+fn print_json<T: serde::Serialize>(value: &T) {
+  println!("{}", to_json(value));
+}
+
+fn level_text(level: u16) -> String {
+  format!("{:>4} {:>6.1} dB", level, level_to_db(level))
+}
+
+fn fader_line(fader: &Fader) -> String {
+  format!(
+    "fader {:<3} {:<12} {}  {:<3} {:<3} {:?}/{:?}",
+    fader.index + 1,
+    fader.label,
+    level_text(fader.level),
+    if fader.isCut { "CUT" } else { "" },
+    if fader.isPfl { "PFL" } else { "" },
+    fader.pathType,
+    fader.format,
+  )
+}
+
+fn main_line(main: &Main) -> String {
+  format!(
+    "main  {:<3} {:<12} {}      {:<3}",
+    main.index + 1,
+    main.label,
+    level_text(main.level),
+    if main.isPfl { "PFL" } else { "" },
+  )
+}
+
+fn desk_line(info: &DeskInfo) -> String {
+  let unsupported: Vec<String> = info.capabilities.iter()
+    .filter(|capability| !capability.result.is_supported())
+    .map(|capability| command_name(capability.command))
+    .collect();
+  format!("desk  {:<16} unsupported: {}", info.name, if unsupported.is_empty() { String::from("none") } else { unsupported.join(", ") })
+}
+
+fn link_line(link: &LinkStatus) -> String {
+  format!("link  {:<16} {} {} {:?}", link.endpoint, link.role(), if link.connected { "connected" } else { "disconnected" }, link.health)
+}
+
+fn check_line(check: &TemplateCheck) -> String {
+  let result = match check.result {
+    CheckResult::PASS => "ok",
+    CheckResult::MISMATCH => "MISMATCH",
+    CheckResult::MISSING => "MISSING",
+    CheckResult::UNKNOWN => "unknown",
+  };
+  format!("{:<8}  fader {:<3} {:<12} {:<8}  expected {}, is {}", result, check.index + 1, check.label, check.field, check.expected, check.actual)
+}
+
+fn report_line(report: &TemplateReport) -> String {
+  format!(
+    "{}{} checks: {} ok, {} mismatched, {} missing, {} not reported",
+    if report.name.is_empty() { String::new() } else { format!("{} :: ", report.name) },
+    report.checks.len(),
+    report.count(CheckResult::PASS),
+    report.count(CheckResult::MISMATCH),
+    report.count(CheckResult::MISSING),
+    report.count(CheckResult::UNKNOWN),
+  )
+}
+
+fn aux_line(aux: &Aux) -> String {
+  format!("aux   {:<3} {:<12} {}", aux.index + 1, "", level_text(aux.level))
+}
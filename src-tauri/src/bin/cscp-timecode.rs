@@ -0,0 +1,179 @@
+#![allow(non_snake_case, non_camel_case_types)]
+//! Generates and reads timecode for testing cues without a timecode source.
+//!
+//! `ltc` writes LTC to a WAV file the app can read with `"file"` in its timecode config,
+//! with gaps and jumps to try dropouts and locates. `mtc` plays MIDI Timecode to an output,
+//! or to a virtual one on Linux and macOS. `read` prints the frames in an LTC file.
+
+use std::{path::PathBuf, process, thread, time::{Duration, Instant}};
+use clap::{Parser, Subcommand};
+use common::timecode::{FrameRate, Timecode};
+use midir::MidiOutputConnection;
+
+use rust_cscp_controller::{midi::ports::open_output, timecode::{ltc::{LtcDecoder, LtcEncoder}, mtc::{full_frame, quarter_frames}, source::read_wav}};
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILED: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+/// Name of the MIDI output opened when no `--port` is given
+const VIRTUAL_PORT: &str = "CSCP Timecode Generator";
+
+#[derive(Parser)]
+#[command(name = "cscp-timecode", about = "Generate and read timecode for testing cues")]
+struct Cli {
+  /// Frame rate: 24, 25, 29.97df or 30
+  #[arg(long, short, default_value = "25", value_parser = parse_rate)]
+  rate: FrameRate,
+  #[command(subcommand)]
+  command: Command,
+}
+
+#[derive(Subcommand)]
+#[command(rename_all = "lower")]
+enum Command {
+  /// Writes LTC to a WAV file
+  LTC {
+    file: PathBuf,
+    #[arg(long, default_value = "00:00:00:00", value_parser = parse_timecode)]
+    start: Timecode,
+    #[arg(long, default_value_t = 60.0)]
+    seconds: f64,
+    #[arg(long, default_value_t = 48000)]
+    sampleRate: u32,
+    /// Silence for a while, as `AT:SECONDS` with both in seconds from the start of the file
+    #[arg(long, value_parser = parse_gap)]
+    gap: Vec<(f64, f64)>,
+    /// Jumps the timecode, as `AT=HH:MM:SS:FF` with `AT` in seconds from the start of the file
+    #[arg(long, value_parser = parse_jump)]
+    jump: Vec<(f64, Timecode)>,
+  },
+  /// Plays MIDI Timecode in real time
+  MTC {
+    /// MIDI output, matched by part of its name. A virtual output is opened when left out.
+    #[arg(long)]
+    port: Option<String>,
+    #[arg(long, default_value = "00:00:00:00", value_parser = parse_timecode)]
+    start: Timecode,
+    #[arg(long, default_value_t = 60.0)]
+    seconds: f64,
+  },
+  /// Prints the frames read from an LTC WAV file
+  READ { file: PathBuf },
+}
+
+fn parse_rate(text: &str) -> Result<FrameRate, String> {
+  match text.to_lowercase().as_str() {
+    "24" => Ok(FrameRate::FPS_24),
+    "25" => Ok(FrameRate::FPS_25),
+    "29.97df" | "29.97" => Ok(FrameRate::FPS_29_97_DF),
+    "30" => Ok(FrameRate::FPS_30),
+    _ => Err(format!("{} isn't 24, 25, 29.97df or 30", text)),
+  }
+}
+
+fn parse_timecode(text: &str) -> Result<Timecode, String> {
+  text.parse()
+}
+
+fn parse_seconds(text: &str) -> Result<f64, String> {
+  text.parse::<f64>().ok().filter(|seconds| *seconds >= 0.0).ok_or_else(|| format!("{} isn't a number of seconds", text))
+}
+
+fn parse_gap(text: &str) -> Result<(f64, f64), String> {
+  let (at, length) = text.split_once(':').ok_or_else(|| format!("{} isn't AT:SECONDS", text))?;
+  Ok((parse_seconds(at)?, parse_seconds(length)?))
+}
+
+fn parse_jump(text: &str) -> Result<(f64, Timecode), String> {
+  let (at, timecode) = text.split_once('=').ok_or_else(|| format!("{} isn't AT=HH:MM:SS:FF", text))?;
+  Ok((parse_seconds(at)?, timecode.parse()?))
+}
+
+fn main() {
+  let cli = Cli::parse();
+  let result = match cli.command {
+    Command::LTC { file, start, seconds, sampleRate, gap, jump } => ltc(file, cli.rate, start, seconds, sampleRate, gap, jump),
+    Command::MTC { port, start, seconds } => mtc(port, cli.rate, start, seconds),
+    Command::READ { file } => read(file, cli.rate),
+  };
+  process::exit(match result {
+    Ok(()) => EXIT_OK,
+    Err(e) => {
+      eprintln!("{}", e);
+      if e.starts_with("Usage") { EXIT_USAGE } else { EXIT_FAILED }
+    }
+  });
+}
+
+fn ltc(file: PathBuf, rate: FrameRate, start: Timecode, seconds: f64, sampleRate: u32, gaps: Vec<(f64, f64)>, mut jumps: Vec<(f64, Timecode)>) -> Result<(), String> {
+  if !start.is_valid(rate) || jumps.iter().any(|(_, timecode)| !timecode.is_valid(rate)) {
+    return Err(format!("Usage :: every position has to exist at {:?}", rate));
+  }
+  jumps.sort_by(|a, b| a.0.total_cmp(&b.0));
+  let spec = hound::WavSpec { channels: 1, sample_rate: sampleRate, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+  let mut writer = hound::WavWriter::create(&file, spec).map_err(|e| format!("Can't write {} :: {}", file.display(), e))?;
+  let mut encoder = LtcEncoder::new(sampleRate, rate);
+  let frameLength = 1.0 / rate.fps();
+  let mut frame = start.to_frames(rate);
+  let mut jumps = jumps.into_iter().peekable();
+
+  // Timecode keeps counting through gaps, as it would from a player with the audio dropping out
+  for i in 0..(seconds / frameLength).ceil() as u64 {
+    let at = i as f64 * frameLength;
+    while let Some((_, timecode)) = jumps.next_if(|(jump, _)| *jump <= at) {
+      frame = timecode.to_frames(rate);
+    }
+    let silent = gaps.iter().any(|(from, length)| at >= *from && at < from + length);
+    let samples = if silent { encoder.silence() } else { encoder.frame(Timecode::from_frames(frame, rate)) };
+    for sample in samples {
+      writer.write_sample((sample * i16::MAX as f32) as i16).map_err(|e| e.to_string())?;
+    }
+    frame = (frame + 1) % rate.frames_per_day();
+  }
+  writer.finalize().map_err(|e| e.to_string())?;
+  println!("Wrote {:.1} s of {:?} LTC from {} to {}", seconds, rate, start.text(rate), file.display());
+  Ok(())
+}
+
+fn mtc(port: Option<String>, rate: FrameRate, start: Timecode, seconds: f64) -> Result<(), String> {
+  if !start.is_valid(rate) {
+    return Err(format!("Usage :: {} doesn't exist at {:?}", start, rate));
+  }
+  let (mut connection, name) = open_output(VIRTUAL_PORT, port.as_deref())?;
+  println!("Playing to {}", name);
+
+  let send = |connection: &mut MidiOutputConnection, message: &[u8]| connection.send(message).map_err(|e| e.to_string());
+  send(&mut connection, &full_frame(start, rate))?;
+  // Eight quarter frames carry a position and take two frames to send
+  let quarter = Duration::from_secs_f64(1.0 / rate.fps() / 4.0);
+  let began = Instant::now();
+  let mut frame = start.to_frames(rate);
+  let mut sent = 0u32;
+  while began.elapsed().as_secs_f64() < seconds {
+    for message in quarter_frames(Timecode::from_frames(frame, rate), rate) {
+      sent += 1;
+      if let Some(wait) = (began + quarter * sent).checked_duration_since(Instant::now()) {
+        thread::sleep(wait);
+      }
+      send(&mut connection, &message)?;
+    }
+    frame = (frame + 2) % rate.frames_per_day();
+  }
+  println!("Played {:?} MTC from {} to {}", rate, start.text(rate), Timecode::from_frames(frame, rate).text(rate));
+  Ok(())
+}
+
+fn read(file: PathBuf, rate: FrameRate) -> Result<(), String> {
+  let (sampleRate, samples) = read_wav(&file)?;
+  let mut decoder = LtcDecoder::new(sampleRate, rate);
+  // Fed a bit at a time to tell when each frame ended
+  let chunk = (sampleRate / 1000).max(1) as usize;
+  for (i, samples) in samples.chunks(chunk).enumerate() {
+    for frame in decoder.feed(samples) {
+      let rate = if frame.dropFrame { FrameRate::FPS_29_97_DF } else { rate };
+      println!("{:>10.3} s  {}", ((i + 1) * chunk) as f64 / sampleRate as f64, frame.timecode.text(rate));
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,328 @@
+#![allow(non_snake_case)]
+//! Full-screen terminal view of the console for machines without a desktop session.
+//!
+//! Left/right pick a fader, up/down move it by 1 dB (page up/down by 10 dB),
+//! `c` toggles cut, `p` toggles PFL, `0` sets 0 dB, `x` closes it and `q` quits.
+
+use std::{io::{self, Stdout}, path::PathBuf, sync::Arc, time::Duration};
+use clap::Parser;
+use common::{DB, Fader, Main, Source, level::{db_to_level, level_to_db, MAX_LEVEL, MIN_DB}};
+use crossterm::{
+  event::{Event, EventStream, KeyCode, KeyEventKind},
+  execute,
+  terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use futures_util::{lock::Mutex, StreamExt};
+use ratatui::{
+  backend::CrosstermBackend,
+  layout::{Constraint, Direction, Layout, Rect},
+  style::{Color, Modifier, Style},
+  text::{Line, Span},
+  widgets::{Block, Borders, Paragraph},
+  Frame as Screen, Terminal,
+};
+use tokio::sync::{broadcast, mpsc};
+
+use rust_cscp_controller::{
+  audit::log::{AuditLog, ConsoleAudit},
+  config::Config,
+  cscp::{
+    client::CSCPClient,
+    events::ConsoleEvent,
+    requests::{get_db, Request, SetFaderCut, SetFaderLevel, SetFaderPfl},
+  },
+};
+
+const STRIP_WIDTH: u16 = 12;
+
+#[derive(Parser)]
+#[command(name = "cscp-tui", about = "Terminal view and control of a CSCP console")]
+struct Cli {
+  /// Console address as `ip:port`, defaults to the one in the config file
+  #[arg(long, short)]
+  address: Option<String>,
+  /// App config file to take the console address and audit log directory from
+  #[arg(long, short)]
+  config: Option<PathBuf>,
+  /// Console from the config's `consoles` list, defaults to the first one
+  #[arg(long, conflicts_with = "address")]
+  console: Option<String>,
+  /// Where log messages go while the screen is in use
+  #[arg(long, default_value = "cscp-tui.log")]
+  log: PathBuf,
+}
+
+struct App {
+  address: String,
+  db: DB,
+  selected: usize,
+  connected: bool,
+  status: String,
+}
+
+impl App {
+  fn selected_fader(&self) -> Option<&Fader> {
+    self.db.faders.get(self.selected)
+  }
+
+  fn apply(&mut self, event: ConsoleEvent) {
+    match event {
+      ConsoleEvent::FADER(fader) => match self.db.faders.iter_mut().find(|entry| entry.index == fader.index) {
+        Some(entry) => *entry = fader,
+        None => {
+          self.db.faders.push(fader);
+          self.db.faders.sort_by_key(|fader| fader.index);
+        }
+      },
+      ConsoleEvent::MAIN(main) => match self.db.mains.iter_mut().find(|entry| entry.index == main.index) {
+        Some(entry) => *entry = main,
+        None => {
+          self.db.mains.push(main);
+          self.db.mains.sort_by_key(|main| main.index);
+        }
+      },
+      ConsoleEvent::AUX(_) => {}
+      ConsoleEvent::DESK(deskInfo) => self.db.deskInfo = deskInfo,
+      ConsoleEvent::LINK(link) => self.db.link = link,
+    }
+  }
+
+  /// The request for a key press on the selected fader, if it does anything
+  fn key_request(&self, key: KeyCode) -> Option<Request> {
+    let fader = self.selected_fader()?;
+    let index = fader.index;
+    let source = Source::UI;
+    let nudge = |db: f32| SetFaderLevel { index, level: db_to_level(level_to_db(fader.level) + db), source };
+
+    let request = match key {
+      KeyCode::Up => Request::SET_FADER_LEVEL(nudge(1.0)),
+      KeyCode::Down => Request::SET_FADER_LEVEL(nudge(-1.0)),
+      KeyCode::PageUp => Request::SET_FADER_LEVEL(nudge(10.0)),
+      KeyCode::PageDown => Request::SET_FADER_LEVEL(nudge(-10.0)),
+      KeyCode::Char('0') => Request::SET_FADER_LEVEL(SetFaderLevel { index, level: db_to_level(0.0), source }),
+      KeyCode::Char('x') => Request::SET_FADER_LEVEL(SetFaderLevel { index, level: 0, source }),
+      KeyCode::Char('c') => Request::SET_FADER_CUT(SetFaderCut { index, isCut: !fader.isCut, source }),
+      KeyCode::Char('p') => Request::SET_FADER_PFL(SetFaderPfl { index, isPfl: !fader.isPfl, source }),
+      _ => return None,
+    };
+
+    // Keys for commands the desk doesn't support do nothing
+    match request.command() {
+      Some(command) if !self.db.deskInfo.supports(command) => None,
+      _ => Some(request),
+    }
+  }
+}
+
+#[tokio::main]
+async fn main() -> io::Result<()> {
+  let cli = Cli::parse();
+  let config = cli.config.as_deref().map(Config::load).unwrap_or_default();
+  let address = match &cli.address {
+    Some(address) => address.clone(),
+    None => config.console_address(cli.console.as_deref()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+  };
+  // Entries name the console picked from the config, or the address when given one
+  let console = cli.console.clone().or_else(|| cli.address.clone()).unwrap_or_else(|| config.consoles()[0].id.clone());
+  let audit = match config.audit.directory {
+    Some(directory) => AuditLog::new(directory, config.audit.maxFileSize, config.audit.maxFiles),
+    None => AuditLog::disabled(),
+  };
+  let audit = ConsoleAudit::new(Arc::new(Mutex::new(audit)), &console);
+
+  redirect_stderr(&cli.log)?;
+
+  let (input_tx, input_rx) = mpsc::channel(1);
+  let (event_tx, event_rx) = broadcast::channel(256);
+  let (traffic_tx, _) = broadcast::channel(256);
+  let client_address = address.clone();
+  tokio::spawn(async move {
+    if let Err(e) = CSCPClient::connect(client_address, input_rx, event_tx, traffic_tx, audit).await {
+      eprintln!("Could not connect :: {}", e);
+    }
+  });
+
+  enable_raw_mode()?;
+  let mut stdout = io::stdout();
+  execute!(stdout, EnterAlternateScreen)?;
+  let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+
+  let app = App { address, db: DB::default(), selected: 0, connected: false, status: String::from("Connecting") };
+  let result = run(&mut terminal, app, input_tx, event_rx).await;
+
+  disable_raw_mode()?;
+  execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+  terminal.show_cursor()?;
+  result
+}
+
+async fn run(
+  terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+  mut app: App,
+  input_tx: mpsc::Sender<Request>,
+  mut event_rx: broadcast::Receiver<ConsoleEvent>,
+) -> io::Result<()> {
+  let mut keys = EventStream::new();
+  // Desk info doesn't come with an event, so the whole state is re-read now and then
+  let mut refresh = tokio::time::interval(Duration::from_secs(1));
+  let mut listening = true;
+
+  loop {
+    terminal.draw(|screen| draw(screen, &app))?;
+
+    tokio::select! {
+      _ = refresh.tick() => {
+        match get_db(&input_tx).await {
+          Ok(db) => {
+            if !app.connected {
+              app.status = String::from("Connected");
+            }
+            app.connected = true;
+            app.db = db;
+            app.db.faders.sort_by_key(|fader| fader.index);
+            app.db.mains.sort_by_key(|main| main.index);
+          }
+          Err(_) => {
+            app.connected = false;
+            app.status = format!("Not connected to {}, see the log", app.address);
+          }
+        }
+      }
+      event = event_rx.recv(), if listening => {
+        match event {
+          Ok(event) => app.apply(event),
+          Err(broadcast::error::RecvError::Lagged(_)) => {}
+          Err(broadcast::error::RecvError::Closed) => {
+            listening = false;
+            app.connected = false;
+            app.status = String::from("Connection lost");
+          }
+        }
+      }
+      key = keys.next() => {
+        let key = match key {
+          Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => key.code,
+          Some(Ok(_)) => continue,
+          Some(Err(e)) => return Err(e),
+          None => return Ok(()),
+        };
+
+        match key {
+          KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+          KeyCode::Left => app.selected = app.selected.saturating_sub(1),
+          KeyCode::Right => app.selected = (app.selected + 1).min(app.db.faders.len().saturating_sub(1)),
+          key => {
+            if let Some(request) = app.key_request(key) {
+              if input_tx.send(request).await.is_err() {
+                app.status = String::from("Connection lost");
+              }
+            }
+          }
+        }
+      }
+    }
+  }
+}
+
+fn draw(screen: &mut Screen, app: &App) {
+  let rows = Layout::default()
+    .direction(Direction::Vertical)
+    .constraints([Constraint::Length(3), Constraint::Min(10), Constraint::Length(5), Constraint::Length(1)])
+    .split(screen.size());
+
+  let info = &app.db.deskInfo;
+  let name = if info.name.is_empty() { String::from("-") } else { info.name.clone() };
+  let header = Line::from(vec![
+    Span::styled(name, Style::default().add_modifier(Modifier::BOLD)),
+    Span::raw(format!("  CSCP v{}  {} faders  {} mains  {}  ", info.cscpVersion, info.numFaders, info.numMains, app.address)),
+    Span::styled(app.status.clone(), Style::default().fg(if app.connected { Color::Green } else { Color::Red })),
+  ]);
+  screen.render_widget(Paragraph::new(header).block(Block::default().borders(Borders::ALL).title("Console")), rows[0]);
+
+  draw_faders(screen, app, rows[1]);
+  draw_mains(screen, app, rows[2]);
+
+  let help = "←/→ select  ↑/↓ ±1 dB  PgUp/PgDn ±10 dB  0 unity  x close  c cut  p PFL  q quit";
+  screen.render_widget(Paragraph::new(help).style(Style::default().fg(Color::DarkGray)), rows[3]);
+}
+
+fn draw_faders(screen: &mut Screen, app: &App, area: Rect) {
+  let block = Block::default().borders(Borders::ALL).title("Faders");
+  let inner = block.inner(area);
+  screen.render_widget(block, area);
+
+  // Scroll so the selected strip stays on screen
+  let visible = (inner.width / STRIP_WIDTH).max(1) as usize;
+  let first = app.selected.saturating_sub(visible - 1);
+
+  for (slot, fader) in app.db.faders.iter().skip(first).take(visible).enumerate() {
+    let area = Rect { x: inner.x + slot as u16 * STRIP_WIDTH, y: inner.y, width: STRIP_WIDTH, height: inner.height };
+    draw_strip(screen, fader, first + slot == app.selected, area);
+  }
+}
+
+fn draw_strip(screen: &mut Screen, fader: &Fader, selected: bool, area: Rect) {
+  let border = if selected { Style::default().fg(Color::Yellow) } else { Style::default() };
+  let block = Block::default().borders(Borders::ALL).border_style(border).title(format!("{}", fader.index + 1));
+  let inner = block.inner(area);
+  screen.render_widget(block, area);
+
+  let mut lines = vec![
+    Line::from(Span::styled(fader.label.clone(), Style::default().add_modifier(Modifier::BOLD))),
+    Line::from(format!("{:?}/{:?}", fader.pathType, fader.format)),
+    Line::from(vec![
+      Span::styled(if fader.isCut { "CUT " } else { "    " }, Style::default().fg(Color::Red)),
+      Span::styled(if fader.isPfl { "PFL" } else { "" }, Style::default().fg(Color::Yellow)),
+    ]),
+    Line::from(db_text(fader.level)),
+  ];
+
+  // Whatever height is left becomes the fader's meter
+  let meter = inner.height.saturating_sub(lines.len() as u16) as usize;
+  let filled = (fader.level as usize * meter + MAX_LEVEL as usize / 2) / MAX_LEVEL as usize;
+  let colour = if fader.isCut { Color::DarkGray } else { Color::Green };
+  for row in (0..meter).rev() {
+    let bar = if row < filled { "  ██████" } else { "  ······" };
+    lines.push(Line::from(Span::styled(bar, Style::default().fg(colour))));
+  }
+
+  screen.render_widget(Paragraph::new(lines), inner);
+}
+
+fn draw_mains(screen: &mut Screen, app: &App, area: Rect) {
+  let lines: Vec<Line> = app.db.mains.iter().map(main_line).collect();
+  screen.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Mains")), area);
+}
+
+fn main_line(main: &Main) -> Line<'static> {
+  Line::from(vec![
+    Span::raw(format!("{:<3} {:<12} {:>10}  ", main.index + 1, main.label, db_text(main.level))),
+    Span::styled(if main.isPfl { "PFL" } else { "" }, Style::default().fg(Color::Yellow)),
+  ])
+}
+
+fn db_text(level: u16) -> String {
+  let db = level_to_db(level);
+  if db <= MIN_DB {
+    String::from("-inf dB")
+  } else {
+    format!("{:.1} dB", db)
+  }
+}
+
+/// Keeps log output from the client off the screen
+#[cfg(unix)]
+fn redirect_stderr(path: &std::path::Path) -> io::Result<()> {
+  use std::{fs::OpenOptions, os::unix::io::AsRawFd};
+
+  let file = OpenOptions::new().create(true).append(true).open(path)?;
+  if unsafe { libc::dup2(file.as_raw_fd(), libc::STDERR_FILENO) } < 0 {
+    return Err(io::Error::last_os_error());
+  }
+  Ok(())
+}
+
+#[cfg(not(unix))]
+fn redirect_stderr(_path: &std::path::Path) -> io::Result<()> {
+  Ok(())
+}
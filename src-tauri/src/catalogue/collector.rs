@@ -0,0 +1,129 @@
+#![allow(non_snake_case)]
+use std::{collections::{BTreeMap, VecDeque}, sync::Arc};
+use common::{catalogue::{CatalogueEntry, CatalogueSample, Coverage}, inspector::Direction};
+use futures_util::lock::Mutex;
+use tokio::sync::broadcast;
+
+use crate::{cscp::{connection::Traffic, frame::Message}, csv, inspector::decode::{describe, hex}};
+
+pub type CatalogueStorage = Arc<Mutex<Catalogue>>;
+
+/// Distinct payloads kept per command, the oldest is replaced once full
+const MAX_SAMPLES: usize = 20;
+/// Frames kept as context for a sample
+const CONTEXT_FRAMES: usize = 5;
+/// How far back context frames may go, in milliseconds
+const CONTEXT_WINDOW: u64 = 2000;
+
+/// How much of a console message `CSCPClient::connect` understands, keep in step with its match
+pub fn coverage(command: u8) -> Option<(Coverage, &'static str)> {
+  match command {
+    0x00 | 0x01 | 0x02 | 0x05 | 0x07 | 0x08 | 0x0B | 0x0C | 0x0D | 0x11 | 0x13 => None,
+    0x10 => Some((Coverage::PARTIAL, "Aux availability, only the first byte (auxes 1-8) is decoded")),
+    0x14 => Some((Coverage::PARTIAL, "Main availability, only the first three bits are decoded")),
+    0x16 => Some((Coverage::PARTIAL, "Taken to be main routing, two bits per fader, unconfirmed")),
+    _ => Some((Coverage::UNKNOWN, "")),
+  }
+}
+
+/// Console messages the client doesn't fully understand, by command byte
+#[derive(Default)]
+pub struct Catalogue {
+  entries: BTreeMap<u8, CatalogueEntry>,
+  /// Recent frames on the link as (timestamp, description), oldest first
+  recent: VecDeque<(u64, String)>,
+}
+
+impl Catalogue {
+  pub fn push(&mut self, traffic: &Traffic) {
+    let description = format!("{:?} {}", traffic.direction, describe(traffic.direction, &traffic.frame));
+
+    if let (Direction::RX, Message::MSG(msg)) = (traffic.direction, &traffic.frame.msg) {
+      if msg.buffer.len() >= 6 {
+        let (flags, command) = (msg.buffer[3], msg.buffer[4]);
+        if let Some((coverage, note)) = coverage(command) {
+          let payload = msg.buffer[5..msg.buffer.len() - 1].to_vec();
+          self.add(traffic.timestamp, command, coverage, note, flags, payload);
+        }
+      }
+    }
+
+    self.recent.push_back((traffic.timestamp, description));
+    if self.recent.len() > CONTEXT_FRAMES {
+      self.recent.pop_front();
+    }
+  }
+
+  fn add(&mut self, timestamp: u64, command: u8, coverage: Coverage, note: &str, flags: u8, payload: Vec<u8>) {
+    let context: Vec<String> = self.recent.iter()
+      .filter(|(seen, _)| timestamp.saturating_sub(*seen) <= CONTEXT_WINDOW)
+      .map(|(_, description)| description.clone())
+      .collect();
+
+    let entry = self.entries.entry(command).or_insert_with(|| CatalogueEntry {
+      command,
+      coverage,
+      note: note.to_string(),
+      count: 0,
+      firstSeen: timestamp,
+      lastSeen: timestamp,
+      samples: vec![],
+    });
+    entry.count += 1;
+    entry.lastSeen = timestamp;
+
+    match entry.samples.iter_mut().find(|sample| sample.flags == flags && sample.payload == payload) {
+      Some(sample) => {
+        sample.timestamp = timestamp;
+        sample.context = context;
+      }
+      None => {
+        if entry.samples.len() >= MAX_SAMPLES {
+          entry.samples.remove(0);
+        }
+        entry.samples.push(CatalogueSample { timestamp, flags, payload, context });
+      }
+    }
+  }
+
+  /// Catalogued commands in command byte order
+  pub fn entries(&self) -> Vec<CatalogueEntry> {
+    self.entries.values().cloned().collect()
+  }
+}
+
+pub async fn collect(catalogue: CatalogueStorage, mut traffic_rx: broadcast::Receiver<Traffic>) {
+  loop {
+    match traffic_rx.recv().await {
+      Ok(traffic) => catalogue.lock().await.push(&traffic),
+      Err(broadcast::error::RecvError::Lagged(skipped)) => {
+        eprintln!("CATALOGUE :: missed {} frames", skipped);
+      }
+      Err(broadcast::error::RecvError::Closed) => break,
+    }
+  }
+}
+
+/// One row per sample
+pub fn to_csv(entries: &[CatalogueEntry]) -> String {
+  let mut contents = String::from("command,coverage,count,firstSeen,lastSeen,note,sampleTimestamp,flags,payload,context\n");
+
+  for entry in entries {
+    for sample in entry.samples.iter() {
+      contents.push_str(&csv::row(&[
+        format!("{:#04x}", entry.command),
+        format!("{:?}", entry.coverage),
+        entry.count.to_string(),
+        entry.firstSeen.to_string(),
+        entry.lastSeen.to_string(),
+        entry.note.clone(),
+        sample.timestamp.to_string(),
+        format!("{:#04x}", sample.flags),
+        hex(&sample.payload),
+        sample.context.join(" | "),
+      ]));
+    }
+  }
+
+  contents
+}
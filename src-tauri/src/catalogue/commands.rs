@@ -0,0 +1,27 @@
+use std::fs;
+use common::catalogue::CatalogueEntry;
+
+use super::collector::{CatalogueStorage, to_csv};
+
+pub struct CatalogueState {
+  pub inner: CatalogueStorage,
+}
+
+#[tauri::command]
+pub async fn getCatalogue(
+    state: tauri::State<'_, CatalogueState>,
+) -> Result<Vec<CatalogueEntry>, String> {
+    eprintln!("getCatalogue");
+    let catalogue = state.inner.lock().await;
+    Ok(catalogue.entries())
+}
+
+#[tauri::command]
+pub async fn exportCatalogue(
+    path: String,
+    state: tauri::State<'_, CatalogueState>,
+) -> Result<(), String> {
+    eprintln!("exportCatalogue path={}", path);
+    let entries = state.inner.lock().await.entries();
+    fs::write(path, to_csv(&entries)).map_err(|e| e.to_string())
+}
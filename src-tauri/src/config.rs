@@ -0,0 +1,456 @@
+#![allow(non_snake_case)]
+use std::{collections::HashMap, fs, path::{Path, PathBuf}};
+use common::{midi::MidiMapping, rules::Rule, schedule::ScheduleEntry, timecode::{FrameRate, TimecodeCue, TimecodeSource}};
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct ConsoleConfig {
+  pub address: String,
+  /// Endpoints tried in order, the first is the primary. Falls back to `address` when empty
+  pub endpoints: Vec<String>,
+  /// Move back to the primary once it answers again while connected to a backup
+  pub failback: bool,
+  /// Milliseconds to wait for an endpoint to accept the connection
+  pub connectTimeout: u64,
+  /// Milliseconds between reconnect rounds and primary checks
+  pub retryInterval: u64,
+  /// Heartbeat on the console link, on by default and off when `null`
+  pub keepalive: Option<KeepaliveConfig>,
+}
+
+impl Default for ConsoleConfig {
+  fn default() -> Self {
+    ConsoleConfig {
+      address: String::from("172.16.255.5:49556"),
+      endpoints: vec![],
+      failback: false,
+      connectTimeout: 3000,
+      retryInterval: 2000,
+      keepalive: Some(KeepaliveConfig::default()),
+    }
+  }
+}
+
+impl ConsoleConfig {
+  pub fn endpoints(&self) -> Vec<String> {
+    if self.endpoints.is_empty() {
+      vec![self.address.clone()]
+    } else {
+      self.endpoints.clone()
+    }
+  }
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct NamedConsoleConfig {
+  /// Used by the UI and Tauri commands to pick the console
+  pub id: String,
+  #[serde(flatten)]
+  pub console: ConsoleConfig,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct KeepaliveConfig {
+  /// Milliseconds between heartbeats
+  pub interval: u64,
+  /// Milliseconds the console has to answer a heartbeat
+  pub timeout: u64,
+  /// Heartbeats missed in a row before the link counts as degraded
+  pub degradedAfter: u32,
+  /// Heartbeats missed in a row before the link is dropped
+  pub lostAfter: u32,
+}
+
+impl Default for KeepaliveConfig {
+  fn default() -> Self {
+    KeepaliveConfig { interval: 2000, timeout: 1000, degradedAfter: 1, lostAfter: 3 }
+  }
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct AuditConfig {
+  /// Defaults to `audit` inside the app data dir
+  pub directory: Option<PathBuf>,
+  /// Size in bytes at which the current log file is rotated
+  pub maxFileSize: u64,
+  /// Number of rotated files kept besides the current one
+  pub maxFiles: usize,
+}
+
+impl Default for AuditConfig {
+  fn default() -> Self {
+    AuditConfig { directory: None, maxFileSize: 10 * 1024 * 1024, maxFiles: 10 }
+  }
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct OnAirConfig {
+  /// Raw fader level a fader has to be above to count as on air
+  pub threshold: u16,
+  /// Whether a fader also has to be routed to a main. Off by default, as routing comes from a
+  /// message taken to be main routing that hasn't been confirmed against a console.
+  pub useRouting: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+pub enum MirrorMapping {
+  /// Fader n follows fader n
+  INDEX,
+  /// Faders follow the fader with the same label on the other desk, unlabelled faders aren't mirrored
+  LABEL,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+pub enum MirrorDirection {
+  /// Only changes on the source are applied to the target
+  ONE_WAY,
+  /// Changes on either desk are applied to the other
+  BIDIRECTIONAL,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct MirrorConfig {
+  /// Id from `consoles` whose state the target follows
+  pub source: String,
+  pub target: String,
+  /// How faders are paired, mains and auxes always go by index
+  pub mapping: MirrorMapping,
+  pub direction: MirrorDirection,
+}
+
+impl Default for MirrorConfig {
+  fn default() -> Self {
+    MirrorConfig {
+      source: String::new(),
+      target: String::new(),
+      mapping: MirrorMapping::INDEX,
+      direction: MirrorDirection::ONE_WAY,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+pub enum TslVersion {
+  V3_1,
+  V5,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+pub enum TslTransport {
+  UDP,
+  TCP,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct TslDisplay {
+  /// Fader number as shown on the desk, starting at 1
+  pub fader: u16,
+  /// Display address (v3.1, 0-126) or index (v5.0)
+  pub address: u16,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct TslConfig {
+  pub version: TslVersion,
+  pub transport: TslTransport,
+  /// Where the tally controller or UMD listens
+  pub address: String,
+  /// v5.0 screen index
+  pub screen: u16,
+  /// 0-3
+  pub brightness: u8,
+  /// Milliseconds between re-sending every display
+  pub refreshInterval: u64,
+  pub displays: Vec<TslDisplay>,
+}
+
+impl Default for TslConfig {
+  fn default() -> Self {
+    TslConfig {
+      version: TslVersion::V5,
+      transport: TslTransport::UDP,
+      address: String::from("127.0.0.1:8900"),
+      screen: 0,
+      brightness: 3,
+      refreshInterval: 1000,
+      displays: vec![],
+    }
+  }
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct OscConfig {
+  /// Address the OSC server listens on
+  pub bind: String,
+  /// Clients that always receive feedback, as `ip:port`
+  pub clients: Vec<String>,
+}
+
+impl Default for OscConfig {
+  fn default() -> Self {
+    OscConfig { bind: String::from("0.0.0.0:9000"), clients: vec![] }
+  }
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+  /// Address the HTTP server listens on
+  pub bind: String,
+}
+
+impl Default for HttpConfig {
+  fn default() -> Self {
+    HttpConfig { bind: String::from("0.0.0.0:8080") }
+  }
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct EmberConfig {
+  /// Address the Ember+ provider listens on
+  pub bind: String,
+  /// Name of the root node shown to consumers
+  pub identifier: String,
+}
+
+impl Default for EmberConfig {
+  fn default() -> Self {
+    EmberConfig { bind: String::from("0.0.0.0:9092"), identifier: String::from("CSCP") }
+  }
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct ProxyConfig {
+  /// Address downstream CSCP clients connect to
+  pub bind: String,
+}
+
+impl Default for ProxyConfig {
+  fn default() -> Self {
+    ProxyConfig { bind: String::from("0.0.0.0:49556") }
+  }
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct InspectorConfig {
+  /// Number of most recent frames kept
+  pub capacity: usize,
+}
+
+impl Default for InspectorConfig {
+  fn default() -> Self {
+    InspectorConfig { capacity: 5000 }
+  }
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct CaptureConfig {
+  /// Defaults to `captures` inside the app data dir, each run gets its own file
+  pub directory: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct ReplayConfig {
+  /// Capture file to play back
+  pub file: PathBuf,
+  /// 1 plays at the recorded pace, 0 as fast as possible
+  pub speed: f32,
+  /// Starts over at the end of the capture
+  pub repeat: bool,
+}
+
+impl Default for ReplayConfig {
+  fn default() -> Self {
+    ReplayConfig { file: PathBuf::new(), speed: 1.0, repeat: false }
+  }
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct DuckerConfig {
+  pub name: String,
+  /// Fader number or label selector, the others are ducked while any fader it matches is open
+  pub priority: String,
+  /// Fader numbers or label selectors of the faders lowered
+  pub ducked: Vec<String>,
+  /// How many dB the ducked faders are lowered by
+  pub depth: f32,
+  /// Level in dB above which a priority fader counts as open, fully closed when left out
+  pub threshold: Option<f32>,
+  /// Milliseconds the ducked faders take to go down
+  pub attack: u64,
+  /// Milliseconds they take to come back up
+  pub release: u64,
+}
+
+impl Default for DuckerConfig {
+  fn default() -> Self {
+    DuckerConfig {
+      name: String::new(),
+      priority: String::new(),
+      ducked: vec![],
+      depth: 10.0,
+      threshold: None,
+      attack: 200,
+      release: 1000,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct ScriptsConfig {
+  /// Folder the `.rhai` scripts are loaded from, defaults to `scripts` inside the app config dir
+  pub directory: Option<PathBuf>,
+  /// Script to run for a key pressed in the UI, e.g. `"Ctrl+F5": "intro"`
+  pub hotkeys: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct TimecodeConfig {
+  pub source: TimecodeSource,
+  /// Rate the cue times are written at, and of the LOCAL clock
+  pub rate: FrameRate,
+  /// MTC input, matched by part of its name. A virtual input called `CSCP Timecode` is opened when left out.
+  pub port: Option<String>,
+  /// LTC audio input, matched by part of its name, defaults to the system's default input
+  pub device: Option<String>,
+  /// WAV file to read LTC from instead of an audio input, played once in real time
+  pub file: Option<PathBuf>,
+  /// Where the LOCAL clock starts
+  pub start: String,
+  /// Milliseconds to carry on through a dropout before stopping
+  pub freewheel: u64,
+}
+
+impl Default for TimecodeConfig {
+  fn default() -> Self {
+    TimecodeConfig {
+      source: TimecodeSource::LOCAL,
+      rate: FrameRate::FPS_25,
+      port: None,
+      device: None,
+      file: None,
+      start: String::from("00:00:00:00"),
+      freewheel: 2000,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct MidiConfig {
+  /// Input from the surface, matched by part of its name. A virtual port called `CSCP Control` is opened when left out.
+  pub input: Option<String>,
+  /// Output for motor faders and LEDs, matched the same way
+  pub output: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct Config {
+  pub console: ConsoleConfig,
+  /// Named consoles, replacing `console` when present. Every service (TSL, OSC, HTTP, Ember+, the proxy,
+  /// on-air, scripts, rules, the scheduler, ducking, timecode and MIDI) uses the first one, only the mirror picks by id.
+  pub consoles: Vec<NamedConsoleConfig>,
+  pub audit: AuditConfig,
+  pub onAir: OnAirConfig,
+  /// TSL UMD output, disabled when missing
+  pub tsl: Option<TslConfig>,
+  /// OSC server, disabled when missing
+  pub osc: Option<OscConfig>,
+  /// HTTP REST and WebSocket API, disabled when missing
+  pub http: Option<HttpConfig>,
+  /// Ember+ provider, disabled when missing
+  pub ember: Option<EmberConfig>,
+  /// CSCP proxy for other clients, disabled when missing
+  pub proxy: Option<ProxyConfig>,
+  /// Protocol inspector capturing the console link, disabled when missing
+  pub inspector: Option<InspectorConfig>,
+  /// Records the console link to a capture file, disabled when missing
+  pub capture: Option<CaptureConfig>,
+  /// Plays a capture file instead of connecting to the console when present
+  pub replay: Option<ReplayConfig>,
+  /// Mirrors one console's state onto another, disabled when missing
+  pub mirror: Option<MirrorConfig>,
+  /// Actions run when the console gets into a given state, edited from the UI
+  pub rules: Vec<Rule>,
+  pub scripts: ScriptsConfig,
+  /// Faders lowered while a priority fader is open
+  pub duckers: Vec<DuckerConfig>,
+  /// Actions run at set times of day, edited from the UI
+  pub schedule: Vec<ScheduleEntry>,
+  /// Timecode chasing, disabled when missing
+  pub timecode: Option<TimecodeConfig>,
+  /// Fired as the timecode reaches them, edited from the UI
+  pub cues: Vec<TimecodeCue>,
+  /// MIDI control surface, disabled when missing
+  pub midi: Option<MidiConfig>,
+  /// Controls on the surface and what they move, learned or edited from the UI
+  pub midiMappings: Vec<MidiMapping>,
+}
+
+impl Config {
+  /// Reads the config file, falling back to defaults when it is missing or invalid
+  pub fn load(path: &Path) -> Config {
+    match fs::read_to_string(path) {
+      Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+        eprintln!("Invalid config {} :: {}", path.display(), e);
+        Config::default()
+      }),
+      Err(_) => {
+        eprintln!("No config at {}, using defaults", path.display());
+        Config::default()
+      }
+    }
+  }
+
+  /// Replaces `key` in the config file, keeping everything else as written
+  pub fn update(path: &Path, key: &str, value: serde_json::Value) -> Result<(), String> {
+    let mut config = match fs::read_to_string(path) {
+      Ok(contents) => serde_json::from_str(&contents).map_err(|e| format!("Invalid config {} :: {}", path.display(), e))?,
+      Err(_) => serde_json::Value::Object(serde_json::Map::new()),
+    };
+    match config.as_object_mut() {
+      Some(object) => object.insert(String::from(key), value),
+      None => return Err(format!("Config {} isn't a JSON object", path.display())),
+    };
+
+    if let Some(directory) = path.parent() {
+      fs::create_dir_all(directory).map_err(|e| e.to_string())?;
+    }
+    let contents = serde_json::to_string_pretty(&config).map_err(|e| e.to_string())?;
+    fs::write(path, contents).map_err(|e| format!("Could not write config {} :: {}", path.display(), e))
+  }
+
+  /// The configured consoles, `console` named `main` when there is no `consoles` list
+  pub fn consoles(&self) -> Vec<NamedConsoleConfig> {
+    if self.consoles.is_empty() {
+      vec![NamedConsoleConfig { id: String::from("main"), console: self.console.clone() }]
+    } else {
+      self.consoles.clone()
+    }
+  }
+
+  /// First endpoint of the console named `id`, or of the first console when `None`
+  pub fn console_address(&self, id: Option<&str>) -> Result<String, String> {
+    let consoles = self.consoles();
+    let named = match id {
+      Some(id) => consoles.iter().find(|named| named.id == id).ok_or_else(|| format!("No console {} in the config", id))?,
+      None => &consoles[0],
+    };
+    Ok(named.console.endpoints()[0].clone())
+  }
+}
@@ -1,85 +1,449 @@
 #![allow(non_snake_case, non_camel_case_types, unused_variables, unused_imports, dead_code)]
-use std::{io::{Cursor, Error}, sync::Arc};
+use std::{collections::VecDeque, future, io::{Cursor, Error}, sync::Arc, time::Duration};
 use bytes::{BytesMut, Buf, BufMut};
 use futures_util::lock::Mutex;
 use slab::Slab;
 use tauri::Manager;
-use tokio::{net::{TcpStream, ToSocketAddrs, tcp::{OwnedReadHalf, OwnedWriteHalf}}, sync::{mpsc, oneshot}};
+use tokio::{net::{TcpStream, ToSocketAddrs, tcp::{OwnedReadHalf, OwnedWriteHalf}}, sync::{broadcast, mpsc, oneshot}, time};
 use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
-use common::{Fader, DB, AudioType, AudioWidth, DeskInfo};
+use common::{Fader, DB, AudioType, AudioWidth, DeskInfo, BusType, Source, Main, Aux, link::{LinkHealth, LinkStatus, RoundTrip}};
 
-use crate::cscp::connection::Connection;
+use crate::{audit::log::{ConsoleAudit, record}, clock::now_millis, config::{ConsoleConfig, KeepaliveConfig}, cscp::connection::{Connection, TrafficSender}};
 
-use super::{frame::{Frame, Message, FrameAck, FrameError, FrameMessage}, requests::Request};
+use super::{events::ConsoleEvent, frame::{Frame, Message, FrameAck, FrameError, FrameMessage}, heartbeat::Heartbeat, probe::probe, requests::{AckSender, Request, NOT_CONNECTED, UNSUPPORTED}};
 
 pub type DeskInfoStorage = Arc<Mutex<Slab<DeskInfo>>>;
 pub type FadersStorage = Arc<Mutex<Slab<Fader>>>;
+pub type MainsStorage = Arc<Mutex<Slab<Main>>>;
+pub type AuxesStorage = Arc<Mutex<Slab<Aux>>>;
+/// Who is waiting on the ACK/ERR for each frame written to the console, oldest first
+pub type PendingAcks = Arc<Mutex<VecDeque<PendingAck>>>;
+
+pub type LinkStorage = Arc<Mutex<LinkStatus>>;
+pub type ToMcsSender = mpsc::Sender<(Frame, PendingAck)>;
+type ToMcsReceiver = mpsc::Receiver<(Frame, PendingAck)>;
+
+/// Who is waiting on the ACK/ERR for a frame, if anyone
+#[derive(Debug, Default)]
+pub struct PendingAck {
+  pub ack: Option<AckSender>,
+  /// Lets a caller that gives up on the answer find its own slot again
+  pub id: Option<u64>,
+}
+
+impl From<Option<AckSender>> for PendingAck {
+  fn from(ack: Option<AckSender>) -> Self {
+    PendingAck { ack, id: None }
+  }
+}
 
 pub struct CSCPClient;
 
 impl CSCPClient {
-  pub async fn connect<T: ToSocketAddrs>(addr: T, mut input_rx: mpsc::Receiver<Request>, fader_event_tx: mpsc::Sender<Fader>) -> Result<CSCPClient, Error> {
-    let (to_mcs_tx, mut to_mcs_rx): (mpsc::Sender<Frame>, mpsc::Receiver<Frame>) = mpsc::channel(32);
-    let (from_mcs_tx, mut from_mcs_rx): (mpsc::Sender<Frame>, mpsc::Receiver<Frame>) = mpsc::channel(32);
+  /// Connects to a single console and runs until the link drops
+  pub async fn connect<T: ToSocketAddrs>(
+    addr: T,
+    input_rx: mpsc::Receiver<Request>,
+    event_tx: broadcast::Sender<ConsoleEvent>,
+    traffic_tx: TrafficSender,
+    audit: ConsoleAudit,
+  ) -> Result<CSCPClient, Error> {
     let socket = TcpStream::connect(addr).await?;
-    let connection = Connection::new(socket);
-    let (mut read, mut write) = connection.split();
-    println!("Connected");
+    let endpoint = socket.peer_addr()?.to_string();
+    let mut link = Link::start(input_rx, event_tx, traffic_tx, audit);
+    if let SessionEnd::LOST(reason) = link.session(0, endpoint, socket, None, None).await {
+      eprintln!("LINK :: lost :: {}", reason);
+    }
 
-    let faders_storage = FadersStorage::default();
-    let inbound_faders_storage = faders_storage.clone();
-    let desk_info_storage = DeskInfoStorage::default();
-    let inbound_desk_info_storage = desk_info_storage.clone();
+    Ok(CSCPClient)
+  }
+
+  /// Connects to the first configured endpoint that answers and fails over to the next one
+  /// whenever the link drops, resyncing the console state on every new link
+  pub async fn run(
+    console: ConsoleConfig,
+    input_rx: mpsc::Receiver<Request>,
+    event_tx: broadcast::Sender<ConsoleEvent>,
+    traffic_tx: TrafficSender,
+    audit: ConsoleAudit,
+  ) -> Result<CSCPClient, Error> {
+    let endpoints = console.endpoints();
+    let connect_timeout = Duration::from_millis(console.connectTimeout);
+    let retry_interval = Duration::from_millis(console.retryInterval);
+    let mut link = Link::start(input_rx, event_tx, traffic_tx, audit);
+    let mut next = 0;
+    let mut primary: Option<TcpStream> = None;
+
+    loop {
+      let (index, socket) = match primary.take() {
+        Some(socket) => (0, socket),
+        None => match connect_any(&endpoints, next, connect_timeout).await {
+          Some(connected) => connected,
+          None => {
+            eprintln!("LINK :: no endpoint answered, retrying in {:?}", retry_interval);
+            time::sleep(retry_interval).await;
+            continue;
+          }
+        },
+      };
+      let failback = (console.failback && index > 0).then(|| Failback {
+        endpoint: endpoints[0].clone(),
+        connect_timeout,
+        retry_interval,
+      });
+
+      match link.session(index, endpoints[index].clone(), socket, failback, console.keepalive.clone()).await {
+        SessionEnd::LOST(reason) => {
+          eprintln!("LINK :: lost {} :: {}", endpoints[index], reason);
+          next = (index + 1) % endpoints.len();
+        }
+        SessionEnd::FAILBACK(socket) => {
+          eprintln!("LINK :: primary {} is back, failing back", endpoints[0]);
+          primary = Some(socket);
+        }
+      }
+    }
+  }
+}
 
-    // to_mcs_tx.send(Frame::set_fader_level(3, 744)).await.unwrap();
-    to_mcs_tx.send(Frame::get_console_info()).await.unwrap();
-    // to_mcs_tx.send(Frame::get_fader_pfl(3)).await.unwrap();
-    // to_mcs_tx.send(Frame::set_fader_pfl(3, false)).await.unwrap();
-    // to_mcs_tx.send(Frame::get_fader_label(3)).await.unwrap();
-    // self.set_fader_level(0x01, 1024);
+/// Applies `update` to the link status and reports the result
+async fn update_link<F: FnOnce(&mut LinkStatus)>(link_storage: &LinkStorage, event_tx: &broadcast::Sender<ConsoleEvent>, update: F) {
+  let status = {
+    let mut status = link_storage.lock().await;
+    let connected = status.connected;
+    update(&mut status);
+    if status.connected != connected {
+      status.since = now_millis();
+    }
+    status.clone()
+  };
+  let _ = event_tx.send(ConsoleEvent::LINK(status));
+}
+
+/// Tries every endpoint once, starting at `first` and wrapping around
+async fn connect_any(endpoints: &[String], first: usize, connect_timeout: Duration) -> Option<(usize, TcpStream)> {
+  for offset in 0..endpoints.len() {
+    let index = (first + offset) % endpoints.len();
+    match time::timeout(connect_timeout, TcpStream::connect(&endpoints[index])).await {
+      Ok(Ok(socket)) => return Some((index, socket)),
+      Ok(Err(e)) => eprintln!("LINK :: can't connect to {} :: {}", endpoints[index], e),
+      Err(_) => eprintln!("LINK :: {} didn't answer within {:?}", endpoints[index], connect_timeout),
+    }
+  }
+  None
+}
+
+/// Watches for the primary to come back while connected to a backup
+struct Failback {
+  endpoint: String,
+  connect_timeout: Duration,
+  retry_interval: Duration,
+}
+
+impl Failback {
+  async fn wait(&self) -> TcpStream {
+    loop {
+      time::sleep(self.retry_interval).await;
+      if let Ok(Ok(socket)) = time::timeout(self.connect_timeout, TcpStream::connect(&self.endpoint)).await {
+        return socket;
+      }
+    }
+  }
+}
 
-    let read_manager =  tokio::spawn(async move {
+/// How a session with one endpoint ended
+enum SessionEnd {
+  LOST(String),
+  /// The primary answered again, with the connection to it
+  FAILBACK(TcpStream),
+}
+
+/// Everything that outlives a single connection: the storage, the listeners applying requests
+/// and reports to it, and the queues between them and the socket
+struct Link {
+  to_mcs_tx: ToMcsSender,
+  to_mcs_rx: ToMcsReceiver,
+  from_mcs_tx: mpsc::Sender<Frame>,
+  pending_acks: PendingAcks,
+  desk_info_storage: DeskInfoStorage,
+  faders_storage: FadersStorage,
+  mains_storage: MainsStorage,
+  auxes_storage: AuxesStorage,
+  link_storage: LinkStorage,
+  event_tx: broadcast::Sender<ConsoleEvent>,
+  traffic_tx: TrafficSender,
+}
+
+impl Link {
+  /// Reads and writes frames on `socket` until the link drops or, with `failback`, the primary is back.
+  /// With `keepalive` the link is also dropped when the console stops answering heartbeats.
+  async fn session(
+    &mut self,
+    index: usize,
+    endpoint: String,
+    socket: TcpStream,
+    failback: Option<Failback>,
+    keepalive: Option<KeepaliveConfig>,
+  ) -> SessionEnd {
+    let connection = Connection::new(socket).tap(self.traffic_tx.clone());
+    let (mut read, mut write) = connection.split();
+    eprintln!("LINK :: connected to {}", endpoint);
+
+    // Nothing written on the previous link will be answered anymore
+    self.pending_acks.lock().await.clear();
+    self.reject_queued();
+    // Another endpoint may be another desk, drop what the last one reported before it resyncs
+    if self.link_storage.lock().await.index != index {
+      self.faders_storage.lock().await.clear();
+      self.mains_storage.lock().await.clear();
+      self.auxes_storage.lock().await.clear();
+    }
+    self.set_status(|status| {
+      if status.since != 0 && status.index != index {
+        status.failovers += 1;
+      }
+      status.endpoint = endpoint;
+      status.index = index;
+      status.connected = true;
+      status.health = LinkHealth::GOOD;
+      status.missed = 0;
+      status.roundTrip = RoundTrip::default();
+    }).await;
+
+    // Resync, the console answers the desk info query with its whole state
+    let (info_ack_tx, info_ack_rx) = oneshot::channel();
+    self.pending_acks.lock().await.push_back(Some(info_ack_tx).into());
+    if let Err(e) = write.write_frame(Frame::get_console_info()).await {
+      self.set_status(|status| status.connected = false).await;
+      return SessionEnd::LOST(e);
+    }
+
+    // Probe once the console has answered the desk info query, its state dump comes first
+    let probe_to_mcs_tx = self.to_mcs_tx.clone();
+    let probe_traffic_tx = self.traffic_tx.clone();
+    let probe_pending_acks = self.pending_acks.clone();
+    let probe_desk_info_storage = self.desk_info_storage.clone();
+    let probe_event_tx = self.event_tx.clone();
+    let prober = tokio::spawn(async move {
+      if !matches!(info_ack_rx.await, Ok(Ok(()))) {
+        eprintln!("PROBE :: console didn't ACK the desk info query, not probing");
+        return;
+      }
+      if let Some(capabilities) = probe(&probe_to_mcs_tx, &probe_traffic_tx, &probe_pending_acks).await {
+        let mut deskInfo = get_desk_info(&probe_desk_info_storage).await;
+        deskInfo.capabilities = capabilities;
+        update_desk_info(&probe_desk_info_storage, deskInfo.clone()).await.unwrap();
+        let _ = probe_event_tx.send(ConsoleEvent::DESK(deskInfo));
+      }
+    });
+
+    let from_mcs_tx = self.from_mcs_tx.clone();
+    let read_manager = async move {
       loop {
-        // println!("Before read frame");
-        if let Some(frame) = read.read_frame().await.unwrap() {
-          println!("Reading CSCP frame");
-          from_mcs_tx.send(frame).await.unwrap();
-          // println!("Posted CSCP frame");
+        match read.read_frame().await {
+          Ok(Some(frame)) => {
+            if from_mcs_tx.send(frame).await.is_err() {
+              return String::from("Client stopped");
+            }
+          }
+          Ok(None) => {}
+          Err(e) => return e,
         }
       }
-    });
+    };
 
-    let write_manager = tokio::spawn(async move {
+    let pending_acks = self.pending_acks.clone();
+    let to_mcs_rx = &mut self.to_mcs_rx;
+    let write_manager = async move {
       loop {
-        // println!("Before try recv");
-        if let Some(frame) = to_mcs_rx.recv().await {
-          println!("Forward CSCP MSG {:?}", frame);
-          write.write_frame(frame).await.unwrap();
+        match to_mcs_rx.recv().await {
+          Some((frame, ack)) => {
+            pending_acks.lock().await.push_back(ack);
+            if let Err(e) = write.write_frame(frame).await {
+              return e;
+            }
+          }
+          None => return String::from("Client stopped"),
         }
       }
-    });
+    };
+
+    let heartbeat_to_mcs_tx = self.to_mcs_tx.clone();
+    let heartbeat_link_storage = self.link_storage.clone();
+    let heartbeat_event_tx = self.event_tx.clone();
+    let heartbeat = async move {
+      let keepalive = match keepalive {
+        Some(keepalive) => keepalive,
+        None => return future::pending().await,
+      };
+      let mut heartbeat = Heartbeat::new(&keepalive);
+      loop {
+        match heartbeat.beat(&heartbeat_to_mcs_tx).await {
+          Some(roundTrip) => {
+            if heartbeat_link_storage.lock().await.missed != 0 {
+              eprintln!("LINK :: console answers heartbeats again");
+            }
+            update_link(&heartbeat_link_storage, &heartbeat_event_tx, |status| {
+              status.health = LinkHealth::GOOD;
+              status.missed = 0;
+              status.roundTrip = roundTrip;
+            }).await;
+          }
+          None => {
+            let missed = heartbeat_link_storage.lock().await.missed + 1;
+            eprintln!("LINK :: {} heartbeats missed in a row", missed);
+            let health = if missed >= keepalive.lostAfter {
+              LinkHealth::LOST
+            } else if missed >= keepalive.degradedAfter {
+              LinkHealth::DEGRADED
+            } else {
+              LinkHealth::GOOD
+            };
+            update_link(&heartbeat_link_storage, &heartbeat_event_tx, |status| {
+              status.health = health;
+              status.missed = missed;
+            }).await;
+            if health == LinkHealth::LOST {
+              return format!("{} heartbeats missed", missed);
+            }
+          }
+        }
+      }
+    };
+
+    let primary = async {
+      match &failback {
+        Some(failback) => failback.wait().await,
+        None => future::pending().await,
+      }
+    };
+
+    let end = tokio::select! {
+      reason = read_manager => SessionEnd::LOST(reason),
+      reason = write_manager => SessionEnd::LOST(reason),
+      reason = heartbeat => SessionEnd::LOST(reason),
+      socket = primary => SessionEnd::FAILBACK(socket),
+    };
+    prober.abort();
+    self.set_status(|status| status.connected = false).await;
+    self.reject_queued();
+
+    end
+  }
+
+  /// Answers every frame still queued for the console with NOT_CONNECTED, none of them was written
+  fn reject_queued(&mut self) {
+    let mut rejected = 0;
+    while let Ok((_, pending)) = self.to_mcs_rx.try_recv() {
+      if let Some(ack) = pending.ack {
+        let _ = ack.send(Err(NOT_CONNECTED));
+      }
+      rejected += 1;
+    }
+    if rejected > 0 {
+      eprintln!("LINK :: dropped {} frames queued for the console", rejected);
+    }
+  }
+
+  async fn set_status<F: FnOnce(&mut LinkStatus)>(&self, update: F) {
+    update_link(&self.link_storage, &self.event_tx, update).await;
+  }
+
+  /// Sets up the storage and spawns the listeners, nothing is connected yet
+  fn start(
+    mut input_rx: mpsc::Receiver<Request>,
+    event_tx: broadcast::Sender<ConsoleEvent>,
+    traffic_tx: TrafficSender,
+    audit: ConsoleAudit,
+  ) -> Link {
+    let (to_mcs_tx, to_mcs_rx): (ToMcsSender, ToMcsReceiver) = mpsc::channel(32);
+    let (from_mcs_tx, mut from_mcs_rx): (mpsc::Sender<Frame>, mpsc::Receiver<Frame>) = mpsc::channel(32);
+
+    let faders_storage = FadersStorage::default();
+    let inbound_faders_storage = faders_storage.clone();
+    let desk_info_storage = DeskInfoStorage::default();
+    let inbound_desk_info_storage = desk_info_storage.clone();
+    let mains_storage = MainsStorage::default();
+    let inbound_mains_storage = mains_storage.clone();
+    let auxes_storage = AuxesStorage::default();
+    let inbound_auxes_storage = auxes_storage.clone();
+    let link_storage = LinkStorage::default();
+    let inbound_link_storage = link_storage.clone();
+    let inbound_audit = audit.clone();
+    let pending_acks = PendingAcks::default();
+    let outbound_pending_acks = pending_acks.clone();
+    let link = Link {
+      to_mcs_tx: to_mcs_tx.clone(),
+      to_mcs_rx,
+      from_mcs_tx,
+      pending_acks,
+      desk_info_storage: desk_info_storage.clone(),
+      faders_storage: faders_storage.clone(),
+      mains_storage: mains_storage.clone(),
+      auxes_storage: auxes_storage.clone(),
+      link_storage,
+      event_tx: event_tx.clone(),
+      traffic_tx,
+    };
 
     let inbound_listener = tokio::spawn(async move {
       loop {
         if let Some(req) = input_rx.recv().await {
-          println!("Recv JS request {:?}", req);
+          let (req, ack) = match req {
+            Request::CONFIRMED(req, ack) => (*req, Some(ack)),
+            req => (req, None),
+          };
+          if let Some(command) = req.command() {
+            if !get_desk_info(&inbound_desk_info_storage).await.supports(command) {
+              eprintln!("Console doesn't support {}, dropping {:?}", common::capability::command_name(command), req);
+              if let Some(ack) = ack {
+                let _ = ack.send(Err(UNSUPPORTED));
+              }
+              continue;
+            }
+          }
+          // Only the session writes to the console, a frame queued now would wait for the next
+          // link and go to whichever endpoint that is
+          let writes = req.command().is_some() || matches!(req, Request::SEND_FRAME(_));
+          if writes && !inbound_link_storage.lock().await.connected {
+            eprintln!("Console isn't connected, dropping {:?}", req);
+            if let Some(ack) = ack {
+              let _ = ack.send(Err(NOT_CONNECTED));
+            }
+            continue;
+          }
           match req {
             Request::SET_FADER_LEVEL(msg) => {
-              to_mcs_tx.send(Frame::set_fader_level(msg.index, msg.level)).await.unwrap();
+              let fader = get_fader(&inbound_faders_storage, msg.index).await;
+              record(&inbound_audit, msg.source, BusType::FADER, msg.index, "level", fader.map(|f| f.level), msg.level).await;
+              to_mcs_tx.send((Frame::set_fader_level(msg.index, msg.level), ack.into())).await.unwrap();
             }
             Request::SET_FADER_CUT(msg) => {
-              to_mcs_tx.send(Frame::set_fader_cut(msg.index, msg.isCut)).await.unwrap();
+              let fader = get_fader(&inbound_faders_storage, msg.index).await;
+              record(&inbound_audit, msg.source, BusType::FADER, msg.index, "cut", fader.map(|f| f.isCut), msg.isCut).await;
+              to_mcs_tx.send((Frame::set_fader_cut(msg.index, msg.isCut), ack.into())).await.unwrap();
             }
             Request::SET_FADER_PFL(msg) => {
-              to_mcs_tx.send(Frame::set_fader_pfl(msg.index, msg.isPfl)).await.unwrap();
+              let fader = get_fader(&inbound_faders_storage, msg.index).await;
+              record(&inbound_audit, msg.source, BusType::FADER, msg.index, "pfl", fader.map(|f| f.isPfl), msg.isPfl).await;
+              to_mcs_tx.send((Frame::set_fader_pfl(msg.index, msg.isPfl), ack.into())).await.unwrap();
             }
             Request::SET_MAIN_LEVEL(msg) => {
-              to_mcs_tx.send(Frame::set_main_level(msg.index, msg.level)).await.unwrap();
+              let main = get_main(&inbound_mains_storage, msg.index).await;
+              record(&inbound_audit, msg.source, BusType::MAIN, msg.index, "level", main.map(|m| m.level), msg.level).await;
+              to_mcs_tx.send((Frame::set_main_level(msg.index, msg.level), ack.into())).await.unwrap();
             }
             Request::SET_MAIN_PFL(msg) => {
-              to_mcs_tx.send(Frame::set_main_pfl(msg.index, msg.isPfl)).await.unwrap();
+              let main = get_main(&inbound_mains_storage, msg.index).await;
+              record(&inbound_audit, msg.source, BusType::MAIN, msg.index, "pfl", main.map(|m| m.isPfl), msg.isPfl).await;
+              to_mcs_tx.send((Frame::set_main_pfl(msg.index, msg.isPfl), ack.into())).await.unwrap();
+            }
+            Request::SET_AUX_LEVEL(msg) => {
+              let aux = get_aux(&inbound_auxes_storage, msg.index).await;
+              record(&inbound_audit, msg.source, BusType::AUX, msg.index, "level", aux.map(|a| a.level), msg.level).await;
+              to_mcs_tx.send((Frame::set_aux_level(msg.index, msg.level), ack.into())).await.unwrap();
             }
             Request::GET_DB(sender) => {
               let mut db = DB::default();
@@ -89,6 +453,10 @@ impl CSCPClient {
               let faders = faders.iter().map(|(_, book)| book).cloned().collect();
               db.faders = faders;
 
+              // Mains and auxes
+              db.mains = inbound_mains_storage.lock().await.iter().map(|(_, main)| main).cloned().collect();
+              db.auxes = inbound_auxes_storage.lock().await.iter().map(|(_, aux)| aux).cloned().collect();
+
               // Desk info
               let deskInfo = inbound_desk_info_storage.lock().await;
               let deskInfos: Vec<&DeskInfo> = deskInfo.iter().map(|(_, book)| book).collect();
@@ -96,24 +464,37 @@ impl CSCPClient {
                 db.deskInfo = deskInfo[0].clone();
               }
 
-              sender.send(db).unwrap();
+              db.link = inbound_link_storage.lock().await.clone();
+
+              let _ = sender.send(db);
+            }
+            Request::GET_LINK(sender) => {
+              let _ = sender.send(inbound_link_storage.lock().await.clone());
             }
+            Request::SEND_FRAME(frame) => {
+              to_mcs_tx.send((frame, ack.into())).await.unwrap();
+            }
+            Request::CONFIRMED(..) => {}
           }
         }
       }
     });
 
     let outbound_listener = tokio::spawn(async move {
-      println!("Start listener");
+      eprintln!("Start listener");
       loop {
         if let Some(frame) = from_mcs_rx.recv().await {
-          // println!("CSCP MSG {:?}", msg);
+          // eprintln!("CSCP MSG {:?}", msg);
           match frame.msg {
             Message::ACK(_) => {
-              println!("ACK");
+              if let Some(PendingAck { ack: Some(ack), .. }) = outbound_pending_acks.lock().await.pop_front() {
+                let _ = ack.send(Ok(()));
+              }
             }
             Message::ERR(error) => {
-              println!("Error {}", error.error);
+              if let Some(PendingAck { ack: Some(ack), .. }) = outbound_pending_acks.lock().await.pop_front() {
+                let _ = ack.send(Err(error.error));
+              }
             }
             Message::MSG(data) => {
               let mut buffer = Cursor::new(&data.buffer[..]);
@@ -126,13 +507,16 @@ impl CSCPClient {
                   let faderNum = buffer.get_u16();
                   let level = buffer.get_u16();
 
-                  // println!("FADER LEVEL :: faderNum={} level={}", faderNum, level);
+                  // eprintln!("FADER LEVEL :: faderNum={} level={}", faderNum, level);
                   {
                     let mut fader = get_or_create_fader(&faders_storage, faderNum).await.unwrap();
+                    if fader.level != level {
+                      record(&audit, Source::CONSOLE, BusType::FADER, faderNum, "level", fader.level, level).await;
+                    }
                     fader.level = level;
                     update_fader(&faders_storage, fader.clone()).await.unwrap();
 
-                    fader_event_tx.send(fader).await.unwrap();
+                    let _ = event_tx.send(ConsoleEvent::FADER(fader));
                   }
                 }
                 0x01 => {
@@ -142,13 +526,16 @@ impl CSCPClient {
                   let faderNum = buffer.get_u16();
                   let isCut = buffer.get_u8() != 0;
 
-                  // println!("FADER CUT :: faderNum={} isCut={}", faderNum, isCut);
+                  // eprintln!("FADER CUT :: faderNum={} isCut={}", faderNum, isCut);
                   {
                     let mut fader = get_or_create_fader(&faders_storage, faderNum).await.unwrap();
+                    if fader.isCut != isCut {
+                      record(&audit, Source::CONSOLE, BusType::FADER, faderNum, "cut", fader.isCut, isCut).await;
+                    }
                     fader.isCut = isCut;
                     update_fader(&faders_storage, fader.clone()).await.unwrap();
 
-                    fader_event_tx.send(fader).await.unwrap();
+                    let _ = event_tx.send(ConsoleEvent::FADER(fader));
                   }
                 }
                 0x02 => {
@@ -157,7 +544,17 @@ impl CSCPClient {
                   let faderNum = buffer.get_u16();
                   let level = buffer.get_u16();
             
-                  // println!("MAIN LEVEL :: MN={} level={}", faderNum, level);
+                  // eprintln!("MAIN LEVEL :: MN={} level={}", faderNum, level);
+                  {
+                    let mut main = get_or_create_main(&mains_storage, faderNum).await.unwrap();
+                    if main.level != level {
+                      record(&audit, Source::CONSOLE, BusType::MAIN, faderNum, "level", main.level, level).await;
+                    }
+                    main.level = level;
+                    update_main(&mains_storage, main.clone()).await.unwrap();
+
+                    let _ = event_tx.send(ConsoleEvent::MAIN(main));
+                  }
                 }
                 0x05 => {
                   // Fader PFL
@@ -165,13 +562,16 @@ impl CSCPClient {
                   let faderNum = buffer.get_u16();
                   let isPfl = buffer.get_u8() != 0;
             
-                  // println!("FADER PFL :: faderNum={} isPfl={}", faderNum, isPfl);
+                  // eprintln!("FADER PFL :: faderNum={} isPfl={}", faderNum, isPfl);
                   {
                     let mut fader = get_or_create_fader(&faders_storage, faderNum).await.unwrap();
+                    if fader.isPfl != isPfl {
+                      record(&audit, Source::CONSOLE, BusType::FADER, faderNum, "pfl", fader.isPfl, isPfl).await;
+                    }
                     fader.isPfl = isPfl;
                     update_fader(&faders_storage, fader.clone()).await.unwrap();
 
-                    fader_event_tx.send(fader).await.unwrap();
+                    let _ = event_tx.send(ConsoleEvent::FADER(fader));
                   }
                 }
                 0x07 => {
@@ -179,7 +579,7 @@ impl CSCPClient {
                   // print("Desk name", message[5:-1].decode('utf-8'))
                   let name = String::from_utf8_lossy(&data.buffer[5..data.buffer.len() - 1]).to_string();
             
-                  println!("DESK NAME :: name={}", name);
+                  eprintln!("DESK NAME :: name={}", name);
                 }
                 0x08 => {
                   // Desk Info
@@ -190,10 +590,14 @@ impl CSCPClient {
                   let numMains = buffer.get_u16();
                   let name = String::from_utf8_lossy(&data.buffer[17..data.buffer.len() - 1]).to_string();
             
-                  println!("DESK INFO :: cscpVersion={}, numFaders={}, numMains={}, name={}", cscpVersion, numFaders, numMains, name);
+                  eprintln!("DESK INFO :: cscpVersion={}, numFaders={}, numMains={}, name={}", cscpVersion, numFaders, numMains, name);
 
                   {
-                    update_desk_info(&desk_info_storage, DeskInfo { cscpVersion, numFaders, numMains, name }).await.unwrap();
+                    let capabilities = get_desk_info(&desk_info_storage).await.capabilities;
+                    let deskInfo = DeskInfo { cscpVersion, numFaders, numMains, name, capabilities };
+                    update_desk_info(&desk_info_storage, deskInfo.clone()).await.unwrap();
+
+                    let _ = event_tx.send(ConsoleEvent::DESK(deskInfo));
                   }
                 }
                 0x0B => {
@@ -202,22 +606,35 @@ impl CSCPClient {
                   let faderNum = buffer.get_u16();
                   let label = String::from_utf8_lossy(&data.buffer[7..data.buffer.len() - 1]).to_string();
             
-                  // println!("FADER LABEL :: faderNum={} label={}", faderNum, label);
+                  // eprintln!("FADER LABEL :: faderNum={} label={}", faderNum, label);
                   {
                     let mut fader = get_or_create_fader(&faders_storage, faderNum).await.unwrap();
+                    if fader.label != label {
+                      record(&audit, Source::CONSOLE, BusType::FADER, faderNum, "label", fader.label.clone(), label.clone()).await;
+                    }
                     fader.label = label;
                     update_fader(&faders_storage, fader.clone()).await.unwrap();
 
-                    fader_event_tx.send(fader).await.unwrap();
+                    let _ = event_tx.send(ConsoleEvent::FADER(fader));
                   }
                 }
                 0x0C => {
                   // Main PFL
                   buffer.set_position(5);
                   let faderNum = buffer.get_u16();
-                  let isPfl = buffer.get_u8() == 0;
+                  let isPfl = buffer.get_u8() != 0;
             
-                  // println!("MAIN PFL :: MN={} isPfl={}", faderNum, isPfl);
+                  // eprintln!("MAIN PFL :: MN={} isPfl={}", faderNum, isPfl);
+                  {
+                    let mut main = get_or_create_main(&mains_storage, faderNum).await.unwrap();
+                    if main.isPfl != isPfl {
+                      record(&audit, Source::CONSOLE, BusType::MAIN, faderNum, "pfl", main.isPfl, isPfl).await;
+                    }
+                    main.isPfl = isPfl;
+                    update_main(&mains_storage, main.clone()).await.unwrap();
+
+                    let _ = event_tx.send(ConsoleEvent::MAIN(main));
+                  }
                 }
                 0x0D => {
                   // Main Label
@@ -225,7 +642,17 @@ impl CSCPClient {
                   let faderNum = buffer.get_u16();
                   let label = String::from_utf8_lossy(&data.buffer[7..data.buffer.len() - 1]).to_string();
             
-                  // println!("MAIN LABEL :: MN={} label={}", faderNum, label);
+                  // eprintln!("MAIN LABEL :: MN={} label={}", faderNum, label);
+                  {
+                    let mut main = get_or_create_main(&mains_storage, faderNum).await.unwrap();
+                    if main.label != label {
+                      record(&audit, Source::CONSOLE, BusType::MAIN, faderNum, "label", main.label.clone(), label.clone()).await;
+                    }
+                    main.label = label;
+                    update_main(&mains_storage, main.clone()).await.unwrap();
+
+                    let _ = event_tx.send(ConsoleEvent::MAIN(main));
+                  }
                 }
                 0x10 => {
                   // Aux availability
@@ -243,7 +670,7 @@ impl CSCPClient {
                   let aux7 = auxPage1 >> 6 & 1 != 0;
                   let aux8 = auxPage1 >> 7 & 1 != 0;
 
-                  println!("AUXES :: auxes={:?}", vec![aux1, aux2, aux3, aux4, aux5, aux6, aux7, aux8]);
+                  eprintln!("AUXES :: auxes={:?}", vec![aux1, aux2, aux3, aux4, aux5, aux6, aux7, aux8]);
                 }
                 0x11 => {
                   // Fader format
@@ -252,14 +679,20 @@ impl CSCPClient {
                   let audioType = buffer.get_u8();
                   let audioWidth = buffer.get_u8();
 
-                  // println!("FADER FORMAT :: faderNum={} audioType={} audioWidth={}", faderNum, audioType, audioWidth);
+                  // eprintln!("FADER FORMAT :: faderNum={} audioType={} audioWidth={}", faderNum, audioType, audioWidth);
                   {
                     let mut fader = get_or_create_fader(&faders_storage, faderNum).await.unwrap();
-                    fader.pathType = FromPrimitive::from_u8(audioType).unwrap_or(AudioType::U);
-                    fader.format = FromPrimitive::from_u8(audioWidth).unwrap_or(AudioWidth::NP);
+                    let pathType = FromPrimitive::from_u8(audioType).unwrap_or(AudioType::U);
+                    let format = FromPrimitive::from_u8(audioWidth).unwrap_or(AudioWidth::NP);
+                    if fader.pathType != pathType || fader.format != format {
+                      let oldFormat = format!("{:?}/{:?}", fader.pathType, fader.format);
+                      record(&audit, Source::CONSOLE, BusType::FADER, faderNum, "format", oldFormat, format!("{:?}/{:?}", pathType, format)).await;
+                    }
+                    fader.pathType = pathType;
+                    fader.format = format;
                     update_fader(&faders_storage, fader.clone()).await.unwrap();
 
-                    fader_event_tx.send(fader).await.unwrap();
+                    let _ = event_tx.send(ConsoleEvent::FADER(fader));
                   }
                 }
                 0x13 => {
@@ -267,8 +700,16 @@ impl CSCPClient {
                   buffer.set_position(5);
                   let faderNum = buffer.get_u16();
                   let level = buffer.get_u16();
-
-                  println!("AUX LEVEL :: AUX={} level={}", faderNum, level);
+                  {
+                    let mut aux = get_or_create_aux(&auxes_storage, faderNum).await.unwrap();
+                    if aux.level != level {
+                      record(&audit, Source::CONSOLE, BusType::AUX, faderNum, "level", aux.level, level).await;
+                    }
+                    aux.level = level;
+                    update_aux(&auxes_storage, aux.clone()).await.unwrap();
+
+                    let _ = event_tx.send(ConsoleEvent::AUX(aux));
+                  }
                 }
                 0x14 => {
                   // Main availability
@@ -280,10 +721,10 @@ impl CSCPClient {
                   let main2 = mainPage1 >> 1 & 1 != 0;
                   let main3 = mainPage1 >> 2 & 1 != 0;
 
-                  println!("MAINS :: mains={:?}", vec![main1, main2, main3]);
+                  eprintln!("MAINS :: mains={:?}", vec![main1, main2, main3]);
                 }
                 0x16 => {
-                  // Input?
+                  // Input? Samples are collected in the catalogue
                   buffer.set_position(5);
                   let fader1to4 = buffer.get_u8();
                   // bit 0 = fader 0 L > B
@@ -291,8 +732,26 @@ impl CSCPClient {
                   // but 2 = fader 1 L > B
                   // etc..
 
-                  println!("INPUT :: buffer={:?}", data);
+                  // Treat either leg being routed as the fader being routed to a main
+                  for (byteNum, routes) in data.buffer[5..data.buffer.len() - 1].iter().enumerate() {
+                    for slot in 0..4 {
+                      let faderNum = (byteNum * 4 + slot) as u16;
+                      let isRouted = routes >> (slot * 2) & 0b11 != 0;
+
+                      let fader = get_fader(&faders_storage, faderNum).await;
+                      if let Some(mut fader) = fader {
+                        if fader.isRouted != Some(isRouted) {
+                          record(&audit, Source::CONSOLE, BusType::FADER, faderNum, "routed", fader.isRouted, isRouted).await;
+                          fader.isRouted = Some(isRouted);
+                          update_fader(&faders_storage, fader.clone()).await.unwrap();
+
+                          let _ = event_tx.send(ConsoleEvent::FADER(fader));
+                        }
+                      }
+                    }
+                  }
                 }
+                // Unknown commands end up in the catalogue, see `catalogue::collector::coverage`
                 _ => {}
               }
             }
@@ -301,15 +760,15 @@ impl CSCPClient {
       }
     });
 
-    read_manager.await.unwrap();
-    write_manager.await.unwrap();
-    inbound_listener.await.unwrap();
-    outbound_listener.await.unwrap();
-
-    Ok(CSCPClient)
+    link
   }
 }
 
+async fn get_fader(faders_storage: &FadersStorage, index: u16) -> Option<Fader> {
+  let faders = faders_storage.lock().await;
+  faders.iter().map(|(_, fader)| fader).find(|fader| fader.index == index).cloned()
+}
+
 async fn get_or_create_fader(faders_storage: &FadersStorage, index: u16) -> Result<Fader, ()> {
   let mut faders = faders_storage.lock().await;
 
@@ -319,12 +778,10 @@ async fn get_or_create_fader(faders_storage: &FadersStorage, index: u16) -> Resu
 
   for (_, fader) in faders.iter() {
     if fader.index == index {
-      println!("Return existing fader");
       return Ok(fader.clone());
     }
   }
 
-  println!("Create new fader");
   let fader = Fader::new(index);
   let cloned_fader = fader.clone();
   let entry = faders.vacant_entry();
@@ -348,7 +805,6 @@ async fn update_fader(faders_storage: &FadersStorage, fader: Fader) -> Result<()
     faders.remove(id.unwrap());
   }
 
-  println!("UPDATED FADER :: {:?}", fader);
   
   let entry = faders.vacant_entry();
   entry.insert(fader);
@@ -356,6 +812,67 @@ async fn update_fader(faders_storage: &FadersStorage, fader: Fader) -> Result<()
   Ok(())
 }
 
+async fn get_main(mains_storage: &MainsStorage, index: u16) -> Option<Main> {
+  let mains = mains_storage.lock().await;
+  mains.iter().map(|(_, main)| main).find(|main| main.index == index).cloned()
+}
+
+async fn get_or_create_main(mains_storage: &MainsStorage, index: u16) -> Result<Main, ()> {
+  let mut mains = mains_storage.lock().await;
+
+  if let Some((_, main)) = mains.iter().find(|(_, main)| main.index == index) {
+    return Ok(main.clone());
+  }
+
+  let main = Main::new(index);
+  mains.insert(main.clone());
+  Ok(main)
+}
+
+async fn update_main(mains_storage: &MainsStorage, main: Main) -> Result<(), ()> {
+  let mut mains = mains_storage.lock().await;
+
+  match mains.iter().find(|(_, entry)| entry.index == main.index).map(|(id, _)| id) {
+    Some(id) => mains[id] = main,
+    None => { mains.insert(main); }
+  }
+
+  Ok(())
+}
+
+async fn get_aux(auxes_storage: &AuxesStorage, index: u16) -> Option<Aux> {
+  let auxes = auxes_storage.lock().await;
+  auxes.iter().map(|(_, aux)| aux).find(|aux| aux.index == index).cloned()
+}
+
+async fn get_or_create_aux(auxes_storage: &AuxesStorage, index: u16) -> Result<Aux, ()> {
+  let mut auxes = auxes_storage.lock().await;
+
+  if let Some((_, aux)) = auxes.iter().find(|(_, aux)| aux.index == index) {
+    return Ok(aux.clone());
+  }
+
+  let aux = Aux::new(index);
+  auxes.insert(aux.clone());
+  Ok(aux)
+}
+
+async fn update_aux(auxes_storage: &AuxesStorage, aux: Aux) -> Result<(), ()> {
+  let mut auxes = auxes_storage.lock().await;
+
+  match auxes.iter().find(|(_, entry)| entry.index == aux.index).map(|(id, _)| id) {
+    Some(id) => auxes[id] = aux,
+    None => { auxes.insert(aux); }
+  }
+
+  Ok(())
+}
+
+async fn get_desk_info(desk_info_storage: &DeskInfoStorage) -> DeskInfo {
+  let info = desk_info_storage.lock().await;
+  info.iter().map(|(_, deskInfo)| deskInfo).next().cloned().unwrap_or_else(DeskInfo::default)
+}
+
 async fn update_desk_info(desk_info_storage: &DeskInfoStorage, desk_info: DeskInfo) -> Result<(), ()> {
   let mut info = desk_info_storage.lock().await;
 
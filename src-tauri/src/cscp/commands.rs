@@ -1,77 +1,139 @@
-use common::DB;
+use std::path::Path;
+use common::{DB, Source, link::LinkStatus, template::{verify, TemplateReport}};
 use tauri::{async_runtime::Mutex};
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::mpsc;
 
-use crate::cscp::requests::{SetFaderLevel, SetFaderCut, SetFaderPfl};
+use crate::{cscp::requests::{SetFaderLevel, SetFaderCut, SetFaderPfl}, template};
 
-use super::requests::Request;
+use super::{requests::{Request, get_db, get_link}, selector::{FaderSelector, SelectError}};
 
+/// Request senders of the consoles by id, in config order
 pub struct AsyncProcInputTx {
-  pub inner: Mutex<mpsc::Sender<Request>>,
+  pub inner: Mutex<Vec<(String, mpsc::Sender<Request>)>>,
+}
+
+impl AsyncProcInputTx {
+  /// Sender of `console`, or of the first console when `None`
+  async fn sender(&self, console: Option<String>) -> Result<mpsc::Sender<Request>, String> {
+    let consoles = self.inner.lock().await;
+    match console {
+      Some(console) => consoles.iter()
+        .find(|(id, _)| *id == console)
+        .map(|(_, sender)| sender.clone())
+        .ok_or_else(|| format!("Unknown console {}", console)),
+      None => consoles.first()
+        .map(|(_, sender)| sender.clone())
+        .ok_or_else(|| String::from("No console configured")),
+    }
+  }
+}
+
+/// Index of the fader picked by `label`, looked up on the console as it is now, or else `index`
+async fn fader_index(sender: &mpsc::Sender<Request>, index: Option<u16>, label: Option<String>) -> Result<u16, String> {
+  match (index, label) {
+    (_, Some(label)) => {
+      let selector: FaderSelector = label.parse().map_err(|e: SelectError| e.to_string())?;
+      selector.resolve(&get_db(sender).await?.faders).map_err(|e| e.to_string())
+    }
+    (Some(index), None) => Ok(index),
+    (None, None) => Err(String::from("Either index or label is needed")),
+  }
+}
+
+#[tauri::command]
+pub async fn listConsoles(
+    state: tauri::State<'_, AsyncProcInputTx>,
+) -> Result<Vec<String>, String> {
+    Ok(state.inner.lock().await.iter().map(|(id, _)| id.clone()).collect())
 }
 
 #[tauri::command]
 pub async fn setFaderLevel(
-    index: u16,
+    console: Option<String>,
+    index: Option<u16>,
+    label: Option<String>,
     level: u16,
     state: tauri::State<'_, AsyncProcInputTx>,
 ) -> Result<(), String> {
-    println!("setFaderLevel faderNum={} level={}", index, level);
+    eprintln!("setFaderLevel console={:?} faderNum={:?} label={:?} level={}", console, index, label, level);
     // info!(?message, "js2rs");
-    let async_proc_input_tx = state.inner.lock().await;
+    let async_proc_input_tx = state.sender(console).await?;
+    let index = fader_index(&async_proc_input_tx, index, label).await?;
     async_proc_input_tx
-        .send(Request::SET_FADER_LEVEL(SetFaderLevel { index, level }))
+        .send(Request::SET_FADER_LEVEL(SetFaderLevel { index, level, source: Source::UI }))
         .await
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub async fn setFaderCut(
-    index: u16,
+    console: Option<String>,
+    index: Option<u16>,
+    label: Option<String>,
     isCut: bool,
     state: tauri::State<'_, AsyncProcInputTx>,
 ) -> Result<(), String> {
-    println!("setFaderCut faderNum={} isCut={}", index, isCut);
+    eprintln!("setFaderCut console={:?} faderNum={:?} label={:?} isCut={}", console, index, label, isCut);
     // info!(?message, "js2rs");
-    let async_proc_input_tx = state.inner.lock().await;
+    let async_proc_input_tx = state.sender(console).await?;
+    let index = fader_index(&async_proc_input_tx, index, label).await?;
     async_proc_input_tx
-        .send(Request::SET_FADER_CUT(SetFaderCut { index, isCut }))
+        .send(Request::SET_FADER_CUT(SetFaderCut { index, isCut, source: Source::UI }))
         .await
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub async fn setFaderPfl(
-    index: u16,
+    console: Option<String>,
+    index: Option<u16>,
+    label: Option<String>,
     isPfl: bool,
     state: tauri::State<'_, AsyncProcInputTx>,
 ) -> Result<(), String> {
-    println!("setFaderPfl faderNum={} isPfl={}", index, isPfl);
+    eprintln!("setFaderPfl console={:?} faderNum={:?} label={:?} isPfl={}", console, index, label, isPfl);
     // info!(?message, "js2rs");
-    let async_proc_input_tx = state.inner.lock().await;
+    let async_proc_input_tx = state.sender(console).await?;
+    let index = fader_index(&async_proc_input_tx, index, label).await?;
     async_proc_input_tx
-        .send(Request::SET_FADER_PFL(SetFaderPfl { index, isPfl }))
+        .send(Request::SET_FADER_PFL(SetFaderPfl { index, isPfl, source: Source::UI }))
         .await
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub async fn getDatabase(
+    console: Option<String>,
     state: tauri::State<'_, AsyncProcInputTx>,
 ) -> Result<DB, String> {
-    println!("Send DB");
+    eprintln!("Send DB console={:?}", console);
     // info!(?message, "js2rs");
-    let (single_tx, single_rx) = oneshot::channel();
-    let async_proc_input_tx = state.inner.lock().await;
+    let async_proc_input_tx = state.sender(console).await?;
+    let res = get_db(&async_proc_input_tx).await?;
 
-    async_proc_input_tx
-        .send(Request::GET_DB(single_tx))
-        .await
-        .unwrap();
+    eprintln!("DB {:?}", res);
 
-    let res = single_rx.await.unwrap();
+    Ok(res)
+}
 
-    println!("DB {:?}", res);
+#[tauri::command]
+pub async fn getLinkStatus(
+    console: Option<String>,
+    state: tauri::State<'_, AsyncProcInputTx>,
+) -> Result<LinkStatus, String> {
+    let async_proc_input_tx = state.sender(console).await?;
+    get_link(&async_proc_input_tx).await
+}
 
-    Ok(res)
+#[tauri::command]
+pub async fn verifyTemplate(
+    console: Option<String>,
+    path: String,
+    state: tauri::State<'_, AsyncProcInputTx>,
+) -> Result<TemplateReport, String> {
+    eprintln!("verifyTemplate console={:?} path={}", console, path);
+    let template = template::load(Path::new(&path))?;
+    let async_proc_input_tx = state.sender(console).await?;
+    let db = get_db(&async_proc_input_tx).await?;
+    Ok(verify(&template, &db))
 }
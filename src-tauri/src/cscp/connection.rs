@@ -1,42 +1,64 @@
 use std::{io::{Cursor}};
 use bytes::{BytesMut, Buf};
-use tokio::{net::{TcpStream, tcp::{OwnedReadHalf, OwnedWriteHalf}}};
+use common::inspector::Direction;
+use tokio::{net::{TcpStream, tcp::{OwnedReadHalf, OwnedWriteHalf}}, sync::broadcast};
 use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
 
+use crate::clock::now_millis;
 use super::frame::{Frame, Message, FrameAck, FrameError, FrameMessage};
+
+/// A frame read or written on a tapped connection
+#[derive(Debug, Clone)]
+pub struct Traffic {
+  /// Milliseconds since the unix epoch
+  pub timestamp: u64,
+  pub direction: Direction,
+  pub frame: Frame,
+}
+
+pub type TrafficSender = broadcast::Sender<Traffic>;
+
+fn tee(tap: &Option<TrafficSender>, direction: Direction, frame: &Frame) {
+  if let Some(tap) = tap {
+    let _ = tap.send(Traffic { timestamp: now_millis(), direction, frame: frame.clone() });
+  }
+}
+
 pub struct Connection {
   stream: TcpStream,
   buffer: BytesMut,
+  tap: Option<TrafficSender>,
 }
 
 pub struct ConnectionRead {
   stream: OwnedReadHalf,
   buffer: BytesMut,
+  tap: Option<TrafficSender>,
 }
 
 impl ConnectionRead {
   // @TODO reuse these methods from Connection
   pub async fn read_frame(&mut self) -> Result<Option<Frame>, String> {
     loop {
-      // println!("Before read frame");
+      // eprintln!("Before read frame");
       if let Some(frame) = self.parse_frame()? {
-        // println!("Got frame");
+        // eprintln!("Got frame");
+        tee(&self.tap, Direction::RX, &frame);
         return Ok(Some(frame));
       }
 
-      // println!("No frame yet {:?}", self.buffer);
+      // eprintln!("No frame yet {:?}", self.buffer);
 
       match self.stream.read_buf(&mut self.buffer).await {
         Ok(0) => {
-          println!("buffer empty, stop");
-          panic!("Dead stream mate");
+          eprintln!("buffer empty, stop");
+          return Err(String::from("Connection closed"));
         }
-        Ok(n) => {
-          println!("Read {} bytes from stream", n);
+        Ok(_) => {
           return Ok(None);
         }
         Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
-          // println!("Could block?");
+          // eprintln!("Could block?");
           return Ok(None);
         }
         Err(e) => {
@@ -55,12 +77,12 @@ impl ConnectionRead {
 
         match buf.get_u8() {
           0x04 => {
-            // println!("Read ACK");
+            // eprintln!("Read ACK");
             self.buffer.advance(1);
             return Ok(Some(Frame::new(Message::ACK(FrameAck{}))));
           }
           0x05 => {
-            // println!("Read ERR");
+            // eprintln!("Read ERR");
             let frame = Frame::new(Message::ERR(FrameError{ error: buf.get_u8() }));
 
             self.buffer.advance(2);
@@ -68,8 +90,8 @@ impl ConnectionRead {
             return Ok(Some(frame));
           }
           _ => {
-            // println!("Read MSG");
-            let len = (buf.get_u8() + 4) as usize;
+            // eprintln!("Read MSG");
+            let len = buf.get_u8() as usize + 4;
             buf.set_position(0);
 
             let next_buffer = get_frame(&mut buf, len).to_vec();
@@ -89,18 +111,14 @@ impl ConnectionRead {
 
 pub struct ConnectionWrite {
   stream: OwnedWriteHalf,
+  tap: Option<TrafficSender>,
 }
 
 impl ConnectionWrite {
   pub async fn write_frame(&mut self, frame: Frame) -> Result<(), String> {
-    match frame.msg {
-      Message::MSG(msg) => {
-        self.stream.write_all(&msg.buffer).await.expect("Could not write to stream");
-        self.stream.flush().await.unwrap();
-      }
-      _ => {}
-    }
-
+    self.stream.write_all(&frame.to_bytes()).await.map_err(|e| e.to_string())?;
+    self.stream.flush().await.map_err(|e| e.to_string())?;
+    tee(&self.tap, Direction::TX, &frame);
     Ok(())
   }
 }
@@ -110,12 +128,22 @@ impl Connection {
     Connection {
       stream,
       buffer: BytesMut::with_capacity(4096),
+      tap: None,
     }
   }
 
+  /// Sends a copy of every frame read or written on this connection to `tap`
+  pub fn tap(mut self, tap: TrafficSender) -> Connection {
+    self.tap = Some(tap);
+    self
+  }
+
   pub fn split(self) -> (ConnectionRead, ConnectionWrite) {
     let (rx, tx)  = self.stream.into_split();
-    (ConnectionRead { stream: rx, buffer: self.buffer }, ConnectionWrite { stream: tx })
+    (
+      ConnectionRead { stream: rx, buffer: self.buffer, tap: self.tap.clone() },
+      ConnectionWrite { stream: tx, tap: self.tap },
+    )
   }
 }
 
@@ -0,0 +1,14 @@
+use common::{Aux, DeskInfo, Fader, Main, link::LinkStatus};
+
+/// State changes reported by the console, after they have been applied to the client's storage
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type", content = "data")]
+pub enum ConsoleEvent {
+  FADER(Fader),
+  MAIN(Main),
+  AUX(Aux),
+  /// Desk info, including the capabilities once probed
+  DESK(DeskInfo),
+  /// The link to the console came up, went down or moved to another endpoint
+  LINK(LinkStatus),
+}
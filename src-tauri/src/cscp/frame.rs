@@ -31,6 +31,15 @@ impl Frame {
     Frame { msg }
   }
 
+  /// The frame as it goes over the wire
+  pub fn to_bytes(&self) -> Vec<u8> {
+    match &self.msg {
+      Message::MSG(msg) => msg.buffer.clone(),
+      Message::ACK(_) => vec![0x04],
+      Message::ERR(error) => vec![0x05, error.error],
+    }
+  }
+
   pub fn set_fader_level(fader_number: u16, value: u16) -> Frame {
     let mut fader_number_buffer = BytesMut::with_capacity(2);
     fader_number_buffer.put_u16(fader_number);
@@ -71,6 +80,14 @@ impl Frame {
     Frame::send(vec![0x80, 0x0C], fader_number_buffer.to_vec(), value_buffer.to_vec())
   }
 
+  pub fn set_aux_level(aux_number: u16, value: u16) -> Frame {
+    let mut fader_number_buffer = BytesMut::with_capacity(2);
+    fader_number_buffer.put_u16(aux_number);
+    let mut value_buffer = BytesMut::with_capacity(2);
+    value_buffer.put_u16(value);
+    Frame::send(vec![0x80, 0x13], fader_number_buffer.to_vec(), value_buffer.to_vec())
+  }
+
   pub fn get_console_name() -> Frame {
     Frame::send(vec![0x00, 0x07], vec![], vec![])
   }
@@ -138,7 +155,7 @@ impl Frame {
       _ => {
         if remaining < 4 { return Err("NoFrame".to_string()); }
         src.set_position(1);
-        let len = (src.get_u8() + 4) as usize;
+        let len = src.get_u8() as usize + 4;
         if len <= remaining {
           return Ok(());
         }
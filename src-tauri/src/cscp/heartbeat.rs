@@ -0,0 +1,49 @@
+use std::{collections::VecDeque, time::Duration};
+use common::link::RoundTrip;
+use tokio::{sync::oneshot, time::{self, Instant}};
+
+use crate::config::KeepaliveConfig;
+
+use super::{client::ToMcsSender, frame::Frame};
+
+/// Number of latest heartbeats the round trip figures cover
+const WINDOW: usize = 20;
+
+/// Asks the console for its name at a fixed interval and times the answers
+pub struct Heartbeat {
+  interval: Duration,
+  timeout: Duration,
+  samples: VecDeque<f32>,
+}
+
+impl Heartbeat {
+  pub fn new(config: &KeepaliveConfig) -> Heartbeat {
+    Heartbeat {
+      interval: Duration::from_millis(config.interval),
+      timeout: Duration::from_millis(config.timeout),
+      samples: VecDeque::with_capacity(WINDOW),
+    }
+  }
+
+  /// Waits for the next interval and sends a heartbeat.
+  /// Returns the round trip figures when the console answers in time, `None` when it doesn't.
+  pub async fn beat(&mut self, to_mcs_tx: &ToMcsSender) -> Option<RoundTrip> {
+    time::sleep(self.interval).await;
+
+    let (ack_tx, ack_rx) = oneshot::channel();
+    let sent = Instant::now();
+    to_mcs_tx.send((Frame::get_console_name(), Some(ack_tx).into())).await.ok()?;
+
+    // An ERR shows the console is there just as well as an ACK
+    match time::timeout(self.timeout, ack_rx).await {
+      Ok(Ok(_)) => {
+        if self.samples.len() == WINDOW {
+          self.samples.pop_front();
+        }
+        self.samples.push_back(sent.elapsed().as_secs_f32() * 1000.0);
+        Some(RoundTrip::from_samples(self.samples.make_contiguous()))
+      }
+      _ => None,
+    }
+  }
+}
@@ -1,5 +1,9 @@
 pub mod client;
 pub mod commands;
+pub mod events;
 pub mod requests;
 pub mod frame;
 pub mod connection;
+pub mod probe;
+pub mod heartbeat;
+pub mod selector;
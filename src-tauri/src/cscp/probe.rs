@@ -0,0 +1,98 @@
+use std::time::Duration;
+use common::{capability::*, inspector::Direction};
+use tokio::{sync::{broadcast, oneshot}, time::{self, Instant}};
+
+use super::{client::{PendingAck, PendingAcks, ToMcsSender}, connection::{Traffic, TrafficSender}, frame::{Frame, Message}};
+
+/// Queries probed after connecting. Channel queries ask about the first fader, main or aux.
+const PROBES: [(u8, bool); 13] = [
+  (FADER_LEVEL, true),
+  (FADER_CUT, true),
+  (FADER_PFL, true),
+  (FADER_LABEL, true),
+  (FADER_FORMAT, true),
+  (MAIN_LEVEL, true),
+  (MAIN_PFL, true),
+  (MAIN_LABEL, true),
+  (AUX_LEVEL, true),
+  (DESK_NAME, false),
+  (AUX_AVAILABILITY, false),
+  (MAIN_AVAILABILITY, false),
+  (ROUTING, false),
+];
+
+/// How long the console has to ACK or ERR a query
+const ACK_TIMEOUT: Duration = Duration::from_secs(1);
+/// How long after the ACK a report still counts as the reply
+const REPLY_WINDOW: Duration = Duration::from_millis(300);
+
+/// Sends each known query in turn and records how the console answers it.
+/// Returns `None` when the connection goes away or the console doesn't answer at all.
+pub async fn probe(
+  to_mcs_tx: &ToMcsSender,
+  traffic_tx: &TrafficSender,
+  pending_acks: &PendingAcks,
+) -> Option<Vec<Capability>> {
+  let mut capabilities = vec![];
+
+  for (command, isChannel) in PROBES {
+    let data = if isChannel { 0u16.to_be_bytes().to_vec() } else { vec![] };
+    let mut traffic_rx = traffic_tx.subscribe();
+    let (ack_tx, ack_rx) = oneshot::channel();
+    // Only one query is out at a time, so its command tells its slot apart
+    let id = Some(command as u64);
+    to_mcs_tx.send((Frame::send(vec![0x00, command], data, vec![]), PendingAck { ack: Some(ack_tx), id })).await.ok()?;
+
+    let result = match time::timeout(ACK_TIMEOUT, ack_rx).await {
+      Ok(Ok(Ok(()))) => {
+        if replied(&mut traffic_rx, command).await { ProbeResult::REPLY } else { ProbeResult::ACK }
+      }
+      Ok(Ok(Err(_))) => ProbeResult::ERR,
+      Ok(Err(_)) => return None,
+      Err(_) => {
+        // Nothing will answer the query any more, don't let it take the next frame's ACK.
+        // Other callers' slots stay, the console may still answer those late.
+        {
+          let mut pending = pending_acks.lock().await;
+          if let Some(position) = pending.iter().position(|slot| slot.id == id) {
+            pending.remove(position);
+          }
+        }
+        // Every console answers fader level queries, so nothing is answering probes (e.g. a replay)
+        if capabilities.is_empty() {
+          eprintln!("PROBE :: no answer, leaving every command enabled");
+          return None;
+        }
+        ProbeResult::TIMEOUT
+      }
+    };
+
+    eprintln!("PROBE :: {} {:?}", command_name(command), result);
+    capabilities.push(Capability { command, result });
+  }
+
+  Some(capabilities)
+}
+
+/// Whether a report for the command follows the query's ACK. The console answers in
+/// order, so the first ACK read since the query was sent is the query's.
+async fn replied(traffic_rx: &mut broadcast::Receiver<Traffic>, command: u8) -> bool {
+  let mut acked = false;
+  let deadline = Instant::now() + REPLY_WINDOW;
+
+  loop {
+    let traffic = match time::timeout_at(deadline, traffic_rx.recv()).await {
+      Ok(Ok(traffic)) => traffic,
+      Ok(Err(broadcast::error::RecvError::Lagged(_))) => continue,
+      _ => return false,
+    };
+    if traffic.direction != Direction::RX {
+      continue;
+    }
+    match traffic.frame.msg {
+      Message::ACK(_) => acked = true,
+      Message::MSG(msg) if acked && msg.buffer.get(4) == Some(&command) => return true,
+      _ => {}
+    }
+  }
+}
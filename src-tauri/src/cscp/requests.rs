@@ -1,35 +1,49 @@
-use common::DB;
-use tokio::sync::oneshot;
+use common::{DB, Source, link::LinkStatus, capability::{AUX_LEVEL, FADER_CUT, FADER_LEVEL, FADER_PFL, MAIN_LEVEL, MAIN_PFL}};
+
+use super::frame::Frame;
+use tokio::sync::{mpsc, oneshot};
 
 
 #[derive(Debug, Clone)]
 pub struct SetFaderLevel {
   pub index: u16,
   pub level: u16,
+  pub source: Source,
 }
 
 #[derive(Debug, Clone)]
 pub struct SetFaderCut {
   pub index: u16,
   pub isCut: bool,
+  pub source: Source,
 }
 
 #[derive(Debug, Clone)]
 pub struct SetFaderPfl {
   pub index: u16,
   pub isPfl: bool,
+  pub source: Source,
 }
 
 #[derive(Debug, Clone)]
 pub struct SetMainLevel {
   pub index: u16,
   pub level: u16,
+  pub source: Source,
 }
 
 #[derive(Debug, Clone)]
 pub struct SetMainPfl {
   pub index: u16,
   pub isPfl: bool,
+  pub source: Source,
+}
+
+#[derive(Debug, Clone)]
+pub struct SetAuxLevel {
+  pub index: u16,
+  pub level: u16,
+  pub source: Source,
 }
 
 #[derive(Debug)]
@@ -39,5 +53,58 @@ pub enum Request {
   SET_FADER_PFL(SetFaderPfl),
   SET_MAIN_LEVEL(SetMainLevel),
   SET_MAIN_PFL(SetMainPfl),
+  SET_AUX_LEVEL(SetAuxLevel),
   GET_DB(oneshot::Sender<DB>),
+  /// Link status with heartbeat round trip figures
+  GET_LINK(oneshot::Sender<LinkStatus>),
+  /// Writes a frame to the console as is
+  SEND_FRAME(Frame),
+  /// Runs the wrapped request and reports the console's ACK (`Ok`) or ERR code (`Err`)
+  CONFIRMED(Box<Request>, AckSender),
+}
+
+impl Request {
+  /// Command byte of the frame a set turns into
+  pub fn command(&self) -> Option<u8> {
+    match self {
+      Request::SET_FADER_LEVEL(_) => Some(FADER_LEVEL),
+      Request::SET_FADER_CUT(_) => Some(FADER_CUT),
+      Request::SET_FADER_PFL(_) => Some(FADER_PFL),
+      Request::SET_MAIN_LEVEL(_) => Some(MAIN_LEVEL),
+      Request::SET_MAIN_PFL(_) => Some(MAIN_PFL),
+      Request::SET_AUX_LEVEL(_) => Some(AUX_LEVEL),
+      Request::CONFIRMED(request, _) => request.command(),
+      _ => None,
+    }
+  }
+}
+
+pub type AckSender = oneshot::Sender<Result<(), u8>>;
+
+/// Error code the client answers with itself when a set is for a command the console doesn't
+/// support, nothing is sent to the console
+pub const UNSUPPORTED: u8 = 0xFF;
+/// Error code the client answers with itself when a set arrives while the console isn't
+/// connected, sets aren't kept to be sent on the next link
+pub const NOT_CONNECTED: u8 = 0xFE;
+
+/// Asks the client for a snapshot of the current console state
+pub async fn get_db(input_tx: &mpsc::Sender<Request>) -> Result<DB, String> {
+  let (single_tx, single_rx) = oneshot::channel();
+  input_tx.send(Request::GET_DB(single_tx)).await.map_err(|e| e.to_string())?;
+  single_rx.await.map_err(|e| e.to_string())
+}
+
+/// Asks the client for the state of its link to the console
+pub async fn get_link(input_tx: &mpsc::Sender<Request>) -> Result<LinkStatus, String> {
+  let (single_tx, single_rx) = oneshot::channel();
+  input_tx.send(Request::GET_LINK(single_tx)).await.map_err(|e| e.to_string())?;
+  single_rx.await.map_err(|e| e.to_string())
+}
+
+/// Sends a request and waits for the console to answer it. The inner `Err` is the console's error code.
+pub async fn confirm(input_tx: &mpsc::Sender<Request>, request: Request) -> Result<Result<(), u8>, String> {
+  let (ack_tx, ack_rx) = oneshot::channel();
+  input_tx.send(Request::CONFIRMED(Box::new(request), ack_tx)).await.map_err(|e| e.to_string())?;
+  ack_rx.await.map_err(|e| e.to_string())
 }
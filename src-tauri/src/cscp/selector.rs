@@ -0,0 +1,167 @@
+#![allow(non_camel_case_types)]
+use std::{fmt, str::FromStr};
+use common::Fader;
+use regex::{Regex, RegexBuilder};
+
+/// Picks a fader by its label.
+///
+/// - `re:^PRES \d$` matches a regular expression, case-sensitive unless it says `(?i)`
+/// - `glob:PRES*`, or any text with `*` or `?`, matches a glob ignoring case
+/// - `label:PRES 1`, or any other text, matches the whole label ignoring case
+///
+/// Labels are compared without surrounding spaces.
+#[derive(Debug, Clone)]
+pub struct FaderSelector {
+  text: String,
+  matcher: Matcher,
+}
+
+#[derive(Debug, Clone)]
+enum Matcher {
+  EXACT(String),
+  PATTERN(Regex),
+}
+
+/// A fader by its 1-based number, as shown on the desk, or by label
+#[derive(Debug, Clone)]
+pub enum FaderRef {
+  NUMBER(u16),
+  LABEL(FaderSelector),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectError {
+  /// The selector can't be parsed
+  INVALID(String),
+  NOT_FOUND(String),
+  /// The selector and the number and label of every fader it matches
+  AMBIGUOUS(String, Vec<(u16, String)>),
+}
+
+impl fmt::Display for SelectError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SelectError::INVALID(e) => write!(f, "{}", e),
+      SelectError::NOT_FOUND(text) => write!(f, "No fader labelled \"{}\"", text),
+      SelectError::AMBIGUOUS(text, matches) => {
+        let faders: Vec<String> = matches.iter().map(|(number, label)| format!("{} ({})", number, label)).collect();
+        write!(f, "\"{}\" matches {} faders: {}", text, matches.len(), faders.join(", "))
+      }
+    }
+  }
+}
+
+impl std::error::Error for SelectError {}
+
+impl FromStr for FaderSelector {
+  type Err = SelectError;
+
+  fn from_str(text: &str) -> Result<Self, Self::Err> {
+    let matcher = if let Some(pattern) = text.strip_prefix("re:") {
+      Matcher::PATTERN(Regex::new(pattern).map_err(|e| SelectError::INVALID(format!("Bad regex \"{}\" :: {}", pattern, e)))?)
+    } else if let Some(pattern) = text.strip_prefix("glob:") {
+      glob(pattern)?
+    } else if let Some(label) = text.strip_prefix("label:") {
+      Matcher::EXACT(label.trim().to_lowercase())
+    } else if text.contains(['*', '?']) {
+      glob(text)?
+    } else {
+      Matcher::EXACT(text.trim().to_lowercase())
+    };
+
+    if matches!(&matcher, Matcher::EXACT(label) if label.is_empty()) {
+      return Err(SelectError::INVALID(String::from("Empty fader label")));
+    }
+    Ok(Self { text: text.to_string(), matcher })
+  }
+}
+
+impl FaderSelector {
+  pub fn matches(&self, label: &str) -> bool {
+    let label = label.trim();
+    match &self.matcher {
+      Matcher::EXACT(expected) => label.to_lowercase() == *expected,
+      Matcher::PATTERN(pattern) => pattern.is_match(label),
+    }
+  }
+
+  /// Every fader the selector matches
+  pub fn select<'a>(&self, faders: &'a [Fader]) -> Vec<&'a Fader> {
+    let mut selected: Vec<&Fader> = faders.iter().filter(|fader| self.matches(&fader.label)).collect();
+    selected.sort_by_key(|fader| fader.index);
+    selected
+  }
+
+  /// Index of the one fader the selector matches. Look the labels up just before
+  /// using this so faders renamed on the desk are followed.
+  pub fn resolve(&self, faders: &[Fader]) -> Result<u16, SelectError> {
+    match self.select(faders).as_slice() {
+      [] => Err(SelectError::NOT_FOUND(self.text.clone())),
+      [fader] => Ok(fader.index),
+      selected => Err(SelectError::AMBIGUOUS(
+        self.text.clone(),
+        selected.iter().map(|fader| (fader.index + 1, fader.label.trim().to_string())).collect(),
+      )),
+    }
+  }
+}
+
+impl fmt::Display for FaderSelector {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.text)
+  }
+}
+
+/// A glob as an anchored, case-insensitive regex
+fn glob(pattern: &str) -> Result<Matcher, SelectError> {
+  let mut translated = String::from("^");
+  for c in pattern.trim().chars() {
+    match c {
+      '*' => translated.push_str(".*"),
+      '?' => translated.push('.'),
+      c => translated.push_str(&regex::escape(&c.to_string())),
+    }
+  }
+  translated.push('$');
+
+  RegexBuilder::new(&translated)
+    .case_insensitive(true)
+    .build()
+    .map(Matcher::PATTERN)
+    .map_err(|e| SelectError::INVALID(format!("Bad glob \"{}\" :: {}", pattern, e)))
+}
+
+impl FromStr for FaderRef {
+  type Err = SelectError;
+
+  /// Plain numbers are fader numbers, anything else is a `FaderSelector`
+  fn from_str(text: &str) -> Result<Self, Self::Err> {
+    match text.trim().parse::<u16>() {
+      Ok(number) => Ok(FaderRef::NUMBER(number)),
+      Err(_) => text.parse().map(FaderRef::LABEL),
+    }
+  }
+}
+
+impl FaderRef {
+  /// 0-based index of the fader, with labels looked up in `faders`
+  pub fn index(&self, faders: &[Fader]) -> Result<u16, SelectError> {
+    match self {
+      FaderRef::NUMBER(number) => number.checked_sub(1).ok_or_else(|| SelectError::INVALID(String::from("Numbers start at 1"))),
+      FaderRef::LABEL(selector) => selector.resolve(faders),
+    }
+  }
+
+  pub fn is_label(&self) -> bool {
+    matches!(self, FaderRef::LABEL(_))
+  }
+}
+
+impl fmt::Display for FaderRef {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      FaderRef::NUMBER(number) => write!(f, "{}", number),
+      FaderRef::LABEL(selector) => write!(f, "{}", selector),
+    }
+  }
+}
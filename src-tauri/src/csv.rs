@@ -0,0 +1,14 @@
+/// Joins the fields into one CSV line, quoting any that need it
+pub fn row(fields: &[String]) -> String {
+  let mut line = fields.iter().map(|field| escape(field)).collect::<Vec<String>>().join(",");
+  line.push('\n');
+  line
+}
+
+fn escape(field: &str) -> String {
+  if field.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", field.replace('"', "\"\""))
+  } else {
+    field.to_string()
+  }
+}
@@ -0,0 +1,269 @@
+#![allow(non_snake_case)]
+use std::{collections::{HashMap, VecDeque}, time::Duration};
+use common::{BusType, Fader, Source, level::{db_to_level, level_to_db, MIN_DB}};
+use tokio::{sync::{broadcast, mpsc}, time::{self, Instant}};
+
+use crate::{
+  config::DuckerConfig,
+  cscp::{events::ConsoleEvent, requests::{get_db, Request}, selector::FaderRef},
+  fade::{self, FADE_STEP},
+};
+
+/// How long the console has to report back a level the ducker sent before it counts as a manual move
+const ECHO_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long after the console (re)connects reports are taken as its state dump rather than changes
+const SETTLE_TIME: Duration = Duration::from_secs(3);
+
+struct Fade {
+  from: u16,
+  to: u16,
+  start: Instant,
+  duration: Duration,
+  /// Level last sent
+  level: u16,
+}
+
+/// A fader the ducker has lowered or is bringing back up
+struct Ducked {
+  /// Level it goes back to on release
+  restore: u16,
+  fade: Option<Fade>,
+  /// Levels sent to the console and not reported back yet
+  sent: VecDeque<(u16, Instant)>,
+}
+
+impl Ducked {
+  /// Whether `level` is the console reporting back a level the ducker sent
+  fn is_echo(&mut self, level: u16, now: Instant) -> bool {
+    self.sent.retain(|(_, at)| now.duration_since(*at) < ECHO_TIMEOUT);
+    match self.sent.iter().position(|(sent, _)| *sent == level) {
+      Some(position) => {
+        self.sent.drain(..=position);
+        true
+      }
+      None => false,
+    }
+  }
+}
+
+struct Ducker {
+  config: DuckerConfig,
+  priority: FaderRef,
+  ducked: Vec<FaderRef>,
+  /// Whether a priority fader is open
+  active: bool,
+  faders: HashMap<u16, Ducked>,
+}
+
+impl Ducker {
+  fn new(config: DuckerConfig) -> Result<Ducker, String> {
+    let priority = config.priority.parse::<FaderRef>().map_err(|e| e.to_string())?;
+    let ducked = config.ducked.iter()
+      .map(|fader| fader.parse::<FaderRef>().map_err(|e| e.to_string()))
+      .collect::<Result<Vec<FaderRef>, String>>()?;
+    Ok(Ducker { config, priority, ducked, active: false, faders: HashMap::new() })
+  }
+
+  fn is_priority(&self, fader: &Fader) -> bool {
+    picks(&self.priority, fader)
+  }
+
+  fn is_open(&self, faders: &HashMap<u16, Fader>) -> bool {
+    let threshold = self.config.threshold.unwrap_or(MIN_DB);
+    faders.values().any(|fader| self.is_priority(fader) && !fader.isCut && level_to_db(fader.level) > threshold)
+  }
+
+  /// Level a fader at `level` is ducked to
+  fn ducked_level(&self, level: u16) -> u16 {
+    db_to_level(level_to_db(level) - self.config.depth)
+  }
+
+  fn fade(&mut self, index: u16, from: u16, to: u16, duration: u64, now: Instant) {
+    if let Some(ducked) = self.faders.get_mut(&index) {
+      ducked.fade = Some(Fade { from, to, start: now, duration: Duration::from_millis(duration), level: from });
+    }
+  }
+
+  /// Lowers every ducked fader that is open
+  fn duck(&mut self, faders: &HashMap<u16, Fader>, now: Instant) {
+    eprintln!("DUCKING :: {} ducking", self.config.name);
+    let targets: Vec<&Fader> = faders.values()
+      .filter(|fader| !self.is_priority(fader) && self.ducked.iter().any(|ducked| picks(ducked, fader)))
+      .collect();
+    for fader in targets {
+      // A fader still coming back up keeps the level it had before
+      let restore = match self.faders.get(&fader.index) {
+        Some(ducked) => ducked.restore,
+        None if fader.level == 0 => continue,
+        None => fader.level,
+      };
+      self.faders.entry(fader.index).or_insert(Ducked { restore, fade: None, sent: VecDeque::new() });
+      let to = self.ducked_level(restore);
+      self.fade(fader.index, fader.level, to, self.config.attack, now);
+    }
+  }
+
+  /// Brings every ducked fader back to its restore level
+  fn release(&mut self, faders: &HashMap<u16, Fader>, now: Instant) {
+    eprintln!("DUCKING :: {} releasing", self.config.name);
+    let moves: Vec<(u16, u16, u16)> = self.faders.iter()
+      .filter_map(|(index, ducked)| faders.get(index).map(|fader| (*index, fader.level, ducked.restore)))
+      .collect();
+    for (index, from, to) in moves {
+      self.fade(index, from, to, self.config.release, now);
+    }
+  }
+
+  /// Takes a fader moved on the desk, or by anything but the ducker, as the operator's.
+  /// Fade steps still on their way would move it again, so the level is sent back then.
+  fn touched(&mut self, fader: &Fader, now: Instant) -> Option<Request> {
+    let ducked = self.faders.get_mut(&fader.index)?;
+    if ducked.is_echo(fader.level, now) {
+      return None;
+    }
+    let overtaken = ducked.fade.is_some() && !ducked.sent.is_empty();
+    if self.active {
+      // Where it was put is the new ducked level, it comes back up by the depth from there
+      ducked.fade = None;
+      ducked.restore = if fader.level == 0 { 0 } else { db_to_level(level_to_db(fader.level) + self.config.depth) };
+      eprintln!("DUCKING :: {} fader {} moved, restoring to {}", self.config.name, fader.index + 1, ducked.restore);
+      if overtaken {
+        ducked.sent.push_back((fader.level, now));
+      }
+    } else {
+      self.faders.remove(&fader.index);
+    }
+    overtaken.then(|| fade::level_request(BusType::FADER, fader.index, fader.level, Source::AUTOMATION))
+  }
+
+  /// Moves every fading fader on, returning the levels to send
+  fn step(&mut self, now: Instant) -> Vec<Request> {
+    let mut requests = vec![];
+    let active = self.active;
+    self.faders.retain(|index, ducked| {
+      let fade = match &mut ducked.fade {
+        Some(fade) => fade,
+        None => return active,
+      };
+      let elapsed = now.duration_since(fade.start);
+      let level = fade::level_at(fade.from, fade.to, elapsed, fade.duration);
+      if level != fade.level {
+        fade.level = level;
+        ducked.sent.push_back((level, now));
+        requests.push(fade::level_request(BusType::FADER, *index, level, Source::AUTOMATION));
+      }
+      if elapsed < fade.duration {
+        return true;
+      }
+      ducked.fade = None;
+      // Released faders are back where they were and left to the operator
+      active
+    });
+    requests
+  }
+}
+
+/// Whether `fader` picks this fader, by number or label
+fn picks(fader: &FaderRef, candidate: &Fader) -> bool {
+  match fader {
+    FaderRef::NUMBER(number) => candidate.index + 1 == *number,
+    FaderRef::LABEL(selector) => selector.matches(&candidate.label),
+  }
+}
+
+struct Ducking {
+  duckers: Vec<Ducker>,
+  faders: HashMap<u16, Fader>,
+  connected: bool,
+  settleUntil: Option<Instant>,
+}
+
+impl Ducking {
+  /// Takes in a console change, returning the levels to send back
+  fn handle(&mut self, event: ConsoleEvent, now: Instant) -> Vec<Request> {
+    let mut requests = vec![];
+    match event {
+      ConsoleEvent::FADER(fader) => {
+        // Cut, PFL and label changes are reported with the fader too
+        if self.faders.get(&fader.index).is_some_and(|old| old.level != fader.level) {
+          requests.extend(self.duckers.iter_mut().filter_map(|ducker| ducker.touched(&fader, now)));
+        }
+        self.faders.insert(fader.index, fader);
+      }
+      ConsoleEvent::LINK(link) => {
+        if link.connected && !self.connected {
+          self.settleUntil = Some(now + SETTLE_TIME);
+        }
+        self.connected = link.connected;
+        return requests;
+      }
+      _ => return requests,
+    }
+    self.update(now);
+    requests
+  }
+
+  /// Ducks or releases the duckers whose priority faders opened or closed
+  fn update(&mut self, now: Instant) {
+    let settling = self.settleUntil.is_some_and(|until| now < until);
+    let Ducking { duckers, faders, .. } = self;
+    for ducker in duckers.iter_mut() {
+      let open = ducker.is_open(faders);
+      if open == ducker.active {
+        continue;
+      }
+      ducker.active = open;
+      // A priority fader open on (re)connecting may have ducked the others already
+      if settling {
+        continue;
+      }
+      if open {
+        ducker.duck(faders, now);
+      } else {
+        ducker.release(faders, now);
+      }
+    }
+  }
+
+  fn step(&mut self, now: Instant) -> Vec<Request> {
+    self.duckers.iter_mut().flat_map(|ducker| ducker.step(now)).collect()
+  }
+}
+
+/// Lowers each ducker's faders while its priority fader is open and brings them back after
+pub async fn run(configs: Vec<DuckerConfig>, input_tx: mpsc::Sender<Request>, mut event_rx: broadcast::Receiver<ConsoleEvent>) -> Result<(), String> {
+  let mut duckers = vec![];
+  for config in configs {
+    match Ducker::new(config.clone()) {
+      Ok(ducker) => duckers.push(ducker),
+      Err(e) => eprintln!("DUCKING :: {} is off :: {}", config.name, e),
+    }
+  }
+
+  let db = get_db(&input_tx).await?;
+  let mut ducking = Ducking {
+    duckers,
+    faders: db.faders.into_iter().map(|fader| (fader.index, fader)).collect(),
+    connected: db.link.connected,
+    settleUntil: Some(Instant::now() + SETTLE_TIME),
+  };
+  ducking.update(Instant::now());
+  let mut tick = time::interval(FADE_STEP);
+
+  loop {
+    let requests = tokio::select! {
+      event = event_rx.recv() => match event {
+        Ok(event) => ducking.handle(event, Instant::now()),
+        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+          eprintln!("DUCKING :: missed {} changes", skipped);
+          continue;
+        }
+        Err(broadcast::error::RecvError::Closed) => return Ok(()),
+      },
+      _ = tick.tick() => ducking.step(Instant::now()),
+    };
+    for request in requests {
+      input_tx.send(request).await.map_err(|e| e.to_string())?;
+    }
+  }
+}
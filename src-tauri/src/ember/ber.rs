@@ -0,0 +1,341 @@
+#![allow(non_snake_case)]
+//! The subset of BER (X.690) used by Glow: single byte tags, definite lengths
+//! when encoding, definite and indefinite lengths when decoding.
+
+pub const BOOLEAN: u8 = 0x01;
+pub const INTEGER: u8 = 0x02;
+pub const REAL: u8 = 0x09;
+pub const UTF8_STRING: u8 = 0x0C;
+pub const RELATIVE_OID: u8 = 0x0D;
+pub const SET: u8 = 0x31;
+
+/// Indefinite lengths nested deeper than this are refused rather than read on the stack
+pub const MAX_DEPTH: usize = 64;
+/// Length bytes after the first, enough for any length that fits in a u64
+const MAX_LENGTH_BYTES: usize = 8;
+
+pub const fn application(number: u8) -> u8 {
+  0x60 | number
+}
+
+pub const fn context(number: u8) -> u8 {
+  0xA0 | number
+}
+
+#[derive(Debug, Clone)]
+pub struct Tlv<'a> {
+  pub tag: u8,
+  pub value: &'a [u8],
+}
+
+pub fn tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+  let mut encoded = vec![tag];
+  let len = value.len();
+  if len < 0x80 {
+    encoded.push(len as u8);
+  } else {
+    let bytes: Vec<u8> = len.to_be_bytes().iter().copied().skip_while(|&b| b == 0).collect();
+    encoded.push(0x80 | bytes.len() as u8);
+    encoded.extend(bytes);
+  }
+  encoded.extend_from_slice(value);
+  encoded
+}
+
+pub fn integer(value: i64) -> Vec<u8> {
+  tlv(INTEGER, &integer_value(value))
+}
+
+pub fn boolean(value: bool) -> Vec<u8> {
+  tlv(BOOLEAN, &[if value { 0xFF } else { 0x00 }])
+}
+
+pub fn utf8(value: &str) -> Vec<u8> {
+  tlv(UTF8_STRING, value.as_bytes())
+}
+
+pub fn real(value: f64) -> Vec<u8> {
+  tlv(REAL, &real_value(value))
+}
+
+pub fn relative_oid(path: &[u32]) -> Vec<u8> {
+  let mut encoded = vec![];
+  for &id in path {
+    let mut groups = vec![(id & 0x7F) as u8];
+    let mut rest = id >> 7;
+    while rest > 0 {
+      groups.push(0x80 | (rest & 0x7F) as u8);
+      rest >>= 7;
+    }
+    encoded.extend(groups.iter().rev());
+  }
+  tlv(RELATIVE_OID, &encoded)
+}
+
+/// Shortest two's complement encoding
+fn integer_value(value: i64) -> Vec<u8> {
+  let bytes = value.to_be_bytes();
+  let mut start = 0;
+  while start < 7
+    && ((bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0) || (bytes[start] == 0xFF && bytes[start + 1] & 0x80 != 0))
+  {
+    start += 1;
+  }
+  bytes[start..].to_vec()
+}
+
+/// Binary (base 2) encoding
+fn real_value(value: f64) -> Vec<u8> {
+  if value == 0.0 {
+    return vec![];
+  }
+  if value.is_nan() {
+    return vec![0x42];
+  }
+  if value.is_infinite() {
+    return vec![if value > 0.0 { 0x40 } else { 0x41 }];
+  }
+
+  let bits = value.to_bits();
+  let sign = (bits >> 63) as u8;
+  let exponentBits = ((bits >> 52) & 0x7FF) as i64;
+  let fraction = bits & 0x000F_FFFF_FFFF_FFFF;
+  let (mut mantissa, mut exponent) = if exponentBits == 0 {
+    (fraction, -1074)
+  } else {
+    (fraction | 1 << 52, exponentBits - 1075)
+  };
+  while mantissa & 1 == 0 {
+    mantissa >>= 1;
+    exponent += 1;
+  }
+
+  let exponentBytes = integer_value(exponent);
+  let mut encoded = vec![0x80 | sign << 6 | (exponentBytes.len() as u8 - 1)];
+  encoded.extend(exponentBytes);
+  encoded.extend(mantissa.to_be_bytes().iter().skip_while(|&&b| b == 0));
+  encoded
+}
+
+/// Reads consecutive TLVs filling `data`
+pub fn read_all(data: &[u8]) -> Result<Vec<Tlv<'_>>, String> {
+  let mut items = vec![];
+  let mut position = 0;
+  while position < data.len() {
+    let (item, next) = read(data, position)?;
+    items.push(item);
+    position = next;
+  }
+  Ok(items)
+}
+
+/// Reads the TLV at `position`, returning it and the position after it
+pub fn read(data: &[u8], position: usize) -> Result<(Tlv<'_>, usize), String> {
+  read_nested(data, position, 0)
+}
+
+fn read_nested(data: &[u8], position: usize, depth: usize) -> Result<(Tlv<'_>, usize), String> {
+  let tag = *data.get(position).ok_or("Missing tag")?;
+  let mut position = position + 1;
+
+  if tag & 0x1F == 0x1F {
+    // Multi byte tag number, not used by Glow so the number itself is dropped
+    while *data.get(position).ok_or("Truncated tag")? & 0x80 != 0 {
+      position += 1;
+    }
+    position += 1;
+  }
+
+  let first = *data.get(position).ok_or("Missing length")?;
+  position += 1;
+
+  if first == 0x80 {
+    if depth >= MAX_DEPTH {
+      return Err(format!("Nested deeper than {}", MAX_DEPTH));
+    }
+    // Indefinite length, runs until the end-of-contents marker
+    let start = position;
+    loop {
+      if data.get(position..position + 2) == Some(&[0x00, 0x00]) {
+        return Ok((Tlv { tag, value: &data[start..position] }, position + 2));
+      }
+      let (_, next) = read_nested(data, position, depth + 1)?;
+      position = next;
+    }
+  }
+
+  let len = if first & 0x80 == 0 {
+    first as usize
+  } else {
+    let count = (first & 0x7F) as usize;
+    if count > MAX_LENGTH_BYTES {
+      return Err(format!("Length of {} bytes", count));
+    }
+    let end = position.checked_add(count).ok_or("Value overruns data")?;
+    let bytes = data.get(position..end).ok_or("Truncated length")?;
+    position = end;
+    let len = bytes.iter().fold(0u64, |len, &b| len << 8 | b as u64);
+    usize::try_from(len).map_err(|_| "Value overruns data")?
+  };
+
+  let end = position.checked_add(len).ok_or("Value overruns data")?;
+  let value = data.get(position..end).ok_or("Value overruns data")?;
+  Ok((Tlv { tag, value }, end))
+}
+
+pub fn read_integer(value: &[u8]) -> i64 {
+  if value.is_empty() {
+    return 0;
+  }
+  let initial: i64 = if value[0] & 0x80 != 0 { -1 } else { 0 };
+  value.iter().fold(initial, |acc, &b| acc << 8 | b as i64)
+}
+
+pub fn read_boolean(value: &[u8]) -> bool {
+  value.iter().any(|&b| b != 0)
+}
+
+pub fn read_utf8(value: &[u8]) -> String {
+  String::from_utf8_lossy(value).to_string()
+}
+
+pub fn read_relative_oid(value: &[u8]) -> Vec<u32> {
+  let mut path = vec![];
+  let mut current = 0u32;
+  for &b in value {
+    current = current << 7 | (b & 0x7F) as u32;
+    if b & 0x80 == 0 {
+      path.push(current);
+      current = 0;
+    }
+  }
+  path
+}
+
+pub fn read_real(value: &[u8]) -> Result<f64, String> {
+  let first = match value.first() {
+    None => return Ok(0.0),
+    Some(&first) => first,
+  };
+
+  match first {
+    0x40 => return Ok(f64::INFINITY),
+    0x41 => return Ok(f64::NEG_INFINITY),
+    0x42 => return Ok(f64::NAN),
+    0x43 => return Ok(-0.0),
+    _ => {}
+  }
+  if first & 0x80 == 0 {
+    return Err(String::from("Decimal reals aren't supported"));
+  }
+
+  let sign = if first & 0x40 != 0 { -1.0 } else { 1.0 };
+  let baseBits = match first >> 4 & 0x03 {
+    0 => 1,
+    1 => 3,
+    2 => 4,
+    _ => return Err(String::from("Bad real base")),
+  };
+  let scale = (first >> 2 & 0x03) as i32;
+
+  let (exponentLen, start) = match first & 0x03 {
+    3 => (*value.get(1).ok_or("Truncated real")? as usize, 2),
+    n => (n as usize + 1, 1),
+  };
+  let exponentBytes = value.get(start..start + exponentLen).ok_or("Truncated real")?;
+  let exponent = i32::try_from(read_integer(exponentBytes)).map_err(|_| "Value overruns data")?;
+  let power = exponent.checked_mul(baseBits).and_then(|power| power.checked_add(scale)).ok_or("Value overruns data")?;
+  let mantissa = value[start + exponentLen..].iter().fold(0u64, |acc, &b| acc << 8 | b as u64);
+
+  // Applied in two halves, as 2^power alone underflows to zero for the smallest subnormals
+  Ok(sign * mantissa as f64 * 2f64.powi(power / 2) * 2f64.powi(power - power / 2))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn read_one(data: &[u8]) -> Tlv<'_> {
+    let (tlv, end) = read(data, 0).unwrap();
+    assert_eq!(end, data.len());
+    tlv
+  }
+
+  #[test]
+  fn round_trips_integers() {
+    for value in [0, 1, -1, 127, 128, -128, -129, 65535, i64::MIN, i64::MAX] {
+      let encoded = integer(value);
+      let tlv = read_one(&encoded);
+      assert_eq!((tlv.tag, read_integer(tlv.value)), (INTEGER, value));
+    }
+    assert_eq!(integer(128), [INTEGER, 2, 0x00, 0x80]);
+  }
+
+  #[test]
+  fn round_trips_reals() {
+    for value in [0.0, 1.0, -1.5, 0.1, 1023.0, -96.25, 1e300, 5e-324, f64::INFINITY, f64::NEG_INFINITY] {
+      assert_eq!(read_real(read_one(&real(value)).value), Ok(value));
+    }
+    assert!(read_real(read_one(&real(f64::NAN)).value).unwrap().is_nan());
+  }
+
+  #[test]
+  fn round_trips_strings_booleans_and_paths() {
+    assert_eq!(read_utf8(read_one(&utf8("MIC 1 ü")).value), "MIC 1 ü");
+    assert!(read_boolean(read_one(&boolean(true)).value));
+    assert!(!read_boolean(read_one(&boolean(false)).value));
+    let path = [1, 2, 127, 128, 65537, u32::MAX];
+    assert_eq!(read_relative_oid(read_one(&relative_oid(&path)).value), path);
+  }
+
+  #[test]
+  fn round_trips_long_lengths() {
+    let value = vec![0x55; 300];
+    let encoded = tlv(application(1), &value);
+    assert_eq!(encoded[..4], [application(1), 0x82, 0x01, 0x2C]);
+    assert_eq!(read_one(&encoded).value, value);
+  }
+
+  #[test]
+  fn reads_indefinite_lengths() {
+    let inner = integer(5);
+    let mut data = vec![context(0), 0x80];
+    data.extend(&inner);
+    data.extend([0x00, 0x00]);
+    data.extend(boolean(true));
+    let items = read_all(&data).unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0].value, inner);
+    assert_eq!(items[1].tag, BOOLEAN);
+  }
+
+  #[test]
+  fn refuses_malformed_input() {
+    // Truncated tag, length and value
+    assert!(read(&[], 0).is_err());
+    assert!(read(&[0x1F, 0x81], 0).is_err());
+    assert!(read(&[INTEGER], 0).is_err());
+    assert!(read(&[INTEGER, 0x82, 0x01], 0).is_err());
+    assert!(read(&[INTEGER, 3, 1, 2], 0).is_err());
+    // Indefinite length never ended
+    assert!(read(&[context(0), 0x80, INTEGER, 1, 1], 0).is_err());
+    // Lengths too long to hold, or that run past the end of memory
+    assert!(read(&[INTEGER, 0x89, 1, 0, 0, 0, 0, 0, 0, 0, 0], 0).is_err());
+    assert!(read(&[INTEGER, 0x88, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0], 0).is_err());
+    // Exponent past i32
+    assert!(read_real(&[0x83, 5, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 1]).is_err());
+    assert!(read_real(&[0x01]).is_err());
+    assert!(read_real(&[0x81, 0x01]).is_err());
+  }
+
+  #[test]
+  fn refuses_deep_nesting() {
+    let nested = |depth: usize| {
+      let mut data = [context(0), 0x80].repeat(depth);
+      data.extend([0x00, 0x00].repeat(depth));
+      data
+    };
+    assert!(read(&nested(MAX_DEPTH), 0).is_ok());
+    assert!(read(&nested(MAX_DEPTH + 1), 0).is_err());
+  }
+}
@@ -0,0 +1,248 @@
+#![allow(non_camel_case_types)]
+//! The parts of the Glow DTD a provider needs: reading GetDirectory and value
+//! changes from consumers, writing nodes and parameters back.
+
+use super::ber::{self, application, context, read, read_all, Tlv, MAX_DEPTH};
+
+const ROOT: u8 = application(0);
+const PARAMETER: u8 = application(1);
+const COMMAND: u8 = application(2);
+const NODE: u8 = application(3);
+const ELEMENT_COLLECTION: u8 = application(4);
+const QUALIFIED_PARAMETER: u8 = application(9);
+const QUALIFIED_NODE: u8 = application(10);
+const ROOT_ELEMENT_COLLECTION: u8 = application(11);
+
+const COMMAND_GET_DIRECTORY: i64 = 32;
+
+const ACCESS_READ: i64 = 1;
+const ACCESS_READ_WRITE: i64 = 3;
+
+const TYPE_INTEGER: i64 = 1;
+const TYPE_REAL: i64 = 2;
+const TYPE_STRING: i64 = 3;
+const TYPE_BOOLEAN: i64 = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GlowValue {
+  INT(i64),
+  REAL(f64),
+  STRING(String),
+  BOOL(bool),
+}
+
+impl GlowValue {
+  pub fn as_f64(&self) -> Option<f64> {
+    match self {
+      GlowValue::INT(value) => Some(*value as f64),
+      GlowValue::REAL(value) => Some(*value),
+      GlowValue::BOOL(value) => Some(*value as i64 as f64),
+      GlowValue::STRING(value) => value.parse().ok(),
+    }
+  }
+
+  pub fn as_bool(&self) -> Option<bool> {
+    match self {
+      GlowValue::BOOL(value) => Some(*value),
+      GlowValue::STRING(value) => value.parse().ok(),
+      value => value.as_f64().map(|value| value != 0.0),
+    }
+  }
+
+  fn encode(&self) -> Vec<u8> {
+    match self {
+      GlowValue::INT(value) => ber::integer(*value),
+      GlowValue::REAL(value) => ber::real(*value),
+      GlowValue::STRING(value) => ber::utf8(value),
+      GlowValue::BOOL(value) => ber::boolean(*value),
+    }
+  }
+
+  fn type_id(&self) -> i64 {
+    match self {
+      GlowValue::INT(_) => TYPE_INTEGER,
+      GlowValue::REAL(_) => TYPE_REAL,
+      GlowValue::STRING(_) => TYPE_STRING,
+      GlowValue::BOOL(_) => TYPE_BOOLEAN,
+    }
+  }
+
+  fn decode(tlv: &Tlv) -> Result<GlowValue, String> {
+    match tlv.tag {
+      ber::INTEGER => Ok(GlowValue::INT(ber::read_integer(tlv.value))),
+      ber::REAL => Ok(GlowValue::REAL(ber::read_real(tlv.value)?)),
+      ber::UTF8_STRING => Ok(GlowValue::STRING(ber::read_utf8(tlv.value))),
+      ber::BOOLEAN => Ok(GlowValue::BOOL(ber::read_boolean(tlv.value))),
+      tag => Err(format!("Unsupported value type {:#04x}", tag)),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GlowRequest {
+  GET_DIRECTORY(Vec<u32>),
+  SET_VALUE(Vec<u32>, GlowValue),
+}
+
+#[derive(Debug, Clone)]
+pub struct NodeContents {
+  pub identifier: String,
+  pub description: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParameterContents {
+  pub identifier: String,
+  pub description: Option<String>,
+  pub value: GlowValue,
+  pub minimum: Option<GlowValue>,
+  pub maximum: Option<GlowValue>,
+  pub writable: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum GlowElement {
+  NODE {
+    path: Vec<u32>,
+    contents: Option<NodeContents>,
+    children: Vec<GlowElement>,
+  },
+  PARAMETER {
+    path: Vec<u32>,
+    contents: ParameterContents,
+  },
+}
+
+/// Reads every request in a Glow message
+pub fn decode(data: &[u8]) -> Result<Vec<GlowRequest>, String> {
+  let (root, _) = read(data, 0)?;
+  if root.tag != ROOT {
+    return Err(format!("Unexpected root tag {:#04x}", root.tag));
+  }
+
+  let mut requests = vec![];
+  for collection in read_all(root.value)? {
+    if collection.tag != ROOT_ELEMENT_COLLECTION {
+      continue;
+    }
+    for element in elements(collection.value)? {
+      walk(&element, &[], &mut requests, 0)?;
+    }
+  }
+  Ok(requests)
+}
+
+/// Unwraps the `[0]` around each entry of an element collection
+fn elements(data: &[u8]) -> Result<Vec<Tlv<'_>>, String> {
+  let mut items = vec![];
+  for wrapper in read_all(data)? {
+    if wrapper.tag == context(0) {
+      items.push(read(wrapper.value, 0)?.0);
+    }
+  }
+  Ok(items)
+}
+
+fn walk(element: &Tlv, parent: &[u32], requests: &mut Vec<GlowRequest>, depth: usize) -> Result<(), String> {
+  if depth >= MAX_DEPTH {
+    return Err(format!("Elements nested deeper than {}", MAX_DEPTH));
+  }
+  let fields = read_all(element.value)?;
+  let field = |n: u8| fields.iter().find(|field| field.tag == context(n)).map(|field| read(field.value, 0)).transpose();
+
+  match element.tag {
+    COMMAND => {
+      let number = field(0)?.map(|(tlv, _)| ber::read_integer(tlv.value));
+      if number == Some(COMMAND_GET_DIRECTORY) {
+        requests.push(GlowRequest::GET_DIRECTORY(parent.to_vec()));
+      }
+      return Ok(());
+    }
+    NODE | PARAMETER | QUALIFIED_NODE | QUALIFIED_PARAMETER => {}
+    _ => return Ok(()),
+  }
+
+  let path = match (element.tag, field(0)?) {
+    (QUALIFIED_NODE | QUALIFIED_PARAMETER, Some((tlv, _))) => ber::read_relative_oid(tlv.value),
+    (_, Some((tlv, _))) => {
+      let mut path = parent.to_vec();
+      path.push(ber::read_integer(tlv.value) as u32);
+      path
+    }
+    (_, None) => return Err(String::from("Element without a number or path")),
+  };
+
+  if matches!(element.tag, PARAMETER | QUALIFIED_PARAMETER) {
+    if let Some((contents, _)) = field(1)? {
+      for item in read_all(contents.value)? {
+        if item.tag == context(2) {
+          let (value, _) = read(item.value, 0)?;
+          requests.push(GlowRequest::SET_VALUE(path.clone(), GlowValue::decode(&value)?));
+        }
+      }
+    }
+  }
+
+  if let Some((children, _)) = field(2)? {
+    if children.tag == ELEMENT_COLLECTION {
+      for child in elements(children.value)? {
+        walk(&child, &path, requests, depth + 1)?;
+      }
+    }
+  }
+
+  Ok(())
+}
+
+/// Wraps top level elements into a Glow message
+pub fn encode(elements: &[GlowElement]) -> Vec<u8> {
+  let collection: Vec<u8> = elements.iter().flat_map(|element| ber::tlv(context(0), &encode_element(element, true))).collect();
+  ber::tlv(ROOT, &ber::tlv(ROOT_ELEMENT_COLLECTION, &collection))
+}
+
+/// Top level elements are written with their full path, children with just their number
+fn encode_element(element: &GlowElement, qualified: bool) -> Vec<u8> {
+  let (tag, path, mut fields) = match element {
+    GlowElement::NODE { path, contents, children } => {
+      let mut fields = vec![];
+      if let Some(contents) = contents {
+        let mut set = ber::tlv(context(0), &ber::utf8(&contents.identifier));
+        if let Some(description) = &contents.description {
+          set.extend(ber::tlv(context(1), &ber::utf8(description)));
+        }
+        fields.extend(ber::tlv(context(1), &ber::tlv(ber::SET, &set)));
+      }
+      if !children.is_empty() {
+        let collection: Vec<u8> = children.iter().flat_map(|child| ber::tlv(context(0), &encode_element(child, false))).collect();
+        fields.extend(ber::tlv(context(2), &ber::tlv(ELEMENT_COLLECTION, &collection)));
+      }
+      (if qualified { QUALIFIED_NODE } else { NODE }, path, fields)
+    }
+    GlowElement::PARAMETER { path, contents } => {
+      let mut set = ber::tlv(context(0), &ber::utf8(&contents.identifier));
+      if let Some(description) = &contents.description {
+        set.extend(ber::tlv(context(1), &ber::utf8(description)));
+      }
+      set.extend(ber::tlv(context(2), &contents.value.encode()));
+      if let Some(minimum) = &contents.minimum {
+        set.extend(ber::tlv(context(3), &minimum.encode()));
+      }
+      if let Some(maximum) = &contents.maximum {
+        set.extend(ber::tlv(context(4), &maximum.encode()));
+      }
+      let access = if contents.writable { ACCESS_READ_WRITE } else { ACCESS_READ };
+      set.extend(ber::tlv(context(5), &ber::integer(access)));
+      set.extend(ber::tlv(context(13), &ber::integer(contents.value.type_id())));
+      let fields = ber::tlv(context(1), &ber::tlv(ber::SET, &set));
+      (if qualified { QUALIFIED_PARAMETER } else { PARAMETER }, path, fields)
+    }
+  };
+
+  let mut encoded = if qualified {
+    ber::tlv(context(0), &ber::relative_oid(path))
+  } else {
+    ber::tlv(context(0), &ber::integer(*path.last().unwrap_or(&0) as i64))
+  };
+  encoded.append(&mut fields);
+  ber::tlv(tag, &encoded)
+}
@@ -0,0 +1,5 @@
+pub mod ber;
+pub mod glow;
+pub mod provider;
+pub mod s101;
+pub mod tree;
@@ -0,0 +1,109 @@
+use std::{io, net::SocketAddr};
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpListener, TcpStream}, sync::{broadcast, mpsc}};
+
+use crate::{config::EmberConfig, cscp::{events::ConsoleEvent, requests::{get_db, Request}}};
+use super::{glow::{self, GlowElement, GlowRequest}, s101::{self, S101Decoder, S101Message}, tree};
+
+/// Ember+ provider over S101/TCP. Consumers browse the tree described in
+/// [`tree`], can change writable parameters and receive every console change.
+pub async fn run(config: EmberConfig, input_tx: mpsc::Sender<Request>, event_tx: broadcast::Sender<ConsoleEvent>) -> io::Result<()> {
+  let listener = TcpListener::bind(&config.bind).await?;
+  eprintln!("EMBER :: listening on {}", config.bind);
+
+  loop {
+    let (stream, peer) = listener.accept().await?;
+    eprintln!("EMBER :: consumer connected {}", peer);
+
+    let identifier = config.identifier.clone();
+    let input_tx = input_tx.clone();
+    let event_rx = event_tx.subscribe();
+    tokio::spawn(async move {
+      if let Err(e) = serve(stream, peer, identifier, input_tx, event_rx).await {
+        eprintln!("EMBER :: consumer {} failed :: {}", peer, e);
+      }
+      eprintln!("EMBER :: consumer disconnected {}", peer);
+    });
+  }
+}
+
+async fn serve(
+  mut stream: TcpStream,
+  peer: SocketAddr,
+  identifier: String,
+  input_tx: mpsc::Sender<Request>,
+  mut event_rx: broadcast::Receiver<ConsoleEvent>,
+) -> io::Result<()> {
+  let mut decoder = S101Decoder::default();
+  let mut buffer = [0u8; 4096];
+
+  loop {
+    tokio::select! {
+      received = stream.read(&mut buffer) => {
+        let n = received?;
+        if n == 0 {
+          return Ok(());
+        }
+        for message in decoder.push(&buffer[..n]) {
+          match message {
+            S101Message::KEEPALIVE_REQUEST => stream.write_all(&s101::encode(&S101Message::KEEPALIVE_RESPONSE)).await?,
+            S101Message::KEEPALIVE_RESPONSE => {}
+            S101Message::EMBER(payload) => match glow::decode(&payload) {
+              Ok(requests) => {
+                let elements = handle(&identifier, &input_tx, requests).await;
+                write(&mut stream, &elements).await?;
+              }
+              Err(e) => eprintln!("EMBER :: bad message from {} :: {}", peer, e),
+            },
+          }
+        }
+      }
+      event = event_rx.recv() => {
+        match event {
+          Ok(event) => write(&mut stream, &tree::event_elements(&event)).await?,
+          Err(broadcast::error::RecvError::Lagged(_)) => continue,
+          Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        }
+      }
+    }
+  }
+}
+
+/// Value changes are forwarded to the console and reported back once it confirms them
+async fn handle(identifier: &str, input_tx: &mpsc::Sender<Request>, requests: Vec<GlowRequest>) -> Vec<GlowElement> {
+  let mut elements = vec![];
+
+  for request in requests {
+    match request {
+      GlowRequest::GET_DIRECTORY(path) => {
+        let db = match get_db(input_tx).await {
+          Ok(db) => db,
+          Err(e) => {
+            eprintln!("EMBER :: failed to read state :: {}", e);
+            continue;
+          }
+        };
+        match tree::directory(identifier, &db, &path) {
+          Some(element) => elements.push(element),
+          None => eprintln!("EMBER :: nothing at {:?}", path),
+        }
+      }
+      GlowRequest::SET_VALUE(path, value) => match tree::to_request(&path, &value) {
+        Some(request) => {
+          if let Err(e) = input_tx.send(request).await {
+            eprintln!("EMBER :: failed to forward {:?} :: {}", path, e);
+          }
+        }
+        None => eprintln!("EMBER :: can't set {:?} to {:?}", path, value),
+      },
+    }
+  }
+
+  elements
+}
+
+async fn write(stream: &mut TcpStream, elements: &[GlowElement]) -> io::Result<()> {
+  if elements.is_empty() {
+    return Ok(());
+  }
+  stream.write_all(&s101::encode(&S101Message::EMBER(glow::encode(elements)))).await
+}
@@ -0,0 +1,237 @@
+#![allow(non_snake_case, non_camel_case_types)]
+//! S101 framing used to carry Glow over TCP.
+
+const BOF: u8 = 0xFE;
+const EOF: u8 = 0xFF;
+const CE: u8 = 0xFD;
+const XOR: u8 = 0x20;
+const INVALID: u8 = 0xF8;
+
+const SLOT: u8 = 0x00;
+const MESSAGE_EMBER: u8 = 0x0E;
+const COMMAND_EMBER: u8 = 0x00;
+const COMMAND_KEEPALIVE_REQUEST: u8 = 0x01;
+const COMMAND_KEEPALIVE_RESPONSE: u8 = 0x02;
+const VERSION: u8 = 0x01;
+const DTD_GLOW: u8 = 0x01;
+/// Glow DTD 2.50, minor then major
+const GLOW_VERSION: [u8; 2] = [0x32, 0x02];
+
+const FLAG_FIRST: u8 = 0x80;
+const FLAG_LAST: u8 = 0x40;
+
+/// Payload bytes per frame before a message is split over several frames
+const MAX_PAYLOAD: usize = 1024;
+/// Frames growing past this without an EOF are dropped
+const MAX_FRAME: usize = 64 * 1024;
+/// Messages growing past this without a last frame are dropped
+const MAX_MESSAGE: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum S101Message {
+  KEEPALIVE_REQUEST,
+  KEEPALIVE_RESPONSE,
+  /// A complete Glow payload, reassembled if it spanned several frames
+  EMBER(Vec<u8>),
+}
+
+pub fn encode(message: &S101Message) -> Vec<u8> {
+  match message {
+    S101Message::KEEPALIVE_REQUEST => frame(&[SLOT, MESSAGE_EMBER, COMMAND_KEEPALIVE_REQUEST, VERSION]),
+    S101Message::KEEPALIVE_RESPONSE => frame(&[SLOT, MESSAGE_EMBER, COMMAND_KEEPALIVE_RESPONSE, VERSION]),
+    S101Message::EMBER(payload) => {
+      let chunks: Vec<&[u8]> = if payload.is_empty() { vec![&[]] } else { payload.chunks(MAX_PAYLOAD).collect() };
+      let last = chunks.len() - 1;
+
+      let mut encoded = vec![];
+      for (n, chunk) in chunks.iter().enumerate() {
+        let mut flags = 0;
+        if n == 0 { flags |= FLAG_FIRST; }
+        if n == last { flags |= FLAG_LAST; }
+
+        let mut data = vec![SLOT, MESSAGE_EMBER, COMMAND_EMBER, VERSION, flags, DTD_GLOW, GLOW_VERSION.len() as u8];
+        data.extend_from_slice(&GLOW_VERSION);
+        data.extend_from_slice(chunk);
+        encoded.extend(frame(&data));
+      }
+      encoded
+    }
+  }
+}
+
+fn frame(data: &[u8]) -> Vec<u8> {
+  let crc = !crc(data);
+  let mut encoded = vec![BOF];
+  for &b in data.iter().chain(crc.to_le_bytes().iter()) {
+    if b >= INVALID {
+      encoded.push(CE);
+      encoded.push(b ^ XOR);
+    } else {
+      encoded.push(b);
+    }
+  }
+  encoded.push(EOF);
+  encoded
+}
+
+/// CRC-CCITT, reflected
+fn crc(data: &[u8]) -> u16 {
+  let mut crc: u16 = 0xFFFF;
+  for &b in data {
+    crc ^= b as u16;
+    for _ in 0..8 {
+      crc = if crc & 1 != 0 { (crc >> 1) ^ 0x8408 } else { crc >> 1 };
+    }
+  }
+  crc
+}
+
+/// Turns a byte stream back into messages
+#[derive(Default)]
+pub struct S101Decoder {
+  frame: Option<Vec<u8>>,
+  escaped: bool,
+  /// Message being reassembled, from its first frame on
+  payload: Option<Vec<u8>>,
+}
+
+impl S101Decoder {
+  pub fn push(&mut self, bytes: &[u8]) -> Vec<S101Message> {
+    let mut messages = vec![];
+
+    for &b in bytes {
+      match b {
+        BOF => {
+          self.frame = Some(vec![]);
+          self.escaped = false;
+        }
+        EOF => {
+          if let Some(frame) = self.frame.take() {
+            match self.finish(frame) {
+              Ok(Some(message)) => messages.push(message),
+              Ok(None) => {}
+              Err(e) => eprintln!("EMBER :: dropped S101 frame :: {}", e),
+            }
+          }
+        }
+        CE => self.escaped = true,
+        b => {
+          if let Some(frame) = self.frame.as_mut() {
+            if frame.len() < MAX_FRAME {
+              frame.push(if self.escaped { b ^ XOR } else { b });
+            } else {
+              eprintln!("EMBER :: dropped S101 frame :: over {} bytes", MAX_FRAME);
+              self.frame = None;
+            }
+          }
+          self.escaped = false;
+        }
+      }
+    }
+
+    messages
+  }
+
+  fn finish(&mut self, frame: Vec<u8>) -> Result<Option<S101Message>, String> {
+    if frame.len() < 6 {
+      return Err(String::from("Frame too short"));
+    }
+    let (data, checksum) = frame.split_at(frame.len() - 2);
+    if !crc(data) != u16::from_le_bytes([checksum[0], checksum[1]]) {
+      return Err(String::from("Bad CRC"));
+    }
+    if data[1] != MESSAGE_EMBER {
+      return Err(format!("Unknown message type {:#04x}", data[1]));
+    }
+
+    match data[2] {
+      COMMAND_KEEPALIVE_REQUEST => Ok(Some(S101Message::KEEPALIVE_REQUEST)),
+      COMMAND_KEEPALIVE_RESPONSE => Ok(Some(S101Message::KEEPALIVE_RESPONSE)),
+      COMMAND_EMBER => {
+        if data.len() < 7 {
+          return Err(String::from("Ember frame too short"));
+        }
+        let flags = data[4];
+        let appBytes = data[6] as usize;
+        let payload = data.get(7 + appBytes..).ok_or("Truncated Ember frame")?;
+
+        if flags & FLAG_FIRST != 0 {
+          self.payload = Some(vec![]);
+        }
+        let message = self.payload.as_mut().ok_or("Ember frame without a first frame")?;
+        if message.len() + payload.len() > MAX_MESSAGE {
+          self.payload = None;
+          return Err(format!("Message over {} bytes", MAX_MESSAGE));
+        }
+        message.extend_from_slice(payload);
+
+        if flags & FLAG_LAST != 0 {
+          return Ok(self.payload.take().map(S101Message::EMBER));
+        }
+        Ok(None)
+      }
+      command => Err(format!("Unknown command {:#04x}", command)),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn decode(bytes: &[u8]) -> Vec<S101Message> {
+    S101Decoder::default().push(bytes)
+  }
+
+  #[test]
+  fn round_trips_keepalives() {
+    for message in [S101Message::KEEPALIVE_REQUEST, S101Message::KEEPALIVE_RESPONSE] {
+      assert_eq!(decode(&encode(&message)), [message]);
+    }
+  }
+
+  #[test]
+  fn round_trips_ember_payloads() {
+    // Every byte value so escaping is covered, long enough to span several frames
+    let payload: Vec<u8> = (0..=255).cycle().take(MAX_PAYLOAD * 3 + 10).collect();
+    for message in [S101Message::EMBER(vec![]), S101Message::EMBER(vec![0xFE, 0xFF, 0xFD, 0xF8]), S101Message::EMBER(payload)] {
+      let encoded = encode(&message);
+      assert_eq!(decode(&encoded), [message.clone()]);
+      // Arriving a few bytes at a time
+      let mut decoder = S101Decoder::default();
+      let messages: Vec<_> = encoded.chunks(7).flat_map(|chunk| decoder.push(chunk)).collect();
+      assert_eq!(messages, [message]);
+    }
+  }
+
+  #[test]
+  fn drops_frames_with_a_bad_crc() {
+    let mut encoded = encode(&S101Message::KEEPALIVE_REQUEST);
+    encoded[2] ^= 0x01;
+    encoded.extend(encode(&S101Message::KEEPALIVE_RESPONSE));
+    assert_eq!(decode(&encoded), [S101Message::KEEPALIVE_RESPONSE]);
+  }
+
+  #[test]
+  fn drops_continuations_without_a_first_frame() {
+    let encoded = encode(&S101Message::EMBER(vec![1; MAX_PAYLOAD + 1]));
+    // Skip the first frame, ending at its EOF
+    let second = encoded.iter().position(|&b| b == EOF).unwrap() + 1;
+    assert_eq!(decode(&encoded[second..]), []);
+    assert_eq!(decode(&[&[BOF, 0x01, EOF][..], &encode(&S101Message::KEEPALIVE_REQUEST)].concat()), [S101Message::KEEPALIVE_REQUEST]);
+  }
+
+  #[test]
+  fn drops_oversized_frames_and_messages() {
+    let mut decoder = S101Decoder::default();
+    let mut bytes = vec![BOF];
+    bytes.extend(vec![0x01; MAX_FRAME + 1]);
+    bytes.push(EOF);
+    assert_eq!(decoder.push(&bytes), []);
+    assert_eq!(decoder.push(&encode(&S101Message::KEEPALIVE_REQUEST)), [S101Message::KEEPALIVE_REQUEST]);
+
+    let encoded = encode(&S101Message::EMBER(vec![2; MAX_MESSAGE + 1]));
+    assert_eq!(decoder.push(&encoded), []);
+    assert_eq!(decoder.push(&encode(&S101Message::EMBER(vec![3]))), [S101Message::EMBER(vec![3])]);
+  }
+}
@@ -0,0 +1,189 @@
+//! Maps the console database onto an Ember+ tree.
+//!
+//! ```text
+//! 1 {identifier}
+//!   1 faders / {n} fader{n} / 1 label, 2 level, 3 gain, 4 cut, 5 pfl
+//!   2 mains  / {n} main{n}  / 1 label, 2 level, 3 gain, 5 pfl
+//!   3 auxes  / {n} aux{n}   / 2 level, 3 gain
+//! ```
+//!
+//! `{n}` is 1-based, `level` is the raw 0-1023 fader position and `gain` is in dB.
+
+use common::{Aux, BusType, DB, Fader, Main, Source, level::{db_to_level, level_to_db, MAX_LEVEL, MIN_DB}};
+
+use crate::cscp::{events::ConsoleEvent, requests::{Request, SetAuxLevel, SetFaderCut, SetFaderLevel, SetFaderPfl, SetMainLevel, SetMainPfl}};
+use super::glow::{GlowElement, GlowValue, NodeContents, ParameterContents};
+
+const ROOT: u32 = 1;
+
+const FADERS: u32 = 1;
+const MAINS: u32 = 2;
+const AUXES: u32 = 3;
+
+const LABEL: u32 = 1;
+const LEVEL: u32 = 2;
+const GAIN: u32 = 3;
+const CUT: u32 = 4;
+const PFL: u32 = 5;
+
+const MAX_DB: f64 = 10.0;
+
+/// Answers a GetDirectory for `path`, `None` when nothing lives there
+pub fn directory(identifier: &str, db: &DB, path: &[u32]) -> Option<GlowElement> {
+  match path {
+    [] | [ROOT] => Some(node(vec![ROOT], identifier, if path.is_empty() { vec![] } else { buses() })),
+    [ROOT, bus] => {
+      let children = match *bus {
+        FADERS => db.faders.iter().map(|fader| fader_node(fader, false)).collect(),
+        MAINS => db.mains.iter().map(|main| main_node(main, false)).collect(),
+        AUXES => db.auxes.iter().map(|aux| aux_node(aux, false)).collect(),
+        _ => return None,
+      };
+      let mut element = buses().into_iter().find(|element| element_path(element) == path)?;
+      if let GlowElement::NODE { children: existing, .. } = &mut element {
+        *existing = children;
+      }
+      Some(element)
+    }
+    [ROOT, bus, number, rest @ ..] => {
+      let index = u16::try_from(number.checked_sub(1)?).ok()?;
+      let element = match *bus {
+        FADERS => fader_node(db.faders.iter().find(|fader| fader.index == index)?, true),
+        MAINS => main_node(db.mains.iter().find(|main| main.index == index)?, true),
+        AUXES => aux_node(db.auxes.iter().find(|aux| aux.index == index)?, true),
+        _ => return None,
+      };
+      match rest {
+        [] => Some(element),
+        [parameter] => match element {
+          GlowElement::NODE { children, .. } => children.into_iter().find(|child| element_path(child).last() == Some(parameter)),
+          _ => None,
+        },
+        _ => None,
+      }
+    }
+    _ => None,
+  }
+}
+
+/// Turns a consumer's value change into a console request
+pub fn to_request(path: &[u32], value: &GlowValue) -> Option<Request> {
+  let (bus, number, parameter) = match path {
+    [ROOT, FADERS, number, parameter] => (BusType::FADER, number, *parameter),
+    [ROOT, MAINS, number, parameter] => (BusType::MAIN, number, *parameter),
+    [ROOT, AUXES, number, parameter] => (BusType::AUX, number, *parameter),
+    _ => return None,
+  };
+  let index = u16::try_from(number.checked_sub(1)?).ok()?;
+  let source = Source::API;
+
+  let level = match parameter {
+    LEVEL => Some(value.as_f64()?.round().clamp(0.0, MAX_LEVEL as f64) as u16),
+    GAIN => Some(db_to_level(value.as_f64()? as f32)),
+    _ => None,
+  };
+
+  let request = match (bus, parameter, level) {
+    (BusType::FADER, _, Some(level)) => Request::SET_FADER_LEVEL(SetFaderLevel { index, level, source }),
+    (BusType::FADER, CUT, _) => Request::SET_FADER_CUT(SetFaderCut { index, isCut: value.as_bool()?, source }),
+    (BusType::FADER, PFL, _) => Request::SET_FADER_PFL(SetFaderPfl { index, isPfl: value.as_bool()?, source }),
+    (BusType::MAIN, _, Some(level)) => Request::SET_MAIN_LEVEL(SetMainLevel { index, level, source }),
+    (BusType::MAIN, PFL, _) => Request::SET_MAIN_PFL(SetMainPfl { index, isPfl: value.as_bool()?, source }),
+    (BusType::AUX, _, Some(level)) => Request::SET_AUX_LEVEL(SetAuxLevel { index, level, source }),
+    _ => return None,
+  };
+
+  Some(request)
+}
+
+/// Parameters to push to consumers after a console change
+pub fn event_elements(event: &ConsoleEvent) -> Vec<GlowElement> {
+  let node = match event {
+    ConsoleEvent::FADER(fader) => fader_node(fader, true),
+    ConsoleEvent::MAIN(main) => main_node(main, true),
+    ConsoleEvent::AUX(aux) => aux_node(aux, true),
+    ConsoleEvent::DESK(_) | ConsoleEvent::LINK(_) => return vec![],
+  };
+  match node {
+    GlowElement::NODE { children, .. } => children,
+    parameter => vec![parameter],
+  }
+}
+
+fn element_path(element: &GlowElement) -> &[u32] {
+  match element {
+    GlowElement::NODE { path, .. } => path,
+    GlowElement::PARAMETER { path, .. } => path,
+  }
+}
+
+fn node(path: Vec<u32>, identifier: &str, children: Vec<GlowElement>) -> GlowElement {
+  GlowElement::NODE {
+    path,
+    contents: Some(NodeContents { identifier: identifier.to_string(), description: None }),
+    children,
+  }
+}
+
+fn buses() -> Vec<GlowElement> {
+  vec![
+    node(vec![ROOT, FADERS], "faders", vec![]),
+    node(vec![ROOT, MAINS], "mains", vec![]),
+    node(vec![ROOT, AUXES], "auxes", vec![]),
+  ]
+}
+
+/// Bus entries only list their parameters when asked for directly
+fn bus_node(bus: u32, name: &str, index: u16, label: Option<&str>, parameters: Vec<GlowElement>, expand: bool) -> GlowElement {
+  let number = index as u32 + 1;
+  GlowElement::NODE {
+    path: vec![ROOT, bus, number],
+    contents: Some(NodeContents {
+      identifier: format!("{}{}", name, number),
+      description: label.filter(|label| !label.is_empty()).map(str::to_string),
+    }),
+    children: if expand { parameters } else { vec![] },
+  }
+}
+
+fn parameter(path: &[u32], number: u32, identifier: &str, value: GlowValue, range: Option<(GlowValue, GlowValue)>, writable: bool) -> GlowElement {
+  let mut path = path.to_vec();
+  path.push(number);
+  let (minimum, maximum) = match range {
+    Some((minimum, maximum)) => (Some(minimum), Some(maximum)),
+    None => (None, None),
+  };
+  GlowElement::PARAMETER {
+    path,
+    contents: ParameterContents { identifier: identifier.to_string(), description: None, value, minimum, maximum, writable },
+  }
+}
+
+fn level_parameters(path: &[u32], level: u16) -> Vec<GlowElement> {
+  vec![
+    parameter(path, LEVEL, "level", GlowValue::INT(level as i64), Some((GlowValue::INT(0), GlowValue::INT(MAX_LEVEL as i64))), true),
+    parameter(path, GAIN, "gain", GlowValue::REAL(level_to_db(level) as f64), Some((GlowValue::REAL(MIN_DB as f64), GlowValue::REAL(MAX_DB))), true),
+  ]
+}
+
+fn fader_node(fader: &Fader, expand: bool) -> GlowElement {
+  let path = [ROOT, FADERS, fader.index as u32 + 1];
+  let mut parameters = vec![parameter(&path, LABEL, "label", GlowValue::STRING(fader.label.clone()), None, false)];
+  parameters.extend(level_parameters(&path, fader.level));
+  parameters.push(parameter(&path, CUT, "cut", GlowValue::BOOL(fader.isCut), None, true));
+  parameters.push(parameter(&path, PFL, "pfl", GlowValue::BOOL(fader.isPfl), None, true));
+  bus_node(FADERS, "fader", fader.index, Some(&fader.label), parameters, expand)
+}
+
+fn main_node(main: &Main, expand: bool) -> GlowElement {
+  let path = [ROOT, MAINS, main.index as u32 + 1];
+  let mut parameters = vec![parameter(&path, LABEL, "label", GlowValue::STRING(main.label.clone()), None, false)];
+  parameters.extend(level_parameters(&path, main.level));
+  parameters.push(parameter(&path, PFL, "pfl", GlowValue::BOOL(main.isPfl), None, true));
+  bus_node(MAINS, "main", main.index, Some(&main.label), parameters, expand)
+}
+
+fn aux_node(aux: &Aux, expand: bool) -> GlowElement {
+  let path = [ROOT, AUXES, aux.index as u32 + 1];
+  bus_node(AUXES, "aux", aux.index, None, level_parameters(&path, aux.level), expand)
+}
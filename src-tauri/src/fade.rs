@@ -0,0 +1,54 @@
+use std::time::Duration;
+use common::{BusType, Source};
+use tokio::{sync::mpsc, time};
+
+use crate::cscp::requests::{Request, SetAuxLevel, SetFaderLevel, SetMainLevel};
+
+/// Time between two level steps of a fade
+pub const FADE_STEP: Duration = Duration::from_millis(40);
+
+/// Levels a fade from `from` to `to` goes through, one per `FADE_STEP` and ending on `to`.
+/// The fader travels at an even speed, as it would by hand. Slow fades repeat levels.
+pub fn steps(from: u16, to: u16, duration: Duration) -> Vec<u16> {
+  let count = (duration.as_millis() / FADE_STEP.as_millis()).max(1) as i32;
+  (1..=count)
+    .map(|step| (from as i32 + (to as i32 - from as i32) * step / count) as u16)
+    .collect()
+}
+
+/// Level a fade from `from` to `to` has reached after `elapsed`
+pub fn level_at(from: u16, to: u16, elapsed: Duration, duration: Duration) -> u16 {
+  if elapsed >= duration {
+    return to;
+  }
+  let ratio = elapsed.as_secs_f32() / duration.as_secs_f32();
+  (from as f32 + (to as f32 - from as f32) * ratio).round() as u16
+}
+
+/// The request setting the level of any kind of bus
+pub fn level_request(bus: BusType, index: u16, level: u16, source: Source) -> Request {
+  match bus {
+    BusType::FADER => Request::SET_FADER_LEVEL(SetFaderLevel { index, level, source }),
+    BusType::MAIN => Request::SET_MAIN_LEVEL(SetMainLevel { index, level, source }),
+    BusType::AUX => Request::SET_AUX_LEVEL(SetAuxLevel { index, level, source }),
+  }
+}
+
+/// Moves every `(bus, index, level)` in `from` to `to` over `duration`, returning once it is done
+pub async fn run(input_tx: &mpsc::Sender<Request>, from: Vec<(BusType, u16, u16)>, to: u16, duration: Duration, source: Source) -> Result<(), String> {
+  let steps: Vec<Vec<u16>> = from.iter().map(|(_, _, level)| steps(*level, to, duration)).collect();
+  let mut sent: Vec<u16> = from.iter().map(|(_, _, level)| *level).collect();
+  let mut tick = time::interval(FADE_STEP);
+  tick.tick().await;
+
+  for step in 0..steps.first().map_or(0, |levels| levels.len()) {
+    tick.tick().await;
+    for (last, (&(bus, index, _), levels)) in sent.iter_mut().zip(from.iter().zip(steps.iter())) {
+      if levels[step] != *last {
+        *last = levels[step];
+        input_tx.send(level_request(bus, index, levels[step], source)).await.map_err(|e| e.to_string())?;
+      }
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,101 @@
+#![allow(non_snake_case)]
+use std::{collections::VecDeque, sync::Arc};
+use common::inspector::{CapturedFrame, Direction, FrameKind};
+use futures_util::lock::Mutex;
+use tokio::sync::broadcast;
+
+use crate::cscp::{connection::Traffic, frame::Message};
+use super::decode::{command, describe};
+
+pub type InspectorStorage = Arc<Mutex<Inspector>>;
+
+/// Ring buffer of the most recent frames on the console link. ACK and ERR frames are
+/// paired with the written frame they answer, the console replies to them in order.
+pub struct Inspector {
+  capacity: usize,
+  frames: VecDeque<CapturedFrame>,
+  nextId: u64,
+  /// Ids of written MSG frames still waiting on an ACK or ERR, oldest first
+  pending: VecDeque<u64>,
+}
+
+impl Inspector {
+  pub fn new(capacity: usize) -> Inspector {
+    Inspector { capacity, frames: VecDeque::new(), nextId: 0, pending: VecDeque::new() }
+  }
+
+  pub fn push(&mut self, traffic: Traffic) -> CapturedFrame {
+    let id = self.nextId;
+    self.nextId += 1;
+
+    let (kind, replyTo) = match (&traffic.frame.msg, traffic.direction) {
+      (Message::MSG(_), Direction::TX) => {
+        self.pending.push_back(id);
+        (FrameKind::MSG, None)
+      }
+      (Message::MSG(_), Direction::RX) => (FrameKind::MSG, None),
+      (Message::ACK(_), Direction::RX) => (FrameKind::ACK, self.pending.pop_front()),
+      (Message::ERR(_), Direction::RX) => (FrameKind::ERR, self.pending.pop_front()),
+      (Message::ACK(_), Direction::TX) => (FrameKind::ACK, None),
+      (Message::ERR(_), Direction::TX) => (FrameKind::ERR, None),
+    };
+
+    let frame = CapturedFrame {
+      id,
+      timestamp: traffic.timestamp,
+      direction: traffic.direction,
+      kind,
+      bytes: traffic.frame.to_bytes(),
+      command: command(&traffic.frame),
+      description: describe(traffic.direction, &traffic.frame),
+      replyTo,
+    };
+
+    self.frames.push_back(frame.clone());
+    while self.frames.len() > self.capacity {
+      self.frames.pop_front();
+    }
+
+    frame
+  }
+
+  /// Captured frames oldest first, optionally only MSG frames with the given command byte
+  /// together with the ACK/ERR answering them
+  pub fn frames(&self, command: Option<u8>) -> Vec<CapturedFrame> {
+    let command = match command {
+      Some(command) => command,
+      None => return self.frames.iter().cloned().collect(),
+    };
+
+    let matching: Vec<u64> = self.frames.iter()
+      .filter(|frame| frame.command == Some(command))
+      .map(|frame| frame.id)
+      .collect();
+
+    self.frames.iter()
+      .filter(|frame| frame.command == Some(command) || frame.replyTo.is_some_and(|id| matching.contains(&id)))
+      .cloned()
+      .collect()
+  }
+
+  /// Drops the captured frames, pairing carries on with frames still in flight
+  pub fn clear(&mut self) {
+    self.frames.clear();
+  }
+}
+
+/// Captures every frame on the console link, handing each one to `publish` as well
+pub async fn capture<F: Fn(CapturedFrame)>(inspector: InspectorStorage, mut traffic_rx: broadcast::Receiver<Traffic>, publish: F) {
+  loop {
+    match traffic_rx.recv().await {
+      Ok(traffic) => {
+        let frame = inspector.lock().await.push(traffic);
+        publish(frame);
+      }
+      Err(broadcast::error::RecvError::Lagged(skipped)) => {
+        eprintln!("INSPECTOR :: missed {} frames", skipped);
+      }
+      Err(broadcast::error::RecvError::Closed) => break,
+    }
+  }
+}
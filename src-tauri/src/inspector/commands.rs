@@ -0,0 +1,26 @@
+use common::inspector::CapturedFrame;
+
+use super::capture::InspectorStorage;
+
+pub struct InspectorState {
+  pub inner: InspectorStorage,
+}
+
+#[tauri::command]
+pub async fn getInspectorFrames(
+    command: Option<u8>,
+    state: tauri::State<'_, InspectorState>,
+) -> Result<Vec<CapturedFrame>, String> {
+    eprintln!("getInspectorFrames command={:?}", command);
+    let inspector = state.inner.lock().await;
+    Ok(inspector.frames(command))
+}
+
+#[tauri::command]
+pub async fn clearInspector(
+    state: tauri::State<'_, InspectorState>,
+) -> Result<(), String> {
+    eprintln!("clearInspector");
+    state.inner.lock().await.clear();
+    Ok(())
+}
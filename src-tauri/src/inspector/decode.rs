@@ -0,0 +1,131 @@
+#![allow(non_snake_case, non_camel_case_types)]
+use common::{inspector::Direction, level::level_to_db};
+
+use crate::cscp::frame::{Frame, Message};
+
+const SET: u8 = 0x80;
+
+/// How the value following a channel number is shown
+enum Value {
+  LEVEL,
+  ON_OFF,
+  TEXT,
+  FORMAT,
+}
+
+/// Commands addressing a fader, main or aux by number
+fn channel_command(cmd: u8) -> Option<(&'static str, &'static str, Value)> {
+  let command = match cmd {
+    0x00 => ("fader", "level", Value::LEVEL),
+    0x01 => ("fader", "cut", Value::ON_OFF),
+    0x02 => ("main", "level", Value::LEVEL),
+    0x05 => ("fader", "pfl", Value::ON_OFF),
+    0x0B => ("fader", "label", Value::TEXT),
+    0x0C => ("main", "pfl", Value::ON_OFF),
+    0x0D => ("main", "label", Value::TEXT),
+    0x11 => ("fader", "format", Value::FORMAT),
+    0x13 => ("aux", "level", Value::LEVEL),
+    _ => return None,
+  };
+  Some(command)
+}
+
+/// Command byte of a MSG frame
+pub fn command(frame: &Frame) -> Option<u8> {
+  match &frame.msg {
+    Message::MSG(msg) => msg.buffer.get(4).copied(),
+    _ => None,
+  }
+}
+
+/// Human readable meaning of a frame, e.g. "Set fader 3 level 744 (-5.2 dB)".
+/// Channels are numbered from 1 as on the desk.
+pub fn describe(direction: Direction, frame: &Frame) -> String {
+  match &frame.msg {
+    Message::ACK(_) => String::from("ACK"),
+    Message::ERR(error) => format!("ERR {:#04x}", error.error),
+    Message::MSG(msg) => describe_message(direction, &msg.buffer),
+  }
+}
+
+fn describe_message(direction: Direction, buffer: &[u8]) -> String {
+  if buffer.len() < 6 || buffer[0] != 0xF1 || buffer.len() != buffer[1] as usize + 4 {
+    return String::from("Malformed frame");
+  }
+
+  let (flags, cmd) = (buffer[3], buffer[4]);
+  let data = &buffer[5..buffer.len() - 1];
+  let verb = match (flags & SET != 0, direction) {
+    (true, _) => "Set",
+    (false, Direction::TX) => "Get",
+    (false, Direction::RX) => "Report",
+  };
+
+  let mut description = match channel_command(cmd) {
+    Some((bus, property, kind)) if data.len() >= 2 => {
+      let number = u16::from_be_bytes([data[0], data[1]]) as u32 + 1;
+      let value = &data[2..];
+      if value.is_empty() {
+        format!("{} {} {} {}", verb, bus, number, property)
+      } else {
+        format!("{} {} {} {} {}", verb, bus, number, property, describe_value(kind, value))
+      }
+    }
+    _ => describe_desk(verb, cmd, data),
+  };
+
+  // Everything after the length byte sums to zero
+  if buffer[3..].iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+    description.push_str(" (bad checksum)");
+  }
+
+  description
+}
+
+fn describe_value(kind: Value, value: &[u8]) -> String {
+  match (kind, value) {
+    (Value::LEVEL, [high, low, ..]) => {
+      let level = u16::from_be_bytes([*high, *low]);
+      format!("{} ({:.1} dB)", level, level_to_db(level))
+    }
+    (Value::ON_OFF, [state, ..]) => on_off(*state != 0).to_string(),
+    (Value::FORMAT, [pathType, width, ..]) => format!("type {} width {}", pathType, width),
+    (Value::TEXT, text) => format!("\"{}\"", String::from_utf8_lossy(text)),
+    (_, value) => hex(value),
+  }
+}
+
+/// Commands about the desk as a whole
+fn describe_desk(verb: &str, cmd: u8, data: &[u8]) -> String {
+  match cmd {
+    0x07 if data.is_empty() => format!("{} desk name", verb),
+    0x07 => format!("{} desk name \"{}\"", verb, String::from_utf8_lossy(data)),
+    0x08 if data.len() >= 12 => {
+      let word = |at: usize| u16::from_be_bytes([data[at], data[at + 1]]);
+      format!(
+        "{} desk info version {} faders {} mains {} name \"{}\"",
+        verb, word(0), word(2), word(4), String::from_utf8_lossy(&data[12..]),
+      )
+    }
+    0x08 => format!("{} desk info", verb),
+    0x10 => with_data(format!("{} aux availability", verb), data),
+    0x14 => with_data(format!("{} main availability", verb), data),
+    0x16 => with_data(format!("{} routing", verb), data),
+    _ => with_data(format!("{} unknown command {:#04x}", verb, cmd), data),
+  }
+}
+
+fn with_data(description: String, data: &[u8]) -> String {
+  if data.is_empty() {
+    return description;
+  }
+  format!("{} {}", description, hex(data))
+}
+
+fn on_off(isOn: bool) -> &'static str {
+  if isOn { "on" } else { "off" }
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<String>>().join(" ")
+}
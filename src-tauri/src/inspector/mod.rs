@@ -0,0 +1,3 @@
+pub mod capture;
+pub mod commands;
+pub mod decode;
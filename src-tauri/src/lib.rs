@@ -0,0 +1,27 @@
+//! Backend of the controller: the CSCP client and the services built on it.
+//! The Tauri app in `main.rs` and the binaries in `src/bin` share these modules.
+
+pub mod api;
+pub mod audit;
+pub mod catalogue;
+pub mod clock;
+pub mod config;
+pub mod cscp;
+pub mod csv;
+pub mod ducking;
+pub mod ember;
+pub mod fade;
+pub mod inspector;
+pub mod midi;
+pub mod mirror;
+pub mod onair;
+pub mod osc;
+pub mod proxy;
+pub mod rules;
+pub mod scene;
+pub mod schedule;
+pub mod scripts;
+pub mod session;
+pub mod template;
+pub mod timecode;
+pub mod tsl;
@@ -3,14 +3,27 @@
     windows_subsystem = "windows"
 )]
 
-use common::Fader;
-use cscp::commands::AsyncProcInputTx;
-use tauri::{async_runtime::Mutex, Manager, Window};
-use tokio::sync::mpsc;
+use std::{path::PathBuf, sync::Arc};
 
-use crate::cscp::{commands::{setFaderLevel, setFaderCut, setFaderPfl, getDatabase}, client::CSCPClient};
+use futures_util::lock::Mutex as StorageMutex;
+use tauri::{async_runtime::Mutex, Manager, Window};
+use tokio::sync::{broadcast, mpsc};
 
-mod cscp;
+use common::console::OnConsole;
+use rust_cscp_controller::{api, ducking, ember, mirror, osc, proxy, session, tsl};
+use rust_cscp_controller::mirror::sync::MirrorConsole;
+use rust_cscp_controller::clock::now_millis;
+use rust_cscp_controller::audit::{commands::{AuditLogState, queryAuditLog, exportAuditLog}, log::{AuditLog, ConsoleAudit}};
+use rust_cscp_controller::catalogue::{collector::{Catalogue, collect}, commands::{CatalogueState, getCatalogue, exportCatalogue}};
+use rust_cscp_controller::config::Config;
+use rust_cscp_controller::cscp::{commands::{AsyncProcInputTx, listConsoles, setFaderLevel, setFaderCut, setFaderPfl, getDatabase, getLinkStatus, verifyTemplate}, client::CSCPClient, events::ConsoleEvent};
+use rust_cscp_controller::inspector::{capture::{Inspector, capture}, commands::{InspectorState, getInspectorFrames, clearInspector}};
+use rust_cscp_controller::midi::{bridge::MidiBridge, commands::{MidiState, getMidiMappings, getMidiStatus, saveMidiMappings, learnMidi, cancelMidiLearn}};
+use rust_cscp_controller::rules::{commands::{RulesState, getRules, getRuleStatus, saveRules}, engine::RuleEngine};
+use rust_cscp_controller::schedule::{commands::{ScheduleState, getSchedule, getScheduleStatus, saveSchedule, previewSchedule}, scheduler::Scheduler};
+use rust_cscp_controller::timecode::{chase::Chaser, commands::{TimecodeState, getCues, getTimecodeStatus, saveCues, timecodeTransport}};
+use rust_cscp_controller::scripts::{commands::{ScriptsState, listScripts, runScript, stopScript, getScriptOutput}, host::ScriptHost};
+use rust_cscp_controller::onair::{commands::{OnAirState, getOnAirReport, getAsRunLog, exportOnAirReport, exportAsRunLog}, tracker::{OnAirTracker, track}};
 
 // Learn more about Tauri commands at https://tauri.app/v1/guides/features/command
 #[tauri::command]
@@ -20,37 +33,282 @@ fn greet(name: &str) -> String {
 
 fn main() {
     let (async_proc_input_tx, async_proc_input_rx) = mpsc::channel(1);
-    let (fader_event_tx, mut fader_event_rx) = mpsc::channel(1);
+    let (event_tx, _) = broadcast::channel(256);
+    let (traffic_tx, _) = broadcast::channel(256);
 
     tauri::Builder::default()
-        .manage(AsyncProcInputTx {
-            inner: Mutex::new(async_proc_input_tx),
-        })
         .invoke_handler(tauri::generate_handler![
             greet,
+            listConsoles,
             setFaderLevel,
             setFaderCut,
             setFaderPfl,
             getDatabase,
+            getLinkStatus,
+            verifyTemplate,
+            queryAuditLog,
+            exportAuditLog,
+            getOnAirReport,
+            getAsRunLog,
+            exportOnAirReport,
+            exportAsRunLog,
+            getInspectorFrames,
+            clearInspector,
+            getCatalogue,
+            exportCatalogue,
+            getRules,
+            getRuleStatus,
+            saveRules,
+            listScripts,
+            runScript,
+            stopScript,
+            getScriptOutput,
+            getSchedule,
+            getScheduleStatus,
+            saveSchedule,
+            previewSchedule,
+            getCues,
+            getTimecodeStatus,
+            saveCues,
+            timecodeTransport,
+            getMidiMappings,
+            getMidiStatus,
+            saveMidiMappings,
+            learnMidi,
+            cancelMidiLearn,
         ])
-        .setup(|app| {
-            tauri::async_runtime::spawn(async move {
-                let _client = CSCPClient::connect(
-                    "172.16.255.5:49556",
-                    async_proc_input_rx,
-                    fader_event_tx,
-                ).await.unwrap();
-                println!("Client disconnected");
+        .setup(move |app| {
+            let config_dir = app.path_resolver().app_config_dir().unwrap_or_else(|| PathBuf::from("."));
+            let data_dir = app.path_resolver().app_data_dir().unwrap_or_else(|| PathBuf::from("."));
+            let config_path = config_dir.join("config.json");
+            let config = Config::load(&config_path);
+            let consoles = config.consoles();
+
+            let audit = Arc::new(StorageMutex::new(AuditLog::new(
+                config.audit.directory.clone().unwrap_or_else(|| data_dir.join("audit")),
+                config.audit.maxFileSize,
+                config.audit.maxFiles,
+            )));
+            app.manage(AuditLogState { inner: audit.clone() });
+
+            let onair = Arc::new(StorageMutex::new(OnAirTracker::new(config.onAir.clone())));
+            app.manage(OnAirState { inner: onair.clone() });
+            tauri::async_runtime::spawn(track(onair, event_tx.subscribe()));
+
+            let catalogue = Arc::new(StorageMutex::new(Catalogue::default()));
+            app.manage(CatalogueState { inner: catalogue.clone() });
+            tauri::async_runtime::spawn(collect(catalogue, traffic_tx.subscribe()));
+
+            let inspector_config = config.inspector.clone().unwrap_or_default();
+            let inspector = Arc::new(StorageMutex::new(Inspector::new(inspector_config.capacity)));
+            app.manage(InspectorState { inner: inspector.clone() });
+            if config.inspector.is_some() {
+                let inspector_window = app.get_window("main").unwrap();
+                tauri::async_runtime::spawn(capture(inspector, traffic_tx.subscribe(), move |frame| {
+                    inspector_window
+                        .emit("inspector::frame", frame)
+                        .unwrap();
+                }));
+            }
+
+            let scripts_dir = config.scripts.directory.clone().unwrap_or_else(|| config_dir.join("scripts"));
+            let (script_host, script_output_rx) = ScriptHost::new(&config.scripts, scripts_dir, async_proc_input_tx.clone(), event_tx.clone());
+            let scripts = Arc::new(StorageMutex::new(script_host));
+            app.manage(ScriptsState { inner: scripts.clone() });
+            let scripts_window = app.get_window("main").unwrap();
+            tauri::async_runtime::spawn(rust_cscp_controller::scripts::host::publish(scripts.clone(), script_output_rx, move |output| {
+                scripts_window
+                    .emit("script::output", output)
+                    .unwrap();
+            }));
+
+            let schedule = Arc::new(StorageMutex::new(Scheduler::new(config.schedule.clone(), data_dir.join("schedule-state.json"))));
+            app.manage(ScheduleState {
+                inner: schedule.clone(),
+                configPath: config_path.clone(),
+                input_tx: async_proc_input_tx.clone(),
+                scripts: scripts.clone(),
             });
+            tauri::async_runtime::spawn(rust_cscp_controller::schedule::scheduler::run(schedule, scripts.clone(), async_proc_input_tx.clone(), config_dir.clone()));
+
+            let (transport_tx, transport_rx) = mpsc::channel(16);
+            let chaser = Arc::new(StorageMutex::new(Chaser::new(config.timecode.as_ref(), config.cues.clone(), config_dir.clone())));
+            app.manage(TimecodeState {
+                inner: chaser.clone(),
+                configPath: config_path.clone(),
+                transport_tx,
+            });
+            if let Some(timecode_config) = config.timecode.clone() {
+                let timecode_window = app.get_window("main").unwrap();
+                tauri::async_runtime::spawn(rust_cscp_controller::timecode::chase::run(chaser, timecode_config, transport_rx, scripts.clone(), async_proc_input_tx.clone(), event_tx.subscribe(), move |status| {
+                    timecode_window
+                        .emit("timecode::status", status)
+                        .unwrap();
+                }));
+            }
+
+            let midi = Arc::new(StorageMutex::new(MidiBridge::new(config.midi.as_ref(), config.midiMappings.clone())));
+            app.manage(MidiState { inner: midi.clone(), configPath: config_path.clone() });
+            if let Some(midi_config) = config.midi.clone() {
+                tauri::async_runtime::spawn(rust_cscp_controller::midi::bridge::run(midi, midi_config, async_proc_input_tx.clone(), event_tx.subscribe()));
+            }
+
+            let rules = Arc::new(StorageMutex::new(RuleEngine::new(config.rules.clone())));
+            app.manage(RulesState { inner: rules.clone(), configPath: config_path });
+            let rules_window = app.get_window("main").unwrap();
+            tauri::async_runtime::spawn(rust_cscp_controller::rules::engine::run(rules, scripts, async_proc_input_tx.clone(), event_tx.subscribe(), move |alert| {
+                rules_window
+                    .emit("rule::alert", alert)
+                    .unwrap();
+            }));
+
+            if !config.duckers.is_empty() {
+                let duckers = config.duckers.clone();
+                let ducking_input_tx = async_proc_input_tx.clone();
+                let ducking_event_rx = event_tx.subscribe();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = ducking::ducker::run(duckers, ducking_input_tx, ducking_event_rx).await {
+                        eprintln!("Ducking stopped :: {}", e);
+                    }
+                });
+            }
+
+            if let Some(tsl_config) = config.tsl.clone() {
+                let onAir = config.onAir.clone();
+                let tsl_event_rx = event_tx.subscribe();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = tsl::sender::run(tsl_config, onAir, tsl_event_rx).await {
+                        eprintln!("TSL sender stopped :: {}", e);
+                    }
+                });
+            }
+
+            if let Some(osc_config) = config.osc.clone() {
+                let osc_input_tx = async_proc_input_tx.clone();
+                let osc_event_rx = event_tx.subscribe();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = osc::server::run(osc_config, osc_input_tx, osc_event_rx).await {
+                        eprintln!("OSC server stopped :: {}", e);
+                    }
+                });
+            }
+
+            if let Some(http_config) = config.http.clone() {
+                let http_input_tx = async_proc_input_tx.clone();
+                let http_event_tx = event_tx.clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = api::server::run(http_config, http_input_tx, http_event_tx).await {
+                        eprintln!("HTTP server stopped :: {}", e);
+                    }
+                });
+            }
+
+            if let Some(ember_config) = config.ember.clone() {
+                let ember_input_tx = async_proc_input_tx.clone();
+                let ember_event_tx = event_tx.clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = ember::provider::run(ember_config, ember_input_tx, ember_event_tx).await {
+                        eprintln!("Ember+ provider stopped :: {}", e);
+                    }
+                });
+            }
+
+            if let Some(proxy_config) = config.proxy.clone() {
+                let proxy_input_tx = async_proc_input_tx.clone();
+                let proxy_traffic_tx = traffic_tx.clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = proxy::server::run(proxy_config, proxy_input_tx, proxy_traffic_tx).await {
+                        eprintln!("CSCP proxy stopped :: {}", e);
+                    }
+                });
+            }
+
+            if let Some(capture_config) = config.capture.clone() {
+                let path = capture_config.directory
+                    .unwrap_or_else(|| data_dir.join("captures"))
+                    .join(format!("capture-{}.jsonl", now_millis()));
+                let capture_traffic_rx = traffic_tx.subscribe();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = session::recorder::record(path, capture_traffic_rx).await {
+                        eprintln!("Session capture stopped :: {}", e);
+                    }
+                });
+            }
 
-            // let app_handle = app.handle();
             let main_window = app.get_window("main").unwrap();
-            tauri::async_runtime::spawn(async move {
-                loop {
-                    if let Some(fader) = fader_event_rx.recv().await {
-                        publish_fader(fader, &main_window);
+            let mut senders = vec![(consoles[0].id.clone(), async_proc_input_tx.clone())];
+            let mut handles = vec![MirrorConsole { id: consoles[0].id.clone(), input_tx: async_proc_input_tx.clone(), event_tx: event_tx.clone() }];
+            spawn_publisher(consoles[0].id.clone(), event_tx.subscribe(), main_window.clone());
+
+            // Further consoles only feed the UI, every service stays on the first one
+            for named in consoles.iter().skip(1) {
+                let (console_input_tx, console_input_rx) = mpsc::channel(1);
+                let (console_event_tx, _) = broadcast::channel(256);
+                let (console_traffic_tx, _) = broadcast::channel(256);
+                senders.push((named.id.clone(), console_input_tx.clone()));
+                handles.push(MirrorConsole { id: named.id.clone(), input_tx: console_input_tx, event_tx: console_event_tx.clone() });
+                spawn_publisher(named.id.clone(), console_event_tx.subscribe(), main_window.clone());
+
+                let console_config = named.console.clone();
+                let console_audit = ConsoleAudit::new(audit.clone(), &named.id);
+                let id = named.id.clone();
+                tauri::async_runtime::spawn(async move {
+                    let _client = CSCPClient::run(
+                        console_config,
+                        console_input_rx,
+                        console_event_tx,
+                        console_traffic_tx,
+                        console_audit,
+                    ).await.unwrap();
+                    eprintln!("Client {} disconnected", id);
+                });
+            }
+            app.manage(AsyncProcInputTx { inner: Mutex::new(senders) });
+
+            if let Some(mirror_config) = config.mirror.clone() {
+                let find = |id: &str| handles.iter().find(|handle| handle.id == id).cloned();
+                match (find(&mirror_config.source), find(&mirror_config.target)) {
+                    (Some(source), Some(target)) => {
+                        tauri::async_runtime::spawn(async move {
+                            if let Err(e) = mirror::sync::run(mirror_config, source, target).await {
+                                eprintln!("Mirror stopped :: {}", e);
+                            }
+                        });
                     }
+                    _ => eprintln!("Mirror needs consoles {} and {} in `consoles`", mirror_config.source, mirror_config.target),
                 }
+            }
+
+            let first_console = consoles[0].console.clone();
+            let audit = ConsoleAudit::new(audit, &consoles[0].id);
+            tauri::async_runtime::spawn(async move {
+                let _client = match config.replay {
+                    Some(replay_config) => {
+                        let records = match session::file::read(&replay_config.file) {
+                            Ok(records) => records,
+                            Err(e) => {
+                                eprintln!("Can't replay {} :: {}", replay_config.file.display(), e);
+                                return;
+                            }
+                        };
+                        let address = session::replay::serve(records, replay_config.speed, replay_config.repeat).await.unwrap();
+                        CSCPClient::connect(
+                            address,
+                            async_proc_input_rx,
+                            event_tx,
+                            traffic_tx,
+                            audit,
+                        ).await.unwrap()
+                    }
+                    None => CSCPClient::run(
+                        first_console,
+                        async_proc_input_rx,
+                        event_tx,
+                        traffic_tx,
+                        audit,
+                    ).await.unwrap(),
+                };
+                eprintln!("Client disconnected");
             });
             Ok(())
         })
@@ -58,9 +316,48 @@ fn main() {
         .expect("error while running tauri application");
 }
 
-fn publish_fader(fader: Fader, manager: &Window) {
-    println!("fader::changed {:?}", fader);
-    manager
-        .emit("fader::changed", fader)
-        .unwrap();
+fn spawn_publisher(console: String, mut event_rx: broadcast::Receiver<ConsoleEvent>, window: Window) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match event_rx.recv().await {
+                Ok(event) => publish_event(&console, event, &window),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// Emits a console change to the UI, tagged with the console it came from
+fn publish_event(console: &str, event: ConsoleEvent, manager: &Window) {
+    let console = console.to_string();
+    match event {
+        ConsoleEvent::FADER(fader) => {
+            eprintln!("fader::changed {:?}", fader);
+            manager
+                .emit("fader::changed", OnConsole { console, data: fader })
+                .unwrap();
+        }
+        ConsoleEvent::MAIN(main) => {
+            manager
+                .emit("main::changed", OnConsole { console, data: main })
+                .unwrap();
+        }
+        ConsoleEvent::AUX(aux) => {
+            manager
+                .emit("aux::changed", OnConsole { console, data: aux })
+                .unwrap();
+        }
+        ConsoleEvent::DESK(deskInfo) => {
+            manager
+                .emit("desk::changed", OnConsole { console, data: deskInfo })
+                .unwrap();
+        }
+        ConsoleEvent::LINK(link) => {
+            eprintln!("link::changed {:?}", link);
+            manager
+                .emit("link::changed", OnConsole { console, data: link })
+                .unwrap();
+        }
+    }
 }
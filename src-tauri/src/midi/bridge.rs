@@ -0,0 +1,371 @@
+#![allow(non_snake_case)]
+use std::{collections::{HashMap, HashSet}, sync::Arc, time::Duration};
+use common::{
+  Fader, Source,
+  level::MAX_LEVEL,
+  midi::{MidiControl, MidiMapping, MidiStatus, MidiTarget},
+};
+use futures_util::lock::Mutex;
+use tokio::{sync::{broadcast, mpsc, oneshot}, time::{self, Instant}};
+
+use crate::{
+  config::MidiConfig,
+  cscp::{events::ConsoleEvent, requests::{get_db, Request, SetFaderCut, SetFaderLevel, SetFaderPfl}},
+  schedule::scheduler::{check_fader, targets},
+};
+use super::{message::{encode, ControlValue, MidiParser}, ports::{open_input, open_output}};
+
+pub type MidiStorage = Arc<Mutex<MidiBridge>>;
+
+/// Name of the ports opened when none are configured
+pub const VIRTUAL_PORT: &str = "CSCP Control";
+const SOURCE: Source = Source::API;
+/// Feedback to a control moved this recently waits, so a motor fader doesn't fight the hand on it
+const HOLD: Duration = Duration::from_millis(300);
+/// Learning picks from everything moved this long after the first control
+const LEARN_WINDOW: Duration = Duration::from_millis(400);
+const TICK: Duration = Duration::from_millis(50);
+
+struct Learning {
+  target: MidiTarget,
+  /// Controls moved so far, in order
+  seen: Vec<MidiControl>,
+  since: Option<Instant>,
+  reply: oneshot::Sender<MidiMapping>,
+}
+
+/// Moves faders from a MIDI surface and sends their state back to it
+pub struct MidiBridge {
+  mappings: Vec<MidiMapping>,
+  faders: HashMap<u16, Fader>,
+  parser: MidiParser,
+  learning: Option<Learning>,
+  /// When each control last moved
+  touched: HashMap<MidiControl, Instant>,
+  /// Controls whose feedback waits for them to be let go
+  held: HashSet<MidiControl>,
+  /// Value each control was last sent or sent us
+  values: HashMap<MidiControl, u16>,
+  /// Whether every control needs its feedback sent again
+  resync: bool,
+  input: Option<String>,
+  output: Option<String>,
+  error: Option<String>,
+}
+
+impl MidiBridge {
+  /// The bridge for `config`, which does nothing when there is none
+  pub fn new(config: Option<&MidiConfig>, mappings: Vec<MidiMapping>) -> MidiBridge {
+    let mut bridge = MidiBridge {
+      mappings: vec![],
+      faders: HashMap::new(),
+      parser: MidiParser::default(),
+      learning: None,
+      touched: HashMap::new(),
+      held: HashSet::new(),
+      values: HashMap::new(),
+      resync: false,
+      input: None,
+      output: None,
+      error: config.is_none().then(|| String::from("MIDI is off, add `midi` to the config")),
+    };
+    bridge.set_mappings(mappings);
+    bridge
+  }
+
+  pub fn mappings(&self) -> Vec<MidiMapping> {
+    self.mappings.clone()
+  }
+
+  /// Replaces the mappings, leaving out any that can't work
+  pub fn set_mappings(&mut self, mappings: Vec<MidiMapping>) {
+    self.mappings = mappings.into_iter()
+      .filter(|mapping| validate(mapping).map_err(|e| eprintln!("MIDI :: mapping off :: {}", e)).is_ok())
+      .collect();
+    self.values.clear();
+    self.resync = true;
+  }
+
+  pub fn status(&self) -> MidiStatus {
+    MidiStatus {
+      input: self.input.clone(),
+      output: self.output.clone(),
+      error: self.error.clone(),
+      learning: self.learning.as_ref().map(|learning| learning.target.clone()),
+    }
+  }
+
+  pub fn set_ports(&mut self, input: Option<String>, output: Option<String>, errors: Vec<String>) {
+    self.input = input;
+    self.output = output;
+    self.error = (!errors.is_empty()).then(|| errors.join("; "));
+  }
+
+  /// Maps the next control moved to `target`, replacing what that control or `target` was mapped to
+  pub fn learn(&mut self, target: MidiTarget) -> Result<oneshot::Receiver<MidiMapping>, String> {
+    if self.input.is_none() {
+      return Err(self.error.clone().unwrap_or_else(|| String::from("No MIDI input to learn from")));
+    }
+    check_fader(target.fader())?;
+    eprintln!("MIDI :: learning {}", target);
+    let (reply, reply_rx) = oneshot::channel();
+    self.learning = Some(Learning { target, seen: vec![], since: None, reply });
+    Ok(reply_rx)
+  }
+
+  pub fn cancel_learn(&mut self) {
+    self.learning = None;
+  }
+
+  pub fn seed(&mut self, faders: Vec<Fader>) {
+    self.faders = faders.into_iter().map(|fader| (fader.index, fader)).collect();
+    self.resync = true;
+  }
+
+  /// Feedback for a change on the console
+  pub fn handle(&mut self, event: &ConsoleEvent, now: Instant) -> Vec<Vec<u8>> {
+    match event {
+      ConsoleEvent::FADER(fader) => {
+        self.faders.insert(fader.index, fader.clone());
+        self.feedback(fader, now)
+      }
+      _ => vec![],
+    }
+  }
+
+  /// What a message from the surface asks of the console
+  pub fn input(&mut self, message: &[u8], now: Instant) -> Vec<Request> {
+    let values = self.parser.feed(message);
+    if let Some(learning) = self.learning.as_mut() {
+      for ControlValue { control, .. } in values {
+        if fits(&control, &learning.target) && !learning.seen.contains(&control) {
+          learning.seen.push(control);
+          learning.since.get_or_insert(now);
+        }
+      }
+      return vec![];
+    }
+
+    let faders: Vec<Fader> = self.faders.values().cloned().collect();
+    let mut requests = vec![];
+    for ControlValue { control, value } in values {
+      let mapping = match self.mappings.iter().find(|mapping| mapping.control == control) {
+        Some(mapping) => mapping.clone(),
+        None => continue,
+      };
+      let picked = match targets(mapping.target.fader(), &faders) {
+        Ok(picked) => picked,
+        Err(e) => {
+          eprintln!("MIDI :: {} :: {}", control, e);
+          continue;
+        }
+      };
+      self.touched.insert(control.clone(), now);
+      self.values.insert(control.clone(), value);
+      let pressed = value > control.max_value() / 2 || (matches!(control, MidiControl::NOTE { .. }) && value > 0);
+
+      match &mapping.target {
+        MidiTarget::FADER_LEVEL { .. } => {
+          let level = (value as f32 * MAX_LEVEL as f32 / control.max_value() as f32).round() as u16;
+          requests.extend(picked.into_iter().filter(|fader| fader.level != level)
+            .map(|fader| Request::SET_FADER_LEVEL(SetFaderLevel { index: fader.index, level, source: SOURCE })));
+        }
+        MidiTarget::FADER_CUT { .. } | MidiTarget::FADER_PFL { .. } => {
+          if !mapping.momentary && !pressed {
+            // Surfaces often put out their own LED on release, so it is set back to the state
+            self.values.remove(&control);
+            self.resync = true;
+            continue;
+          }
+          let first = picked[0];
+          let isCut = matches!(mapping.target, MidiTarget::FADER_CUT { .. });
+          let on = if mapping.momentary { pressed } else if isCut { !first.isCut } else { !first.isPfl };
+          requests.extend(picked.into_iter().map(|fader| if isCut {
+            Request::SET_FADER_CUT(SetFaderCut { index: fader.index, isCut: on, source: SOURCE })
+          } else {
+            Request::SET_FADER_PFL(SetFaderPfl { index: fader.index, isPfl: on, source: SOURCE })
+          }));
+        }
+      }
+    }
+    requests
+  }
+
+  /// Finishes learning and sends feedback held back or asked for
+  pub fn tick(&mut self, now: Instant) -> Vec<Vec<u8>> {
+    if matches!(&self.learning, Some(learning) if learning.since.is_some_and(|since| now.duration_since(since) >= LEARN_WINDOW)) {
+      let learning = self.learning.take().unwrap();
+      if let Some(control) = best(&learning.seen) {
+        let mapping = MidiMapping { control, target: learning.target, momentary: false };
+        eprintln!("MIDI :: learned {} for {}", mapping.control, mapping.target);
+        self.mappings.retain(|existing| existing.control != mapping.control && existing.target != mapping.target);
+        self.mappings.push(mapping.clone());
+        self.resync = true;
+        let _ = learning.reply.send(mapping);
+      }
+    }
+
+    let mut messages = vec![];
+    if std::mem::take(&mut self.resync) {
+      self.held.clear();
+      let faders: Vec<Fader> = self.faders.values().cloned().collect();
+      for fader in faders {
+        messages.extend(self.feedback(&fader, now));
+      }
+    }
+    let released: Vec<MidiControl> = self.held.iter()
+      .filter(|control| self.touched.get(*control).map_or(true, |at| now.duration_since(*at) >= HOLD))
+      .cloned()
+      .collect();
+    for control in released {
+      self.held.remove(&control);
+      let faders: Vec<Fader> = self.faders.values().cloned().collect();
+      for mapping in self.mappings.clone().iter().filter(|mapping| mapping.control == control) {
+        if let Some(fader) = targets(mapping.target.fader(), &faders).ok().and_then(|picked| picked.first().cloned()) {
+          messages.extend(self.send(mapping, fader, now));
+        }
+      }
+    }
+    messages
+  }
+
+  /// Messages setting every control mapped to `fader` to its state
+  fn feedback(&mut self, fader: &Fader, now: Instant) -> Vec<Vec<u8>> {
+    let mut messages = vec![];
+    let faders: Vec<Fader> = self.faders.values().cloned().collect();
+    for mapping in self.mappings.clone().iter() {
+      // A control on several faders shows the first of them
+      let first = targets(mapping.target.fader(), &faders).ok().and_then(|picked| picked.first().map(|first| first.index));
+      if first == Some(fader.index) {
+        messages.extend(self.send(mapping, fader, now));
+      }
+    }
+    messages
+  }
+
+  fn send(&mut self, mapping: &MidiMapping, fader: &Fader, now: Instant) -> Vec<Vec<u8>> {
+    let control = &mapping.control;
+    let max = control.max_value();
+    let value = match mapping.target {
+      MidiTarget::FADER_LEVEL { .. } => {
+        if self.touched.get(control).is_some_and(|at| now.duration_since(*at) < HOLD) {
+          self.held.insert(control.clone());
+          return vec![];
+        }
+        (fader.level as f32 * max as f32 / MAX_LEVEL as f32).round() as u16
+      }
+      MidiTarget::FADER_CUT { .. } => if fader.isCut { max } else { 0 },
+      MidiTarget::FADER_PFL { .. } => if fader.isPfl { max } else { 0 },
+    };
+    if self.values.get(control) == Some(&value) {
+      return vec![];
+    }
+    self.values.insert(control.clone(), value);
+    encode(control, value)
+  }
+}
+
+/// Whether `control` can move `target`: levels need a fader or knob, cuts and PFLs a button
+fn fits(control: &MidiControl, target: &MidiTarget) -> bool {
+  match target {
+    MidiTarget::FADER_LEVEL { .. } => !matches!(control, MidiControl::NOTE { .. }),
+    MidiTarget::FADER_CUT { .. } | MidiTarget::FADER_PFL { .. } => matches!(control, MidiControl::NOTE { .. } | MidiControl::CC { .. }),
+  }
+}
+
+/// The finest of the controls moved, so a 14-bit fader isn't learned as the 7-bit CC it also sends
+fn best(seen: &[MidiControl]) -> Option<MidiControl> {
+  let rank = |control: &MidiControl| match control {
+    MidiControl::NRPN { .. } => 4,
+    MidiControl::CC14 { .. } => 3,
+    MidiControl::PITCH_BEND { .. } => 2,
+    MidiControl::NOTE { .. } => 1,
+    MidiControl::CC { .. } => 0,
+  };
+  // The first seen wins a tie, max_by_key would keep the last
+  seen.iter().rev().max_by_key(|control| rank(control)).cloned()
+}
+
+/// Why `mapping` can't work, if it can't
+pub fn validate(mapping: &MidiMapping) -> Result<(), String> {
+  let fail = |e: String| format!("{} :: {}", mapping.control, e);
+  let control = &mapping.control;
+  if !(1..=16).contains(&control.channel()) {
+    return Err(fail(String::from("Channels go from 1 to 16")));
+  }
+  match control {
+    MidiControl::CC { controller, .. } | MidiControl::NOTE { note: controller, .. } if *controller > 127 => return Err(fail(String::from("Numbers go up to 127"))),
+    MidiControl::CC14 { controller, .. } if *controller > 31 => return Err(fail(String::from("14-bit CCs go up to 31"))),
+    MidiControl::NRPN { parameter, .. } if *parameter > 16383 => return Err(fail(String::from("NRPNs go up to 16383"))),
+    _ => {}
+  }
+  if !fits(control, &mapping.target) {
+    return Err(fail(format!("can't move {}", mapping.target)));
+  }
+  check_fader(mapping.target.fader()).map_err(fail)
+}
+
+/// Why `mappings` can't be used together, if they can't
+pub fn validate_all(mappings: &[MidiMapping]) -> Result<(), String> {
+  for (i, mapping) in mappings.iter().enumerate() {
+    validate(mapping)?;
+    if mappings[..i].iter().any(|other| other.control == mapping.control) {
+      return Err(format!("{} :: mapped twice", mapping.control));
+    }
+  }
+  Ok(())
+}
+
+/// Opens the ports and bridges them to the console until it goes away
+pub async fn run(bridge: MidiStorage, config: MidiConfig, input_tx: mpsc::Sender<Request>, mut event_rx: broadcast::Receiver<ConsoleEvent>) {
+  let (message_tx, mut message_rx) = mpsc::unbounded_channel();
+  let mut errors = vec![];
+  let input = open_input(VIRTUAL_PORT, config.input.as_deref(), move |_, message, _| {
+    let _ = message_tx.send(message.to_vec());
+  }).map_err(|e| errors.push(format!("Input :: {}", e))).ok();
+  let mut output = open_output(VIRTUAL_PORT, config.output.as_deref()).map_err(|e| errors.push(format!("Output :: {}", e))).ok();
+  for error in errors.iter() {
+    eprintln!("MIDI :: {}", error);
+  }
+  let names = (input.as_ref().map(|(_, name)| name.clone()), output.as_ref().map(|(_, name)| name.clone()));
+  eprintln!("MIDI :: input {:?}, output {:?}", names.0, names.1);
+  bridge.lock().await.set_ports(names.0, names.1, errors);
+  if input.is_none() && output.is_none() {
+    return;
+  }
+  if let Ok(db) = get_db(&input_tx).await {
+    bridge.lock().await.seed(db.faders);
+  }
+  let mut tick = time::interval(TICK);
+
+  loop {
+    let (requests, messages) = tokio::select! {
+      Some(message) = message_rx.recv() => (bridge.lock().await.input(&message, Instant::now()), vec![]),
+      event = event_rx.recv() => match event {
+        Ok(event) => (vec![], bridge.lock().await.handle(&event, Instant::now())),
+        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+          eprintln!("MIDI :: missed {} changes", skipped);
+          bridge.lock().await.resync = true;
+          continue;
+        }
+        Err(broadcast::error::RecvError::Closed) => return,
+      },
+      _ = tick.tick() => (vec![], bridge.lock().await.tick(Instant::now())),
+    };
+
+    if let Some((connection, name)) = output.as_mut() {
+      for message in messages {
+        if let Err(e) = connection.send(&message) {
+          eprintln!("MIDI :: can't send to {} :: {}", name, e);
+          break;
+        }
+      }
+    }
+    for request in requests {
+      if input_tx.send(request).await.is_err() {
+        eprintln!("MIDI :: the client stopped taking requests");
+        return;
+      }
+    }
+  }
+}
@@ -0,0 +1,70 @@
+use std::{path::PathBuf, time::Duration};
+use common::midi::{MidiMapping, MidiStatus, MidiTarget};
+use tokio::time;
+
+use crate::config::Config;
+use super::bridge::{validate_all, MidiStorage};
+
+/// How long LEARN waits for a control to move
+const LEARN_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub struct MidiState {
+  pub inner: MidiStorage,
+  /// Config file the mappings are saved to
+  pub configPath: PathBuf,
+}
+
+#[tauri::command]
+pub async fn getMidiMappings(
+    state: tauri::State<'_, MidiState>,
+) -> Result<Vec<MidiMapping>, String> {
+    Ok(state.inner.lock().await.mappings())
+}
+
+#[tauri::command]
+pub async fn getMidiStatus(
+    state: tauri::State<'_, MidiState>,
+) -> Result<MidiStatus, String> {
+    Ok(state.inner.lock().await.status())
+}
+
+#[tauri::command]
+pub async fn saveMidiMappings(
+    mappings: Vec<MidiMapping>,
+    state: tauri::State<'_, MidiState>,
+) -> Result<Vec<MidiMapping>, String> {
+    eprintln!("saveMidiMappings count={}", mappings.len());
+    validate_all(&mappings)?;
+    let value = serde_json::to_value(&mappings).map_err(|e| e.to_string())?;
+    Config::update(&state.configPath, "midiMappings", value)?;
+
+    let mut bridge = state.inner.lock().await;
+    bridge.set_mappings(mappings);
+    Ok(bridge.mappings())
+}
+
+/// Waits for a control to move and maps it to `target`. The mapping is live but not saved.
+#[tauri::command]
+pub async fn learnMidi(
+    target: MidiTarget,
+    state: tauri::State<'_, MidiState>,
+) -> Result<MidiMapping, String> {
+    eprintln!("learnMidi {}", target);
+    let learned = state.inner.lock().await.learn(target)?;
+    match time::timeout(LEARN_TIMEOUT, learned).await {
+        Ok(Ok(mapping)) => Ok(mapping),
+        Ok(Err(_)) => Err(String::from("Learning was cancelled")),
+        Err(_) => {
+            state.inner.lock().await.cancel_learn();
+            Err(format!("Nothing moved in {} seconds", LEARN_TIMEOUT.as_secs()))
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn cancelMidiLearn(
+    state: tauri::State<'_, MidiState>,
+) -> Result<(), String> {
+    state.inner.lock().await.cancel_learn();
+    Ok(())
+}
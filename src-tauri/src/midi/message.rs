@@ -0,0 +1,188 @@
+#![allow(non_snake_case)]
+use common::midi::MidiControl;
+
+const NOTE_OFF: u8 = 0x80;
+const NOTE_ON: u8 = 0x90;
+const CONTROL_CHANGE: u8 = 0xB0;
+const PITCH_BEND: u8 = 0xE0;
+
+const DATA_ENTRY: u8 = 6;
+const DATA_ENTRY_LSB: u8 = 38;
+const NRPN_LSB: u8 = 98;
+const NRPN_MSB: u8 = 99;
+const RPN_LSB: u8 = 100;
+const RPN_MSB: u8 = 101;
+
+/// A control moving to `value`, from 0 to its `max_value`. Buttons send 0 when released.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlValue {
+  pub control: MidiControl,
+  pub value: u16,
+}
+
+/// State kept per channel to put multi-message values back together
+#[derive(Default, Clone, Copy)]
+struct Channel {
+  /// NRPN selected by CC 99 and 98, none once an RPN is selected
+  parameter: Option<(u8, u8)>,
+  dataMsb: u8,
+  dataLsb: u8,
+  /// High bits last sent on CC 0-31
+  msb: [Option<u8>; 32],
+}
+
+/// Turns MIDI messages into control values.
+///
+/// CC 6, 38 and 98 to 101 make up NRPNs and never show as CCs of their own, with the value given
+/// once CC 38 arrives. A CC from 0 to 31 followed by the one 32 above gives a `CC14` as well as the two `CC`s.
+#[derive(Default)]
+pub struct MidiParser {
+  channels: [Channel; 16],
+}
+
+impl MidiParser {
+  pub fn feed(&mut self, message: &[u8]) -> Vec<ControlValue> {
+    let (status, data) = match message.split_first() {
+      Some((status, data)) if *status < 0xF0 => (*status, data),
+      _ => return vec![],
+    };
+    let number = status & 0x0F;
+    let channel = number + 1;
+    let state = &mut self.channels[number as usize];
+    let value = |control: MidiControl, value: u16| ControlValue { control, value };
+
+    match (status & 0xF0, data) {
+      (NOTE_ON, [note, velocity, ..]) => vec![value(MidiControl::NOTE { channel, note: *note }, *velocity as u16)],
+      (NOTE_OFF, [note, ..]) => vec![value(MidiControl::NOTE { channel, note: *note }, 0)],
+      (PITCH_BEND, [lsb, msb, ..]) => vec![value(MidiControl::PITCH_BEND { channel }, (*msb as u16) << 7 | *lsb as u16)],
+      (CONTROL_CHANGE, [controller, level, ..]) => {
+        let (controller, level) = (*controller, *level);
+        match controller {
+          NRPN_MSB => {
+            state.parameter = Some((level, state.parameter.map_or(0, |(_, lsb)| lsb)));
+            vec![]
+          }
+          NRPN_LSB => {
+            state.parameter = Some((state.parameter.map_or(0, |(msb, _)| msb), level));
+            vec![]
+          }
+          RPN_MSB | RPN_LSB => {
+            state.parameter = None;
+            vec![]
+          }
+          // The value is only complete once the low bits follow, so the old ones are never mixed in
+          DATA_ENTRY => {
+            state.dataMsb = level;
+            state.dataLsb = 0;
+            vec![]
+          }
+          DATA_ENTRY_LSB => {
+            state.dataLsb = level;
+            match state.parameter {
+              Some((msb, lsb)) => vec![value(
+                MidiControl::NRPN { channel, parameter: (msb as u16) << 7 | lsb as u16 },
+                (state.dataMsb as u16) << 7 | state.dataLsb as u16,
+              )],
+              None => vec![],
+            }
+          }
+          0..=31 => {
+            state.msb[controller as usize] = Some(level);
+            vec![value(MidiControl::CC { channel, controller }, level as u16)]
+          }
+          32..=63 => {
+            let mut values = vec![value(MidiControl::CC { channel, controller }, level as u16)];
+            if let Some(msb) = state.msb[controller as usize - 32] {
+              values.push(value(MidiControl::CC14 { channel, controller: controller - 32 }, (msb as u16) << 7 | level as u16));
+            }
+            values
+          }
+          _ => vec![value(MidiControl::CC { channel, controller }, level as u16)],
+        }
+      }
+      _ => vec![],
+    }
+  }
+}
+
+/// The messages that move `control` to `value`, from 0 to its `max_value`
+pub fn encode(control: &MidiControl, value: u16) -> Vec<Vec<u8>> {
+  let value = value.min(control.max_value());
+  let (msb, lsb) = ((value >> 7) as u8 & 0x7F, value as u8 & 0x7F);
+  let channel = control.channel().clamp(1, 16) - 1;
+  let cc = |controller: u8, level: u8| vec![CONTROL_CHANGE | channel, controller, level];
+  match control {
+    MidiControl::CC { controller, .. } => vec![cc(*controller, value as u8)],
+    MidiControl::CC14 { controller, .. } => vec![cc(*controller, msb), cc(controller + 32, lsb)],
+    MidiControl::NRPN { parameter, .. } => vec![
+      cc(NRPN_MSB, (parameter >> 7) as u8 & 0x7F),
+      cc(NRPN_LSB, *parameter as u8 & 0x7F),
+      cc(DATA_ENTRY, msb),
+      cc(DATA_ENTRY_LSB, lsb),
+    ],
+    MidiControl::PITCH_BEND { .. } => vec![vec![PITCH_BEND | channel, lsb, msb]],
+    MidiControl::NOTE { note, .. } => vec![vec![NOTE_ON | channel, *note, value as u8]],
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn feed(messages: &[&[u8]]) -> Vec<ControlValue> {
+    let mut parser = MidiParser::default();
+    messages.iter().flat_map(|message| parser.feed(message)).collect()
+  }
+
+  fn value(control: MidiControl, value: u16) -> ControlValue {
+    ControlValue { control, value }
+  }
+
+  #[test]
+  fn parses_cc() {
+    assert_eq!(feed(&[&[0xB0, 7, 100]]), [value(MidiControl::CC { channel: 1, controller: 7 }, 100)]);
+    assert_eq!(feed(&[&[0xBF, 80, 0]]), [value(MidiControl::CC { channel: 16, controller: 80 }, 0)]);
+  }
+
+  #[test]
+  fn parses_cc14() {
+    let cc14 = MidiControl::CC14 { channel: 2, controller: 7 };
+    assert_eq!(
+      feed(&[&[0xB1, 7, 70], &[0xB1, 39, 40]]),
+      [
+        value(MidiControl::CC { channel: 2, controller: 7 }, 70),
+        value(MidiControl::CC { channel: 2, controller: 39 }, 40),
+        value(cc14, 9000),
+      ]
+    );
+    // Low bits without high bits first are only a CC
+    assert_eq!(feed(&[&[0xB1, 39, 40]]), [value(MidiControl::CC { channel: 2, controller: 39 }, 40)]);
+  }
+
+  #[test]
+  fn parses_nrpn_once_per_value() {
+    let nrpn = MidiControl::NRPN { channel: 3, parameter: 130 };
+    let mut parser = MidiParser::default();
+    let mut values = vec![];
+    for message in encode(&nrpn, 100).iter().chain(&encode(&nrpn, 9000)) {
+      values.extend(parser.feed(message));
+    }
+    assert_eq!(values, [value(nrpn.clone(), 100), value(nrpn.clone(), 9000)]);
+    // Data entry alone reuses the selected parameter and never mixes in the last low bits
+    assert_eq!(parser.feed(&[0xB2, 6, 1]), []);
+    assert_eq!(parser.feed(&[0xB2, 38, 2]), [value(nrpn, 130)]);
+  }
+
+  #[test]
+  fn ignores_data_entry_after_rpn() {
+    assert_eq!(feed(&[&[0xB0, 99, 1], &[0xB0, 98, 2], &[0xB0, 101, 0], &[0xB0, 6, 64], &[0xB0, 38, 0]]), []);
+  }
+
+  #[test]
+  fn parses_pitch_bend() {
+    let bend = MidiControl::PITCH_BEND { channel: 4 };
+    assert_eq!(feed(&[&[0xE3, 0x00, 0x40]]), [value(bend.clone(), 8192)]);
+    assert_eq!(feed(&[&[0xE3, 0x7F, 0x7F]]), [value(bend.clone(), 16383)]);
+    assert_eq!(feed(&encode(&bend, 8195).iter().map(|m| m.as_slice()).collect::<Vec<_>>()), [value(bend, 8195)]);
+  }
+}
@@ -0,0 +1,4 @@
+pub mod bridge;
+pub mod commands;
+pub mod message;
+pub mod ports;
@@ -0,0 +1,65 @@
+#![allow(non_snake_case)]
+use midir::{Ignore, MidiInput, MidiInputConnection, MidiOutput, MidiOutputConnection};
+
+/// Whether a port called `name` is the one asked for
+pub fn matches(name: &str, wanted: &str) -> bool {
+  name.to_lowercase().contains(&wanted.to_lowercase())
+}
+
+/// Opens the input with `wanted` in its name, or a virtual input called `name` when there is no `wanted`.
+/// Gives back the connection and the name of the port.
+pub fn open_input<F>(name: &str, wanted: Option<&str>, callback: F) -> Result<(MidiInputConnection<()>, String), String>
+where F: FnMut(u64, &[u8], &mut ()) + Send + 'static {
+  let mut input = MidiInput::new(name).map_err(|e| e.to_string())?;
+  // SysEx, MIDI clock and quarter frames are filtered out by default
+  input.ignore(Ignore::ActiveSense);
+  match wanted {
+    Some(wanted) => {
+      let (port, portName) = input.ports().into_iter()
+        .find_map(|port| input.port_name(&port).ok().filter(|portName| matches(portName, wanted)).map(|portName| (port, portName)))
+        .ok_or_else(|| format!("No MIDI input {}", wanted))?;
+      let connection = input.connect(&port, name, callback, ()).map_err(|e| e.to_string())?;
+      Ok((connection, portName))
+    }
+    None => virtual_input(input, name, callback).map(|connection| (connection, name.to_string())),
+  }
+}
+
+/// Opens the output with `wanted` in its name, or a virtual output called `name` when there is no `wanted`
+pub fn open_output(name: &str, wanted: Option<&str>) -> Result<(MidiOutputConnection, String), String> {
+  let output = MidiOutput::new(name).map_err(|e| e.to_string())?;
+  match wanted {
+    Some(wanted) => {
+      let (port, portName) = output.ports().into_iter()
+        .find_map(|port| output.port_name(&port).ok().filter(|portName| matches(portName, wanted)).map(|portName| (port, portName)))
+        .ok_or_else(|| format!("No MIDI output {}", wanted))?;
+      let connection = output.connect(&port, name).map_err(|e| e.to_string())?;
+      Ok((connection, portName))
+    }
+    None => virtual_output(output, name).map(|connection| (connection, name.to_string())),
+  }
+}
+
+#[cfg(unix)]
+fn virtual_input<F>(input: MidiInput, name: &str, callback: F) -> Result<MidiInputConnection<()>, String>
+where F: FnMut(u64, &[u8], &mut ()) + Send + 'static {
+  use midir::os::unix::VirtualInput;
+  input.create_virtual(name, callback, ()).map_err(|e| e.to_string())
+}
+
+#[cfg(not(unix))]
+fn virtual_input<F>(_input: MidiInput, _name: &str, _callback: F) -> Result<MidiInputConnection<()>, String>
+where F: FnMut(u64, &[u8], &mut ()) + Send + 'static {
+  Err(String::from("Virtual MIDI ports need Linux or macOS, name an input port"))
+}
+
+#[cfg(unix)]
+fn virtual_output(output: MidiOutput, name: &str) -> Result<MidiOutputConnection, String> {
+  use midir::os::unix::VirtualOutput;
+  output.create_virtual(name).map_err(|e| e.to_string())
+}
+
+#[cfg(not(unix))]
+fn virtual_output(_output: MidiOutput, _name: &str) -> Result<MidiOutputConnection, String> {
+  Err(String::from("Virtual MIDI ports need Linux or macOS, name an output port"))
+}
@@ -0,0 +1,274 @@
+#![allow(non_snake_case)]
+use std::{collections::{HashMap, VecDeque}, time::Duration};
+use common::{Aux, BusType, Fader, Main, Source};
+use tokio::{sync::{broadcast, mpsc}, time::Instant};
+
+use crate::{
+  config::{MirrorConfig, MirrorDirection, MirrorMapping},
+  cscp::{events::ConsoleEvent, requests::{get_db, Request, SetAuxLevel, SetFaderCut, SetFaderLevel, SetFaderPfl, SetMainLevel, SetMainPfl}},
+};
+
+/// How long a console has to report back a value the mirror wrote before it no longer counts as an echo
+const ECHO_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long after a link comes up reports are taken as the console's state dump rather than changes
+const SETTLE_TIME: Duration = Duration::from_secs(3);
+
+/// A console the mirror can watch and control
+#[derive(Clone)]
+pub struct MirrorConsole {
+  pub id: String,
+  pub input_tx: mpsc::Sender<Request>,
+  pub event_tx: broadcast::Sender<ConsoleEvent>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Param {
+  LEVEL,
+  CUT,
+  PFL,
+}
+
+/// One parameter of one bus, booleans as 0 and 1
+#[derive(Debug, Clone, Copy)]
+struct Change {
+  bus: BusType,
+  index: u16,
+  param: Param,
+  value: u16,
+}
+
+/// State last reported by one console, plus what the mirror wrote to it and expects back
+#[derive(Default)]
+struct Side {
+  faders: HashMap<u16, Fader>,
+  mains: HashMap<u16, Main>,
+  auxes: HashMap<u16, Aux>,
+  echoes: HashMap<(BusType, u16, Param), VecDeque<(u16, Instant)>>,
+  settleUntil: Option<Instant>,
+  connected: bool,
+  /// Requests for the console, forwarded in order without holding up the mirror
+  outbox: Option<mpsc::UnboundedSender<Request>>,
+}
+
+impl Side {
+  fn value(&self, bus: BusType, index: u16, param: Param) -> Option<u16> {
+    match (bus, param) {
+      (BusType::FADER, Param::LEVEL) => self.faders.get(&index).map(|fader| fader.level),
+      (BusType::FADER, Param::CUT) => self.faders.get(&index).map(|fader| fader.isCut as u16),
+      (BusType::FADER, Param::PFL) => self.faders.get(&index).map(|fader| fader.isPfl as u16),
+      (BusType::MAIN, Param::LEVEL) => self.mains.get(&index).map(|main| main.level),
+      (BusType::MAIN, Param::PFL) => self.mains.get(&index).map(|main| main.isPfl as u16),
+      (BusType::AUX, Param::LEVEL) => self.auxes.get(&index).map(|aux| aux.level),
+      _ => None,
+    }
+  }
+
+  /// Stores a reported bus and returns what changed, everything when the bus is new
+  fn apply(&mut self, event: &ConsoleEvent) -> (bool, Vec<Change>) {
+    let (bus, index, params): (BusType, u16, Vec<(Param, u16)>) = match event {
+      ConsoleEvent::FADER(fader) => (BusType::FADER, fader.index, vec![
+        (Param::LEVEL, fader.level),
+        (Param::CUT, fader.isCut as u16),
+        (Param::PFL, fader.isPfl as u16),
+      ]),
+      ConsoleEvent::MAIN(main) => (BusType::MAIN, main.index, vec![(Param::LEVEL, main.level), (Param::PFL, main.isPfl as u16)]),
+      ConsoleEvent::AUX(aux) => (BusType::AUX, aux.index, vec![(Param::LEVEL, aux.level)]),
+      _ => return (false, vec![]),
+    };
+    let first = self.value(bus, index, Param::LEVEL).is_none();
+    let changes = params.into_iter()
+      .filter(|(param, value)| self.value(bus, index, *param) != Some(*value))
+      .map(|(param, value)| Change { bus, index, param, value })
+      .collect();
+
+    match event {
+      ConsoleEvent::FADER(fader) => { self.faders.insert(fader.index, fader.clone()); }
+      ConsoleEvent::MAIN(main) => { self.mains.insert(main.index, main.clone()); }
+      ConsoleEvent::AUX(aux) => { self.auxes.insert(aux.index, aux.clone()); }
+      _ => {}
+    }
+    (first, changes)
+  }
+
+  /// Writes `change` to the console and expects it to be reported back
+  fn send(&mut self, change: Change) {
+    let source = Source::AUTOMATION;
+    let (index, value) = (change.index, change.value);
+    let request = match (change.bus, change.param) {
+      (BusType::FADER, Param::LEVEL) => Request::SET_FADER_LEVEL(SetFaderLevel { index, level: value, source }),
+      (BusType::FADER, Param::CUT) => Request::SET_FADER_CUT(SetFaderCut { index, isCut: value != 0, source }),
+      (BusType::FADER, Param::PFL) => Request::SET_FADER_PFL(SetFaderPfl { index, isPfl: value != 0, source }),
+      (BusType::MAIN, Param::LEVEL) => Request::SET_MAIN_LEVEL(SetMainLevel { index, level: value, source }),
+      (BusType::MAIN, Param::PFL) => Request::SET_MAIN_PFL(SetMainPfl { index, isPfl: value != 0, source }),
+      (BusType::AUX, Param::LEVEL) => Request::SET_AUX_LEVEL(SetAuxLevel { index, level: value, source }),
+      _ => return,
+    };
+    if let Some(outbox) = &self.outbox {
+      if outbox.send(request).is_ok() {
+        self.echoes.entry((change.bus, change.index, change.param)).or_default().push_back((change.value, Instant::now()));
+      }
+    }
+  }
+
+  /// Whether `change` is the console reporting back a value the mirror wrote to it
+  fn is_echo(&mut self, change: &Change) -> bool {
+    let sent = match self.echoes.get_mut(&(change.bus, change.index, change.param)) {
+      Some(sent) => sent,
+      None => return false,
+    };
+    sent.retain(|(_, at)| at.elapsed() < ECHO_TIMEOUT);
+    match sent.iter().position(|(value, _)| *value == change.value) {
+      Some(position) => {
+        sent.drain(..=position);
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Forgets the reported state, the console resends all of it after reconnecting
+  fn reset(&mut self) {
+    self.faders.clear();
+    self.mains.clear();
+    self.auxes.clear();
+    self.echoes.clear();
+    self.settleUntil = Some(Instant::now() + SETTLE_TIME);
+  }
+
+  fn is_settling(&self) -> bool {
+    self.settleUntil.is_some_and(|until| Instant::now() < until)
+  }
+}
+
+struct Mirror {
+  config: MirrorConfig,
+  sourceSide: Side,
+  targetSide: Side,
+}
+
+impl Mirror {
+  fn handle(&mut self, fromSource: bool, event: ConsoleEvent) {
+    if let ConsoleEvent::LINK(link) = &event {
+      let side = self.side(fromSource);
+      if link.connected && !side.connected {
+        side.reset();
+      }
+      side.connected = link.connected;
+      return;
+    }
+
+    let (first, changes) = self.side(fromSource).apply(&event);
+    let dump = first || self.side(fromSource).is_settling();
+    for change in changes {
+      if self.side(fromSource).is_echo(&change) {
+        continue;
+      }
+      if !fromSource {
+        if dump {
+          // The target's state after (re)connecting, bring it in line with the source
+          self.pull(change);
+          continue;
+        }
+        if self.config.direction == MirrorDirection::ONE_WAY {
+          continue;
+        }
+      }
+      self.push(fromSource, change);
+    }
+  }
+
+  fn side(&mut self, source: bool) -> &mut Side {
+    if source { &mut self.sourceSide } else { &mut self.targetSide }
+  }
+
+  /// Applies a change on one desk to the other
+  fn push(&mut self, fromSource: bool, change: Change) {
+    let index = match self.counterpart(fromSource, change.bus, change.index) {
+      Some(index) => index,
+      None => return,
+    };
+    let change = Change { index, ..change };
+    let to = if fromSource { &mut self.targetSide } else { &mut self.sourceSide };
+    if to.value(change.bus, change.index, change.param) == Some(change.value) {
+      return;
+    }
+    to.send(change);
+  }
+
+  /// Sets a target parameter to the source's value when they differ
+  fn pull(&mut self, targetChange: Change) {
+    let index = match self.counterpart(false, targetChange.bus, targetChange.index) {
+      Some(index) => index,
+      None => return,
+    };
+    match self.sourceSide.value(targetChange.bus, index, targetChange.param) {
+      Some(value) if value != targetChange.value => {
+        self.targetSide.send(Change { value, ..targetChange });
+      }
+      _ => {}
+    }
+  }
+
+  /// Index of the matching bus on the other desk
+  fn counterpart(&self, fromSource: bool, bus: BusType, index: u16) -> Option<u16> {
+    if bus != BusType::FADER || self.config.mapping == MirrorMapping::INDEX {
+      return Some(index);
+    }
+    let (from, to) = if fromSource { (&self.sourceSide, &self.targetSide) } else { (&self.targetSide, &self.sourceSide) };
+    let label = from.faders.get(&index)?.label.trim();
+    if label.is_empty() {
+      return None;
+    }
+    to.faders.values().find(|fader| fader.label.trim() == label).map(|fader| fader.index)
+  }
+}
+
+/// Passes requests on to the console's client one at a time
+fn forward(console: MirrorConsole) -> mpsc::UnboundedSender<Request> {
+  let (outbox_tx, mut outbox_rx) = mpsc::unbounded_channel();
+  tokio::spawn(async move {
+    while let Some(request) = outbox_rx.recv().await {
+      if console.input_tx.send(request).await.is_err() {
+        eprintln!("MIRROR :: {} stopped taking requests", console.id);
+        return;
+      }
+    }
+  });
+  outbox_tx
+}
+
+/// Applies every change reported by `source` to `target`, and with `BIDIRECTIONAL` the other way
+/// round too. When the target (re)connects it is set to the source's state.
+pub async fn run(config: MirrorConfig, source: MirrorConsole, target: MirrorConsole) -> Result<(), String> {
+  let mut source_rx = source.event_tx.subscribe();
+  let mut target_rx = target.event_tx.subscribe();
+  eprintln!("MIRROR :: {} -> {} by {:?}, {:?}", source.id, target.id, config.mapping, config.direction);
+
+  let source_db = get_db(&source.input_tx).await?;
+  let target_db = get_db(&target.input_tx).await?;
+  let sourceSide = Side { connected: source_db.link.connected, outbox: Some(forward(source)), ..Side::default() };
+  let targetSide = Side { connected: target_db.link.connected, outbox: Some(forward(target)), ..Side::default() };
+  let mut mirror = Mirror { config, sourceSide, targetSide };
+
+  // The target's state goes first so the source's is only written where they differ
+  for (fromSource, db) in [(false, target_db), (true, source_db)] {
+    let events = db.faders.into_iter().map(ConsoleEvent::FADER)
+      .chain(db.mains.into_iter().map(ConsoleEvent::MAIN))
+      .chain(db.auxes.into_iter().map(ConsoleEvent::AUX));
+    for event in events {
+      mirror.handle(fromSource, event);
+    }
+  }
+
+  loop {
+    let (fromSource, event) = tokio::select! {
+      event = source_rx.recv() => (true, event),
+      event = target_rx.recv() => (false, event),
+    };
+    match event {
+      Ok(event) => mirror.handle(fromSource, event),
+      Err(broadcast::error::RecvError::Lagged(n)) => eprintln!("MIRROR :: missed {} changes", n),
+      Err(broadcast::error::RecvError::Closed) => return Ok(()),
+    }
+  }
+}
@@ -0,0 +1,55 @@
+use std::fs;
+use common::onair::{OnAirInterval, OnAirReport};
+
+use crate::clock::now_millis;
+use super::tracker::{OnAirStorage, as_run_to_csv, report_to_csv};
+
+pub struct OnAirState {
+  pub inner: OnAirStorage,
+}
+
+#[tauri::command]
+pub async fn getOnAirReport(
+    from: u64,
+    to: u64,
+    state: tauri::State<'_, OnAirState>,
+) -> Result<OnAirReport, String> {
+    eprintln!("getOnAirReport from={} to={}", from, to);
+    let tracker = state.inner.lock().await;
+    Ok(tracker.report(from, to, now_millis()))
+}
+
+#[tauri::command]
+pub async fn getAsRunLog(
+    from: u64,
+    to: u64,
+    state: tauri::State<'_, OnAirState>,
+) -> Result<Vec<OnAirInterval>, String> {
+    eprintln!("getAsRunLog from={} to={}", from, to);
+    let tracker = state.inner.lock().await;
+    Ok(tracker.as_run(from, to))
+}
+
+#[tauri::command]
+pub async fn exportOnAirReport(
+    from: u64,
+    to: u64,
+    path: String,
+    state: tauri::State<'_, OnAirState>,
+) -> Result<(), String> {
+    eprintln!("exportOnAirReport from={} to={} path={}", from, to, path);
+    let report = state.inner.lock().await.report(from, to, now_millis());
+    fs::write(path, report_to_csv(&report)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn exportAsRunLog(
+    from: u64,
+    to: u64,
+    path: String,
+    state: tauri::State<'_, OnAirState>,
+) -> Result<(), String> {
+    eprintln!("exportAsRunLog from={} to={} path={}", from, to, path);
+    let intervals = state.inner.lock().await.as_run(from, to);
+    fs::write(path, as_run_to_csv(&intervals)).map_err(|e| e.to_string())
+}
@@ -0,0 +1,155 @@
+#![allow(non_snake_case)]
+use std::{cmp, collections::{BTreeMap, HashMap, VecDeque}, sync::Arc};
+use common::{Fader, onair::{OnAirChannelReport, OnAirInterval, OnAirReport}};
+use futures_util::lock::Mutex;
+use tokio::sync::broadcast;
+
+use crate::{clock::now_millis, config::OnAirConfig, cscp::events::ConsoleEvent, csv};
+
+pub type OnAirStorage = Arc<Mutex<OnAirTracker>>;
+
+/// Oldest intervals are dropped past this so a long running session can't grow forever
+const MAX_CLOSED_INTERVALS: usize = 100_000;
+
+/// A fader is on air when it is above the threshold and not cut, and with `useRouting` routed to a main.
+/// Faders the console hasn't reported routing for yet are assumed to be routed.
+pub fn is_on_air(fader: &Fader, config: &OnAirConfig) -> bool {
+  fader.level > config.threshold && !fader.isCut && (!config.useRouting || fader.isRouted != Some(false))
+}
+
+/// On-air intervals, kept in memory only so a restart starts a new log
+pub struct OnAirTracker {
+  config: OnAirConfig,
+  open: HashMap<u16, OnAirInterval>,
+  closed: VecDeque<OnAirInterval>,
+}
+
+impl OnAirTracker {
+  pub fn new(config: OnAirConfig) -> OnAirTracker {
+    OnAirTracker { config, open: HashMap::new(), closed: VecDeque::new() }
+  }
+
+  /// Opens or closes the fader's interval, returning the new on-air state if it changed
+  pub fn update(&mut self, fader: &Fader, now: u64) -> Option<bool> {
+    let isOnAir = is_on_air(fader, &self.config);
+
+    match (self.open.contains_key(&fader.index), isOnAir) {
+      (false, true) => {
+        self.open.insert(fader.index, OnAirInterval { index: fader.index, label: fader.label.clone(), start: now, end: None });
+        Some(true)
+      }
+      (true, false) => {
+        self.close(fader.index, now);
+        Some(false)
+      }
+      _ => None,
+    }
+  }
+
+  /// Closes every open interval, as nothing is known about the faders while the link is down.
+  /// The console's state dump on the next link opens them again.
+  pub fn close_all(&mut self, now: u64) -> usize {
+    let mut indexes: Vec<u16> = self.open.keys().copied().collect();
+    indexes.sort_unstable();
+    for index in indexes.iter() {
+      self.close(*index, now);
+    }
+    indexes.len()
+  }
+
+  fn close(&mut self, index: u16, now: u64) {
+    if let Some(mut interval) = self.open.remove(&index) {
+      interval.end = Some(now);
+      self.closed.push_back(interval);
+      if self.closed.len() > MAX_CLOSED_INTERVALS {
+        self.closed.pop_front();
+      }
+    }
+  }
+
+  /// Every interval overlapping the window, ordered by start time
+  pub fn as_run(&self, from: u64, to: u64) -> Vec<OnAirInterval> {
+    let mut intervals: Vec<OnAirInterval> = self.closed.iter()
+      .chain(self.open.values())
+      .filter(|interval| interval.start <= to && interval.end.map_or(true, |end| end >= from))
+      .cloned()
+      .collect();
+    intervals.sort_by_key(|interval| (interval.start, interval.index));
+    intervals
+  }
+
+  /// On-air time per fader within the window, with open intervals counted up to `now`
+  pub fn report(&self, from: u64, to: u64, now: u64) -> OnAirReport {
+    let mut channels: BTreeMap<u16, OnAirChannelReport> = BTreeMap::new();
+
+    for interval in self.as_run(from, to) {
+      let start = cmp::max(interval.start, from);
+      let end = cmp::min(interval.end.unwrap_or(now), to);
+
+      let channel = channels.entry(interval.index).or_insert_with(|| OnAirChannelReport {
+        index: interval.index,
+        label: interval.label.clone(),
+        duration: 0,
+        count: 0,
+      });
+      channel.label = interval.label;
+      channel.duration += end.saturating_sub(start);
+      channel.count += 1;
+    }
+
+    OnAirReport { from, to, channels: channels.into_values().collect() }
+  }
+}
+
+pub async fn track(tracker: OnAirStorage, mut event_rx: broadcast::Receiver<ConsoleEvent>) {
+  loop {
+    match event_rx.recv().await {
+      Ok(ConsoleEvent::FADER(fader)) => {
+        if let Some(isOnAir) = tracker.lock().await.update(&fader, now_millis()) {
+          eprintln!("ON AIR :: faderNum={} isOnAir={}", fader.index, isOnAir);
+        }
+      }
+      Ok(ConsoleEvent::LINK(status)) if !status.connected => {
+        let closed = tracker.lock().await.close_all(now_millis());
+        if closed > 0 {
+          eprintln!("ON AIR :: link lost, closed {} intervals", closed);
+        }
+      }
+      Ok(_) => {}
+      Err(broadcast::error::RecvError::Lagged(skipped)) => {
+        eprintln!("On air tracker missed {} fader events", skipped);
+      }
+      Err(broadcast::error::RecvError::Closed) => break,
+    }
+  }
+}
+
+pub fn report_to_csv(report: &OnAirReport) -> String {
+  let mut contents = String::from("fader,label,durationMs,count\n");
+
+  for channel in report.channels.iter() {
+    contents.push_str(&csv::row(&[
+      (channel.index + 1).to_string(),
+      channel.label.clone(),
+      channel.duration.to_string(),
+      channel.count.to_string(),
+    ]));
+  }
+
+  contents
+}
+
+pub fn as_run_to_csv(intervals: &[OnAirInterval]) -> String {
+  let mut contents = String::from("fader,label,start,end\n");
+
+  for interval in intervals {
+    contents.push_str(&csv::row(&[
+      (interval.index + 1).to_string(),
+      interval.label.clone(),
+      interval.start.to_string(),
+      interval.end.map(|end| end.to_string()).unwrap_or_default(),
+    ]));
+  }
+
+  contents
+}
@@ -0,0 +1,143 @@
+use std::io::Cursor;
+use bytes::{Buf, BufMut, BytesMut};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OscArg {
+  INT(i32),
+  FLOAT(f32),
+  STRING(String),
+  BOOL(bool),
+}
+
+impl OscArg {
+  pub fn as_f32(&self) -> Option<f32> {
+    match self {
+      OscArg::INT(value) => Some(*value as f32),
+      OscArg::FLOAT(value) => Some(*value),
+      OscArg::BOOL(value) => Some(*value as u8 as f32),
+      OscArg::STRING(value) => value.parse().ok(),
+    }
+  }
+
+  /// Buttons send 1/0, toggles send floats or booleans
+  pub fn as_bool(&self) -> Option<bool> {
+    match self {
+      OscArg::BOOL(value) => Some(*value),
+      arg => arg.as_f32().map(|value| value >= 0.5),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OscMessage {
+  pub address: String,
+  pub args: Vec<OscArg>,
+}
+
+impl OscMessage {
+  pub fn new(address: String, args: Vec<OscArg>) -> OscMessage {
+    OscMessage { address, args }
+  }
+
+  pub fn encode(&self) -> Vec<u8> {
+    let mut buffer = BytesMut::new();
+    put_string(&mut buffer, &self.address);
+
+    let mut tags = String::from(",");
+    for arg in self.args.iter() {
+      tags.push(match arg {
+        OscArg::INT(_) => 'i',
+        OscArg::FLOAT(_) => 'f',
+        OscArg::STRING(_) => 's',
+        OscArg::BOOL(true) => 'T',
+        OscArg::BOOL(false) => 'F',
+      });
+    }
+    put_string(&mut buffer, &tags);
+
+    for arg in self.args.iter() {
+      match arg {
+        OscArg::INT(value) => buffer.put_i32(*value),
+        OscArg::FLOAT(value) => buffer.put_f32(*value),
+        OscArg::STRING(value) => put_string(&mut buffer, value),
+        OscArg::BOOL(_) => {}
+      }
+    }
+
+    buffer.to_vec()
+  }
+}
+
+/// Decodes a packet into its messages, flattening any bundles
+pub fn decode(packet: &[u8]) -> Result<Vec<OscMessage>, String> {
+  let mut src = Cursor::new(packet);
+  let address = get_string(&mut src)?;
+
+  if address == "#bundle" {
+    if src.remaining() < 8 { return Err("Bundle missing time tag".to_string()); }
+    // Time tags are ignored, everything is applied immediately
+    src.advance(8);
+
+    let mut messages = vec![];
+    while src.remaining() >= 4 {
+      let size = src.get_i32() as usize;
+      if size > src.remaining() { return Err("Bundle element overruns packet".to_string()); }
+      let start = src.position() as usize;
+      messages.extend(decode(&packet[start..start + size])?);
+      src.advance(size);
+    }
+    return Ok(messages);
+  }
+
+  if !address.starts_with('/') {
+    return Err(format!("Bad address {:?}", address));
+  }
+
+  // Very old clients omit the type tags entirely
+  if !src.has_remaining() {
+    return Ok(vec![OscMessage::new(address, vec![])]);
+  }
+
+  let tags = get_string(&mut src)?;
+  let mut args = vec![];
+  for tag in tags.chars().skip(1) {
+    match tag {
+      'i' => args.push(OscArg::INT(get_checked(&mut src, 4)?.get_i32())),
+      'f' => args.push(OscArg::FLOAT(get_checked(&mut src, 4)?.get_f32())),
+      'h' => args.push(OscArg::INT(get_checked(&mut src, 8)?.get_i64() as i32)),
+      'd' => args.push(OscArg::FLOAT(get_checked(&mut src, 8)?.get_f64() as f32)),
+      's' | 'S' => args.push(OscArg::STRING(get_string(&mut src)?)),
+      'T' => args.push(OscArg::BOOL(true)),
+      'F' => args.push(OscArg::BOOL(false)),
+      'N' | 'I' => {}
+      tag => return Err(format!("Unsupported OSC type tag {:?}", tag)),
+    }
+  }
+
+  Ok(vec![OscMessage::new(address, args)])
+}
+
+fn get_checked<'a, 'b>(src: &'a mut Cursor<&'b [u8]>, len: usize) -> Result<&'a mut Cursor<&'b [u8]>, String> {
+  if src.remaining() < len {
+    return Err("Truncated OSC argument".to_string());
+  }
+  Ok(src)
+}
+
+/// OSC strings are null terminated and padded to a multiple of four bytes
+fn get_string(src: &mut Cursor<&[u8]>) -> Result<String, String> {
+  let start = src.position() as usize;
+  let bytes = &src.get_ref()[start..];
+  let end = bytes.iter().position(|&b| b == 0).ok_or_else(|| "Unterminated OSC string".to_string())?;
+  let value = String::from_utf8_lossy(&bytes[..end]).to_string();
+
+  let padded = (end + 4) & !3;
+  src.set_position((start + padded.min(bytes.len())) as u64);
+  Ok(value)
+}
+
+fn put_string(buffer: &mut BytesMut, value: &str) {
+  buffer.put_slice(value.as_bytes());
+  let padding = 4 - value.len() % 4;
+  buffer.put_bytes(0, padding);
+}
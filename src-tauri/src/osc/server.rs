@@ -0,0 +1,219 @@
+use std::{collections::HashSet, io, net::SocketAddr};
+use common::{Aux, BusType, Fader, Main, Source, level::{db_to_level, level_to_db}};
+use tokio::{net::UdpSocket, sync::{broadcast, mpsc}};
+
+use crate::{config::OscConfig, cscp::{
+  events::ConsoleEvent,
+  requests::{get_db, Request, SetAuxLevel, SetFaderCut, SetFaderLevel, SetFaderPfl, SetMainLevel, SetMainPfl},
+  selector::{FaderRef, SelectError},
+}};
+use super::message::{decode, OscArg, OscMessage};
+
+/// OSC over UDP. Numbers in addresses are 1-based, as shown on the desk.
+///
+/// - `/fader/{n}/level` (dB), `/fader/{n}/level/raw` (0-1023), `/fader/{n}/cut`, `/fader/{n}/pfl`
+/// - `/main/{n}/level`, `/main/{n}/level/raw`, `/main/{n}/pfl`
+/// - `/aux/{n}/level`, `/aux/{n}/level/raw`
+///
+/// Faders can be addressed by label too, see `FaderSelector`: either in place of the number,
+/// as in `/fader/PRES*/cut`, or for labels that don't fit in an address as a first string
+/// argument to `/label/level`, `/label/cut` and so on.
+///
+/// Sending any of these without arguments replies with the current state.
+/// Anyone sending a message is registered for feedback until they send `/unsubscribe`,
+/// `/subscribe` registers without doing anything else and `/sync` replies with everything.
+pub async fn run(config: OscConfig, input_tx: mpsc::Sender<Request>, mut event_rx: broadcast::Receiver<ConsoleEvent>) -> io::Result<()> {
+  let socket = UdpSocket::bind(&config.bind).await?;
+  eprintln!("OSC :: listening on {}", config.bind);
+
+  let mut clients: HashSet<SocketAddr> = config.clients.iter()
+    .filter_map(|client| client.parse().map_err(|e| eprintln!("OSC :: bad client address {} :: {}", client, e)).ok())
+    .collect();
+  let mut buffer = [0u8; 4096];
+
+  loop {
+    tokio::select! {
+      received = socket.recv_from(&mut buffer) => {
+        let (n, peer) = received?;
+        match decode(&buffer[..n]) {
+          Ok(messages) => {
+            for message in messages {
+              handle(&socket, &input_tx, &mut clients, peer, message).await;
+            }
+          }
+          Err(e) => eprintln!("OSC :: bad packet from {} :: {}", peer, e),
+        }
+      }
+      event = event_rx.recv() => {
+        let messages = match event {
+          Ok(ConsoleEvent::FADER(fader)) => fader_messages(&fader),
+          Ok(ConsoleEvent::MAIN(main)) => main_messages(&main),
+          Ok(ConsoleEvent::AUX(aux)) => aux_messages(&aux),
+          Ok(ConsoleEvent::DESK(_) | ConsoleEvent::LINK(_)) => continue,
+          Err(broadcast::error::RecvError::Lagged(_)) => continue,
+          Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        };
+        for client in clients.iter() {
+          send(&socket, *client, &messages).await;
+        }
+      }
+    }
+  }
+}
+
+async fn handle(socket: &UdpSocket, input_tx: &mpsc::Sender<Request>, clients: &mut HashSet<SocketAddr>, peer: SocketAddr, message: OscMessage) {
+  let parts: Vec<&str> = message.address.trim_start_matches('/').split('/').collect();
+
+  if parts == ["unsubscribe"] {
+    clients.remove(&peer);
+    return;
+  }
+  if clients.insert(peer) {
+    eprintln!("OSC :: registered client {}", peer);
+  }
+
+  let (bus, number, parameter, args) = match parts.as_slice() {
+    ["subscribe"] => return,
+    ["sync"] => {
+      if let Ok(db) = get_db(input_tx).await {
+        let mut messages = vec![];
+        db.faders.iter().for_each(|fader| messages.extend(fader_messages(fader)));
+        db.mains.iter().for_each(|main| messages.extend(main_messages(main)));
+        db.auxes.iter().for_each(|aux| messages.extend(aux_messages(aux)));
+        send(socket, peer, &messages).await;
+      }
+      return;
+    }
+    ["label", parameter @ ..] => match message.args.split_first() {
+      Some((OscArg::STRING(label), args)) => (BusType::FADER, label.as_str(), parameter, args),
+      _ => {
+        eprintln!("OSC :: {} needs a fader label as its first argument", message.address);
+        return;
+      }
+    },
+    ["fader", number, parameter @ ..] => (BusType::FADER, *number, parameter, message.args.as_slice()),
+    ["main", number, parameter @ ..] => (BusType::MAIN, *number, parameter, message.args.as_slice()),
+    ["aux", number, parameter @ ..] => (BusType::AUX, *number, parameter, message.args.as_slice()),
+    _ => {
+      eprintln!("OSC :: unknown address {}", message.address);
+      return;
+    }
+  };
+
+  let index = match bus {
+    BusType::FADER => fader_index(input_tx, number).await,
+    _ => number.parse::<u16>().ok().and_then(|number| number.checked_sub(1)).ok_or_else(|| String::from("bad number")),
+  };
+  let index = match index {
+    Ok(index) => index,
+    Err(e) => {
+      eprintln!("OSC :: {} :: {}", message.address, e);
+      return;
+    }
+  };
+
+  let arg = match args.first() {
+    Some(arg) => arg,
+    None => {
+      reply_state(socket, input_tx, peer, bus, index).await;
+      return;
+    }
+  };
+
+  match to_request(bus, index, parameter, arg) {
+    Some(request) => {
+      if let Err(e) = input_tx.send(request).await {
+        eprintln!("OSC :: failed to forward {} :: {}", message.address, e);
+      }
+    }
+    None => eprintln!("OSC :: can't handle {} {:?}", message.address, message.args),
+  }
+}
+
+/// Index of the fader numbered or labelled `fader`, labels are looked up as the console reports them now
+async fn fader_index(input_tx: &mpsc::Sender<Request>, fader: &str) -> Result<u16, String> {
+  let fader: FaderRef = fader.parse().map_err(|e: SelectError| e.to_string())?;
+  let faders = if fader.is_label() { get_db(input_tx).await?.faders } else { vec![] };
+  fader.index(&faders).map_err(|e| e.to_string())
+}
+
+fn to_request(bus: BusType, index: u16, parameter: &[&str], arg: &OscArg) -> Option<Request> {
+  let source = Source::API;
+
+  let request = match (bus, parameter) {
+    (BusType::FADER, ["level"]) => Request::SET_FADER_LEVEL(SetFaderLevel { index, level: db_to_level(arg.as_f32()?), source }),
+    (BusType::FADER, ["level", "raw"]) => Request::SET_FADER_LEVEL(SetFaderLevel { index, level: raw_level(arg)?, source }),
+    (BusType::FADER, ["cut"]) => Request::SET_FADER_CUT(SetFaderCut { index, isCut: arg.as_bool()?, source }),
+    (BusType::FADER, ["pfl"]) => Request::SET_FADER_PFL(SetFaderPfl { index, isPfl: arg.as_bool()?, source }),
+    (BusType::MAIN, ["level"]) => Request::SET_MAIN_LEVEL(SetMainLevel { index, level: db_to_level(arg.as_f32()?), source }),
+    (BusType::MAIN, ["level", "raw"]) => Request::SET_MAIN_LEVEL(SetMainLevel { index, level: raw_level(arg)?, source }),
+    (BusType::MAIN, ["pfl"]) => Request::SET_MAIN_PFL(SetMainPfl { index, isPfl: arg.as_bool()?, source }),
+    (BusType::AUX, ["level"]) => Request::SET_AUX_LEVEL(SetAuxLevel { index, level: db_to_level(arg.as_f32()?), source }),
+    (BusType::AUX, ["level", "raw"]) => Request::SET_AUX_LEVEL(SetAuxLevel { index, level: raw_level(arg)?, source }),
+    _ => return None,
+  };
+
+  Some(request)
+}
+
+fn raw_level(arg: &OscArg) -> Option<u16> {
+  Some(arg.as_f32()?.round().clamp(0.0, common::level::MAX_LEVEL as f32) as u16)
+}
+
+async fn reply_state(socket: &UdpSocket, input_tx: &mpsc::Sender<Request>, peer: SocketAddr, bus: BusType, index: u16) {
+  let db = match get_db(input_tx).await {
+    Ok(db) => db,
+    Err(e) => {
+      eprintln!("OSC :: failed to read state :: {}", e);
+      return;
+    }
+  };
+
+  let messages = match bus {
+    BusType::FADER => db.faders.iter().find(|fader| fader.index == index).map(fader_messages),
+    BusType::MAIN => db.mains.iter().find(|main| main.index == index).map(main_messages),
+    BusType::AUX => db.auxes.iter().find(|aux| aux.index == index).map(aux_messages),
+  };
+
+  if let Some(messages) = messages {
+    send(socket, peer, &messages).await;
+  }
+}
+
+fn fader_messages(fader: &Fader) -> Vec<OscMessage> {
+  let prefix = format!("/fader/{}", fader.index + 1);
+  vec![
+    OscMessage::new(format!("{}/level", prefix), vec![OscArg::FLOAT(level_to_db(fader.level))]),
+    OscMessage::new(format!("{}/level/raw", prefix), vec![OscArg::INT(fader.level as i32)]),
+    OscMessage::new(format!("{}/cut", prefix), vec![OscArg::INT(fader.isCut as i32)]),
+    OscMessage::new(format!("{}/pfl", prefix), vec![OscArg::INT(fader.isPfl as i32)]),
+    OscMessage::new(format!("{}/label", prefix), vec![OscArg::STRING(fader.label.clone())]),
+  ]
+}
+
+fn main_messages(main: &Main) -> Vec<OscMessage> {
+  let prefix = format!("/main/{}", main.index + 1);
+  vec![
+    OscMessage::new(format!("{}/level", prefix), vec![OscArg::FLOAT(level_to_db(main.level))]),
+    OscMessage::new(format!("{}/level/raw", prefix), vec![OscArg::INT(main.level as i32)]),
+    OscMessage::new(format!("{}/pfl", prefix), vec![OscArg::INT(main.isPfl as i32)]),
+    OscMessage::new(format!("{}/label", prefix), vec![OscArg::STRING(main.label.clone())]),
+  ]
+}
+
+fn aux_messages(aux: &Aux) -> Vec<OscMessage> {
+  let prefix = format!("/aux/{}", aux.index + 1);
+  vec![
+    OscMessage::new(format!("{}/level", prefix), vec![OscArg::FLOAT(level_to_db(aux.level))]),
+    OscMessage::new(format!("{}/level/raw", prefix), vec![OscArg::INT(aux.level as i32)]),
+  ]
+}
+
+async fn send(socket: &UdpSocket, peer: SocketAddr, messages: &[OscMessage]) {
+  for message in messages {
+    if let Err(e) = socket.send_to(&message.encode(), peer).await {
+      eprintln!("OSC :: failed to send to {} :: {}", peer, e);
+      return;
+    }
+  }
+}
@@ -0,0 +1,255 @@
+use std::{io, net::SocketAddr, time::Duration};
+use common::{Aux, DB, Fader, Main, Source, inspector::Direction};
+use tokio::{net::TcpListener, sync::{broadcast, mpsc, oneshot}};
+
+use crate::{config::ProxyConfig, cscp::{
+  connection::{Connection, Traffic, TrafficSender},
+  frame::{Frame, FrameAck, FrameError, Message},
+  requests::{get_db, Request, SetAuxLevel, SetFaderCut, SetFaderLevel, SetFaderPfl, SetMainLevel, SetMainPfl},
+}};
+
+const SET: u8 = 0x80;
+
+/// What a downstream client gets back for one of its frames, queued so replies keep the order of the frames
+enum Reply {
+  READY(Vec<Frame>),
+  CONSOLE(oneshot::Receiver<Result<(), u8>>),
+}
+
+/// CSCP proxy sharing the client's console link with any number of downstream CSCP clients.
+///
+/// Queries are answered from the cached state where possible and forwarded to the console
+/// otherwise. Sets are forwarded and the console's ACK/ERR goes back to whoever sent them.
+/// Every message from the console is passed on to all downstream clients.
+pub async fn run(config: ProxyConfig, input_tx: mpsc::Sender<Request>, traffic_tx: TrafficSender) -> io::Result<()> {
+  let listener = TcpListener::bind(&config.bind).await?;
+  eprintln!("PROXY :: listening on {}", config.bind);
+
+  loop {
+    let (stream, peer) = match listener.accept().await {
+      Ok(accepted) => accepted,
+      Err(e) => {
+        // Running out of file descriptors fails every accept, so back off rather than spin
+        eprintln!("PROXY :: failed to accept a client :: {}", e);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        continue;
+      }
+    };
+    eprintln!("PROXY :: client connected {}", peer);
+    tokio::spawn(serve(Connection::new(stream), peer, input_tx.clone(), traffic_tx.subscribe()));
+  }
+}
+
+async fn serve(connection: Connection, peer: SocketAddr, input_tx: mpsc::Sender<Request>, mut traffic_rx: broadcast::Receiver<Traffic>) {
+  let (mut read, mut write) = connection.split();
+  let (out_tx, mut out_rx) = mpsc::channel::<Frame>(256);
+
+  let writer = tokio::spawn(async move {
+    while let Some(frame) = out_rx.recv().await {
+      if let Err(e) = write.write_frame(frame).await {
+        eprintln!("PROXY :: failed to write to {} :: {}", peer, e);
+        return;
+      }
+    }
+  });
+
+  let (reply_tx, mut reply_rx) = mpsc::channel::<Reply>(256);
+  let sequencer_tx = out_tx.clone();
+  let sequencer = tokio::spawn(async move {
+    while let Some(reply) = reply_rx.recv().await {
+      let frames = match reply {
+        Reply::READY(frames) => frames,
+        Reply::CONSOLE(ack_rx) => match ack_rx.await {
+          Ok(Ok(())) => vec![Frame::new(Message::ACK(FrameAck {}))],
+          Ok(Err(error)) => vec![Frame::new(Message::ERR(FrameError { error }))],
+          Err(_) => continue,
+        },
+      };
+      for frame in frames {
+        if sequencer_tx.send(frame).await.is_err() {
+          return;
+        }
+      }
+    }
+  });
+
+  let fan_out_tx = out_tx.clone();
+  let fan_out = tokio::spawn(async move {
+    loop {
+      match traffic_rx.recv().await {
+        Ok(Traffic { direction: Direction::RX, frame: frame @ Frame { msg: Message::MSG(_) }, .. }) => {
+          if fan_out_tx.send(frame).await.is_err() {
+            return;
+          }
+        }
+        Ok(_) => {}
+        Err(broadcast::error::RecvError::Lagged(n)) => eprintln!("PROXY :: {} dropped {} console messages", peer, n),
+        Err(broadcast::error::RecvError::Closed) => return,
+      }
+    }
+  });
+
+  loop {
+    match read.read_frame().await {
+      Ok(Some(frame)) => {
+        if let Message::MSG(msg) = frame.msg {
+          handle(&input_tx, &reply_tx, msg.buffer).await;
+        }
+      }
+      Ok(None) => {}
+      Err(e) => {
+        eprintln!("PROXY :: client disconnected {} :: {}", peer, e);
+        break;
+      }
+    }
+  }
+
+  fan_out.abort();
+  sequencer.abort();
+  writer.abort();
+}
+
+async fn handle(input_tx: &mpsc::Sender<Request>, reply_tx: &mpsc::Sender<Reply>, buffer: Vec<u8>) {
+  if buffer.len() < 6 {
+    eprintln!("PROXY :: ignoring short frame {:?}", buffer);
+    return;
+  }
+  let (flags, cmd) = (buffer[3], buffer[4]);
+  let data = &buffer[5..buffer.len() - 1];
+
+  if flags & SET != 0 {
+    let request = to_request(cmd, data).unwrap_or_else(|| Request::SEND_FRAME(raw_frame(&buffer)));
+    forward(input_tx, reply_tx, request).await;
+    return;
+  }
+
+  let db = match get_db(input_tx).await {
+    Ok(db) => db,
+    Err(e) => {
+      eprintln!("PROXY :: failed to read state :: {}", e);
+      return;
+    }
+  };
+
+  match answer(&db, cmd, data) {
+    Some(frames) => {
+      let frames = [vec![Frame::new(Message::ACK(FrameAck {}))], frames].concat();
+      let _ = reply_tx.send(Reply::READY(frames)).await;
+    }
+    // Not cached, the console's reply reaches every client
+    None => forward(input_tx, reply_tx, Request::SEND_FRAME(raw_frame(&buffer))).await,
+  }
+}
+
+/// Queues the console's ACK/ERR for the client without waiting for it
+async fn forward(input_tx: &mpsc::Sender<Request>, reply_tx: &mpsc::Sender<Reply>, request: Request) {
+  let (ack_tx, ack_rx) = oneshot::channel();
+  if let Err(e) = input_tx.send(Request::CONFIRMED(Box::new(request), ack_tx)).await {
+    eprintln!("PROXY :: failed to forward :: {}", e);
+    return;
+  }
+  let _ = reply_tx.send(Reply::CONSOLE(ack_rx)).await;
+}
+
+fn raw_frame(buffer: &[u8]) -> Frame {
+  Frame::send(buffer[3..5].to_vec(), buffer[5..buffer.len() - 1].to_vec(), vec![])
+}
+
+fn number(data: &[u8]) -> Option<u16> {
+  Some(u16::from_be_bytes([*data.first()?, *data.get(1)?]))
+}
+
+fn value_u16(data: &[u8]) -> Option<u16> {
+  Some(u16::from_be_bytes([*data.get(2)?, *data.get(3)?]))
+}
+
+fn value_bool(data: &[u8]) -> Option<bool> {
+  Some(*data.get(2)? != 0)
+}
+
+fn to_request(cmd: u8, data: &[u8]) -> Option<Request> {
+  let index = number(data)?;
+  let source = Source::API;
+
+  let request = match cmd {
+    0x00 => Request::SET_FADER_LEVEL(SetFaderLevel { index, level: value_u16(data)?, source }),
+    0x01 => Request::SET_FADER_CUT(SetFaderCut { index, isCut: value_bool(data)?, source }),
+    0x05 => Request::SET_FADER_PFL(SetFaderPfl { index, isPfl: value_bool(data)?, source }),
+    0x02 => Request::SET_MAIN_LEVEL(SetMainLevel { index, level: value_u16(data)?, source }),
+    0x0C => Request::SET_MAIN_PFL(SetMainPfl { index, isPfl: value_bool(data)?, source }),
+    0x13 => Request::SET_AUX_LEVEL(SetAuxLevel { index, level: value_u16(data)?, source }),
+    _ => return None,
+  };
+
+  Some(request)
+}
+
+/// Builds the console's reply to a query from the cache, `None` when it isn't known
+fn answer(db: &DB, cmd: u8, data: &[u8]) -> Option<Vec<Frame>> {
+  let info = &db.deskInfo;
+
+  match cmd {
+    0x07 if !info.name.is_empty() => return Some(vec![Frame::send(vec![0x00, 0x07], vec![], info.name.as_bytes().to_vec())]),
+    0x08 if info.numFaders > 0 => {
+      let mut value = [info.cscpVersion.to_be_bytes(), info.numFaders.to_be_bytes(), info.numMains.to_be_bytes()].concat();
+      value.extend([0u8; 6]);
+      value.extend(info.name.as_bytes());
+
+      let mut frames = vec![Frame::send(vec![0x00, 0x08], vec![], value)];
+      db.faders.iter().for_each(|fader| frames.extend(fader_frames(fader)));
+      db.mains.iter().for_each(|main| frames.extend(main_frames(main)));
+      db.auxes.iter().for_each(|aux| frames.extend(aux_frames(aux)));
+      return Some(frames);
+    }
+    _ => {}
+  }
+
+  let index = number(data)?;
+  let fader = || db.faders.iter().find(|fader| fader.index == index);
+  let main = || db.mains.iter().find(|main| main.index == index);
+
+  let frame = match cmd {
+    0x00 => reply(0x00, index, fader()?.level.to_be_bytes().to_vec()),
+    0x01 => reply(0x01, index, vec![fader()?.isCut as u8]),
+    0x05 => reply(0x05, index, vec![fader()?.isPfl as u8]),
+    0x0B => reply(0x0B, index, fader()?.label.as_bytes().to_vec()),
+    0x11 => {
+      let fader = fader()?;
+      reply(0x11, index, vec![fader.pathType as u8, fader.format as u8])
+    }
+    0x02 => reply(0x02, index, main()?.level.to_be_bytes().to_vec()),
+    // The console reports main PFL as 0 when it is on
+    0x0C => reply(0x0C, index, vec![!main()?.isPfl as u8]),
+    0x0D => reply(0x0D, index, main()?.label.as_bytes().to_vec()),
+    0x13 => reply(0x13, index, db.auxes.iter().find(|aux| aux.index == index)?.level.to_be_bytes().to_vec()),
+    _ => return None,
+  };
+
+  Some(vec![frame])
+}
+
+fn reply(cmd: u8, index: u16, value: Vec<u8>) -> Frame {
+  Frame::send(vec![0x00, cmd], index.to_be_bytes().to_vec(), value)
+}
+
+fn fader_frames(fader: &Fader) -> Vec<Frame> {
+  vec![
+    reply(0x11, fader.index, vec![fader.pathType as u8, fader.format as u8]),
+    reply(0x0B, fader.index, fader.label.as_bytes().to_vec()),
+    reply(0x00, fader.index, fader.level.to_be_bytes().to_vec()),
+    reply(0x01, fader.index, vec![fader.isCut as u8]),
+    reply(0x05, fader.index, vec![fader.isPfl as u8]),
+  ]
+}
+
+fn main_frames(main: &Main) -> Vec<Frame> {
+  vec![
+    reply(0x0D, main.index, main.label.as_bytes().to_vec()),
+    reply(0x02, main.index, main.level.to_be_bytes().to_vec()),
+    reply(0x0C, main.index, vec![!main.isPfl as u8]),
+  ]
+}
+
+fn aux_frames(aux: &Aux) -> Vec<Frame> {
+  vec![reply(0x13, aux.index, aux.level.to_be_bytes().to_vec())]
+}
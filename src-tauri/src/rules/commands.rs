@@ -0,0 +1,40 @@
+use std::path::PathBuf;
+use common::rules::{Rule, RuleStatus};
+
+use crate::config::Config;
+use super::engine::{validate, RuleStorage};
+
+pub struct RulesState {
+  pub inner: RuleStorage,
+  /// Config file the rules are saved to
+  pub configPath: PathBuf,
+}
+
+#[tauri::command]
+pub async fn getRules(
+    state: tauri::State<'_, RulesState>,
+) -> Result<Vec<Rule>, String> {
+    Ok(state.inner.lock().await.rules())
+}
+
+#[tauri::command]
+pub async fn getRuleStatus(
+    state: tauri::State<'_, RulesState>,
+) -> Result<Vec<RuleStatus>, String> {
+    Ok(state.inner.lock().await.status())
+}
+
+#[tauri::command]
+pub async fn saveRules(
+    rules: Vec<Rule>,
+    state: tauri::State<'_, RulesState>,
+) -> Result<Vec<RuleStatus>, String> {
+    eprintln!("saveRules count={}", rules.len());
+    rules.iter().try_for_each(validate)?;
+    let value = serde_json::to_value(&rules).map_err(|e| e.to_string())?;
+    Config::update(&state.configPath, "rules", value)?;
+
+    let mut engine = state.inner.lock().await;
+    engine.set_rules(rules);
+    Ok(engine.status())
+}
@@ -0,0 +1,404 @@
+#![allow(non_snake_case)]
+use std::{collections::{HashMap, VecDeque}, sync::Arc, time::Duration};
+use common::{Aux, Fader, Main, Source, DB, level::{db_to_level, level_to_db, MIN_DB}, rules::{BusState, Condition, Rule, RuleAction, RuleAlert, RuleStatus}};
+use futures_util::lock::Mutex;
+use tokio::{sync::{broadcast, mpsc}, time::{self, Instant}};
+
+use crate::{
+  clock::now_millis,
+  cscp::{
+    events::ConsoleEvent,
+    requests::{get_db, Request, SetAuxLevel, SetFaderCut, SetFaderLevel, SetFaderPfl, SetMainLevel, SetMainPfl},
+    selector::{FaderRef, SelectError},
+  },
+  scripts::host::ScriptStorage,
+};
+
+pub type RuleStorage = Arc<Mutex<RuleEngine>>;
+
+/// A rule firing more than this often within `LOOP_WINDOW` is taken to be in a loop and suspended
+const MAX_FIRINGS: usize = 10;
+const LOOP_WINDOW: Duration = Duration::from_secs(10);
+/// How long after the console (re)connects reports are taken as its state dump rather than changes
+const SETTLE_TIME: Duration = Duration::from_secs(3);
+/// How often hold times and cooldowns are checked
+const TICK: Duration = Duration::from_millis(100);
+
+/// What the rules that fired want done
+#[derive(Default)]
+pub struct Fired {
+  pub requests: Vec<Request>,
+  pub alerts: Vec<RuleAlert>,
+  /// Scripts to start, with the rule starting them
+  pub scripts: Vec<(String, String)>,
+}
+
+struct RuleState {
+  rule: Rule,
+  /// The condition's fader, parsed once
+  fader: Option<FaderRef>,
+  /// Why the rule can't run
+  error: Option<String>,
+  /// When the condition started to hold
+  since: Option<Instant>,
+  /// Whether the rule is done for the current stretch of the condition holding
+  fired: bool,
+  /// Whether `then` actually ran for it, so `otherwise` has something to undo
+  ran: bool,
+  lastFired: Option<Instant>,
+  lastFiredAt: Option<u64>,
+  firings: u64,
+  recent: VecDeque<Instant>,
+  suspended: bool,
+}
+
+impl RuleState {
+  fn new(rule: Rule) -> RuleState {
+    let error = validate(&rule).err();
+    let fader = rule.when.fader.as_ref().and_then(|fader| fader.parse().ok());
+    RuleState {
+      rule, fader, error,
+      since: None, fired: false, ran: false,
+      lastFired: None, lastFiredAt: None, firings: 0, recent: VecDeque::new(), suspended: false,
+    }
+  }
+
+  fn is_running(&self) -> bool {
+    self.rule.enabled && !self.suspended && self.error.is_none()
+  }
+}
+
+/// Console state as reported, the rules and where each of them stands
+#[derive(Default)]
+pub struct RuleEngine {
+  rules: Vec<RuleState>,
+  faders: HashMap<u16, Fader>,
+  mains: HashMap<u16, Main>,
+  auxes: HashMap<u16, Aux>,
+  connected: bool,
+  settleUntil: Option<Instant>,
+}
+
+impl RuleEngine {
+  pub fn new(rules: Vec<Rule>) -> RuleEngine {
+    let mut engine = RuleEngine::default();
+    engine.set_rules(rules);
+    engine
+  }
+
+  pub fn rules(&self) -> Vec<Rule> {
+    self.rules.iter().map(|state| state.rule.clone()).collect()
+  }
+
+  /// Replaces the rules. Conditions that already hold don't fire until they next start to.
+  pub fn set_rules(&mut self, rules: Vec<Rule>) {
+    self.rules = rules.into_iter().map(RuleState::new).collect();
+    for error in self.rules.iter().filter_map(|state| state.error.as_ref()) {
+      eprintln!("RULES :: rule off :: {}", error);
+    }
+    self.step(Instant::now(), true);
+  }
+
+  pub fn status(&self) -> Vec<RuleStatus> {
+    self.rules.iter().map(|state| RuleStatus {
+      name: state.rule.name.clone(),
+      enabled: state.rule.enabled,
+      active: state.since.is_some(),
+      suspended: state.suspended,
+      error: state.error.clone(),
+      firings: state.firings,
+      lastFired: state.lastFiredAt,
+    }).collect()
+  }
+
+  /// Takes in the state the console has reported so far
+  pub fn seed(&mut self, db: DB, now: Instant) {
+    self.faders = db.faders.into_iter().map(|fader| (fader.index, fader)).collect();
+    self.mains = db.mains.into_iter().map(|main| (main.index, main)).collect();
+    self.auxes = db.auxes.into_iter().map(|aux| (aux.index, aux)).collect();
+    self.connected = db.link.connected;
+    self.step(now, true);
+  }
+
+  pub fn handle(&mut self, event: &ConsoleEvent, now: Instant) -> Fired {
+    match event {
+      ConsoleEvent::FADER(fader) => { self.faders.insert(fader.index, fader.clone()); }
+      ConsoleEvent::MAIN(main) => { self.mains.insert(main.index, main.clone()); }
+      ConsoleEvent::AUX(aux) => { self.auxes.insert(aux.index, aux.clone()); }
+      ConsoleEvent::LINK(link) => {
+        if link.connected && !self.connected {
+          self.settleUntil = Some(now + SETTLE_TIME);
+        }
+        self.connected = link.connected;
+      }
+      ConsoleEvent::DESK(_) => {}
+    }
+    self.evaluate(now)
+  }
+
+  /// Fires every rule whose condition has held long enough, and undoes those that stopped holding
+  pub fn evaluate(&mut self, now: Instant) -> Fired {
+    let settling = self.settleUntil.is_some_and(|until| now < until);
+    self.step(now, settling)
+  }
+
+  /// With `baseline` conditions found holding are taken as the starting state and don't fire
+  fn step(&mut self, now: Instant, baseline: bool) -> Fired {
+    let RuleEngine { rules, faders, mains, auxes, .. } = self;
+    let mut fired = Fired::default();
+
+    for state in rules.iter_mut() {
+      if !state.is_running() {
+        state.since = None;
+        continue;
+      }
+
+      let holds = holds(&state.rule.when, state.fader.as_ref(), faders, mains, auxes);
+      let actions = if holds {
+        let since = *state.since.get_or_insert(now);
+        let cooled = state.lastFired.map_or(true, |at| now.duration_since(at) >= Duration::from_millis(state.rule.cooldown));
+        if state.fired {
+          None
+        } else if baseline {
+          state.fired = true;
+          None
+        } else if now.duration_since(since) >= Duration::from_millis(state.rule.holdFor) && cooled {
+          state.fired = true;
+          state.ran = true;
+          Some(state.rule.then.clone())
+        } else {
+          None
+        }
+      } else {
+        state.since = None;
+        state.fired = false;
+        if std::mem::take(&mut state.ran) && !state.rule.otherwise.is_empty() {
+          Some(state.rule.otherwise.clone())
+        } else {
+          None
+        }
+      };
+
+      let actions = match actions {
+        Some(actions) => actions,
+        None => continue,
+      };
+
+      state.recent.push_back(now);
+      while state.recent.front().is_some_and(|at| now.duration_since(*at) > LOOP_WINDOW) {
+        state.recent.pop_front();
+      }
+      if state.recent.len() > MAX_FIRINGS {
+        state.suspended = true;
+        state.since = None;
+        fired.alerts.push(RuleAlert {
+          rule: state.rule.name.clone(),
+          message: format!("Fired {} times in {} s and was suspended, save the rules to resume it", state.recent.len(), LOOP_WINDOW.as_secs()),
+          timestamp: now_millis(),
+        });
+        continue;
+      }
+
+      state.lastFired = Some(now);
+      state.lastFiredAt = Some(now_millis());
+      state.firings += 1;
+      eprintln!("RULES :: {} fired", state.rule.name);
+      for action in actions.iter() {
+        act(action, &state.rule.name, faders, mains, auxes, &mut fired);
+      }
+    }
+
+    fired
+  }
+}
+
+/// Why `rule` can't run, if it can't
+pub fn validate(rule: &Rule) -> Result<(), String> {
+  if rule.name.trim().is_empty() {
+    return Err(String::from("Every rule needs a name"));
+  }
+  let fail = |e: String| Err(format!("{} :: {}", rule.name, e));
+  let when = &rule.when;
+
+  match (&when.fader, when.main, when.aux) {
+    (Some(fader), None, None) => {
+      if let Err(e) = fader.parse::<FaderRef>().and_then(|fader| check_number(&fader)) {
+        return fail(e.to_string());
+      }
+    }
+    (None, Some(number), None) | (None, None, Some(number)) if number == 0 => return fail(String::from("Numbers start at 1")),
+    (None, Some(_), None) => {
+      if matches!(when.is, BusState::CUT | BusState::UNCUT) {
+        return fail(String::from("Mains have no cut, use CLOSED"));
+      }
+    }
+    (None, None, Some(_)) => {
+      if !matches!(when.is, BusState::OPEN | BusState::CLOSED) {
+        return fail(String::from("Auxes can only be OPEN or CLOSED"));
+      }
+    }
+    _ => return fail(String::from("The condition needs exactly one of fader, main and aux")),
+  }
+
+  if rule.then.is_empty() && rule.otherwise.is_empty() {
+    return fail(String::from("The rule has no actions"));
+  }
+  for action in rule.then.iter().chain(rule.otherwise.iter()) {
+    let checked = match action {
+      RuleAction::SET_FADER_LEVEL { fader, level, db } => fader.parse::<FaderRef>().and_then(|fader| check_number(&fader)).map_err(|e| e.to_string())
+        .and_then(|_| check_level(*level, *db)),
+      RuleAction::SET_FADER_CUT { fader, .. } | RuleAction::SET_FADER_PFL { fader, .. } => fader.parse::<FaderRef>().and_then(|fader| check_number(&fader)).map_err(|e| e.to_string()),
+      RuleAction::SET_MAIN_LEVEL { main: number, level, db } | RuleAction::SET_AUX_LEVEL { aux: number, level, db } => {
+        if *number == 0 { Err(String::from("Numbers start at 1")) } else { check_level(*level, *db) }
+      }
+      RuleAction::SET_MAIN_PFL { main, .. } => if *main == 0 { Err(String::from("Numbers start at 1")) } else { Ok(()) },
+      RuleAction::ALERT { .. } => Ok(()),
+      RuleAction::RUN_SCRIPT { script } => if script.trim().is_empty() { Err(String::from("RUN_SCRIPT needs a script")) } else { Ok(()) },
+    };
+    if let Err(e) = checked {
+      return fail(e);
+    }
+  }
+  Ok(())
+}
+
+fn check_number(fader: &FaderRef) -> Result<(), SelectError> {
+  match fader {
+    FaderRef::NUMBER(0) => Err(SelectError::INVALID(String::from("Numbers start at 1"))),
+    _ => Ok(()),
+  }
+}
+
+fn check_level(level: Option<u16>, db: Option<f32>) -> Result<(), String> {
+  if level.is_none() && db.is_none() {
+    return Err(String::from("Level actions need level or db"));
+  }
+  Ok(())
+}
+
+/// Every reported fader `fader` picks, any number of them for a label
+fn pick<'a>(fader: &FaderRef, faders: &'a HashMap<u16, Fader>) -> Vec<&'a Fader> {
+  match fader {
+    FaderRef::NUMBER(number) => number.checked_sub(1).and_then(|index| faders.get(&index)).into_iter().collect(),
+    FaderRef::LABEL(selector) => faders.values().filter(|fader| selector.matches(&fader.label)).collect(),
+  }
+}
+
+fn holds(when: &Condition, fader: Option<&FaderRef>, faders: &HashMap<u16, Fader>, mains: &HashMap<u16, Main>, auxes: &HashMap<u16, Aux>) -> bool {
+  let open = |level: u16| level_to_db(level) > when.db.unwrap_or(MIN_DB);
+
+  if let Some(fader) = fader {
+    return pick(fader, faders).into_iter().any(|fader| match when.is {
+      BusState::OPEN => open(fader.level),
+      BusState::CLOSED => !open(fader.level),
+      BusState::CUT => fader.isCut,
+      BusState::UNCUT => !fader.isCut,
+      BusState::PFL => fader.isPfl,
+      BusState::NO_PFL => !fader.isPfl,
+    });
+  }
+  if let Some(main) = when.main.and_then(|number| number.checked_sub(1)).and_then(|index| mains.get(&index)) {
+    return match when.is {
+      BusState::OPEN => open(main.level),
+      BusState::CLOSED => !open(main.level),
+      BusState::PFL => main.isPfl,
+      BusState::NO_PFL => !main.isPfl,
+      BusState::CUT | BusState::UNCUT => false,
+    };
+  }
+  if let Some(aux) = when.aux.and_then(|number| number.checked_sub(1)).and_then(|index| auxes.get(&index)) {
+    return match when.is {
+      BusState::OPEN => open(aux.level),
+      BusState::CLOSED => !open(aux.level),
+      _ => false,
+    };
+  }
+  false
+}
+
+/// Adds the requests `action` needs to `fired`, leaving out anything already as asked
+fn act(action: &RuleAction, rule: &str, faders: &HashMap<u16, Fader>, mains: &HashMap<u16, Main>, auxes: &HashMap<u16, Aux>, fired: &mut Fired) {
+  let source = Source::AUTOMATION;
+  let targets = |fader: &str| fader.parse::<FaderRef>().map(|fader| pick(&fader, faders)).unwrap_or_default();
+  let level = |level: &Option<u16>, db: &Option<f32>| level.or_else(|| db.map(db_to_level)).unwrap_or_default();
+
+  match action {
+    RuleAction::SET_FADER_LEVEL { fader, level: raw, db } => {
+      let level = level(raw, db);
+      fired.requests.extend(targets(fader).into_iter().filter(|fader| fader.level != level)
+        .map(|fader| Request::SET_FADER_LEVEL(SetFaderLevel { index: fader.index, level, source })));
+    }
+    RuleAction::SET_FADER_CUT { fader, isCut } => {
+      fired.requests.extend(targets(fader).into_iter().filter(|fader| fader.isCut != *isCut)
+        .map(|fader| Request::SET_FADER_CUT(SetFaderCut { index: fader.index, isCut: *isCut, source })));
+    }
+    RuleAction::SET_FADER_PFL { fader, isPfl } => {
+      fired.requests.extend(targets(fader).into_iter().filter(|fader| fader.isPfl != *isPfl)
+        .map(|fader| Request::SET_FADER_PFL(SetFaderPfl { index: fader.index, isPfl: *isPfl, source })));
+    }
+    RuleAction::SET_MAIN_LEVEL { main, level: raw, db } => {
+      let (index, level) = (main - 1, level(raw, db));
+      if mains.get(&index).map_or(true, |main| main.level != level) {
+        fired.requests.push(Request::SET_MAIN_LEVEL(SetMainLevel { index, level, source }));
+      }
+    }
+    RuleAction::SET_MAIN_PFL { main, isPfl } => {
+      let index = main - 1;
+      if mains.get(&index).map_or(true, |main| main.isPfl != *isPfl) {
+        fired.requests.push(Request::SET_MAIN_PFL(SetMainPfl { index, isPfl: *isPfl, source }));
+      }
+    }
+    RuleAction::SET_AUX_LEVEL { aux, level: raw, db } => {
+      let (index, level) = (aux - 1, level(raw, db));
+      if auxes.get(&index).map_or(true, |aux| aux.level != level) {
+        fired.requests.push(Request::SET_AUX_LEVEL(SetAuxLevel { index, level, source }));
+      }
+    }
+    RuleAction::ALERT { message } => {
+      fired.alerts.push(RuleAlert { rule: String::from(rule), message: message.clone(), timestamp: now_millis() });
+    }
+    RuleAction::RUN_SCRIPT { script } => {
+      fired.scripts.push((String::from(rule), script.trim().to_string()));
+    }
+  }
+}
+
+/// Runs the rules against every console change, sending what they ask for, starting
+/// their scripts and passing their alerts to `publish`
+pub async fn run<F: Fn(RuleAlert)>(engine: RuleStorage, scripts: ScriptStorage, input_tx: mpsc::Sender<Request>, mut event_rx: broadcast::Receiver<ConsoleEvent>, publish: F) {
+  if let Ok(db) = get_db(&input_tx).await {
+    engine.lock().await.seed(db, Instant::now());
+  }
+  let mut tick = time::interval(TICK);
+
+  loop {
+    let fired = tokio::select! {
+      event = event_rx.recv() => match event {
+        Ok(event) => engine.lock().await.handle(&event, Instant::now()),
+        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+          eprintln!("RULES :: missed {} changes", skipped);
+          continue;
+        }
+        Err(broadcast::error::RecvError::Closed) => return,
+      },
+      _ = tick.tick() => engine.lock().await.evaluate(Instant::now()),
+    };
+
+    let mut alerts = fired.alerts;
+    for (rule, script) in fired.scripts {
+      if let Err(e) = scripts.lock().await.start(&script, &format!("rule {}", rule)) {
+        alerts.push(RuleAlert { rule, message: e, timestamp: now_millis() });
+      }
+    }
+    for alert in alerts {
+      eprintln!("RULES :: {} :: {}", alert.rule, alert.message);
+      publish(alert);
+    }
+    for request in fired.requests {
+      if input_tx.send(request).await.is_err() {
+        eprintln!("RULES :: the client stopped taking requests");
+        return;
+      }
+    }
+  }
+}
@@ -0,0 +1,40 @@
+#![allow(non_snake_case)]
+use common::{scene::Scene, Source};
+
+use crate::cscp::requests::{Request, SetAuxLevel, SetFaderCut, SetFaderLevel, SetFaderPfl, SetMainLevel, SetMainPfl};
+
+/// The requests that bring the desk to `scene`, in the order they should be sent
+pub fn requests(scene: &Scene, source: Source) -> Vec<Request> {
+  let mut requests = vec![];
+
+  for fader in scene.faders.iter() {
+    let index = fader.index;
+    if let Some(isCut) = fader.isCut {
+      requests.push(Request::SET_FADER_CUT(SetFaderCut { index, isCut, source }));
+    }
+    if let Some(level) = fader.level {
+      requests.push(Request::SET_FADER_LEVEL(SetFaderLevel { index, level, source }));
+    }
+    if let Some(isPfl) = fader.isPfl {
+      requests.push(Request::SET_FADER_PFL(SetFaderPfl { index, isPfl, source }));
+    }
+  }
+
+  for main in scene.mains.iter() {
+    let index = main.index;
+    if let Some(level) = main.level {
+      requests.push(Request::SET_MAIN_LEVEL(SetMainLevel { index, level, source }));
+    }
+    if let Some(isPfl) = main.isPfl {
+      requests.push(Request::SET_MAIN_PFL(SetMainPfl { index, isPfl, source }));
+    }
+  }
+
+  for aux in scene.auxes.iter() {
+    if let Some(level) = aux.level {
+      requests.push(Request::SET_AUX_LEVEL(SetAuxLevel { index: aux.index, level, source }));
+    }
+  }
+
+  requests
+}
@@ -0,0 +1,60 @@
+use std::path::PathBuf;
+use chrono::Local;
+use common::schedule::{ScheduleEntry, ScheduleStatus, ScheduledRun};
+use tokio::sync::mpsc;
+
+use crate::{config::Config, cscp::requests::{get_db, Request}, scripts::host::ScriptStorage};
+use super::scheduler::{preview, validate_all, ScheduleStorage};
+
+pub struct ScheduleState {
+  pub inner: ScheduleStorage,
+  /// Config file the schedule is saved to
+  pub configPath: PathBuf,
+  pub input_tx: mpsc::Sender<Request>,
+  /// Checked for the scripts the preview runs
+  pub scripts: ScriptStorage,
+}
+
+#[tauri::command]
+pub async fn getSchedule(
+    state: tauri::State<'_, ScheduleState>,
+) -> Result<Vec<ScheduleEntry>, String> {
+    Ok(state.inner.lock().await.entries())
+}
+
+#[tauri::command]
+pub async fn getScheduleStatus(
+    state: tauri::State<'_, ScheduleState>,
+) -> Result<Vec<ScheduleStatus>, String> {
+    Ok(state.inner.lock().await.status(Local::now()))
+}
+
+#[tauri::command]
+pub async fn saveSchedule(
+    entries: Vec<ScheduleEntry>,
+    state: tauri::State<'_, ScheduleState>,
+) -> Result<Vec<ScheduleStatus>, String> {
+    eprintln!("saveSchedule count={}", entries.len());
+    validate_all(&entries)?;
+    let value = serde_json::to_value(&entries).map_err(|e| e.to_string())?;
+    Config::update(&state.configPath, "schedule", value)?;
+
+    let mut scheduler = state.inner.lock().await;
+    scheduler.set_entries(entries);
+    Ok(scheduler.status(Local::now()))
+}
+
+/// What `entries`, saved or not, would do over the next `hours`
+#[tauri::command]
+pub async fn previewSchedule(
+    entries: Vec<ScheduleEntry>,
+    hours: u32,
+    state: tauri::State<'_, ScheduleState>,
+) -> Result<Vec<ScheduledRun>, String> {
+    eprintln!("previewSchedule count={} hours={}", entries.len(), hours);
+    validate_all(&entries)?;
+    let db = get_db(&state.input_tx).await?;
+    let scripts: Vec<String> = state.scripts.lock().await.list()?.into_iter().map(|script| script.name).collect();
+    let directory = state.configPath.parent().map(PathBuf::from).unwrap_or_default();
+    Ok(preview(&entries, &db, &scripts, &directory, Local::now(), hours))
+}
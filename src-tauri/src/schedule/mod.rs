@@ -0,0 +1,3 @@
+pub mod commands;
+pub mod scheduler;
+pub mod timing;
@@ -0,0 +1,447 @@
+#![allow(non_snake_case)]
+use std::{
+  collections::{HashMap, HashSet},
+  fs,
+  path::{Path, PathBuf},
+  sync::Arc,
+  time::Duration,
+};
+use chrono::{DateTime, Local, TimeZone};
+use common::{
+  BusType, DB, Fader, Source,
+  level::{db_to_level, level_to_db},
+  scene::Scene,
+  schedule::{MissedPolicy, ScheduleAction, ScheduleEntry, ScheduleStatus, ScheduledRun},
+};
+use futures_util::lock::Mutex;
+use tokio::{sync::mpsc, time::{self, Instant}};
+
+use crate::{
+  cscp::{requests::{get_db, Request, SetFaderCut, SetFaderLevel}, selector::{FaderRef, SelectError}},
+  fade, scene,
+  scripts::host::ScriptStorage,
+};
+use super::timing::Timing;
+
+pub type ScheduleStorage = Arc<Mutex<Scheduler>>;
+
+/// How often the clock is checked
+const TICK: Duration = Duration::from_secs(1);
+/// A run this late is still on time, anything later counts as missed
+const LATE_LIMIT: Duration = Duration::from_secs(60);
+/// How far back missed runs are looked for after the app was closed
+const MISSED_WINDOW: Duration = Duration::from_secs(8 * 24 * 60 * 60);
+/// How often the state file is written when nothing ran
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+const PREVIEW_LIMIT: usize = 200;
+const SOURCE: Source = Source::AUTOMATION;
+
+/// What the scheduler remembers across restarts, to tell which runs were missed
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+struct SavedState {
+  /// Everything due up to here has been run or skipped
+  checkedUntil: Option<u64>,
+  lastRuns: HashMap<String, u64>,
+}
+
+struct EntryState {
+  entry: ScheduleEntry,
+  timing: Option<Timing>,
+  /// Why the entry can't run
+  error: Option<String>,
+  /// What went wrong the last time it ran
+  lastError: Option<String>,
+}
+
+/// The schedule and when each entry last ran
+pub struct Scheduler {
+  entries: Vec<EntryState>,
+  /// Entries running now, a run still going when the next is due skips that one
+  running: HashSet<String>,
+  statePath: PathBuf,
+  saved: SavedState,
+  lastSave: Instant,
+}
+
+impl Scheduler {
+  /// The scheduler for `entries`, picking up where the last session left off from `statePath`
+  pub fn new(entries: Vec<ScheduleEntry>, statePath: PathBuf) -> Scheduler {
+    let saved = match fs::read_to_string(&statePath) {
+      Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+        eprintln!("SCHEDULE :: invalid state {} :: {}", statePath.display(), e);
+        SavedState::default()
+      }),
+      Err(_) => SavedState::default(),
+    };
+    let mut scheduler = Scheduler { entries: vec![], running: HashSet::new(), statePath, saved, lastSave: Instant::now() };
+    scheduler.set_entries(entries);
+    scheduler
+  }
+
+  pub fn entries(&self) -> Vec<ScheduleEntry> {
+    self.entries.iter().map(|state| state.entry.clone()).collect()
+  }
+
+  /// Replaces the schedule, runs already due are left to the next tick
+  pub fn set_entries(&mut self, entries: Vec<ScheduleEntry>) {
+    self.entries = entries.into_iter().map(|entry| {
+      let (timing, error) = match validate(&entry) {
+        Ok(timing) => (Some(timing), None),
+        Err(e) => {
+          eprintln!("SCHEDULE :: entry off :: {}", e);
+          (None, Some(e))
+        }
+      };
+      EntryState { entry, timing, error, lastError: None }
+    }).collect();
+    let names: HashSet<&String> = self.entries.iter().map(|state| &state.entry.name).collect();
+    self.saved.lastRuns.retain(|name, _| names.contains(name));
+  }
+
+  pub fn status(&self, now: DateTime<Local>) -> Vec<ScheduleStatus> {
+    let horizon = now + chrono::Duration::days(366);
+    self.entries.iter().map(|state| ScheduleStatus {
+      name: state.entry.name.clone(),
+      enabled: state.entry.enabled,
+      next: state.timing.as_ref()
+        .filter(|_| state.entry.enabled)
+        .and_then(|timing| timing.between(now, horizon, 1).first().map(millis)),
+      lastRun: self.saved.lastRuns.get(&state.entry.name).copied(),
+      error: state.error.clone().or_else(|| state.lastError.clone()),
+    }).collect()
+  }
+
+  /// The entries to run now, in the order they were due. Runs missed while the app
+  /// wasn't running are run or dropped by each entry's `missed`.
+  pub fn due(&mut self, now: DateTime<Local>) -> Vec<ScheduleEntry> {
+    let checked = self.saved.checkedUntil.and_then(at).unwrap_or(now);
+    let oldest = now - chrono::Duration::from_std(MISSED_WINDOW).unwrap();
+    let late = chrono::Duration::from_std(LATE_LIMIT).unwrap();
+    let mut due = vec![];
+
+    for state in self.entries.iter() {
+      let timing = match &state.timing {
+        Some(timing) if state.entry.enabled => timing,
+        _ => continue,
+      };
+      let name = &state.entry.name;
+      let mut from = checked.max(oldest);
+      if let Some(lastRun) = self.saved.lastRuns.get(name).copied().and_then(at) {
+        from = from.max(lastRun);
+      }
+      let runs = timing.between(from, now, usize::MAX);
+      let latest = match runs.last() {
+        Some(latest) => *latest,
+        None => continue,
+      };
+
+      if now - latest > late {
+        match state.entry.missed {
+          MissedPolicy::SKIP => {
+            eprintln!("SCHEDULE :: {} skipping {} missed runs, the last at {}", name, runs.len(), latest.format("%F %T"));
+            continue;
+          }
+          MissedPolicy::RUN_LATEST => eprintln!("SCHEDULE :: {} running the last of {} missed runs, due at {}", name, runs.len(), latest.format("%F %T")),
+        }
+      }
+      if self.running.contains(name) {
+        eprintln!("SCHEDULE :: {} is still running, skipping the run due at {}", name, latest.format("%F %T"));
+        continue;
+      }
+      due.push((latest, state.entry.clone()));
+    }
+
+    self.saved.checkedUntil = Some(millis(&now));
+    due.sort_by_key(|(latest, _)| *latest);
+    for (_, entry) in due.iter() {
+      self.running.insert(entry.name.clone());
+      self.saved.lastRuns.insert(entry.name.clone(), millis(&now));
+    }
+    if !due.is_empty() || self.lastSave.elapsed() >= SAVE_INTERVAL {
+      self.save();
+    }
+    due.into_iter().map(|(_, entry)| entry).collect()
+  }
+
+  /// Records how the run of `name` went
+  pub fn finished(&mut self, name: &str, error: Option<String>) {
+    self.running.remove(name);
+    if let Some(state) = self.entries.iter_mut().find(|state| state.entry.name == name) {
+      state.lastError = error;
+    }
+  }
+
+  fn save(&mut self) {
+    self.lastSave = Instant::now();
+    let written = serde_json::to_string(&self.saved)
+      .map_err(|e| e.to_string())
+      .and_then(|contents| {
+        if let Some(directory) = self.statePath.parent() {
+          fs::create_dir_all(directory).map_err(|e| e.to_string())?;
+        }
+        fs::write(&self.statePath, contents).map_err(|e| e.to_string())
+      });
+    if let Err(e) = written {
+      eprintln!("SCHEDULE :: can't write {} :: {}", self.statePath.display(), e);
+    }
+  }
+}
+
+fn millis(at: &DateTime<Local>) -> u64 {
+  at.timestamp_millis().max(0) as u64
+}
+
+fn at(millis: u64) -> Option<DateTime<Local>> {
+  Local.timestamp_millis_opt(millis as i64).single()
+}
+
+/// Why `entry` can't run, if it can't, or when it runs
+pub fn validate(entry: &ScheduleEntry) -> Result<Timing, String> {
+  if entry.name.trim().is_empty() {
+    return Err(String::from("Every schedule entry needs a name"));
+  }
+  let fail = |e: String| format!("{} :: {}", entry.name, e);
+  let timing = Timing::parse(&entry.when).map_err(fail)?;
+
+  if entry.actions.is_empty() {
+    return Err(fail(String::from("The entry has no actions")));
+  }
+  for action in entry.actions.iter() {
+    let checked = match action {
+      ScheduleAction::RECALL_SCENE { scene } => if scene.trim().is_empty() { Err(String::from("RECALL_SCENE needs a scene")) } else { Ok(()) },
+      ScheduleAction::FADE { fader, .. } | ScheduleAction::SET_FADER_CUT { fader, .. } => check_fader(fader),
+      ScheduleAction::SET_FADER_LEVEL { fader, level, db } => check_fader(fader).and_then(|_| {
+        if level.is_none() && db.is_none() { Err(String::from("Level actions need level or db")) } else { Ok(()) }
+      }),
+      ScheduleAction::RUN_SCRIPT { script } => if script.trim().is_empty() { Err(String::from("RUN_SCRIPT needs a script")) } else { Ok(()) },
+    };
+    checked.map_err(fail)?;
+  }
+  Ok(timing)
+}
+
+/// Checks a whole schedule, names have to be unique as runs are remembered by name
+pub fn validate_all(entries: &[ScheduleEntry]) -> Result<(), String> {
+  let mut names = HashSet::new();
+  for entry in entries {
+    validate(entry)?;
+    if !names.insert(entry.name.trim()) {
+      return Err(format!("{} :: two entries have this name", entry.name));
+    }
+  }
+  Ok(())
+}
+
+pub fn check_fader(fader: &str) -> Result<(), String> {
+  match fader.parse::<FaderRef>() {
+    Ok(FaderRef::NUMBER(0)) => Err(String::from("Numbers start at 1")),
+    Ok(_) => Ok(()),
+    Err(e) => Err(e.to_string()),
+  }
+}
+
+/// Every fader `fader` picks, failing when it picks none
+pub fn targets<'a>(fader: &str, faders: &'a [Fader]) -> Result<Vec<&'a Fader>, String> {
+  let picked = match fader.parse::<FaderRef>().map_err(|e| e.to_string())? {
+    FaderRef::NUMBER(number) => faders.iter().filter(|fader| fader.index + 1 == number).collect(),
+    FaderRef::LABEL(selector) => selector.select(faders),
+  };
+  if picked.is_empty() {
+    return Err(match fader.parse::<u16>() {
+      Ok(number) => format!("No fader {}", number),
+      Err(_) => SelectError::NOT_FOUND(String::from(fader)).to_string(),
+    });
+  }
+  Ok(picked)
+}
+
+/// Scene files are found inside the config dir unless the path is absolute
+pub fn read_scene(scene: &str, configDir: &Path) -> Result<Scene, String> {
+  let path = configDir.join(scene.trim());
+  let contents = fs::read_to_string(&path).map_err(|e| format!("Can't read scene {} :: {}", path.display(), e))?;
+  serde_json::from_str(&contents).map_err(|e| format!("Invalid scene {} :: {}", path.display(), e))
+}
+
+pub fn level_of(level: Option<u16>, db: Option<f32>) -> u16 {
+  level.or_else(|| db.map(db_to_level)).unwrap_or_default()
+}
+
+/// Runs the actions of `entry` in order, carrying on past any that fail
+pub async fn execute(entry: &ScheduleEntry, input_tx: &mpsc::Sender<Request>, scripts: &ScriptStorage, configDir: &Path) -> Result<(), String> {
+  eprintln!("SCHEDULE :: {} running", entry.name);
+  let mut errors = vec![];
+  for action in entry.actions.iter() {
+    if let Err(e) = act(action, &entry.name, input_tx, scripts, configDir).await {
+      eprintln!("SCHEDULE :: {} :: {}", entry.name, e);
+      errors.push(e);
+    }
+  }
+  if errors.is_empty() { Ok(()) } else { Err(errors.join("; ")) }
+}
+
+async fn act(action: &ScheduleAction, name: &str, input_tx: &mpsc::Sender<Request>, scripts: &ScriptStorage, configDir: &Path) -> Result<(), String> {
+  let send = |request: Request| async move { input_tx.send(request).await.map_err(|e| e.to_string()) };
+  match action {
+    ScheduleAction::RECALL_SCENE { scene } => {
+      let scene = read_scene(scene, configDir)?;
+      for request in scene::requests(&scene, SOURCE) {
+        send(request).await?;
+      }
+    }
+    ScheduleAction::FADE { fader, db, duration } => {
+      let faders = get_db(input_tx).await?.faders;
+      let from = targets(fader, &faders)?.into_iter().map(|fader| (BusType::FADER, fader.index, fader.level)).collect();
+      fade::run(input_tx, from, db_to_level(*db), Duration::from_millis(*duration), SOURCE).await?;
+    }
+    ScheduleAction::SET_FADER_LEVEL { fader, level, db } => {
+      let faders = get_db(input_tx).await?.faders;
+      let level = level_of(*level, *db);
+      for fader in targets(fader, &faders)?.into_iter().filter(|fader| fader.level != level) {
+        send(Request::SET_FADER_LEVEL(SetFaderLevel { index: fader.index, level, source: SOURCE })).await?;
+      }
+    }
+    ScheduleAction::SET_FADER_CUT { fader, isCut } => {
+      let faders = get_db(input_tx).await?.faders;
+      for fader in targets(fader, &faders)?.into_iter().filter(|fader| fader.isCut != *isCut) {
+        send(Request::SET_FADER_CUT(SetFaderCut { index: fader.index, isCut: *isCut, source: SOURCE })).await?;
+      }
+    }
+    ScheduleAction::RUN_SCRIPT { script } => {
+      scripts.lock().await.start(script.trim(), &format!("schedule {}", name))?;
+    }
+  }
+  Ok(())
+}
+
+/// Runs every entry as it comes due, each in its own task so fades don't hold up the others
+pub async fn run(scheduler: ScheduleStorage, scripts: ScriptStorage, input_tx: mpsc::Sender<Request>, configDir: PathBuf) {
+  let mut tick = time::interval(TICK);
+  loop {
+    tick.tick().await;
+    let due = scheduler.lock().await.due(Local::now());
+    for entry in due {
+      let (scheduler, scripts, input_tx, configDir) = (scheduler.clone(), scripts.clone(), input_tx.clone(), configDir.clone());
+      tokio::spawn(async move {
+        let result = execute(&entry, &input_tx, &scripts, &configDir).await;
+        scheduler.lock().await.finished(&entry.name, result.err());
+      });
+    }
+  }
+}
+
+fn db_text(level: u16) -> String {
+  format!("{:.1} dB", level_to_db(level))
+}
+
+fn fader_text(fader: &Fader) -> String {
+  format!("Fader {} ({})", fader.index + 1, fader.label.trim())
+}
+
+/// What `action` would do to the desk as it is in `db`, one line per change
+fn describe(action: &ScheduleAction, db: &DB, scripts: &[String], configDir: &Path) -> Vec<String> {
+  let faders = &db.faders;
+  let picked = |fader: &str| targets(fader, faders);
+  match action {
+    ScheduleAction::RECALL_SCENE { scene } => {
+      let recalled = match read_scene(scene, configDir) {
+        Ok(recalled) => recalled,
+        Err(e) => return vec![format!("Recall scene {} :: {}", scene, e)],
+      };
+      let mut lines = vec![format!("Recall scene {}", scene)];
+      for wanted in recalled.faders.iter() {
+        let fader = match faders.iter().find(|fader| fader.index == wanted.index) {
+          Some(fader) => fader,
+          None => {
+            lines.push(format!("  Fader {} isn't on the desk", wanted.index + 1));
+            continue;
+          }
+        };
+        if let Some(isCut) = wanted.isCut.filter(|isCut| *isCut != fader.isCut) {
+          lines.push(format!("  {} {}", fader_text(fader), if isCut { "cut" } else { "uncut" }));
+        }
+        if let Some(level) = wanted.level.filter(|level| *level != fader.level) {
+          lines.push(format!("  {} {} -> {}", fader_text(fader), db_text(fader.level), db_text(level)));
+        }
+        if let Some(isPfl) = wanted.isPfl.filter(|isPfl| *isPfl != fader.isPfl) {
+          lines.push(format!("  {} PFL {}", fader_text(fader), if isPfl { "on" } else { "off" }));
+        }
+      }
+      for wanted in recalled.mains.iter() {
+        let main = db.mains.iter().find(|main| main.index == wanted.index);
+        if let Some(level) = wanted.level.filter(|level| main.map_or(true, |main| main.level != *level)) {
+          lines.push(format!("  Main {} {} -> {}", wanted.index + 1, main.map_or_else(|| String::from("?"), |main| db_text(main.level)), db_text(level)));
+        }
+        if let Some(isPfl) = wanted.isPfl.filter(|isPfl| main.map_or(true, |main| main.isPfl != *isPfl)) {
+          lines.push(format!("  Main {} PFL {}", wanted.index + 1, if isPfl { "on" } else { "off" }));
+        }
+      }
+      for wanted in recalled.auxes.iter() {
+        let aux = db.auxes.iter().find(|aux| aux.index == wanted.index);
+        if let Some(level) = wanted.level.filter(|level| aux.map_or(true, |aux| aux.level != *level)) {
+          lines.push(format!("  Aux {} {} -> {}", wanted.index + 1, aux.map_or_else(|| String::from("?"), |aux| db_text(aux.level)), db_text(level)));
+        }
+      }
+      if lines.len() == 1 {
+        lines[0].push_str(", nothing changes");
+      }
+      lines
+    }
+    ScheduleAction::FADE { fader, db, duration } => match picked(fader) {
+      Ok(picked) => picked.into_iter().map(|fader| {
+        format!("Fade {} {} -> {} over {:.1} s", fader_text(fader), db_text(fader.level), db_text(db_to_level(*db)), *duration as f32 / 1000.0)
+      }).collect(),
+      Err(e) => vec![format!("Fade {} :: {}", fader, e)],
+    },
+    ScheduleAction::SET_FADER_LEVEL { fader, level, db } => match picked(fader) {
+      Ok(picked) => {
+        let level = level_of(*level, *db);
+        picked.into_iter().map(|fader| if fader.level == level {
+          format!("{} stays at {}", fader_text(fader), db_text(level))
+        } else {
+          format!("{} {} -> {}", fader_text(fader), db_text(fader.level), db_text(level))
+        }).collect()
+      }
+      Err(e) => vec![format!("Set fader {} :: {}", fader, e)],
+    },
+    ScheduleAction::SET_FADER_CUT { fader, isCut } => match picked(fader) {
+      Ok(picked) => picked.into_iter().map(|fader| match (*isCut, fader.isCut == *isCut) {
+        (true, false) => format!("Cut {}", fader_text(fader)),
+        (false, false) => format!("Uncut {}", fader_text(fader)),
+        (true, true) => format!("{} stays cut", fader_text(fader)),
+        (false, true) => format!("{} stays uncut", fader_text(fader)),
+      }).collect(),
+      Err(e) => vec![format!("Cut fader {} :: {}", fader, e)],
+    },
+    ScheduleAction::RUN_SCRIPT { script } => {
+      if scripts.iter().any(|name| name == script.trim()) {
+        vec![format!("Run script {}", script)]
+      } else {
+        vec![format!("Run script {}, which isn't in the scripts folder", script)]
+      }
+    }
+  }
+}
+
+/// The runs of `entries` in the next `hours` and what each would do to the desk as it is now.
+/// Later runs may find the desk changed by earlier ones, so only the first of each is exact.
+pub fn preview(entries: &[ScheduleEntry], db: &DB, scripts: &[String], configDir: &Path, now: DateTime<Local>, hours: u32) -> Vec<ScheduledRun> {
+  let until = now + chrono::Duration::hours(hours as i64);
+  let mut runs = vec![];
+  for entry in entries.iter().filter(|entry| entry.enabled) {
+    let timing = match validate(entry) {
+      Ok(timing) => timing,
+      Err(_) => continue,
+    };
+    let steps: Vec<String> = entry.actions.iter().flat_map(|action| describe(action, db, scripts, configDir)).collect();
+    runs.extend(timing.between(now, until, PREVIEW_LIMIT).iter().map(|at| ScheduledRun {
+      name: entry.name.clone(),
+      at: millis(at),
+      steps: steps.clone(),
+    }));
+  }
+  runs.sort_by_key(|run| run.at);
+  runs.truncate(PREVIEW_LIMIT);
+  runs
+}
+
@@ -0,0 +1,87 @@
+use chrono::{DateTime, Datelike, Duration, Local, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use common::schedule::{Weekday, When};
+
+/// When `when` says to run, in local time
+pub enum Timing {
+  ONCE(DateTime<Local>),
+  DAILY { time: NaiveTime, days: Vec<chrono::Weekday> },
+  EVERY { minutes: u32, from: NaiveTime, to: NaiveTime },
+}
+
+pub fn parse_time(text: &str) -> Result<NaiveTime, String> {
+  NaiveTime::parse_from_str(text.trim(), "%H:%M:%S")
+    .or_else(|_| NaiveTime::parse_from_str(text.trim(), "%H:%M"))
+    .map_err(|_| format!("\"{}\" isn't a time like 06:00 or 06:00:30", text))
+}
+
+fn weekday(day: Weekday) -> chrono::Weekday {
+  match day {
+    Weekday::MON => chrono::Weekday::Mon,
+    Weekday::TUE => chrono::Weekday::Tue,
+    Weekday::WED => chrono::Weekday::Wed,
+    Weekday::THU => chrono::Weekday::Thu,
+    Weekday::FRI => chrono::Weekday::Fri,
+    Weekday::SAT => chrono::Weekday::Sat,
+    Weekday::SUN => chrono::Weekday::Sun,
+  }
+}
+
+/// A wall-clock time on the given day. Times skipped by a clock change don't happen,
+/// and those it repeats happen the first time round.
+fn local(date: NaiveDate, time: NaiveTime) -> Option<DateTime<Local>> {
+  match Local.from_local_datetime(&date.and_time(time)) {
+    LocalResult::Single(at) => Some(at),
+    LocalResult::Ambiguous(first, _) => Some(first),
+    LocalResult::None => None,
+  }
+}
+
+impl Timing {
+  pub fn parse(when: &When) -> Result<Timing, String> {
+    match when {
+      When::ONCE { at } => {
+        let naive = NaiveDateTime::parse_from_str(at.trim(), "%Y-%m-%dT%H:%M:%S")
+          .or_else(|_| NaiveDateTime::parse_from_str(at.trim(), "%Y-%m-%dT%H:%M"))
+          .map_err(|_| format!("\"{}\" isn't a date and time like 2024-05-01T06:00", at))?;
+        local(naive.date(), naive.time()).map(Timing::ONCE).ok_or_else(|| format!("{} doesn't happen in local time", at))
+      }
+      When::DAILY { time, days } => Ok(Timing::DAILY { time: parse_time(time)?, days: days.iter().map(|day| weekday(*day)).collect() }),
+      When::EVERY { minutes, from, to } => {
+        if *minutes == 0 || *minutes > 24 * 60 {
+          return Err(String::from("EVERY needs minutes from 1 to 1440"));
+        }
+        let from = from.as_deref().map(parse_time).transpose()?.unwrap_or(NaiveTime::MIN);
+        let to = to.as_deref().map(parse_time).transpose()?.unwrap_or_else(|| NaiveTime::from_hms_opt(23, 59, 59).unwrap());
+        Ok(Timing::EVERY { minutes: *minutes, from, to })
+      }
+    }
+  }
+
+  /// Every run after `from` up to and including `to`, at most `limit` of them
+  pub fn between(&self, from: DateTime<Local>, to: DateTime<Local>, limit: usize) -> Vec<DateTime<Local>> {
+    let within = |at: &DateTime<Local>| *at > from && *at <= to;
+    if let Timing::ONCE(at) = self {
+      return if within(at) { vec![*at] } else { vec![] };
+    }
+
+    let mut runs = vec![];
+    let mut date = from.date_naive();
+    while date <= to.date_naive() && runs.len() < limit {
+      let times: Vec<NaiveTime> = match self {
+        Timing::DAILY { time, days } if days.is_empty() || days.contains(&date.weekday()) => vec![*time],
+        Timing::EVERY { minutes, from, to } => (0..24 * 60 / minutes)
+          .map(|step| NaiveTime::MIN + Duration::minutes((step * minutes) as i64))
+          .filter(|time| time >= from && time <= to)
+          .collect(),
+        _ => vec![],
+      };
+      runs.extend(times.into_iter().filter_map(|time| local(date, time)).filter(within));
+      date = match date.succ_opt() {
+        Some(next) => next,
+        None => break,
+      };
+    }
+    runs.truncate(limit);
+    runs
+  }
+}
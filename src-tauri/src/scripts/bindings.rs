@@ -0,0 +1,309 @@
+#![allow(non_snake_case)]
+use std::{sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}, thread, time::{Duration, Instant}};
+use common::{Aux, BusType, Fader, Main, Source, DB, level::{db_to_level, level_to_db, MAX_LEVEL}, scripts::{OutputKind, ScriptOutput}};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Position, FLOAT, INT};
+use tokio::{runtime::Handle, sync::{broadcast, mpsc}, time};
+
+use crate::{
+  clock::now_millis,
+  cscp::{
+    events::ConsoleEvent,
+    requests::{get_db, Request, SetFaderCut, SetFaderPfl, SetMainPfl},
+    selector::{FaderRef, SelectError},
+  },
+  fade::{self, FADE_STEP},
+};
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// How often a waiting script checks whether it was stopped
+const POLL: Duration = Duration::from_millis(50);
+const SOURCE: Source = Source::AUTOMATION;
+
+/// What the functions of one script run act on
+pub struct Context {
+  pub run: u64,
+  pub script: String,
+  pub input_tx: mpsc::Sender<Request>,
+  /// Console changes since the run started, for `next_event`
+  pub events: Mutex<broadcast::Receiver<ConsoleEvent>>,
+  pub runtime: Handle,
+  pub stop: Arc<AtomicBool>,
+  pub output_tx: mpsc::UnboundedSender<ScriptOutput>,
+}
+
+impl Context {
+  pub fn emit(&self, kind: OutputKind, message: String) {
+    let _ = self.output_tx.send(ScriptOutput { run: self.run, script: self.script.clone(), kind, message, timestamp: now_millis() });
+  }
+
+  fn check_stop(&self) -> ScriptResult<()> {
+    if self.stop.load(Ordering::Relaxed) {
+      return Err(EvalAltResult::ErrorTerminated(Dynamic::UNIT, Position::NONE).into());
+    }
+    Ok(())
+  }
+
+  fn db(&self) -> ScriptResult<DB> {
+    Ok(self.runtime.block_on(get_db(&self.input_tx))?)
+  }
+
+  fn send(&self, request: Request) -> ScriptResult<()> {
+    self.input_tx.blocking_send(request).map_err(|_| "The console client stopped".into())
+  }
+
+  /// Waits `duration`, returning early when the script is stopped
+  fn sleep(&self, duration: Duration) -> ScriptResult<()> {
+    let until = Instant::now() + duration;
+    loop {
+      self.check_stop()?;
+      let now = Instant::now();
+      if now >= until {
+        return Ok(());
+      }
+      thread::sleep((until - now).min(POLL));
+    }
+  }
+
+  /// Every fader `target` picks, which has to be at least one
+  fn faders(&self, target: &Dynamic) -> ScriptResult<Vec<Fader>> {
+    let target = fader_ref(target)?;
+    let db = self.db()?;
+    let faders: Vec<Fader> = match &target {
+      FaderRef::NUMBER(number) => db.faders.into_iter().filter(|fader| fader.index + 1 == *number).collect(),
+      FaderRef::LABEL(selector) => selector.select(&db.faders).into_iter().cloned().collect(),
+    };
+    if faders.is_empty() {
+      return Err(format!("No fader {}", target).into());
+    }
+    Ok(faders)
+  }
+
+  fn main(&self, number: INT) -> ScriptResult<Main> {
+    let index = index(number, "main")?;
+    self.db()?.mains.into_iter().find(|main| main.index == index).ok_or_else(|| format!("No main {}", number).into())
+  }
+
+  fn aux(&self, number: INT) -> ScriptResult<Aux> {
+    let index = index(number, "aux")?;
+    self.db()?.auxes.into_iter().find(|aux| aux.index == index).ok_or_else(|| format!("No aux {}", number).into())
+  }
+
+  /// Moves every `(bus, index, level)` in `from` to `to` over `duration`
+  fn fade(&self, from: Vec<(BusType, u16, u16)>, to: u16, duration: Duration) -> ScriptResult<()> {
+    let steps: Vec<Vec<u16>> = from.iter().map(|(_, _, level)| fade::steps(*level, to, duration)).collect();
+    let mut sent: Vec<u16> = from.iter().map(|(_, _, level)| *level).collect();
+    for step in 0..steps.first().map_or(0, |levels| levels.len()) {
+      self.sleep(FADE_STEP)?;
+      for (last, (&(busType, index, _), levels)) in sent.iter_mut().zip(from.iter().zip(steps.iter())) {
+        if levels[step] != *last {
+          *last = levels[step];
+          self.send(fade::level_request(busType, index, levels[step], SOURCE))?;
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// The next console change, or `()` when nothing changes within `timeout`
+  fn next_event(&self, timeout: Duration) -> ScriptResult<Dynamic> {
+    let until = Instant::now() + timeout;
+    let mut events = self.events.lock().unwrap();
+    loop {
+      self.check_stop()?;
+      let now = Instant::now();
+      if now >= until {
+        return Ok(Dynamic::UNIT);
+      }
+      let event = match self.runtime.block_on(time::timeout((until - now).min(POLL), events.recv())) {
+        Ok(Ok(event)) => event,
+        Ok(Err(broadcast::error::RecvError::Lagged(_))) | Err(_) => continue,
+        Ok(Err(broadcast::error::RecvError::Closed)) => return Err("The console client stopped".into()),
+      };
+      let (kind, mut map) = match event {
+        ConsoleEvent::FADER(fader) => ("FADER", fader_map(&fader)),
+        ConsoleEvent::MAIN(main) => ("MAIN", main_map(&main)),
+        ConsoleEvent::AUX(aux) => ("AUX", aux_map(&aux)),
+        ConsoleEvent::LINK(link) => {
+          let mut map = Map::new();
+          map.insert("connected".into(), link.connected.into());
+          ("LINK", map)
+        }
+        ConsoleEvent::DESK(_) => continue,
+      };
+      map.insert("type".into(), kind.into());
+      return Ok(map.into());
+    }
+  }
+}
+
+/// A fader number or label selector, as the script passed it
+fn fader_ref(target: &Dynamic) -> ScriptResult<FaderRef> {
+  if let Some(number) = target.clone().try_cast::<INT>() {
+    index(number, "fader")?;
+    return Ok(FaderRef::NUMBER(number as u16));
+  }
+  match target.clone().into_string() {
+    Ok(text) => text.parse().map_err(|e: SelectError| e.to_string().into()),
+    Err(kind) => Err(format!("Faders are picked by number or label, not {}", kind).into()),
+  }
+}
+
+/// 0-based index of a bus numbered from 1 in the script
+fn index(number: INT, bus: &str) -> ScriptResult<u16> {
+  match u16::try_from(number) {
+    Ok(number) if number > 0 => Ok(number - 1),
+    _ => Err(format!("No {} {}, numbers start at 1", bus, number).into()),
+  }
+}
+
+fn level(level: INT) -> ScriptResult<u16> {
+  match u16::try_from(level) {
+    Ok(level) if level <= MAX_LEVEL => Ok(level),
+    _ => Err(format!("Level {} isn't within 0-{}", level, MAX_LEVEL).into()),
+  }
+}
+
+/// Raw level of a dB value given as an integer or a decimal
+fn db(db: &Dynamic) -> ScriptResult<u16> {
+  let db = match (db.as_float(), db.as_int()) {
+    (Ok(db), _) => db,
+    (_, Ok(db)) => db as FLOAT,
+    _ => return Err(format!("A level in dB is a number, not {}", db.type_name()).into()),
+  };
+  Ok(db_to_level(db as f32))
+}
+
+fn millis(ms: INT) -> Duration {
+  Duration::from_millis(ms.max(0) as u64)
+}
+
+fn fader_map(fader: &Fader) -> Map {
+  let mut map = Map::new();
+  map.insert("number".into(), ((fader.index + 1) as INT).into());
+  map.insert("label".into(), fader.label.trim().into());
+  map.insert("level".into(), (fader.level as INT).into());
+  map.insert("db".into(), (level_to_db(fader.level) as FLOAT).into());
+  map.insert("isCut".into(), fader.isCut.into());
+  map.insert("isPfl".into(), fader.isPfl.into());
+  map.insert("isRouted".into(), fader.isRouted.map_or(Dynamic::UNIT, Dynamic::from));
+  map
+}
+
+fn main_map(main: &Main) -> Map {
+  let mut map = Map::new();
+  map.insert("number".into(), ((main.index + 1) as INT).into());
+  map.insert("label".into(), main.label.trim().into());
+  map.insert("level".into(), (main.level as INT).into());
+  map.insert("db".into(), (level_to_db(main.level) as FLOAT).into());
+  map.insert("isPfl".into(), main.isPfl.into());
+  map
+}
+
+fn aux_map(aux: &Aux) -> Map {
+  let mut map = Map::new();
+  map.insert("number".into(), ((aux.index + 1) as INT).into());
+  map.insert("level".into(), (aux.level as INT).into());
+  map.insert("db".into(), (level_to_db(aux.level) as FLOAT).into());
+  map
+}
+
+/// A Rhai engine with the console functions bound to `context`
+pub fn engine(context: Arc<Context>) -> Engine {
+  let mut engine = Engine::new();
+
+  let c = context.clone();
+  engine.on_print(move |text| c.emit(OutputKind::PRINT, text.to_string()));
+  let c = context.clone();
+  engine.on_debug(move |text, _, _| c.emit(OutputKind::PRINT, text.to_string()));
+  let stop = context.stop.clone();
+  engine.on_progress(move |_| stop.load(Ordering::Relaxed).then_some(Dynamic::UNIT));
+
+  // Reading the desk
+  let c = context.clone();
+  engine.register_fn("faders", move || -> ScriptResult<Array> {
+    Ok(c.db()?.faders.iter().map(|fader| fader_map(fader).into()).collect())
+  });
+  let c = context.clone();
+  engine.register_fn("faders", move |target: Dynamic| -> ScriptResult<Array> {
+    Ok(c.faders(&target)?.iter().map(|fader| fader_map(fader).into()).collect())
+  });
+  let c = context.clone();
+  engine.register_fn("fader", move |target: Dynamic| -> ScriptResult<Map> {
+    let db = c.db()?;
+    let index = fader_ref(&target)?.index(&db.faders).map_err(|e| e.to_string())?;
+    db.faders.iter().find(|fader| fader.index == index).map(fader_map).ok_or_else(|| format!("No fader {}", target).into())
+  });
+  let c = context.clone();
+  engine.register_fn("main", move |number: INT| -> ScriptResult<Map> { Ok(main_map(&c.main(number)?)) });
+  let c = context.clone();
+  engine.register_fn("aux", move |number: INT| -> ScriptResult<Map> { Ok(aux_map(&c.aux(number)?)) });
+
+  // Setting it
+  let c = context.clone();
+  engine.register_fn("set_level", move |target: Dynamic, raw: INT| -> ScriptResult<()> {
+    let level = level(raw)?;
+    c.faders(&target)?.iter().try_for_each(|fader| c.send(fade::level_request(BusType::FADER, fader.index, level, SOURCE)))
+  });
+  let c = context.clone();
+  engine.register_fn("set_db", move |target: Dynamic, value: Dynamic| -> ScriptResult<()> {
+    let level = db(&value)?;
+    c.faders(&target)?.iter().try_for_each(|fader| c.send(fade::level_request(BusType::FADER, fader.index, level, SOURCE)))
+  });
+  let c = context.clone();
+  engine.register_fn("set_cut", move |target: Dynamic, isCut: bool| -> ScriptResult<()> {
+    c.faders(&target)?.iter().try_for_each(|fader| c.send(Request::SET_FADER_CUT(SetFaderCut { index: fader.index, isCut, source: SOURCE })))
+  });
+  let c = context.clone();
+  engine.register_fn("set_pfl", move |target: Dynamic, isPfl: bool| -> ScriptResult<()> {
+    c.faders(&target)?.iter().try_for_each(|fader| c.send(Request::SET_FADER_PFL(SetFaderPfl { index: fader.index, isPfl, source: SOURCE })))
+  });
+  let c = context.clone();
+  engine.register_fn("set_main_level", move |number: INT, raw: INT| -> ScriptResult<()> {
+    c.send(fade::level_request(BusType::MAIN, index(number, "main")?, level(raw)?, SOURCE))
+  });
+  let c = context.clone();
+  engine.register_fn("set_main_db", move |number: INT, value: Dynamic| -> ScriptResult<()> {
+    c.send(fade::level_request(BusType::MAIN, index(number, "main")?, db(&value)?, SOURCE))
+  });
+  let c = context.clone();
+  engine.register_fn("set_main_pfl", move |number: INT, isPfl: bool| -> ScriptResult<()> {
+    c.send(Request::SET_MAIN_PFL(SetMainPfl { index: index(number, "main")?, isPfl, source: SOURCE }))
+  });
+  let c = context.clone();
+  engine.register_fn("set_aux_level", move |number: INT, raw: INT| -> ScriptResult<()> {
+    c.send(fade::level_request(BusType::AUX, index(number, "aux")?, level(raw)?, SOURCE))
+  });
+  let c = context.clone();
+  engine.register_fn("set_aux_db", move |number: INT, value: Dynamic| -> ScriptResult<()> {
+    c.send(fade::level_request(BusType::AUX, index(number, "aux")?, db(&value)?, SOURCE))
+  });
+
+  // Fades, returning once they are done
+  let c = context.clone();
+  engine.register_fn("fade", move |target: Dynamic, value: Dynamic, ms: INT| -> ScriptResult<()> {
+    let to = db(&value)?;
+    let from = c.faders(&target)?.iter().map(|fader| (BusType::FADER, fader.index, fader.level)).collect();
+    c.fade(from, to, millis(ms))
+  });
+  let c = context.clone();
+  engine.register_fn("fade_main", move |number: INT, value: Dynamic, ms: INT| -> ScriptResult<()> {
+    let (to, main) = (db(&value)?, c.main(number)?);
+    c.fade(vec![(BusType::MAIN, main.index, main.level)], to, millis(ms))
+  });
+  let c = context.clone();
+  engine.register_fn("fade_aux", move |number: INT, value: Dynamic, ms: INT| -> ScriptResult<()> {
+    let (to, aux) = (db(&value)?, c.aux(number)?);
+    c.fade(vec![(BusType::AUX, aux.index, aux.level)], to, millis(ms))
+  });
+
+  // Waiting and telling the operator
+  let c = context.clone();
+  engine.register_fn("sleep", move |ms: INT| -> ScriptResult<()> { c.sleep(millis(ms)) });
+  let c = context.clone();
+  engine.register_fn("next_event", move |ms: INT| -> ScriptResult<Dynamic> { c.next_event(millis(ms)) });
+  let c = context;
+  engine.register_fn("alert", move |message: &str| c.emit(OutputKind::ALERT, message.to_string()));
+
+  engine
+}
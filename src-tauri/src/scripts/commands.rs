@@ -0,0 +1,43 @@
+use common::scripts::{ScriptInfo, ScriptOutput};
+
+use super::host::ScriptStorage;
+
+pub struct ScriptsState {
+  pub inner: ScriptStorage,
+}
+
+#[tauri::command]
+pub async fn listScripts(
+    state: tauri::State<'_, ScriptsState>,
+) -> Result<Vec<ScriptInfo>, String> {
+    state.inner.lock().await.list()
+}
+
+#[tauri::command]
+pub async fn runScript(
+    name: String,
+    hotkey: Option<String>,
+    state: tauri::State<'_, ScriptsState>,
+) -> Result<u64, String> {
+    eprintln!("runScript name={} hotkey={:?}", name, hotkey);
+    let trigger = match hotkey {
+        Some(hotkey) => format!("hotkey {}", hotkey),
+        None => String::from("UI"),
+    };
+    state.inner.lock().await.start(&name, &trigger)
+}
+
+#[tauri::command]
+pub async fn stopScript(
+    name: String,
+    state: tauri::State<'_, ScriptsState>,
+) -> Result<(), String> {
+    state.inner.lock().await.stop(&name)
+}
+
+#[tauri::command]
+pub async fn getScriptOutput(
+    state: tauri::State<'_, ScriptsState>,
+) -> Result<Vec<ScriptOutput>, String> {
+    Ok(state.inner.lock().await.output())
+}
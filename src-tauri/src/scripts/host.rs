@@ -0,0 +1,149 @@
+#![allow(non_snake_case)]
+use std::{
+  collections::{HashMap, VecDeque},
+  fs, io,
+  path::PathBuf,
+  sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex as SyncMutex},
+};
+use common::scripts::{OutputKind, ScriptInfo, ScriptOutput};
+use futures_util::lock::Mutex;
+use rhai::{EvalAltResult, Scope};
+use tokio::{runtime::Handle, sync::{broadcast, mpsc}};
+
+use crate::{config::ScriptsConfig, cscp::{events::ConsoleEvent, requests::Request}};
+use super::bindings::{engine, Context};
+
+pub type ScriptStorage = Arc<Mutex<ScriptHost>>;
+
+/// Output lines kept for the UI
+const OUTPUT_LIMIT: usize = 500;
+const EXTENSION: &str = "rhai";
+
+/// Loads scripts from the scripts folder and runs them against the console, one run per script at a time
+pub struct ScriptHost {
+  directory: PathBuf,
+  hotkeys: HashMap<String, String>,
+  input_tx: mpsc::Sender<Request>,
+  event_tx: broadcast::Sender<ConsoleEvent>,
+  /// Stop flags of the scripts running now
+  running: HashMap<String, Arc<AtomicBool>>,
+  runs: u64,
+  output: VecDeque<ScriptOutput>,
+  output_tx: mpsc::UnboundedSender<ScriptOutput>,
+}
+
+impl ScriptHost {
+  /// The host and the output of its runs, to pass to `publish`
+  pub fn new(config: &ScriptsConfig, directory: PathBuf, input_tx: mpsc::Sender<Request>, event_tx: broadcast::Sender<ConsoleEvent>) -> (ScriptHost, mpsc::UnboundedReceiver<ScriptOutput>) {
+    let (output_tx, output_rx) = mpsc::unbounded_channel();
+    let host = ScriptHost {
+      directory,
+      hotkeys: config.hotkeys.clone(),
+      input_tx, event_tx,
+      running: HashMap::new(),
+      runs: 0,
+      output: VecDeque::new(),
+      output_tx,
+    };
+    (host, output_rx)
+  }
+
+  /// The scripts in the folder by name
+  pub fn list(&self) -> Result<Vec<ScriptInfo>, String> {
+    let entries = match fs::read_dir(&self.directory) {
+      Ok(entries) => entries,
+      Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+      Err(e) => return Err(format!("Can't read {} :: {}", self.directory.display(), e)),
+    };
+    let mut scripts: Vec<ScriptInfo> = entries
+      .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+      .filter(|path| path.extension().is_some_and(|extension| extension == EXTENSION))
+      .filter_map(|path| path.file_stem().map(|name| name.to_string_lossy().to_string()))
+      .map(|name| ScriptInfo {
+        hotkey: self.hotkeys.iter().find(|(_, script)| **script == name).map(|(key, _)| key.clone()),
+        running: self.running.contains_key(&name),
+        name,
+      })
+      .collect();
+    scripts.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(scripts)
+  }
+
+  pub fn output(&self) -> Vec<ScriptOutput> {
+    self.output.iter().cloned().collect()
+  }
+
+  /// Loads and starts the script called `name`, returning the run number.
+  /// `trigger` says what started it and is passed to the script as `trigger`.
+  pub fn start(&mut self, name: &str, trigger: &str) -> Result<u64, String> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+      return Err(format!("No script {}", name));
+    }
+    if self.running.contains_key(name) {
+      return Err(format!("{} is already running", name));
+    }
+    let path = self.directory.join(format!("{}.{}", name, EXTENSION));
+    let source = fs::read_to_string(&path).map_err(|e| format!("Can't read {} :: {}", path.display(), e))?;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let context = Arc::new(Context {
+      run: self.runs + 1,
+      script: String::from(name),
+      input_tx: self.input_tx.clone(),
+      events: SyncMutex::new(self.event_tx.subscribe()),
+      runtime: Handle::current(),
+      stop: stop.clone(),
+      output_tx: self.output_tx.clone(),
+    });
+    let engine = engine(context.clone());
+    let ast = engine.compile(&source).map_err(|e| format!("{} :: {}", name, e))?;
+    self.runs += 1;
+    self.running.insert(String::from(name), stop);
+
+    let trigger = String::from(trigger);
+    context.emit(OutputKind::STARTED, format!("Started by {}", trigger));
+    tokio::task::spawn_blocking(move || {
+      let mut scope = Scope::new();
+      scope.push_constant("trigger", trigger);
+      match engine.run_ast_with_scope(&mut scope, &ast) {
+        Ok(()) => context.emit(OutputKind::FINISHED, String::from("Finished")),
+        Err(e) if matches!(*e, EvalAltResult::ErrorTerminated(..)) => context.emit(OutputKind::FINISHED, String::from("Stopped")),
+        Err(e) => {
+          context.emit(OutputKind::ERROR, e.to_string());
+          context.emit(OutputKind::FINISHED, String::from("Failed"));
+        }
+      }
+    });
+    Ok(self.runs)
+  }
+
+  /// Stops the script at its next step or wait
+  pub fn stop(&mut self, name: &str) -> Result<(), String> {
+    match self.running.get(name) {
+      Some(stop) => {
+        stop.store(true, Ordering::Relaxed);
+        Ok(())
+      }
+      None => Err(format!("{} isn't running", name)),
+    }
+  }
+
+  fn record(&mut self, output: &ScriptOutput) {
+    if output.kind == OutputKind::FINISHED {
+      self.running.remove(&output.script);
+    }
+    if self.output.len() == OUTPUT_LIMIT {
+      self.output.pop_front();
+    }
+    self.output.push_back(output.clone());
+  }
+}
+
+/// Keeps the output of every run and passes it on to `publish`
+pub async fn publish<F: Fn(ScriptOutput)>(host: ScriptStorage, mut output_rx: mpsc::UnboundedReceiver<ScriptOutput>, publish: F) {
+  while let Some(output) = output_rx.recv().await {
+    eprintln!("SCRIPT :: {} #{} {:?} :: {}", output.script, output.run, output.kind, output.message);
+    host.lock().await.record(&output);
+    publish(output);
+  }
+}
@@ -0,0 +1,43 @@
+use std::{fs, path::Path};
+use common::inspector::Direction;
+
+use crate::{cscp::connection::Traffic, inspector::decode::hex};
+
+/// One frame of a capture file. Captures are JSON lines, one record per frame in the
+/// order they went over the wire, with the bytes as hex so they can be read on site.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CaptureRecord {
+  /// Milliseconds since the unix epoch
+  pub timestamp: u64,
+  pub direction: Direction,
+  pub bytes: String,
+}
+
+impl CaptureRecord {
+  pub fn from_traffic(traffic: &Traffic) -> CaptureRecord {
+    CaptureRecord { timestamp: traffic.timestamp, direction: traffic.direction, bytes: hex(&traffic.frame.to_bytes()) }
+  }
+
+  pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+    self.bytes
+      .split_whitespace()
+      .map(|byte| u8::from_str_radix(byte, 16).map_err(|e| format!("bad byte {:?} :: {}", byte, e)))
+      .collect()
+  }
+}
+
+/// Reads a whole capture, failing on the first bad line
+pub fn read(path: &Path) -> Result<Vec<CaptureRecord>, String> {
+  let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+
+  contents
+    .lines()
+    .enumerate()
+    .filter(|(_, line)| !line.trim().is_empty())
+    .map(|(number, line)| {
+      let record: CaptureRecord = serde_json::from_str(line).map_err(|e| format!("line {} :: {}", number + 1, e))?;
+      record.to_bytes().map_err(|e| format!("line {} :: {}", number + 1, e))?;
+      Ok(record)
+    })
+    .collect()
+}
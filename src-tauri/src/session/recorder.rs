@@ -0,0 +1,29 @@
+use std::{io, path::PathBuf};
+use tokio::{fs::{self, File}, io::AsyncWriteExt, sync::broadcast};
+
+use crate::cscp::connection::Traffic;
+use super::file::CaptureRecord;
+
+/// Appends every frame on the console link to `path` until the link goes away
+pub async fn record(path: PathBuf, mut traffic_rx: broadcast::Receiver<Traffic>) -> io::Result<()> {
+  if let Some(directory) = path.parent() {
+    fs::create_dir_all(directory).await?;
+  }
+  let mut file = File::create(&path).await?;
+  eprintln!("CAPTURE :: recording to {}", path.display());
+
+  loop {
+    match traffic_rx.recv().await {
+      Ok(traffic) => {
+        let mut line = serde_json::to_string(&CaptureRecord::from_traffic(&traffic))?;
+        line.push('\n');
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await?;
+      }
+      Err(broadcast::error::RecvError::Lagged(skipped)) => {
+        eprintln!("CAPTURE :: missed {} frames, the capture is incomplete", skipped);
+      }
+      Err(broadcast::error::RecvError::Closed) => return Ok(()),
+    }
+  }
+}
@@ -0,0 +1,71 @@
+use std::{io, net::SocketAddr, time::Duration};
+use common::inspector::Direction;
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpListener, TcpStream}, time};
+
+use super::file::CaptureRecord;
+
+/// Stands in for the console: whoever connects gets the console's side of the capture,
+/// byte for byte and in order, so the client's parser and state store end up exactly
+/// where they were on site. What the client sends is read and dropped, sets aren't ACKed.
+///
+/// `speed` scales the recorded gaps between frames, 0 plays everything at once.
+/// Returns the local address to point the client at.
+pub async fn serve(records: Vec<CaptureRecord>, speed: f32, repeat: bool) -> io::Result<SocketAddr> {
+  let listener = TcpListener::bind("127.0.0.1:0").await?;
+  let address = listener.local_addr()?;
+  eprintln!("REPLAY :: playing {} frames on {}", records.len(), address);
+
+  tokio::spawn(async move {
+    loop {
+      match listener.accept().await {
+        Ok((stream, _)) => {
+          tokio::spawn(play(stream, records.clone(), speed, repeat));
+        }
+        Err(e) => {
+          eprintln!("REPLAY :: stopped :: {}", e);
+          return;
+        }
+      }
+    }
+  });
+
+  Ok(address)
+}
+
+async fn play(stream: TcpStream, records: Vec<CaptureRecord>, speed: f32, repeat: bool) {
+  let (mut read, mut write) = stream.into_split();
+  let mut drain = tokio::spawn(async move {
+    let mut buffer = [0u8; 1024];
+    while let Ok(n) = read.read(&mut buffer).await {
+      if n == 0 {
+        return;
+      }
+    }
+  });
+
+  let received: Vec<&CaptureRecord> = records.iter().filter(|record| record.direction == Direction::RX).collect();
+
+  loop {
+    let mut previous = received.first().map(|record| record.timestamp);
+    for record in received.iter() {
+      if speed > 0.0 {
+        let gap = record.timestamp.saturating_sub(previous.unwrap_or(record.timestamp));
+        time::sleep(Duration::from_secs_f32(gap as f32 / 1000.0 / speed)).await;
+      }
+      previous = Some(record.timestamp);
+
+      // Records were checked when the capture was read
+      let bytes = record.to_bytes().unwrap_or_default();
+      if write.write_all(&bytes).await.is_err() || drain.is_finished() {
+        return;
+      }
+    }
+    if !repeat {
+      break;
+    }
+  }
+
+  eprintln!("REPLAY :: capture finished");
+  // Keep the link open, the client treats a closed one as a lost console
+  let _ = (&mut drain).await;
+}
@@ -0,0 +1,8 @@
+use std::{fs, path::Path};
+use common::template::ShowTemplate;
+
+/// Reads a show template, the JSON form of `ShowTemplate`
+pub fn load(path: &Path) -> Result<ShowTemplate, String> {
+  let contents = fs::read_to_string(path).map_err(|e| format!("Could not read template {} :: {}", path.display(), e))?;
+  serde_json::from_str(&contents).map_err(|e| format!("Invalid template {} :: {}", path.display(), e))
+}
@@ -0,0 +1,507 @@
+#![allow(non_snake_case)]
+use std::{collections::HashMap, path::PathBuf, sync::Arc, time::Duration};
+use common::{
+  Fader, Source,
+  level::db_to_level,
+  scene::{Scene, SceneFader},
+  timecode::{ChaseState, CueAction, CueStatus, FrameRate, Timecode, TimecodeCue, TimecodeSource, TimecodeStatus, Transport},
+};
+use futures_util::lock::Mutex;
+use tokio::{sync::{broadcast, mpsc}, time::{self, Instant}};
+
+use crate::{
+  config::TimecodeConfig,
+  cscp::{events::ConsoleEvent, requests::{get_db, Request, SetFaderCut, SetFaderLevel}},
+  fade::{self, FADE_STEP},
+  scene,
+  schedule::scheduler::{check_fader, level_of, read_scene, targets},
+  scripts::host::ScriptStorage,
+};
+use super::source::{self, Position};
+
+pub type TimecodeStorage = Arc<Mutex<Chaser>>;
+
+/// A position this far from where the timecode should have got to is a locate rather than drift
+const JUMP_LIMIT: f64 = 500.0;
+/// Without a position for this long the timecode has dropped out
+const DROPOUT: Duration = Duration::from_millis(150);
+/// Shortest time between two status updates while the timecode runs
+const STATUS_INTERVAL: Duration = Duration::from_millis(200);
+const SOURCE: Source = Source::AUTOMATION;
+
+struct CueState {
+  cue: TimecodeCue,
+  /// Milliseconds since midnight
+  at: Option<f64>,
+  /// Why the cue can't run
+  error: Option<String>,
+  /// What went wrong the last time it ran
+  lastError: Option<String>,
+}
+
+/// A fader moving with the programme time
+struct Fade {
+  index: u16,
+  from: u16,
+  to: u16,
+  start: f64,
+  duration: f64,
+  /// Level last sent
+  level: u16,
+}
+
+/// What the cues want done
+#[derive(Default)]
+pub struct Fired {
+  pub requests: Vec<Request>,
+  /// Scripts to start, with the cue starting them
+  pub scripts: Vec<(String, String)>,
+}
+
+/// Follows the timecode and fires the cues it passes
+pub struct Chaser {
+  source: TimecodeSource,
+  rate: FrameRate,
+  freewheel: Duration,
+  enabled: bool,
+  configDir: PathBuf,
+  cues: Vec<CueState>,
+  faders: HashMap<u16, Fader>,
+  state: ChaseState,
+  /// Last position read and when
+  anchor: Option<(f64, Instant)>,
+  running: bool,
+  /// Every cue up to here has fired or been chased past
+  firedUntil: Option<f64>,
+  fades: Vec<Fade>,
+  sourceError: Option<String>,
+}
+
+impl Chaser {
+  /// The chaser for `config`, which runs nothing when there is none
+  pub fn new(config: Option<&TimecodeConfig>, cues: Vec<TimecodeCue>, configDir: PathBuf) -> Chaser {
+    let defaults = TimecodeConfig::default();
+    let settings = config.unwrap_or(&defaults);
+    let mut chaser = Chaser {
+      source: settings.source,
+      rate: settings.rate,
+      freewheel: Duration::from_millis(settings.freewheel).max(DROPOUT),
+      enabled: config.is_some(),
+      configDir,
+      cues: vec![],
+      faders: HashMap::new(),
+      state: ChaseState::STOPPED,
+      anchor: None,
+      running: false,
+      firedUntil: None,
+      fades: vec![],
+      sourceError: config.is_none().then(|| String::from("Timecode is off, add `timecode` to the config")),
+    };
+    chaser.set_cues(cues);
+    chaser
+  }
+
+  pub fn cues(&self) -> Vec<TimecodeCue> {
+    self.cues.iter().map(|state| state.cue.clone()).collect()
+  }
+
+  /// Replaces the cues. Those before the current position count as passed and don't fire.
+  pub fn set_cues(&mut self, cues: Vec<TimecodeCue>) {
+    let mut cues: Vec<CueState> = cues.into_iter().map(|cue| {
+      let (at, error) = match validate(&cue, self.rate) {
+        Ok(at) => (Some(at), None),
+        Err(e) => {
+          eprintln!("TIMECODE :: cue off :: {}", e);
+          (None, Some(e))
+        }
+      };
+      CueState { cue, at, error, lastError: None }
+    }).collect();
+    cues.sort_by(|a, b| a.at.unwrap_or(f64::MAX).total_cmp(&b.at.unwrap_or(f64::MAX)));
+    self.cues = cues;
+  }
+
+  /// Whether the LOCAL clock can be moved
+  pub fn transport(&self) -> Result<FrameRate, String> {
+    if !self.enabled || self.source != TimecodeSource::LOCAL {
+      return Err(format!("The transport only moves the LOCAL clock, the timecode comes from {:?}", self.source));
+    }
+    Ok(self.rate)
+  }
+
+  pub fn set_source_error(&mut self, error: Option<String>) {
+    self.sourceError = error;
+  }
+
+  pub fn status(&self, now: Instant) -> TimecodeStatus {
+    let position = self.expected(now);
+    TimecodeStatus {
+      source: self.source,
+      rate: self.rate,
+      state: self.state,
+      position: position.map(|millis| Timecode::from_millis(millis, self.rate).text(self.rate)),
+      error: self.sourceError.clone(),
+      cues: self.cues.iter().map(|state| CueStatus {
+        name: state.cue.name.clone(),
+        at: state.cue.at.clone(),
+        enabled: state.cue.enabled,
+        passed: matches!((state.at, self.firedUntil), (Some(at), Some(until)) if at <= until),
+        error: state.error.clone().or_else(|| state.lastError.clone()),
+      }).collect(),
+    }
+  }
+
+  pub fn seed(&mut self, faders: Vec<Fader>) {
+    self.faders = faders.into_iter().map(|fader| (fader.index, fader)).collect();
+  }
+
+  pub fn handle(&mut self, event: &ConsoleEvent) {
+    if let ConsoleEvent::FADER(fader) = event {
+      self.faders.insert(fader.index, fader.clone());
+    }
+  }
+
+  pub fn cue_error(&mut self, name: &str, error: String) {
+    if let Some(state) = self.cues.iter_mut().find(|state| state.cue.name == name) {
+      state.lastError = Some(error);
+    }
+  }
+
+  /// Where the timecode should be at `now`, running on from the last position through a dropout
+  fn expected(&self, now: Instant) -> Option<f64> {
+    let (millis, at) = self.anchor?;
+    if !self.running {
+      return Some(millis);
+    }
+    Some(millis + now.saturating_duration_since(at).min(self.freewheel).as_secs_f64() * 1000.0)
+  }
+
+  /// Takes in a position read from the source
+  pub fn position(&mut self, position: Position) -> Fired {
+    let expected = self.expected(position.at);
+    let jumped = match (self.firedUntil, expected) {
+      (Some(_), Some(expected)) => (position.millis - expected).abs() > JUMP_LIMIT,
+      _ => true,
+    };
+    let state = if position.running { ChaseState::LOCKED } else { ChaseState::STOPPED };
+    if state != self.state {
+      eprintln!("TIMECODE :: {:?} at {}", state, self.text(position.millis));
+    }
+    self.state = state;
+    self.running = position.running;
+    self.anchor = Some((position.millis, position.at));
+
+    if jumped {
+      self.locate(position.millis)
+    } else {
+      self.advance(position.millis)
+    }
+  }
+
+  /// Moves on through the gaps between positions and notices the timecode dropping out
+  pub fn tick(&mut self, now: Instant) -> Fired {
+    let (millis, at) = match self.anchor {
+      Some(anchor) if self.running => anchor,
+      _ => return Fired::default(),
+    };
+    let silent = now.saturating_duration_since(at);
+    if silent > self.freewheel {
+      let end = millis + self.freewheel.as_secs_f64() * 1000.0;
+      eprintln!("TIMECODE :: lost, stopped at {}", self.text(end));
+      let fired = self.advance(end);
+      self.running = false;
+      self.state = ChaseState::STOPPED;
+      self.anchor = Some((end, now));
+      return fired;
+    }
+    if silent > DROPOUT && self.state == ChaseState::LOCKED {
+      eprintln!("TIMECODE :: dropout at {}, freewheeling", self.text(millis));
+      self.state = ChaseState::FREEWHEEL;
+    }
+    self.advance(millis + silent.as_secs_f64() * 1000.0)
+  }
+
+  fn text(&self, millis: f64) -> String {
+    Timecode::from_millis(millis, self.rate).text(self.rate)
+  }
+
+  fn faders(&self) -> Vec<Fader> {
+    self.faders.values().cloned().collect()
+  }
+
+  /// Fires the cues between the last position and `millis` and moves the fades on
+  fn advance(&mut self, millis: f64) -> Fired {
+    let mut fired = Fired::default();
+    let from = self.firedUntil.unwrap_or(millis);
+    if millis > from {
+      let due: Vec<usize> = (0..self.cues.len())
+        .filter(|i| self.cues[*i].cue.enabled && self.cues[*i].at.is_some_and(|at| at > from && at <= millis))
+        .collect();
+      for i in due {
+        self.fire(i, &mut fired);
+      }
+      self.firedUntil = Some(millis);
+    }
+    self.step(millis, &mut fired);
+    fired
+  }
+
+  fn fire(&mut self, i: usize, fired: &mut Fired) {
+    let (cue, at) = (self.cues[i].cue.clone(), self.cues[i].at.unwrap_or_default());
+    eprintln!("TIMECODE :: {} fired at {}", cue.name, self.text(at));
+    let faders = self.faders();
+    let mut errors = vec![];
+    for action in cue.actions.iter() {
+      let result = match action {
+        CueAction::RECALL_SCENE { scene } => read_scene(scene, &self.configDir).map(|scene| {
+          for fader in scene.faders.iter().filter(|fader| fader.level.is_some()) {
+            self.stop_fade(fader.index);
+          }
+          fired.requests.extend(scene::requests(&scene, SOURCE));
+        }),
+        CueAction::SET_FADER_LEVEL { fader, level, db } => targets(fader, &faders).map(|picked| {
+          let level = level_of(*level, *db);
+          for fader in picked {
+            self.stop_fade(fader.index);
+            if fader.level != level {
+              fired.requests.push(Request::SET_FADER_LEVEL(SetFaderLevel { index: fader.index, level, source: SOURCE }));
+            }
+          }
+        }),
+        CueAction::SET_FADER_CUT { fader, isCut } => targets(fader, &faders).map(|picked| {
+          fired.requests.extend(picked.into_iter().filter(|fader| fader.isCut != *isCut)
+            .map(|fader| Request::SET_FADER_CUT(SetFaderCut { index: fader.index, isCut: *isCut, source: SOURCE })));
+        }),
+        CueAction::FADE { fader, db, duration, from } => targets(fader, &faders).map(|picked| {
+          for fader in picked {
+            self.stop_fade(fader.index);
+            let start = from.map(db_to_level).unwrap_or(fader.level);
+            // The level is sent on the first step when it has to jump to `from`
+            self.fades.push(Fade { index: fader.index, from: start, to: db_to_level(*db), start: at, duration: *duration as f64, level: fader.level });
+          }
+        }),
+        CueAction::RUN_SCRIPT { script } => {
+          fired.scripts.push((cue.name.clone(), script.trim().to_string()));
+          Ok(())
+        }
+      };
+      if let Err(e) = result {
+        eprintln!("TIMECODE :: {} :: {}", cue.name, e);
+        errors.push(e);
+      }
+    }
+    self.cues[i].lastError = (!errors.is_empty()).then(|| errors.join("; "));
+  }
+
+  fn stop_fade(&mut self, index: u16) {
+    self.fades.retain(|fade| fade.index != index);
+  }
+
+  /// Brings the desk to where the cues up to `millis` leave it, without running their scripts,
+  /// and picks up any fade running at that point
+  fn locate(&mut self, millis: f64) -> Fired {
+    eprintln!("TIMECODE :: chasing to {}", self.text(millis));
+    self.fades.clear();
+    let faders = self.faders();
+    let mut state = Scene::default();
+    let passed: Vec<usize> = (0..self.cues.len())
+      .filter(|i| self.cues[*i].cue.enabled && self.cues[*i].at.is_some_and(|at| at <= millis))
+      .collect();
+
+    for i in passed {
+      let (cue, at) = (self.cues[i].cue.clone(), self.cues[i].at.unwrap_or_default());
+      for action in cue.actions.iter() {
+        match action {
+          CueAction::RECALL_SCENE { scene } => match read_scene(scene, &self.configDir) {
+            Ok(scene) => {
+              for wanted in scene.faders {
+                if wanted.level.is_some() {
+                  self.stop_fade(wanted.index);
+                }
+                let fader = fader_state(&mut state, wanted.index);
+                fader.level = wanted.level.or(fader.level);
+                fader.isCut = wanted.isCut.or(fader.isCut);
+                fader.isPfl = wanted.isPfl.or(fader.isPfl);
+              }
+              for wanted in scene.mains {
+                state.mains.retain(|main| main.index != wanted.index);
+                state.mains.push(wanted);
+              }
+              for wanted in scene.auxes {
+                state.auxes.retain(|aux| aux.index != wanted.index);
+                state.auxes.push(wanted);
+              }
+            }
+            Err(e) => self.cues[i].lastError = Some(e),
+          },
+          CueAction::SET_FADER_LEVEL { fader, level, db } => {
+            for fader in targets(fader, &faders).unwrap_or_default() {
+              self.stop_fade(fader.index);
+              fader_state(&mut state, fader.index).level = Some(level_of(*level, *db));
+            }
+          }
+          CueAction::SET_FADER_CUT { fader, isCut } => {
+            for fader in targets(fader, &faders).unwrap_or_default() {
+              fader_state(&mut state, fader.index).isCut = Some(*isCut);
+            }
+          }
+          CueAction::FADE { fader, db, duration, from } => {
+            let to = db_to_level(*db);
+            for fader in targets(fader, &faders).unwrap_or_default() {
+              self.stop_fade(fader.index);
+              let target = fader_state(&mut state, fader.index);
+              if millis >= at + *duration as f64 {
+                target.level = Some(to);
+                continue;
+              }
+              let start = from.map(db_to_level).or(target.level).unwrap_or(fader.level);
+              target.level = None;
+              self.fades.push(Fade { index: fader.index, from: start, to, start: at, duration: *duration as f64, level: fader.level });
+            }
+          }
+          CueAction::RUN_SCRIPT { .. } => {}
+        }
+      }
+    }
+
+    self.firedUntil = Some(millis);
+    let mut fired = Fired {
+      requests: scene::requests(&state, SOURCE).into_iter().filter(|request| self.changes(request)).collect(),
+      ..Fired::default()
+    };
+    self.step(millis, &mut fired);
+    fired
+  }
+
+  /// Whether `request` would change the fader as last reported
+  fn changes(&self, request: &Request) -> bool {
+    match request {
+      Request::SET_FADER_LEVEL(set) => self.faders.get(&set.index).map_or(true, |fader| fader.level != set.level),
+      Request::SET_FADER_CUT(set) => self.faders.get(&set.index).map_or(true, |fader| fader.isCut != set.isCut),
+      Request::SET_FADER_PFL(set) => self.faders.get(&set.index).map_or(true, |fader| fader.isPfl != set.isPfl),
+      _ => true,
+    }
+  }
+
+  /// Sends each fade's level at `millis`
+  fn step(&mut self, millis: f64, fired: &mut Fired) {
+    self.fades.retain_mut(|fade| {
+      let elapsed = (millis - fade.start).max(0.0);
+      let level = fade::level_at(fade.from, fade.to, Duration::from_secs_f64(elapsed / 1000.0), Duration::from_secs_f64(fade.duration / 1000.0));
+      if level != fade.level {
+        fade.level = level;
+        fired.requests.push(fade::level_request(common::BusType::FADER, fade.index, level, SOURCE));
+      }
+      elapsed < fade.duration
+    });
+  }
+}
+
+fn fader_state(scene: &mut Scene, index: u16) -> &mut SceneFader {
+  match scene.faders.iter().position(|fader| fader.index == index) {
+    Some(position) => &mut scene.faders[position],
+    None => {
+      scene.faders.push(SceneFader { index, level: None, isCut: None, isPfl: None });
+      scene.faders.last_mut().unwrap()
+    }
+  }
+}
+
+/// Why `cue` can't run, if it can't, or when it fires in milliseconds since midnight
+pub fn validate(cue: &TimecodeCue, rate: FrameRate) -> Result<f64, String> {
+  if cue.name.trim().is_empty() {
+    return Err(String::from("Every cue needs a name"));
+  }
+  let fail = |e: String| format!("{} :: {}", cue.name, e);
+  let at = cue.at.parse::<Timecode>().map_err(fail)?;
+  if !at.is_valid(rate) {
+    return Err(fail(format!("{} isn't a position at {:?}", cue.at, rate)));
+  }
+  if cue.actions.is_empty() {
+    return Err(fail(String::from("The cue has no actions")));
+  }
+  for action in cue.actions.iter() {
+    let checked = match action {
+      CueAction::RECALL_SCENE { scene } => if scene.trim().is_empty() { Err(String::from("RECALL_SCENE needs a scene")) } else { Ok(()) },
+      CueAction::FADE { fader, .. } | CueAction::SET_FADER_CUT { fader, .. } => check_fader(fader),
+      CueAction::SET_FADER_LEVEL { fader, level, db } => check_fader(fader).and_then(|_| {
+        if level.is_none() && db.is_none() { Err(String::from("Level actions need level or db")) } else { Ok(()) }
+      }),
+      CueAction::RUN_SCRIPT { script } => if script.trim().is_empty() { Err(String::from("RUN_SCRIPT needs a script")) } else { Ok(()) },
+    };
+    checked.map_err(fail)?;
+  }
+  Ok(at.to_millis(rate))
+}
+
+/// Chases the configured source, sending what the cues ask for, starting their scripts
+/// and passing the status to `publish`
+pub async fn run<F: Fn(TimecodeStatus)>(
+  chaser: TimecodeStorage,
+  config: TimecodeConfig,
+  transport_rx: mpsc::Receiver<Transport>,
+  scripts: ScriptStorage,
+  input_tx: mpsc::Sender<Request>,
+  mut event_rx: broadcast::Receiver<ConsoleEvent>,
+  publish: F,
+) {
+  let (position_tx, mut position_rx) = mpsc::unbounded_channel();
+  let _source = match source::start(&config, transport_rx, position_tx) {
+    Ok(source) => Some(source),
+    Err(e) => {
+      eprintln!("TIMECODE :: {:?} source failed :: {}", config.source, e);
+      chaser.lock().await.set_source_error(Some(e));
+      None
+    }
+  };
+  if let Ok(db) = get_db(&input_tx).await {
+    chaser.lock().await.seed(db.faders);
+  }
+  let mut tick = time::interval(FADE_STEP);
+  let mut published: Option<(TimecodeStatus, Instant)> = None;
+
+  loop {
+    let fired = tokio::select! {
+      Some(position) = position_rx.recv() => chaser.lock().await.position(position),
+      event = event_rx.recv() => match event {
+        Ok(event) => {
+          chaser.lock().await.handle(&event);
+          continue;
+        }
+        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+          eprintln!("TIMECODE :: missed {} changes", skipped);
+          continue;
+        }
+        Err(broadcast::error::RecvError::Closed) => return,
+      },
+      _ = tick.tick() => {
+        let now = Instant::now();
+        let mut chaser = chaser.lock().await;
+        let fired = chaser.tick(now);
+        let status = chaser.status(now);
+        let due = match &published {
+          Some((last, at)) => *last != status && (last.state != status.state || now.duration_since(*at) >= STATUS_INTERVAL),
+          None => true,
+        };
+        if due {
+          publish(status.clone());
+          published = Some((status, now));
+        }
+        fired
+      }
+    };
+
+    for (cue, script) in fired.scripts {
+      if let Err(e) = scripts.lock().await.start(&script, &format!("cue {}", cue)) {
+        eprintln!("TIMECODE :: {} :: {}", cue, e);
+        chaser.lock().await.cue_error(&cue, e);
+      }
+    }
+    for request in fired.requests {
+      if input_tx.send(request).await.is_err() {
+        eprintln!("TIMECODE :: the client stopped taking requests");
+        return;
+      }
+    }
+  }
+}
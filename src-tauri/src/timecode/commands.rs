@@ -0,0 +1,60 @@
+use std::path::PathBuf;
+use common::timecode::{Timecode, TimecodeCue, TimecodeStatus, Transport};
+use tokio::{sync::mpsc, time::Instant};
+
+use crate::config::Config;
+use super::chase::{validate, TimecodeStorage};
+
+pub struct TimecodeState {
+  pub inner: TimecodeStorage,
+  /// Config file the cues are saved to
+  pub configPath: PathBuf,
+  /// Moves the LOCAL clock
+  pub transport_tx: mpsc::Sender<Transport>,
+}
+
+#[tauri::command]
+pub async fn getCues(
+    state: tauri::State<'_, TimecodeState>,
+) -> Result<Vec<TimecodeCue>, String> {
+    Ok(state.inner.lock().await.cues())
+}
+
+#[tauri::command]
+pub async fn getTimecodeStatus(
+    state: tauri::State<'_, TimecodeState>,
+) -> Result<TimecodeStatus, String> {
+    Ok(state.inner.lock().await.status(Instant::now()))
+}
+
+#[tauri::command]
+pub async fn saveCues(
+    cues: Vec<TimecodeCue>,
+    state: tauri::State<'_, TimecodeState>,
+) -> Result<TimecodeStatus, String> {
+    eprintln!("saveCues count={}", cues.len());
+    let mut chaser = state.inner.lock().await;
+    let rate = chaser.status(Instant::now()).rate;
+    cues.iter().try_for_each(|cue| validate(cue, rate).map(|_| ()))?;
+    let value = serde_json::to_value(&cues).map_err(|e| e.to_string())?;
+    Config::update(&state.configPath, "cues", value)?;
+
+    chaser.set_cues(cues);
+    Ok(chaser.status(Instant::now()))
+}
+
+#[tauri::command]
+pub async fn timecodeTransport(
+    transport: Transport,
+    state: tauri::State<'_, TimecodeState>,
+) -> Result<(), String> {
+    eprintln!("timecodeTransport {:?}", transport);
+    let rate = state.inner.lock().await.transport()?;
+    if let Transport::LOCATE { position } = &transport {
+        let timecode = position.parse::<Timecode>()?;
+        if !timecode.is_valid(rate) {
+            return Err(format!("{} isn't a position at {:?}", position, rate));
+        }
+    }
+    state.transport_tx.send(transport).await.map_err(|e| e.to_string())
+}
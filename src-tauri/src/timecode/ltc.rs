@@ -0,0 +1,219 @@
+#![allow(non_snake_case)]
+use common::timecode::{FrameRate, Timecode};
+
+/// Bits in an LTC frame
+const FRAME_BITS: usize = 80;
+/// Bits 64 to 79 of every frame, read forwards
+const SYNC_WORD: u128 = 0xBFFC;
+/// Signal level a crossing has to pass, so noise around zero isn't read as transitions
+const HYSTERESIS: f32 = 0.02;
+const DROP_FRAME_BIT: usize = 10;
+
+/// Reads LTC frames from audio. The bit clock is followed as it drifts, so anything from 24 to 30 fps decodes.
+pub struct LtcDecoder {
+  /// Expected length of half a bit in samples
+  half: f32,
+  high: bool,
+  /// Samples since the last transition
+  since: f32,
+  /// Whether the first half of a 1 has been seen
+  pendingOne: bool,
+  bits: u128,
+  count: usize,
+}
+
+/// A frame read from LTC, flagged when it counts drop-frame
+pub struct LtcFrame {
+  pub timecode: Timecode,
+  pub dropFrame: bool,
+}
+
+fn field(bits: u128, start: usize, width: usize) -> u8 {
+  ((bits >> start) & ((1 << width) - 1)) as u8
+}
+
+impl LtcDecoder {
+  pub fn new(sampleRate: u32, rate: FrameRate) -> LtcDecoder {
+    LtcDecoder {
+      half: sampleRate as f32 / (FRAME_BITS as f32 * rate.fps() as f32 * 2.0),
+      high: false,
+      since: 0.0,
+      pendingOne: false,
+      bits: 0,
+      count: 0,
+    }
+  }
+
+  /// Takes in mono samples, returning the frames they finish
+  pub fn feed(&mut self, samples: &[f32]) -> Vec<LtcFrame> {
+    let mut frames = vec![];
+    for sample in samples {
+      self.since += 1.0;
+      let flipped = if self.high { *sample < -HYSTERESIS } else { *sample > HYSTERESIS };
+      if !flipped {
+        continue;
+      }
+      self.high = !self.high;
+      let interval = std::mem::take(&mut self.since);
+      if let Some(frame) = self.transition(interval) {
+        frames.push(frame);
+      }
+    }
+    frames
+  }
+
+  fn transition(&mut self, interval: f32) -> Option<LtcFrame> {
+    // Anything far off the clock is silence or noise ending, start over
+    if interval > self.half * 3.0 || interval < self.half * 0.4 {
+      self.pendingOne = false;
+      self.count = 0;
+      return None;
+    }
+    if interval > self.half * 1.5 {
+      self.half = self.half * 0.9 + interval / 2.0 * 0.1;
+      self.pendingOne = false;
+      return self.bit(false);
+    }
+    self.half = self.half * 0.9 + interval * 0.1;
+    if std::mem::take(&mut self.pendingOne) {
+      return self.bit(true);
+    }
+    self.pendingOne = true;
+    None
+  }
+
+  fn bit(&mut self, one: bool) -> Option<LtcFrame> {
+    self.bits = (self.bits >> 1) | (one as u128) << (FRAME_BITS - 1);
+    self.count += 1;
+    if self.count < FRAME_BITS || self.bits >> 64 != SYNC_WORD {
+      return None;
+    }
+    self.count = 0;
+    let bits = self.bits;
+    let timecode = Timecode::new(
+      field(bits, 48, 4) + field(bits, 56, 2) * 10,
+      field(bits, 32, 4) + field(bits, 40, 3) * 10,
+      field(bits, 16, 4) + field(bits, 24, 3) * 10,
+      field(bits, 0, 4) + field(bits, 8, 2) * 10,
+    );
+    Some(LtcFrame { timecode, dropFrame: bits >> DROP_FRAME_BIT & 1 == 1 })
+  }
+}
+
+/// Writes LTC as audio, for testing the decoder and anything else reading timecode
+pub struct LtcEncoder {
+  samplesPerHalf: f64,
+  /// Fraction of a sample carried over between half bits
+  carry: f64,
+  level: f32,
+  rate: FrameRate,
+}
+
+impl LtcEncoder {
+  pub fn new(sampleRate: u32, rate: FrameRate) -> LtcEncoder {
+    LtcEncoder {
+      samplesPerHalf: sampleRate as f64 / (FRAME_BITS as f64 * rate.fps() * 2.0),
+      carry: 0.0,
+      level: 0.5,
+      rate,
+    }
+  }
+
+  fn bits(&self, timecode: Timecode) -> u128 {
+    let set = |bits: &mut u128, start: usize, value: u8| *bits |= (value as u128) << start;
+    let mut bits = SYNC_WORD << 64;
+    set(&mut bits, 0, timecode.frames % 10);
+    set(&mut bits, 8, timecode.frames / 10);
+    set(&mut bits, DROP_FRAME_BIT, self.rate.is_drop() as u8);
+    set(&mut bits, 16, timecode.seconds % 10);
+    set(&mut bits, 24, timecode.seconds / 10);
+    set(&mut bits, 32, timecode.minutes % 10);
+    set(&mut bits, 40, timecode.minutes / 10);
+    set(&mut bits, 48, timecode.hours % 10);
+    set(&mut bits, 56, timecode.hours / 10);
+    bits
+  }
+
+  fn half_bit(&mut self, samples: &mut Vec<f32>) {
+    let length = self.samplesPerHalf + self.carry;
+    let whole = length.floor();
+    self.carry = length - whole;
+    samples.extend(std::iter::repeat(self.level).take(whole as usize));
+  }
+
+  /// One frame of audio, each bit starting with a transition and ones having another halfway
+  pub fn frame(&mut self, timecode: Timecode) -> Vec<f32> {
+    let bits = self.bits(timecode);
+    let mut samples = vec![];
+    for bit in 0..FRAME_BITS {
+      self.level = -self.level;
+      self.half_bit(&mut samples);
+      if bits >> bit & 1 == 1 {
+        self.level = -self.level;
+      }
+      self.half_bit(&mut samples);
+    }
+    samples
+  }
+
+  /// As many samples of silence as a frame takes
+  pub fn silence(&mut self) -> Vec<f32> {
+    let length = self.samplesPerHalf * FRAME_BITS as f64 * 2.0 + self.carry;
+    self.carry = length - length.floor();
+    vec![0.0; length.floor() as usize]
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Encodes `count` frames from `start` after a frame of silence and decodes them back.
+  /// One more frame follows, as the last bit of a frame only ends when the next one starts.
+  fn round_trip(rate: FrameRate, start: Timecode, count: u32) -> (Vec<Timecode>, Vec<LtcFrame>) {
+    let mut encoder = LtcEncoder::new(48000, rate);
+    let mut decoder = LtcDecoder::new(48000, rate);
+    let first = start.to_frames(rate);
+    let sent: Vec<_> = (first..first + count).map(|frame| Timecode::from_frames(frame, rate)).collect();
+    let mut samples = encoder.silence();
+    for timecode in &sent {
+      samples.extend(encoder.frame(*timecode));
+    }
+    samples.extend(encoder.frame(Timecode::from_frames(first + count, rate)));
+    let mut frames = vec![];
+    for chunk in samples.chunks(48) {
+      frames.extend(decoder.feed(chunk));
+    }
+    (sent, frames)
+  }
+
+  fn check(rate: FrameRate, start: Timecode) {
+    let (sent, frames) = round_trip(rate, start, 30);
+    // The first frame goes on locking to the clock, everything after it has to come back in order
+    assert!(frames.len() >= sent.len() - 1, "{:?}: {} of {} frames decoded", rate, frames.len(), sent.len());
+    let decoded: Vec<_> = frames.iter().map(|frame| frame.timecode).collect();
+    assert_eq!(decoded, sent[sent.len() - decoded.len()..], "{:?}", rate);
+    assert!(frames.iter().all(|frame| frame.dropFrame == rate.is_drop()), "{:?}", rate);
+  }
+
+  #[test]
+  fn round_trips_24() {
+    check(FrameRate::FPS_24, Timecode::new(1, 2, 3, 10));
+  }
+
+  #[test]
+  fn round_trips_25() {
+    check(FrameRate::FPS_25, Timecode::new(10, 59, 59, 12));
+  }
+
+  #[test]
+  fn round_trips_29_97_drop_frame() {
+    // Crosses into a minute where frames 00 and 01 are dropped
+    check(FrameRate::FPS_29_97_DF, Timecode::new(0, 0, 59, 20));
+  }
+
+  #[test]
+  fn round_trips_30() {
+    check(FrameRate::FPS_30, Timecode::new(23, 59, 59, 15));
+  }
+}
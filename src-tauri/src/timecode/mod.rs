@@ -0,0 +1,5 @@
+pub mod chase;
+pub mod commands;
+pub mod ltc;
+pub mod mtc;
+pub mod source;
@@ -0,0 +1,147 @@
+use common::timecode::{FrameRate, Timecode};
+
+const QUARTER_FRAME: u8 = 0xF1;
+
+fn rate_bits(rate: FrameRate) -> u8 {
+  match rate {
+    FrameRate::FPS_24 => 0,
+    FrameRate::FPS_25 => 1,
+    FrameRate::FPS_29_97_DF => 2,
+    FrameRate::FPS_30 => 3,
+  }
+}
+
+fn rate_from_bits(bits: u8) -> FrameRate {
+  match bits & 0x03 {
+    0 => FrameRate::FPS_24,
+    1 => FrameRate::FPS_25,
+    2 => FrameRate::FPS_29_97_DF,
+    _ => FrameRate::FPS_30,
+  }
+}
+
+/// Decodes MIDI Timecode from quarter frames and full frame SysEx
+#[derive(Default)]
+pub struct MtcDecoder {
+  pieces: [u8; 8],
+  /// Quarter frame piece expected next, pieces out of order wait for the next piece 0
+  next: u8,
+}
+
+/// A position read from MTC
+#[derive(Debug, PartialEq)]
+pub struct MtcPosition {
+  pub timecode: Timecode,
+  pub rate: FrameRate,
+  /// False for full frames, sent when the transport locates without playing
+  pub running: bool,
+}
+
+impl MtcDecoder {
+  pub fn feed(&mut self, message: &[u8]) -> Option<MtcPosition> {
+    match message {
+      [QUARTER_FRAME, data] => self.quarter_frame(*data),
+      // F0 7F <device> 01 01 hr mn sc fr F7
+      [0xF0, 0x7F, _, 0x01, 0x01, hours, minutes, seconds, frames, 0xF7] => {
+        self.next = 0;
+        let rate = rate_from_bits(hours >> 5);
+        Some(MtcPosition { timecode: Timecode::new(hours & 0x1F, *minutes, *seconds, *frames), rate, running: false })
+      }
+      _ => None,
+    }
+  }
+
+  fn quarter_frame(&mut self, data: u8) -> Option<MtcPosition> {
+    let piece = data >> 4;
+    if piece != self.next {
+      self.next = 0;
+      if piece != 0 {
+        return None;
+      }
+    }
+    self.pieces[piece as usize] = data & 0x0F;
+    self.next = (piece + 1) % 8;
+    if piece != 7 {
+      return None;
+    }
+
+    let p = &self.pieces;
+    let rate = rate_from_bits(p[7] >> 1);
+    let timecode = Timecode::new(
+      p[6] | (p[7] & 0x01) << 4,
+      p[4] | p[5] << 4,
+      p[2] | p[3] << 4,
+      p[0] | p[1] << 4,
+    );
+    // The pieces took two frames to arrive and describe the frame the first one was sent in
+    let timecode = Timecode::from_frames(timecode.to_frames(rate) + 2, rate);
+    Some(MtcPosition { timecode, rate, running: true })
+  }
+}
+
+/// The eight quarter frame messages sent over the two frames starting at `timecode`
+pub fn quarter_frames(timecode: Timecode, rate: FrameRate) -> [[u8; 2]; 8] {
+  let values = [
+    timecode.frames & 0x0F,
+    timecode.frames >> 4,
+    timecode.seconds & 0x0F,
+    timecode.seconds >> 4,
+    timecode.minutes & 0x0F,
+    timecode.minutes >> 4,
+    timecode.hours & 0x0F,
+    (timecode.hours >> 4) & 0x01 | rate_bits(rate) << 1,
+  ];
+  let mut messages = [[0; 2]; 8];
+  for (piece, value) in values.iter().enumerate() {
+    messages[piece] = [QUARTER_FRAME, (piece as u8) << 4 | value];
+  }
+  messages
+}
+
+/// The full frame SysEx locating to `timecode`
+pub fn full_frame(timecode: Timecode, rate: FrameRate) -> [u8; 10] {
+  [0xF0, 0x7F, 0x7F, 0x01, 0x01, rate_bits(rate) << 5 | timecode.hours, timecode.minutes, timecode.seconds, timecode.frames, 0xF7]
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn quarter_frames_are_two_frames_ahead() {
+    for rate in [FrameRate::FPS_24, FrameRate::FPS_25, FrameRate::FPS_29_97_DF, FrameRate::FPS_30] {
+      let timecode = Timecode::new(13, 45, 30, 20);
+      let mut decoder = MtcDecoder::default();
+      let positions: Vec<_> = quarter_frames(timecode, rate).iter().filter_map(|message| decoder.feed(message)).collect();
+      let expected = Timecode::from_frames(timecode.to_frames(rate) + 2, rate);
+      assert_eq!(positions, [MtcPosition { timecode: expected, rate, running: true }], "{:?}", rate);
+    }
+  }
+
+  #[test]
+  fn quarter_frames_compensate_across_drop_frames() {
+    let rate = FrameRate::FPS_29_97_DF;
+    let mut decoder = MtcDecoder::default();
+    let position = quarter_frames(Timecode::new(0, 0, 59, 28), rate).iter().find_map(|message| decoder.feed(message)).unwrap();
+    assert_eq!(position.timecode, Timecode::new(0, 1, 0, 2));
+  }
+
+  #[test]
+  fn quarter_frames_wait_for_the_first_piece() {
+    let rate = FrameRate::FPS_25;
+    let messages = quarter_frames(Timecode::new(1, 0, 0, 0), rate);
+    let mut decoder = MtcDecoder::default();
+    // Joining halfway through gives nothing until a full run of eight
+    assert!(messages[4..].iter().all(|message| decoder.feed(message).is_none()));
+    assert!(messages.iter().filter_map(|message| decoder.feed(message)).count() == 1);
+  }
+
+  #[test]
+  fn full_frame_locates() {
+    for rate in [FrameRate::FPS_24, FrameRate::FPS_25, FrameRate::FPS_29_97_DF, FrameRate::FPS_30] {
+      let timecode = Timecode::new(23, 59, 58, 17);
+      let position = MtcDecoder::default().feed(&full_frame(timecode, rate));
+      assert_eq!(position, Some(MtcPosition { timecode, rate, running: false }), "{:?}", rate);
+    }
+  }
+}
@@ -0,0 +1,216 @@
+#![allow(non_snake_case)]
+use std::{
+  path::Path,
+  sync::{atomic::{AtomicBool, Ordering}, Arc},
+  thread,
+  time::Duration,
+};
+use common::timecode::{FrameRate, Timecode, TimecodeSource, Transport};
+use cpal::{traits::{DeviceTrait, HostTrait, StreamTrait}, FromSample, SampleFormat, SizedSample};
+use midir::MidiInputConnection;
+use tokio::{sync::mpsc, task::JoinHandle, time::{self, Instant}};
+
+use crate::{config::TimecodeConfig, midi::ports::{self, matches}};
+use super::{ltc::LtcDecoder, mtc::MtcDecoder};
+
+/// Name of the MIDI input opened when no `port` is configured
+pub const VIRTUAL_PORT: &str = "CSCP Timecode";
+/// Audio fed to the decoder at a time when reading a file
+const FILE_CHUNK: Duration = Duration::from_millis(10);
+
+/// Where the timecode is, as read from a source
+#[derive(Debug, Clone, Copy)]
+pub struct Position {
+  /// Milliseconds since midnight
+  pub millis: f64,
+  /// False when the source says it is parked there
+  pub running: bool,
+  pub at: Instant,
+}
+
+/// Keeps a source going until dropped
+pub enum SourceGuard {
+  MIDI(MidiInputConnection<()>),
+  /// Audio streams have to stay on the thread that opened them, which runs until the flag is set
+  THREAD(Arc<AtomicBool>),
+  TASK(JoinHandle<()>),
+}
+
+impl Drop for SourceGuard {
+  fn drop(&mut self) {
+    match self {
+      SourceGuard::MIDI(_) => {}
+      SourceGuard::THREAD(stop) => stop.store(true, Ordering::Relaxed),
+      SourceGuard::TASK(task) => task.abort(),
+    }
+  }
+}
+
+/// Starts reading timecode from the configured source. `transport_rx` moves the LOCAL clock.
+pub fn start(config: &TimecodeConfig, transport_rx: mpsc::Receiver<Transport>, position_tx: mpsc::UnboundedSender<Position>) -> Result<SourceGuard, String> {
+  match config.source {
+    TimecodeSource::LOCAL => {
+      let start = config.start.parse::<Timecode>()?;
+      if !start.is_valid(config.rate) {
+        return Err(format!("{} isn't a position at {:?}", config.start, config.rate));
+      }
+      Ok(SourceGuard::TASK(tokio::spawn(local(start.to_millis(config.rate), config.rate, transport_rx, position_tx))))
+    }
+    TimecodeSource::MTC => mtc(config.port.as_deref(), position_tx).map(SourceGuard::MIDI),
+    TimecodeSource::LTC => match &config.file {
+      Some(path) => ltc_file(path, config.rate, position_tx),
+      None => ltc_input(config.device.clone(), config.rate, position_tx),
+    },
+  }
+}
+
+/// A clock in the app, moving one frame at a time while playing
+async fn local(start: f64, rate: FrameRate, mut transport_rx: mpsc::Receiver<Transport>, position_tx: mpsc::UnboundedSender<Position>) {
+  let mut tick = time::interval(Duration::from_secs_f64(1.0 / rate.fps()));
+  let mut position = start;
+  // Where the clock was when it started playing
+  let mut playing: Option<(f64, Instant)> = None;
+  let now = |playing: &Option<(f64, Instant)>, position: f64| match playing {
+    Some((from, since)) => from + since.elapsed().as_secs_f64() * 1000.0,
+    None => position,
+  };
+  let _ = position_tx.send(Position { millis: position, running: false, at: Instant::now() });
+
+  loop {
+    tokio::select! {
+      transport = transport_rx.recv() => {
+        match transport {
+          Some(Transport::PLAY) => {
+            position = now(&playing, position);
+            playing = Some((position, Instant::now()));
+          }
+          Some(Transport::STOP) => {
+            position = now(&playing, position);
+            playing = None;
+          }
+          Some(Transport::LOCATE { position: text }) => {
+            match text.parse::<Timecode>() {
+              Ok(timecode) => position = timecode.to_millis(rate),
+              Err(e) => eprintln!("TIMECODE :: {}", e),
+            }
+            playing = playing.map(|_| (position, Instant::now()));
+          }
+          None => return,
+        }
+        let _ = position_tx.send(Position { millis: position, running: playing.is_some(), at: Instant::now() });
+      }
+      _ = tick.tick(), if playing.is_some() => {
+        position = now(&playing, position);
+        if position_tx.send(Position { millis: position, running: true, at: Instant::now() }).is_err() {
+          return;
+        }
+      }
+    }
+  }
+}
+
+fn mtc(port: Option<&str>, position_tx: mpsc::UnboundedSender<Position>) -> Result<MidiInputConnection<()>, String> {
+  let mut decoder = MtcDecoder::default();
+  let (connection, name) = ports::open_input(VIRTUAL_PORT, port, move |_, message, _| {
+    if let Some(position) = decoder.feed(message) {
+      let _ = position_tx.send(Position { millis: position.timecode.to_millis(position.rate), running: position.running, at: Instant::now() });
+    }
+  })?;
+  eprintln!("TIMECODE :: reading MTC from {}", name);
+  Ok(connection)
+}
+
+/// The position a decoded LTC frame gives, the frame after it having just started
+fn ltc_position(decoder: &mut LtcDecoder, samples: &[f32], rate: FrameRate, position_tx: &mpsc::UnboundedSender<Position>) {
+  for frame in decoder.feed(samples) {
+    let rate = if frame.dropFrame { FrameRate::FPS_29_97_DF } else { rate };
+    let millis = (frame.timecode.to_frames(rate) + 1) as f64 * 1000.0 / rate.fps();
+    let _ = position_tx.send(Position { millis, running: true, at: Instant::now() });
+  }
+}
+
+fn ltc_file(path: &Path, rate: FrameRate, position_tx: mpsc::UnboundedSender<Position>) -> Result<SourceGuard, String> {
+  let (sampleRate, samples) = read_wav(path)?;
+  let mut decoder = LtcDecoder::new(sampleRate, rate);
+  let chunk = (sampleRate as f64 * FILE_CHUNK.as_secs_f64()) as usize;
+  Ok(SourceGuard::TASK(tokio::spawn(async move {
+    let mut tick = time::interval(FILE_CHUNK);
+    for samples in samples.chunks(chunk.max(1)) {
+      tick.tick().await;
+      ltc_position(&mut decoder, samples, rate, &position_tx);
+    }
+  })))
+}
+
+/// The sample rate and first channel of a WAV file
+pub fn read_wav(path: &Path) -> Result<(u32, Vec<f32>), String> {
+  let mut reader = hound::WavReader::open(path).map_err(|e| format!("Can't read {} :: {}", path.display(), e))?;
+  let spec = reader.spec();
+  let channels = spec.channels.max(1) as usize;
+  let samples: Vec<f32> = match spec.sample_format {
+    hound::SampleFormat::Float => reader.samples::<f32>().step_by(channels).collect::<Result<Vec<f32>, hound::Error>>(),
+    hound::SampleFormat::Int => {
+      let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+      reader.samples::<i32>().step_by(channels).map(|sample| sample.map(|sample| sample as f32 / scale)).collect()
+    }
+  }.map_err(|e| format!("Can't read {} :: {}", path.display(), e))?;
+  Ok((spec.sample_rate, samples))
+}
+
+fn ltc_input(device: Option<String>, rate: FrameRate, position_tx: mpsc::UnboundedSender<Position>) -> Result<SourceGuard, String> {
+  let stop = Arc::new(AtomicBool::new(false));
+  let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+  let running = stop.clone();
+  thread::spawn(move || {
+    let stream = match open_input(device.as_deref(), rate, position_tx) {
+      Ok(stream) => stream,
+      Err(e) => {
+        let _ = ready_tx.send(Err(e));
+        return;
+      }
+    };
+    let _ = ready_tx.send(Ok(()));
+    while !running.load(Ordering::Relaxed) {
+      thread::sleep(Duration::from_millis(200));
+    }
+    drop(stream);
+  });
+  ready_rx.recv().map_err(|e| e.to_string())??;
+  Ok(SourceGuard::THREAD(stop))
+}
+
+fn open_input(wanted: Option<&str>, rate: FrameRate, position_tx: mpsc::UnboundedSender<Position>) -> Result<cpal::Stream, String> {
+  let host = cpal::default_host();
+  let device = match wanted {
+    Some(wanted) => host.input_devices().map_err(|e| e.to_string())?
+      .find(|device| device.name().is_ok_and(|name| matches(&name, wanted)))
+      .ok_or_else(|| format!("No audio input {}", wanted))?,
+    None => host.default_input_device().ok_or_else(|| String::from("No audio input"))?,
+  };
+  let supported = device.default_input_config().map_err(|e| e.to_string())?;
+  let stream = match supported.sample_format() {
+    SampleFormat::F32 => build_input::<f32>(&device, &supported, rate, position_tx),
+    SampleFormat::I16 => build_input::<i16>(&device, &supported, rate, position_tx),
+    SampleFormat::U16 => build_input::<u16>(&device, &supported, rate, position_tx),
+    format => return Err(format!("Audio input format {} isn't supported", format)),
+  }?;
+  stream.play().map_err(|e| e.to_string())?;
+  Ok(stream)
+}
+
+fn build_input<T: SizedSample>(device: &cpal::Device, supported: &cpal::SupportedStreamConfig, rate: FrameRate, position_tx: mpsc::UnboundedSender<Position>) -> Result<cpal::Stream, String>
+where f32: FromSample<T> {
+  let channels = supported.channels().max(1) as usize;
+  let mut decoder = LtcDecoder::new(supported.sample_rate().0, rate);
+  let mut samples = vec![];
+  device.build_input_stream(
+    &supported.config(),
+    move |data: &[T], _: &cpal::InputCallbackInfo| {
+      samples.clear();
+      samples.extend(data.iter().step_by(channels).map(|sample| sample.to_sample::<f32>()));
+      ltc_position(&mut decoder, &samples, rate, &position_tx);
+    },
+    |e| eprintln!("TIMECODE :: audio input failed :: {}", e),
+    None,
+  ).map_err(|e| e.to_string())
+}
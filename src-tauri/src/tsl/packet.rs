@@ -0,0 +1,107 @@
+#![allow(non_snake_case)]
+use bytes::{BufMut, BytesMut};
+
+pub const V3_1_TEXT_LENGTH: usize = 16;
+/// Highest display address v3.1 can carry, 127 being kept for broadcast
+pub const V3_1_MAX_ADDRESS: u16 = 126;
+
+/// TSL UMD v3.1 display message: address, control byte and 16 characters of text
+pub fn v3_1(address: u8, tally: bool, brightness: u8, text: &str) -> Vec<u8> {
+  let mut buffer = BytesMut::with_capacity(2 + V3_1_TEXT_LENGTH);
+  buffer.put_u8(0x80 + (address & 0x7F));
+  // bit 0 = tally 1, bits 4-5 = brightness
+  buffer.put_u8((tally as u8) | (brightness & 0x03) << 4);
+
+  let mut chars: Vec<u8> = text.chars()
+    .map(|c| if (' '..='~').contains(&c) { c as u8 } else { b'?' })
+    .take(V3_1_TEXT_LENGTH)
+    .collect();
+  chars.resize(V3_1_TEXT_LENGTH, b' ');
+  buffer.put_slice(&chars);
+
+  buffer.to_vec()
+}
+
+/// TSL UMD v5.0 packet carrying a single display message.
+/// Text is sent as ASCII unless it needs unicode, in which case it's UTF-16LE.
+pub fn v5(screen: u16, index: u16, tally: bool, brightness: u8, text: &str) -> Vec<u8> {
+  let isUnicode = !text.is_ascii();
+  let text: Vec<u8> = if isUnicode {
+    text.encode_utf16().flat_map(|c| c.to_le_bytes()).collect()
+  } else {
+    text.as_bytes().to_vec()
+  };
+
+  // Red on the right hand, text and left hand tallies when on air
+  let tallyColour = if tally { 1 } else { 0 };
+  let control: u16 = tallyColour | tallyColour << 2 | tallyColour << 4 | ((brightness & 0x03) as u16) << 6;
+
+  let mut message = BytesMut::with_capacity(6 + text.len());
+  message.put_u16_le(index);
+  message.put_u16_le(control);
+  message.put_u16_le(text.len() as u16);
+  message.put_slice(&text);
+
+  let mut buffer = BytesMut::with_capacity(6 + message.len());
+  // Byte count excludes the count itself
+  buffer.put_u16_le((4 + message.len()) as u16);
+  buffer.put_u8(0x00);
+  buffer.put_u8(isUnicode as u8);
+  buffer.put_u16_le(screen);
+  buffer.put_slice(&message);
+
+  buffer.to_vec()
+}
+
+/// Wraps a v5.0 packet for TCP: DLE/STX followed by the packet with any DLE doubled
+pub fn v5_tcp(packet: &[u8]) -> Vec<u8> {
+  let mut wrapped = vec![0xFE, 0x02];
+  for &byte in packet {
+    wrapped.push(byte);
+    if byte == 0xFE {
+      wrapped.push(0xFE);
+    }
+  }
+  wrapped
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn builds_v3_1_messages() {
+    let message = v3_1(5, true, 3, "MIC 1");
+    assert_eq!(message[..2], [0x85, 0x31]);
+    assert_eq!(&message[2..], b"MIC 1           ");
+
+    let message = v3_1(126, false, 0, "A LONG FADER LABEL ü");
+    assert_eq!(message[..2], [0xFE, 0x00]);
+    assert_eq!(&message[2..], b"A LONG FADER LAB");
+    assert_eq!(&v3_1(0, false, 2, "ü")[1..3], [0x20, b'?']);
+  }
+
+  #[test]
+  fn builds_v5_packets() {
+    let packet = v5(1, 0x0203, true, 3, "MIC");
+    assert_eq!(
+      packet,
+      [
+        13, 0, // byte count
+        0x00, 0x00, // version, flags
+        1, 0, // screen
+        0x03, 0x02, // index
+        0xD5, 0x00, // control: red tallies, brightness 3
+        3, 0, b'M', b'I', b'C',
+      ]
+    );
+
+    let packet = v5(0, 1, false, 1, "é");
+    assert_eq!(packet, [12, 0, 0x00, 0x01, 0, 0, 1, 0, 0x40, 0x00, 2, 0, 0xE9, 0x00]);
+  }
+
+  #[test]
+  fn wraps_v5_for_tcp() {
+    assert_eq!(v5_tcp(&[0x01, 0xFE, 0x02]), [0xFE, 0x02, 0x01, 0xFE, 0xFE, 0x02]);
+  }
+}
@@ -0,0 +1,110 @@
+#![allow(non_snake_case)]
+use std::{collections::HashMap, io, time::Duration};
+use tokio::{io::AsyncWriteExt, net::{TcpStream, UdpSocket}, sync::broadcast, time};
+
+use crate::{config::{OnAirConfig, TslConfig, TslTransport, TslVersion}, cscp::events::ConsoleEvent, onair::tracker::is_on_air};
+use super::packet;
+
+#[derive(Debug, Clone, PartialEq)]
+struct DisplayState {
+  text: String,
+  tally: bool,
+}
+
+enum Transport {
+  UDP(UdpSocket),
+  TCP(Option<TcpStream>),
+}
+
+impl Transport {
+  async fn send(&mut self, address: &str, packet: &[u8]) -> io::Result<()> {
+    match self {
+      Transport::UDP(socket) => {
+        socket.send_to(packet, address).await?;
+      }
+      Transport::TCP(stream) => {
+        if stream.is_none() {
+          *stream = Some(TcpStream::connect(address).await?);
+        }
+        if let Err(e) = stream.as_mut().unwrap().write_all(packet).await {
+          // Reconnect on the next send
+          *stream = None;
+          return Err(e);
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Sends a TSL UMD message for every mapped display whenever its fader's label or
+/// on-air state changes, and re-sends all of them every `refreshInterval`.
+pub async fn run(config: TslConfig, onAir: OnAirConfig, mut event_rx: broadcast::Receiver<ConsoleEvent>) -> io::Result<()> {
+  if config.version == TslVersion::V3_1 {
+    if let Some(display) = config.displays.iter().find(|display| display.address > packet::V3_1_MAX_ADDRESS) {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("fader {} is mapped to display {}, v3.1 only has 0-{}", display.fader, display.address, packet::V3_1_MAX_ADDRESS),
+      ));
+    }
+  }
+
+  let mut transport = match config.transport {
+    TslTransport::UDP => Transport::UDP(UdpSocket::bind("0.0.0.0:0").await?),
+    TslTransport::TCP => Transport::TCP(None),
+  };
+  eprintln!("TSL :: sending {:?} over {:?} to {}", config.version, config.transport, config.address);
+
+  let mut states: HashMap<u16, DisplayState> = HashMap::new();
+  let mut refresh = time::interval(Duration::from_millis(config.refreshInterval.max(100)));
+
+  loop {
+    tokio::select! {
+      event = event_rx.recv() => {
+        let fader = match event {
+          Ok(ConsoleEvent::FADER(fader)) => fader,
+          Ok(_) => continue,
+          Err(broadcast::error::RecvError::Lagged(_)) => continue,
+          Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        };
+
+        let state = DisplayState {
+          text: if fader.label.is_empty() { format!("F{}", fader.index + 1) } else { fader.label.clone() },
+          tally: is_on_air(&fader, &onAir),
+        };
+        if states.get(&fader.index) == Some(&state) {
+          continue;
+        }
+
+        for display in config.displays.iter().filter(|display| display.fader == fader.index + 1) {
+          send(&config, &mut transport, display.address, &state).await;
+        }
+        states.insert(fader.index, state);
+      }
+      _ = refresh.tick() => {
+        for display in config.displays.iter() {
+          if let Some(state) = display.fader.checked_sub(1).and_then(|index| states.get(&index)) {
+            send(&config, &mut transport, display.address, state).await;
+          }
+        }
+      }
+    }
+  }
+}
+
+async fn send(config: &TslConfig, transport: &mut Transport, address: u16, state: &DisplayState) {
+  let packet = match config.version {
+    TslVersion::V3_1 => packet::v3_1(address as u8, state.tally, config.brightness, &state.text),
+    TslVersion::V5 => {
+      let packet = packet::v5(config.screen, address, state.tally, config.brightness, &state.text);
+      match transport {
+        Transport::TCP(_) => packet::v5_tcp(&packet),
+        Transport::UDP(_) => packet,
+      }
+    }
+  };
+
+  if let Err(e) = transport.send(&config.address, &packet).await {
+    eprintln!("TSL :: failed to send to {} :: {}", config.address, e);
+  }
+}
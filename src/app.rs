@@ -3,16 +3,38 @@
 use yew::prelude::*;
 
 use crate::components::state::stateManager::*;
+use crate::components::consoles::consoles::*;
 use crate::components::faders::faders::*;
 use crate::components::info::info::*;
+use crate::components::audit::audit::*;
+use crate::components::onair::onair::*;
+use crate::components::inspector::inspector::*;
+use crate::components::catalogue::catalogue::*;
+use crate::components::template::template::*;
+use crate::components::rules::rules::*;
+use crate::components::scripts::scripts::*;
+use crate::components::schedule::schedule::*;
+use crate::components::timecode::timecode::*;
+use crate::components::midi::midi::*;
 
 #[function_component(App)]
 pub fn app() -> Html {
     html! {
         <StateManager>
             <div class="app">
+                <ConsoleSwitcher />
                 <Info />
                 <RenderFaders />
+                <ShowCheck />
+                <Rules />
+                <Scripts />
+                <Schedule />
+                <Timecode />
+                <Midi />
+                <OnAir />
+                <AuditLog />
+                <Inspector />
+                <Catalogue />
             </div>
         </StateManager>
     }
@@ -0,0 +1,141 @@
+use common::{audit::{AuditEntry, AuditQuery}, Source};
+use wasm_bindgen::{prelude::*, JsCast};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+use crate::{components::state::stateManager::StateContext, external::{invoke, log, save}, includes::commands::{QueryAuditLogArgs, ExportAuditLogArgs, SaveDialogOptions}};
+
+const AUDIT_LIMIT: usize = 200;
+
+fn parse_source(value: &str) -> Option<Source> {
+  match value {
+    "CONSOLE" => Some(Source::CONSOLE),
+    "UI" => Some(Source::UI),
+    "API" => Some(Source::API),
+    "AUTOMATION" => Some(Source::AUTOMATION),
+    _ => None,
+  }
+}
+
+fn format_timestamp(timestamp: u64) -> String {
+  let date = js_sys::Date::new(&JsValue::from_f64(timestamp as f64));
+  String::from(date.to_iso_string())
+}
+
+#[function_component(AuditLog)]
+pub fn audit_log() -> Html {
+  let state = use_context::<StateContext>().expect("no state context found");
+  let entries = use_state(Vec::<AuditEntry>::new);
+  let query = use_state(|| AuditQuery { limit: Some(AUDIT_LIMIT), ..AuditQuery::default() });
+
+  let refresh = {
+    let entries = entries.clone();
+    let query = query.clone();
+    Callback::from(move |_| {
+      let entries = entries.clone();
+      let query = (*query).clone();
+      spawn_local(async move {
+        let response = invoke(
+            "queryAuditLog",
+            JsValue::from_serde(&QueryAuditLogArgs { query }).unwrap(),
+        )
+        .await;
+        match JsValue::into_serde::<Vec<AuditEntry>>(&response) {
+          Ok(next) => entries.set(next),
+          Err(e) => log(format!("Audit query failed :: {}", e).as_str()),
+        }
+      });
+    })
+  };
+
+  let export = {
+    let query = query.clone();
+    Callback::from(move |_| {
+      let query = AuditQuery { limit: None, ..(*query).clone() };
+      spawn_local(async move {
+        let path = save(JsValue::from_serde(&SaveDialogOptions { defaultPath: String::from("audit.csv") }).unwrap()).await;
+        if let Some(path) = path.as_string() {
+          invoke(
+              "exportAuditLog",
+              JsValue::from_serde(&ExportAuditLogArgs { query, path }).unwrap(),
+          )
+          .await;
+        }
+      });
+    })
+  };
+
+  let onSourceChange = {
+    let query = query.clone();
+    Callback::from(move |e: Event| {
+      let select: HtmlSelectElement = e.target().unwrap().unchecked_into();
+      query.set(AuditQuery { source: parse_source(&select.value()), ..(*query).clone() });
+    })
+  };
+
+  let onConsoleChange = {
+    let query = query.clone();
+    Callback::from(move |e: Event| {
+      let select: HtmlSelectElement = e.target().unwrap().unchecked_into();
+      let console = Some(select.value()).filter(|console| !console.is_empty());
+      query.set(AuditQuery { console, ..(*query).clone() });
+    })
+  };
+
+  let onIndexChange = {
+    let query = query.clone();
+    Callback::from(move |e: Event| {
+      let input: HtmlInputElement = e.target().unwrap().unchecked_into();
+      // Faders are shown 1-based in the UI
+      let index = input.value().parse::<u16>().ok().and_then(|index| index.checked_sub(1));
+      query.set(AuditQuery { index, ..(*query).clone() });
+    })
+  };
+
+  html!{
+    <div class="audit">
+      <div class="audit__filters">
+        if state.consoles.len() > 1 {
+          <select onchange={onConsoleChange}>
+            <option value="">{"All consoles"}</option>
+            {
+              for state.consoles.iter().map(|console| html!{
+                <option value={console.clone()}>{console}</option>
+              })
+            }
+          </select>
+        }
+        <select onchange={onSourceChange}>
+          <option value="">{"All sources"}</option>
+          <option value="CONSOLE">{"Console"}</option>
+          <option value="UI">{"UI"}</option>
+          <option value="API">{"API"}</option>
+          <option value="AUTOMATION">{"Automation"}</option>
+        </select>
+        <input type="number" min="1" placeholder="Fader" onchange={onIndexChange} />
+        <button type="button" onclick={refresh}>{"REFRESH"}</button>
+        <button type="button" onclick={export}>{"EXPORT CSV"}</button>
+      </div>
+      <table class="audit__entries">
+        {
+          for entries.iter().rev().map(|entry| {
+            html!{
+              <tr>
+                <td>{format_timestamp(entry.timestamp)}</td>
+                if state.consoles.len() > 1 {
+                  <td>{&entry.console}</td>
+                }
+                <td>{format!("{:?}", entry.source)}</td>
+                <td>{format!("{:?} {}", entry.bus, entry.index + 1)}</td>
+                <td>{&entry.parameter}</td>
+                <td>{entry.oldValue.to_string()}</td>
+                <td>{entry.newValue.to_string()}</td>
+              </tr>
+            }
+          })
+        }
+      </table>
+    </div>
+  }
+}
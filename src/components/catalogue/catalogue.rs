@@ -0,0 +1,86 @@
+use common::catalogue::CatalogueEntry;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+
+use crate::{external::{invoke, log, save}, includes::commands::{ExportCatalogueArgs, SaveDialogOptions}};
+
+fn hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<String>>().join(" ")
+}
+
+fn format_timestamp(timestamp: u64) -> String {
+  let date = js_sys::Date::new(&JsValue::from_f64(timestamp as f64));
+  String::from(date.to_iso_string())
+}
+
+#[function_component(Catalogue)]
+pub fn catalogue() -> Html {
+  let entries = use_state(Vec::<CatalogueEntry>::new);
+
+  let refresh = {
+    let entries = entries.clone();
+    Callback::from(move |_| {
+      let entries = entries.clone();
+      spawn_local(async move {
+        let response = invoke("getCatalogue", JsValue::default()).await;
+        match JsValue::into_serde::<Vec<CatalogueEntry>>(&response) {
+          Ok(next) => entries.set(next),
+          Err(e) => log(format!("Catalogue query failed :: {}", e).as_str()),
+        }
+      });
+    })
+  };
+
+  let export = Callback::from(move |_| {
+    spawn_local(async move {
+      let path = save(JsValue::from_serde(&SaveDialogOptions { defaultPath: String::from("catalogue.csv") }).unwrap()).await;
+      if let Some(path) = path.as_string() {
+        invoke(
+            "exportCatalogue",
+            JsValue::from_serde(&ExportCatalogueArgs { path }).unwrap(),
+        )
+        .await;
+      }
+    });
+  });
+
+  html!{
+    <div class="catalogue">
+      <div class="catalogue__controls">
+        <button type="button" onclick={refresh}>{"REFRESH"}</button>
+        <button type="button" onclick={export}>{"EXPORT CSV"}</button>
+      </div>
+      <table class="catalogue__entries">
+        {
+          for entries.iter().map(|entry| {
+            html!{
+              <>
+                <tr class="catalogue__command">
+                  <td>{format!("{:#04x}", entry.command)}</td>
+                  <td>{format!("{:?}", entry.coverage)}</td>
+                  <td>{format!("{} seen", entry.count)}</td>
+                  <td>{format_timestamp(entry.lastSeen)}</td>
+                  <td>{&entry.note}</td>
+                </tr>
+                {
+                  for entry.samples.iter().rev().map(|sample| {
+                    html!{
+                      <tr class="catalogue__sample">
+                        <td></td>
+                        <td>{format!("flags {:#04x}", sample.flags)}</td>
+                        <td class="catalogue__hex">{hex(&sample.payload)}</td>
+                        <td>{format_timestamp(sample.timestamp)}</td>
+                        <td>{sample.context.join(" | ")}</td>
+                      </tr>
+                    }
+                  })
+                }
+              </>
+            }
+          })
+        }
+      </table>
+    </div>
+  }
+}
@@ -0,0 +1,36 @@
+use yew::prelude::*;
+
+use crate::components::state::stateManager::StateContext;
+
+/// Picks the console the other panels show, hidden with a single console.
+/// TSL, OSC, the API and the other services always use the first console.
+#[function_component(ConsoleSwitcher)]
+pub fn consoleSwitcher() -> Html {
+  let state = use_context::<StateContext>().expect("no state context found");
+
+  if state.consoles.len() < 2 {
+    return html!{};
+  }
+
+  html!{
+    <div class="consoles">
+      {
+        for state.consoles.iter().map(|console| {
+          let mut classes = classes!("consoles__console");
+          if *console == state.console {
+            classes.push("consoles__console__active");
+          }
+          let onclick = {
+            let selectConsole = state.selectConsole.clone();
+            let console = console.clone();
+            Callback::from(move |_| selectConsole.emit(console.clone()))
+          };
+          html!{
+            <button class={classes} {onclick}>{console}</button>
+          }
+        })
+      }
+      <span class="consoles__note">{format!("Services run on {}", state.consoles[0])}</span>
+    </div>
+  }
+}
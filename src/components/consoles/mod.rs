@@ -0,0 +1 @@
+pub mod consoles;
\ No newline at end of file
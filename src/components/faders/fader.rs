@@ -1,9 +1,9 @@
 use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::spawn_local;
 use yew::prelude::*;
-use common::{Fader, AudioType};
+use common::{Fader, AudioType, capability::{FADER_CUT, FADER_LEVEL, FADER_PFL}};
 
-use crate::{external::invoke, includes::commands::{SetFaderLevelArgs, SetFaderCutArgs, SetFaderPflArgs}};
+use crate::{components::state::stateManager::StateContext, external::invoke, includes::commands::{SetFaderLevelArgs, SetFaderCutArgs, SetFaderPflArgs}};
 
 #[derive(Properties, PartialEq)]
 pub struct RenderFaderProps {
@@ -12,13 +12,16 @@ pub struct RenderFaderProps {
 
 #[function_component(RenderFader)]
 pub fn fader(props: &RenderFaderProps) -> Html {
+  let state = use_context::<StateContext>().expect("no state context found");
   let index = props.fader.index;
   let setFaderHigh = {
+    let console = state.console.clone();
       Callback::from(move |_| {
+          let console = console.clone();
           spawn_local(async move {
               invoke(
                   "setFaderLevel",
-                  JsValue::from_serde(&SetFaderLevelArgs { index, level: 1023 }).unwrap(),
+                  JsValue::from_serde(&SetFaderLevelArgs { console, index, level: 1023 }).unwrap(),
               )
               .await;
           });
@@ -26,11 +29,13 @@ pub fn fader(props: &RenderFaderProps) -> Html {
   };
 
   let setFaderLow = {
+    let console = state.console.clone();
       Callback::from(move |_| {
+          let console = console.clone();
           spawn_local(async move {
               invoke(
                   "setFaderLevel",
-                  JsValue::from_serde(&SetFaderLevelArgs { index, level: 0 }).unwrap(),
+                  JsValue::from_serde(&SetFaderLevelArgs { console, index, level: 0 }).unwrap(),
               )
               .await;
           });
@@ -39,11 +44,13 @@ pub fn fader(props: &RenderFaderProps) -> Html {
 
   let toggleFaderCut = {
     let isCut = props.fader.isCut;
+    let console = state.console.clone();
       Callback::from(move |_| {
+          let console = console.clone();
           spawn_local(async move {
               invoke(
                   "setFaderCut",
-                  JsValue::from_serde(&SetFaderCutArgs { index, isCut: !isCut }).unwrap(),
+                  JsValue::from_serde(&SetFaderCutArgs { console, index, isCut: !isCut }).unwrap(),
               )
               .await;
           });
@@ -52,11 +59,13 @@ pub fn fader(props: &RenderFaderProps) -> Html {
 
   let toggleFaderPfl = {
     let isPfl = props.fader.isPfl;
+    let console = state.console.clone();
       Callback::from(move |_| {
+          let console = console.clone();
           spawn_local(async move {
               invoke(
                   "setFaderPfl",
-                  JsValue::from_serde(&SetFaderPflArgs { index, isPfl: !isPfl }).unwrap(),
+                  JsValue::from_serde(&SetFaderPflArgs { console, index, isPfl: !isPfl }).unwrap(),
               )
               .await;
           });
@@ -81,10 +90,12 @@ pub fn fader(props: &RenderFaderProps) -> Html {
         <p>{&props.fader.label}</p>
         <p>{&props.fader.level}</p>
         <div class="fader__controls">
-          <button type="button" onclick={setFaderHigh}>{"HIGH"}</button>
-          <button type="button" onclick={setFaderLow}>{"LOW"}</button>
-          <button type="button" class={pflButtonClasses} onclick={toggleFaderPfl}>{"PFL"}</button>
-          if !matches!(props.fader.pathType, AudioType::MN) { <button type="button" class={cutButtonClasses} onclick={toggleFaderCut}>{"CUT"}</button>} 
+          if state.deskInfo.supports(FADER_LEVEL) {
+            <button type="button" onclick={setFaderHigh}>{"HIGH"}</button>
+            <button type="button" onclick={setFaderLow}>{"LOW"}</button>
+          }
+          if state.deskInfo.supports(FADER_PFL) { <button type="button" class={pflButtonClasses} onclick={toggleFaderPfl}>{"PFL"}</button> }
+          if !matches!(props.fader.pathType, AudioType::MN) && state.deskInfo.supports(FADER_CUT) { <button type="button" class={cutButtonClasses} onclick={toggleFaderCut}>{"CUT"}</button>} 
         </div>
       </div>
   }
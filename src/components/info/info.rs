@@ -1,3 +1,4 @@
+use common::{capability::command_name, link::LinkHealth};
 use yew::prelude::*;
 
 use crate::{components::state::stateManager::StateContext};
@@ -11,6 +12,32 @@ pub fn info() -> Html {
     <div class="desk_info">
       <p>{format!("CSCP Version {}", state.deskInfo.cscpVersion)}</p>
       <p>{format!("Name {}", &state.deskInfo.name)}</p>
+      if state.link.connected {
+        <p>{format!("Connected to {} ({})", &state.link.endpoint, state.link.role())}</p>
+        if state.link.roundTrip.samples > 0 {
+          <p>{format!("Round trip {:.1} ms (avg {:.1}, max {:.1})", state.link.roundTrip.last, state.link.roundTrip.average, state.link.roundTrip.max)}</p>
+        }
+      } else {
+        <p class="desk_info__disconnected">{"Not connected"}</p>
+      }
+      {
+        match state.link.health {
+          LinkHealth::GOOD => html!{},
+          LinkHealth::DEGRADED => html!{
+            <p class="desk_info__warning">{format!("Link degraded, {} heartbeats unanswered", state.link.missed)}</p>
+          },
+          LinkHealth::LOST => html!{
+            <p class="desk_info__warning">{format!("Link to {} lost, console stopped answering", &state.link.endpoint)}</p>
+          },
+        }
+      }
+      {
+        for state.deskInfo.capabilities.iter().filter(|capability| !capability.result.is_supported()).map(|capability| {
+          html!{
+            <p class="desk_info__unsupported">{format!("No {} ({:?})", command_name(capability.command), capability.result)}</p>
+          }
+        })
+      }
     </div>
   }
 }
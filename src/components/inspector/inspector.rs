@@ -0,0 +1,180 @@
+use std::{collections::HashMap, rc::Rc};
+
+use common::inspector::{CapturedFrame, Direction, FrameKind};
+use wasm_bindgen::{prelude::*, JsCast};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+use crate::{external::{invoke, listen, log}, includes::{commands::GetInspectorFramesArgs, events::InspectorFrameEvent}};
+
+/// Frames kept in the panel, the backend keeps its own, larger, buffer
+const INSPECTOR_LIMIT: usize = 1000;
+
+struct FramesState {
+  frames: Vec<CapturedFrame>,
+}
+
+enum FramesAction {
+  APPEND(CapturedFrame),
+  REPLACE(Vec<CapturedFrame>),
+  CLEAR,
+}
+
+impl Default for FramesState {
+  fn default() -> Self {
+      Self { frames: vec![] }
+  }
+}
+
+impl Reducible for FramesState {
+  type Action = FramesAction;
+
+  fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+      let next_frames = match action {
+        FramesAction::APPEND(frame) => {
+          let skip = (self.frames.len() + 1).saturating_sub(INSPECTOR_LIMIT);
+          let mut nextFrames: Vec<CapturedFrame> = self.frames.iter().skip(skip).cloned().collect();
+          nextFrames.push(frame);
+          nextFrames
+        }
+        FramesAction::REPLACE(frames) => {
+          let skip = frames.len().saturating_sub(INSPECTOR_LIMIT);
+          frames.into_iter().skip(skip).collect()
+        }
+        FramesAction::CLEAR => vec![],
+      };
+
+      Self { frames: next_frames }.into()
+  }
+}
+
+/// Accepts "0B", "0x0B" or "0x0b"
+fn parse_command(value: &str) -> Option<u8> {
+  let value = value.trim();
+  let value = value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")).unwrap_or(value);
+  u8::from_str_radix(value, 16).ok()
+}
+
+fn hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<String>>().join(" ")
+}
+
+fn format_time(timestamp: u64) -> String {
+  let date = js_sys::Date::new(&JsValue::from_f64(timestamp as f64));
+  format!("{:02}:{:02}:{:02}.{:03}", date.get_hours(), date.get_minutes(), date.get_seconds(), date.get_milliseconds())
+}
+
+fn load(frames: UseReducerHandle<FramesState>, command: Option<u8>) {
+  spawn_local(async move {
+    let response = invoke(
+        "getInspectorFrames",
+        JsValue::from_serde(&GetInspectorFramesArgs { command }).unwrap(),
+    )
+    .await;
+    match JsValue::into_serde::<Vec<CapturedFrame>>(&response) {
+      Ok(next) => frames.dispatch(FramesAction::REPLACE(next)),
+      Err(e) => log(format!("Inspector query failed :: {}", e).as_str()),
+    }
+  });
+}
+
+#[function_component(Inspector)]
+pub fn inspector() -> Html {
+  let frames = use_reducer(FramesState::default);
+  let command = use_state(|| Option::<u8>::None);
+
+  let handler_frames = frames.clone();
+  let frame_handler_ref = use_ref(|| Closure::new(move |ev: JsValue| {
+      match JsValue::into_serde::<InspectorFrameEvent>(&ev) {
+        Ok(frame_event) => handler_frames.dispatch(FramesAction::APPEND(frame_event.payload)),
+        Err(e) => log(format!("Bad inspector frame :: {}", e).as_str()),
+      }
+  }));
+
+  {
+    let frames = frames.clone();
+    use_effect_with_deps(move |_| {
+        spawn_local(async move {
+            listen("inspector::frame", &frame_handler_ref).await;
+        });
+        load(frames, None);
+
+        || {}
+    }, 0);
+  }
+
+  let refresh = {
+    let frames = frames.clone();
+    let command = command.clone();
+    Callback::from(move |_| load(frames.clone(), *command))
+  };
+
+  let clear = {
+    let frames = frames.clone();
+    Callback::from(move |_| {
+      let frames = frames.clone();
+      spawn_local(async move {
+        invoke("clearInspector", JsValue::default()).await;
+        frames.dispatch(FramesAction::CLEAR);
+      });
+    })
+  };
+
+  let onCommandChange = {
+    let command = command.clone();
+    Callback::from(move |e: Event| {
+      let input: HtmlInputElement = e.target().unwrap().unchecked_into();
+      command.set(parse_command(&input.value()));
+    })
+  };
+
+  // Written frames and the ACK/ERR the console answered them with
+  let replies: HashMap<u64, &CapturedFrame> = frames.frames.iter()
+    .filter_map(|frame| frame.replyTo.map(|id| (id, frame)))
+    .collect();
+  let matches = |frame: &CapturedFrame| match *command {
+    None => true,
+    Some(command) => frame.command == Some(command) || frame.replyTo.map_or(false, |id| {
+      frames.frames.iter().any(|sent| sent.id == id && sent.command == Some(command))
+    }),
+  };
+
+  html!{
+    <div class="inspector">
+      <div class="inspector__controls">
+        <input type="text" placeholder="Command byte, e.g. 0x00" onchange={onCommandChange} />
+        <button type="button" onclick={refresh}>{"REFRESH"}</button>
+        <button type="button" onclick={clear}>{"CLEAR"}</button>
+      </div>
+      <table class="inspector__frames">
+        {
+          for frames.frames.iter().rev().filter(|frame| matches(frame)).map(|frame| {
+            let reply = match (frame.kind, frame.replyTo) {
+              (FrameKind::ACK | FrameKind::ERR, Some(id)) => format!("answers #{}", id),
+              (FrameKind::MSG, _) if frame.direction == Direction::TX => match replies.get(&frame.id) {
+                Some(reply) => format!("{} #{}", reply.description, reply.id),
+                None => String::from("waiting"),
+              },
+              _ => String::new(),
+            };
+            let class = classes!(
+              "inspector__frame",
+              (frame.kind == FrameKind::ERR).then(|| "inspector__frame--error"),
+            );
+            html!{
+              <tr class={class}>
+                <td>{format_time(frame.timestamp)}</td>
+                <td>{format!("#{}", frame.id)}</td>
+                <td>{format!("{:?}", frame.direction)}</td>
+                <td class="inspector__hex">{hex(&frame.bytes)}</td>
+                <td>{&frame.description}</td>
+                <td>{reply}</td>
+              </tr>
+            }
+          })
+        }
+      </table>
+    </div>
+  }
+}
@@ -0,0 +1,242 @@
+use common::midi::{MidiMapping, MidiStatus, MidiTarget};
+use wasm_bindgen::{prelude::*, JsCast};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{HtmlInputElement, HtmlSelectElement, HtmlTextAreaElement};
+use yew::prelude::*;
+
+use crate::{external::{invoke, log, try_invoke}, includes::commands::{LearnMidiArgs, SaveMidiMappingsArgs}};
+
+/// Mappings as indented JSON for editing
+fn to_text(mappings: &JsValue) -> String {
+  js_sys::JSON::stringify_with_replacer_and_space(mappings, &JsValue::NULL, &JsValue::from(2))
+    .ok()
+    .and_then(|text| text.as_string())
+    .unwrap_or_default()
+}
+
+fn parse(text: &str) -> Result<Vec<MidiMapping>, String> {
+  let value = js_sys::JSON::parse(text)
+    .map_err(|e| js_sys::Error::from(e).message().as_string().unwrap_or_else(|| String::from("Not JSON")))?;
+  JsValue::into_serde::<Vec<MidiMapping>>(&value).map_err(|e| e.to_string())
+}
+
+fn load_mappings(text: UseStateHandle<String>, mappings: UseStateHandle<Vec<MidiMapping>>) {
+  spawn_local(async move {
+    let response = invoke("getMidiMappings", JsValue::default()).await;
+    text.set(to_text(&response));
+    match JsValue::into_serde::<Vec<MidiMapping>>(&response) {
+      Ok(next) => mappings.set(next),
+      Err(e) => log(format!("MIDI mappings failed :: {}", e).as_str()),
+    }
+  });
+}
+
+fn load_status(status: UseStateHandle<Option<MidiStatus>>) {
+  spawn_local(async move {
+    let response = invoke("getMidiStatus", JsValue::default()).await;
+    match JsValue::into_serde::<MidiStatus>(&response) {
+      Ok(next) => status.set(Some(next)),
+      Err(e) => log(format!("MIDI status failed :: {}", e).as_str()),
+    }
+  });
+}
+
+/// The MIDI surface's ports, what its controls move and LEARN to map them by moving them
+#[function_component(Midi)]
+pub fn midi() -> Html {
+  let text = use_state(String::new);
+  let mappings = use_state(Vec::<MidiMapping>::new);
+  let status = use_state(|| Option::<MidiStatus>::None);
+  let fader = use_state(|| String::from("1"));
+  let kind = use_state(|| String::from("LEVEL"));
+  let learning = use_state(|| false);
+  let notice = use_state(|| Option::<String>::None);
+  let error = use_state(|| Option::<String>::None);
+
+  {
+    let text = text.clone();
+    let mappings = mappings.clone();
+    let status = status.clone();
+    use_effect_with_deps(move |_| {
+        load_mappings(text, mappings);
+        load_status(status);
+
+        || {}
+    }, 0);
+  }
+
+  let onInput = {
+    let text = text.clone();
+    Callback::from(move |e: InputEvent| {
+      let input: HtmlTextAreaElement = e.target().unwrap().unchecked_into();
+      text.set(input.value());
+    })
+  };
+
+  let onFaderInput = {
+    let fader = fader.clone();
+    Callback::from(move |e: InputEvent| {
+      let input: HtmlInputElement = e.target().unwrap().unchecked_into();
+      fader.set(input.value());
+    })
+  };
+
+  let onKindChange = {
+    let kind = kind.clone();
+    Callback::from(move |e: Event| {
+      let select: HtmlSelectElement = e.target().unwrap().unchecked_into();
+      kind.set(select.value());
+    })
+  };
+
+  let learn = {
+    let text = text.clone();
+    let mappings = mappings.clone();
+    let fader = fader.clone();
+    let kind = kind.clone();
+    let learning = learning.clone();
+    let notice = notice.clone();
+    let error = error.clone();
+    Callback::from(move |_| {
+      let fader = fader.trim().to_string();
+      let target = match kind.as_str() {
+        "CUT" => MidiTarget::FADER_CUT { fader },
+        "PFL" => MidiTarget::FADER_PFL { fader },
+        _ => MidiTarget::FADER_LEVEL { fader },
+      };
+      let text = text.clone();
+      let mappings = mappings.clone();
+      let learning = learning.clone();
+      let notice = notice.clone();
+      let error = error.clone();
+      learning.set(true);
+      error.set(None);
+      notice.set(None);
+      spawn_local(async move {
+        let response = try_invoke(
+            "learnMidi",
+            JsValue::from_serde(&LearnMidiArgs { target }).unwrap(),
+        )
+        .await;
+        learning.set(false);
+        match response.map(|response| JsValue::into_serde::<MidiMapping>(&response)) {
+          Ok(Ok(mapping)) => {
+            notice.set(Some(format!("{} now moves {}, SAVE MAPPINGS to keep it", mapping.control, mapping.target)));
+            load_mappings(text, mappings);
+          }
+          Ok(Err(e)) => error.set(Some(e.to_string())),
+          Err(e) => error.set(Some(e.as_string().unwrap_or_else(|| String::from("Learning failed")))),
+        }
+      });
+    })
+  };
+
+  let cancel = Callback::from(move |_| {
+    spawn_local(async move {
+      invoke("cancelMidiLearn", JsValue::default()).await;
+    });
+  });
+
+  let save = {
+    let text = text.clone();
+    let mappings = mappings.clone();
+    let notice = notice.clone();
+    let error = error.clone();
+    Callback::from(move |_| {
+      let next = match parse(&text) {
+        Ok(next) => next,
+        Err(e) => return error.set(Some(e)),
+      };
+      let mappings = mappings.clone();
+      let notice = notice.clone();
+      let error = error.clone();
+      spawn_local(async move {
+        let response = try_invoke(
+            "saveMidiMappings",
+            JsValue::from_serde(&SaveMidiMappingsArgs { mappings: next }).unwrap(),
+        )
+        .await;
+        match response.map(|response| JsValue::into_serde::<Vec<MidiMapping>>(&response)) {
+          Ok(Ok(saved)) => {
+            mappings.set(saved);
+            notice.set(None);
+            error.set(None);
+          }
+          Ok(Err(e)) => error.set(Some(e.to_string())),
+          Err(e) => error.set(Some(e.as_string().unwrap_or_else(|| String::from("Saving the mappings failed")))),
+        }
+      });
+    })
+  };
+
+  let revert = {
+    let text = text.clone();
+    let mappings = mappings.clone();
+    let error = error.clone();
+    Callback::from(move |_| {
+      error.set(None);
+      load_mappings(text.clone(), mappings.clone());
+    })
+  };
+
+  let refresh = {
+    let status = status.clone();
+    Callback::from(move |_| load_status(status.clone()))
+  };
+
+  html!{
+    <div class="midi">
+      if let Some(status) = (*status).as_ref() {
+        <p>
+          {format!(
+            "MIDI in: {}  out: {}",
+            status.input.clone().unwrap_or_else(|| String::from("none")),
+            status.output.clone().unwrap_or_else(|| String::from("none")),
+          )}
+        </p>
+        if let Some(portError) = &status.error {
+          <p class="midi__error">{portError}</p>
+        }
+      }
+      <div class="midi__controls">
+        <input type="text" size="8" value={(*fader).clone()} oninput={onFaderInput} />
+        <select onchange={onKindChange}>
+          <option value="LEVEL">{"LEVEL"}</option>
+          <option value="CUT">{"CUT"}</option>
+          <option value="PFL">{"PFL"}</option>
+        </select>
+        if *learning {
+          <span class="midi__learning">{"Move a control on the surface"}</span>
+          <button type="button" onclick={cancel}>{"CANCEL"}</button>
+        } else {
+          <button type="button" onclick={learn}>{"LEARN"}</button>
+        }
+        <button type="button" onclick={refresh}>{"REFRESH"}</button>
+      </div>
+      if let Some(notice) = (*notice).as_ref() {
+        <p>{notice}</p>
+      }
+      <table class="midi__mappings">
+        {
+          for mappings.iter().map(|mapping| {
+            html!{
+              <tr>
+                <td>{mapping.control.to_string()}</td>
+                <td>{mapping.target.to_string()}</td>
+                <td>{if mapping.momentary { "momentary" } else { "" }}</td>
+              </tr>
+            }
+          })
+        }
+      </table>
+      <textarea class="midi__editor" rows="10" spellcheck="false" value={(*text).clone()} oninput={onInput} />
+      <div class="midi__controls">
+        <button type="button" onclick={save}>{"SAVE MAPPINGS"}</button>
+        <button type="button" onclick={revert}>{"REVERT"}</button>
+      </div>
+      if let Some(error) = (*error).as_ref() {
+        <p class="midi__error">{error}</p>
+      }
+    </div>
+  }
+}
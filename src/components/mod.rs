@@ -1,3 +1,14 @@
 pub mod state;
+pub mod consoles;
 pub mod faders;
-pub mod info;
\ No newline at end of file
+pub mod info;
+pub mod audit;
+pub mod onair;
+pub mod inspector;
+pub mod catalogue;
+pub mod template;
+pub mod rules;
+pub mod scripts;
+pub mod schedule;
+pub mod timecode;
+pub mod midi;
\ No newline at end of file
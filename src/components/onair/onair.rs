@@ -0,0 +1,108 @@
+use common::onair::OnAirReport;
+use wasm_bindgen::{prelude::*, JsCast};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlSelectElement;
+use yew::prelude::*;
+
+use crate::{external::{invoke, log, save}, includes::commands::{OnAirWindowArgs, ExportOnAirArgs, SaveDialogOptions}};
+
+const HOUR: u64 = 60 * 60 * 1000;
+
+fn window_args(hours: u64) -> OnAirWindowArgs {
+  let to = js_sys::Date::now() as u64;
+  OnAirWindowArgs { from: to.saturating_sub(hours * HOUR), to }
+}
+
+fn format_duration(millis: u64) -> String {
+  let seconds = millis / 1000;
+  format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+fn export(command: &'static str, defaultPath: &'static str, hours: u64) {
+  spawn_local(async move {
+    let path = save(JsValue::from_serde(&SaveDialogOptions { defaultPath: String::from(defaultPath) }).unwrap()).await;
+    if let Some(path) = path.as_string() {
+      let OnAirWindowArgs { from, to } = window_args(hours);
+      invoke(
+          command,
+          JsValue::from_serde(&ExportOnAirArgs { from, to, path }).unwrap(),
+      )
+      .await;
+    }
+  });
+}
+
+#[function_component(OnAir)]
+pub fn on_air() -> Html {
+  let report = use_state(|| Option::<OnAirReport>::None);
+  let hours = use_state(|| 1u64);
+
+  let refresh = {
+    let report = report.clone();
+    let hours = hours.clone();
+    Callback::from(move |_| {
+      let report = report.clone();
+      let args = window_args(*hours);
+      spawn_local(async move {
+        let response = invoke(
+            "getOnAirReport",
+            JsValue::from_serde(&args).unwrap(),
+        )
+        .await;
+        match JsValue::into_serde::<OnAirReport>(&response) {
+          Ok(next) => report.set(Some(next)),
+          Err(e) => log(format!("On air report failed :: {}", e).as_str()),
+        }
+      });
+    })
+  };
+
+  let exportReport = {
+    let hours = hours.clone();
+    Callback::from(move |_| export("exportOnAirReport", "on-air.csv", *hours))
+  };
+
+  let exportAsRun = {
+    let hours = hours.clone();
+    Callback::from(move |_| export("exportAsRunLog", "as-run.csv", *hours))
+  };
+
+  let onWindowChange = {
+    let hours = hours.clone();
+    Callback::from(move |e: Event| {
+      let select: HtmlSelectElement = e.target().unwrap().unchecked_into();
+      hours.set(select.value().parse().unwrap_or(1));
+    })
+  };
+
+  html!{
+    <div class="onair">
+      <div class="onair__controls">
+        <select onchange={onWindowChange}>
+          <option value="1">{"Last hour"}</option>
+          <option value="8">{"Last 8 hours"}</option>
+          <option value="24">{"Last 24 hours"}</option>
+        </select>
+        <button type="button" onclick={refresh}>{"REPORT"}</button>
+        <button type="button" onclick={exportReport}>{"EXPORT REPORT"}</button>
+        <button type="button" onclick={exportAsRun}>{"EXPORT AS-RUN"}</button>
+      </div>
+      if let Some(report) = (*report).as_ref() {
+        <table class="onair__channels">
+          {
+            for report.channels.iter().map(|channel| {
+              html!{
+                <tr>
+                  <td>{format!("F{}", channel.index + 1)}</td>
+                  <td>{&channel.label}</td>
+                  <td>{format_duration(channel.duration)}</td>
+                  <td>{channel.count}</td>
+                </tr>
+              }
+            })
+          }
+        </table>
+      }
+    </div>
+  }
+}
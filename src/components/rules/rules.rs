@@ -0,0 +1,196 @@
+use std::rc::Rc;
+
+use common::rules::{Rule, RuleAlert, RuleStatus};
+use wasm_bindgen::{prelude::*, JsCast};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlTextAreaElement;
+use yew::prelude::*;
+
+use crate::{external::{invoke, listen, log, try_invoke}, includes::{commands::SaveRulesArgs, events::RuleAlertEvent}};
+
+/// Alerts kept in the panel
+const ALERT_LIMIT: usize = 20;
+
+#[derive(Default)]
+struct AlertsState {
+  alerts: Vec<RuleAlert>,
+}
+
+impl Reducible for AlertsState {
+  type Action = RuleAlert;
+
+  fn reduce(self: Rc<Self>, alert: Self::Action) -> Rc<Self> {
+      let skip = (self.alerts.len() + 1).saturating_sub(ALERT_LIMIT);
+      let mut alerts: Vec<RuleAlert> = self.alerts.iter().skip(skip).cloned().collect();
+      alerts.push(alert);
+      Self { alerts }.into()
+  }
+}
+
+fn format_time(timestamp: u64) -> String {
+  let date = js_sys::Date::new(&JsValue::from_f64(timestamp as f64));
+  format!("{:02}:{:02}:{:02}", date.get_hours(), date.get_minutes(), date.get_seconds())
+}
+
+/// Rules as indented JSON for editing
+fn to_text(rules: &JsValue) -> String {
+  js_sys::JSON::stringify_with_replacer_and_space(rules, &JsValue::NULL, &JsValue::from(2))
+    .ok()
+    .and_then(|text| text.as_string())
+    .unwrap_or_default()
+}
+
+fn parse(text: &str) -> Result<Vec<Rule>, String> {
+  let value = js_sys::JSON::parse(text)
+    .map_err(|e| js_sys::Error::from(e).message().as_string().unwrap_or_else(|| String::from("Not JSON")))?;
+  JsValue::into_serde::<Vec<Rule>>(&value).map_err(|e| e.to_string())
+}
+
+fn load_rules(text: UseStateHandle<String>) {
+  spawn_local(async move {
+    let response = invoke("getRules", JsValue::default()).await;
+    text.set(to_text(&response));
+  });
+}
+
+fn load_status(status: UseStateHandle<Vec<RuleStatus>>) {
+  spawn_local(async move {
+    let response = invoke("getRuleStatus", JsValue::default()).await;
+    match JsValue::into_serde::<Vec<RuleStatus>>(&response) {
+      Ok(next) => status.set(next),
+      Err(e) => log(format!("Rule status failed :: {}", e).as_str()),
+    }
+  });
+}
+
+/// Rules from the config, where each of them stands and the alerts they raised
+#[function_component(Rules)]
+pub fn rules() -> Html {
+  let text = use_state(String::new);
+  let status = use_state(Vec::<RuleStatus>::new);
+  let error = use_state(|| Option::<String>::None);
+  let alerts = use_reducer(AlertsState::default);
+
+  let handler_alerts = alerts.clone();
+  let handler_status = status.clone();
+  let alert_handler_ref = use_ref(|| Closure::new(move |ev: JsValue| {
+      match JsValue::into_serde::<RuleAlertEvent>(&ev) {
+        Ok(alert_event) => {
+          handler_alerts.dispatch(alert_event.payload);
+          load_status(handler_status.clone());
+        }
+        Err(e) => log(format!("Bad rule alert :: {}", e).as_str()),
+      }
+  }));
+
+  {
+    let text = text.clone();
+    let status = status.clone();
+    use_effect_with_deps(move |_| {
+        spawn_local(async move {
+            listen("rule::alert", &alert_handler_ref).await;
+        });
+        load_rules(text);
+        load_status(status);
+
+        || {}
+    }, 0);
+  }
+
+  let onInput = {
+    let text = text.clone();
+    Callback::from(move |e: InputEvent| {
+      let input: HtmlTextAreaElement = e.target().unwrap().unchecked_into();
+      text.set(input.value());
+    })
+  };
+
+  let save = {
+    let text = text.clone();
+    let status = status.clone();
+    let error = error.clone();
+    Callback::from(move |_| {
+      let rules = match parse(&text) {
+        Ok(rules) => rules,
+        Err(e) => return error.set(Some(e)),
+      };
+      let status = status.clone();
+      let error = error.clone();
+      spawn_local(async move {
+        let response = try_invoke(
+            "saveRules",
+            JsValue::from_serde(&SaveRulesArgs { rules }).unwrap(),
+        )
+        .await;
+        match response.map(|response| JsValue::into_serde::<Vec<RuleStatus>>(&response)) {
+          Ok(Ok(next)) => {
+            status.set(next);
+            error.set(None);
+          }
+          Ok(Err(e)) => error.set(Some(e.to_string())),
+          Err(e) => error.set(Some(e.as_string().unwrap_or_else(|| String::from("Saving the rules failed")))),
+        }
+      });
+    })
+  };
+
+  let revert = {
+    let text = text.clone();
+    let error = error.clone();
+    Callback::from(move |_| {
+      error.set(None);
+      load_rules(text.clone());
+    })
+  };
+
+  let refresh = {
+    let status = status.clone();
+    Callback::from(move |_| load_status(status.clone()))
+  };
+
+  html!{
+    <div class="rules">
+      <table class="rules__status">
+        {
+          for status.iter().map(|rule| {
+            let (class, state) = if let Some(error) = &rule.error {
+              ("rules__broken", error.clone())
+            } else if rule.suspended {
+              ("rules__broken", String::from("suspended"))
+            } else if !rule.enabled {
+              ("rules__disabled", String::from("disabled"))
+            } else if rule.active {
+              ("rules__active", String::from("active"))
+            } else {
+              ("", String::from("waiting"))
+            };
+            html!{
+              <tr class={class}>
+                <td>{&rule.name}</td>
+                <td>{state}</td>
+                <td>{format!("fired {}", rule.firings)}</td>
+                <td>{rule.lastFired.map(format_time).unwrap_or_default()}</td>
+              </tr>
+            }
+          })
+        }
+      </table>
+      <textarea class="rules__editor" rows="12" spellcheck="false" value={(*text).clone()} oninput={onInput} />
+      <div class="rules__controls">
+        <button type="button" onclick={save}>{"SAVE RULES"}</button>
+        <button type="button" onclick={revert}>{"REVERT"}</button>
+        <button type="button" onclick={refresh}>{"REFRESH"}</button>
+      </div>
+      if let Some(error) = (*error).as_ref() {
+        <p class="rules__error">{error}</p>
+      }
+      {
+        for alerts.alerts.iter().rev().map(|alert| {
+          html!{
+            <p class="rules__alert">{format!("{} {} :: {}", format_time(alert.timestamp), &alert.rule, &alert.message)}</p>
+          }
+        })
+      }
+    </div>
+  }
+}
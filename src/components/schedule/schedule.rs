@@ -0,0 +1,205 @@
+use common::schedule::{ScheduleEntry, ScheduleStatus, ScheduledRun};
+use wasm_bindgen::{prelude::*, JsCast};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlTextAreaElement;
+use yew::prelude::*;
+
+use crate::{external::{invoke, log, try_invoke}, includes::commands::{PreviewScheduleArgs, SaveScheduleArgs}};
+
+/// How far ahead PREVIEW looks
+const PREVIEW_HOURS: u32 = 24;
+
+fn format_date(timestamp: u64) -> String {
+  let date = js_sys::Date::new(&JsValue::from_f64(timestamp as f64));
+  format!(
+    "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+    date.get_full_year(), date.get_month() + 1, date.get_date(),
+    date.get_hours(), date.get_minutes(), date.get_seconds(),
+  )
+}
+
+/// Entries as indented JSON for editing
+fn to_text(entries: &JsValue) -> String {
+  js_sys::JSON::stringify_with_replacer_and_space(entries, &JsValue::NULL, &JsValue::from(2))
+    .ok()
+    .and_then(|text| text.as_string())
+    .unwrap_or_default()
+}
+
+fn parse(text: &str) -> Result<Vec<ScheduleEntry>, String> {
+  let value = js_sys::JSON::parse(text)
+    .map_err(|e| js_sys::Error::from(e).message().as_string().unwrap_or_else(|| String::from("Not JSON")))?;
+  JsValue::into_serde::<Vec<ScheduleEntry>>(&value).map_err(|e| e.to_string())
+}
+
+fn load_schedule(text: UseStateHandle<String>) {
+  spawn_local(async move {
+    let response = invoke("getSchedule", JsValue::default()).await;
+    text.set(to_text(&response));
+  });
+}
+
+fn load_status(status: UseStateHandle<Vec<ScheduleStatus>>) {
+  spawn_local(async move {
+    let response = invoke("getScheduleStatus", JsValue::default()).await;
+    match JsValue::into_serde::<Vec<ScheduleStatus>>(&response) {
+      Ok(next) => status.set(next),
+      Err(e) => log(format!("Schedule status failed :: {}", e).as_str()),
+    }
+  });
+}
+
+/// The schedule from the config, when each entry runs next and a dry run of the coming day
+#[function_component(Schedule)]
+pub fn schedule() -> Html {
+  let text = use_state(String::new);
+  let status = use_state(Vec::<ScheduleStatus>::new);
+  let preview = use_state(|| Option::<Vec<ScheduledRun>>::None);
+  let error = use_state(|| Option::<String>::None);
+
+  {
+    let text = text.clone();
+    let status = status.clone();
+    use_effect_with_deps(move |_| {
+        load_schedule(text);
+        load_status(status);
+
+        || {}
+    }, 0);
+  }
+
+  let onInput = {
+    let text = text.clone();
+    Callback::from(move |e: InputEvent| {
+      let input: HtmlTextAreaElement = e.target().unwrap().unchecked_into();
+      text.set(input.value());
+    })
+  };
+
+  let save = {
+    let text = text.clone();
+    let status = status.clone();
+    let error = error.clone();
+    Callback::from(move |_| {
+      let entries = match parse(&text) {
+        Ok(entries) => entries,
+        Err(e) => return error.set(Some(e)),
+      };
+      let status = status.clone();
+      let error = error.clone();
+      spawn_local(async move {
+        let response = try_invoke(
+            "saveSchedule",
+            JsValue::from_serde(&SaveScheduleArgs { entries }).unwrap(),
+        )
+        .await;
+        match response.map(|response| JsValue::into_serde::<Vec<ScheduleStatus>>(&response)) {
+          Ok(Ok(next)) => {
+            status.set(next);
+            error.set(None);
+          }
+          Ok(Err(e)) => error.set(Some(e.to_string())),
+          Err(e) => error.set(Some(e.as_string().unwrap_or_else(|| String::from("Saving the schedule failed")))),
+        }
+      });
+    })
+  };
+
+  // Previews what is in the editor, saved or not
+  let dryRun = {
+    let text = text.clone();
+    let preview = preview.clone();
+    let error = error.clone();
+    Callback::from(move |_| {
+      let entries = match parse(&text) {
+        Ok(entries) => entries,
+        Err(e) => return error.set(Some(e)),
+      };
+      let preview = preview.clone();
+      let error = error.clone();
+      spawn_local(async move {
+        let response = try_invoke(
+            "previewSchedule",
+            JsValue::from_serde(&PreviewScheduleArgs { entries, hours: PREVIEW_HOURS }).unwrap(),
+        )
+        .await;
+        match response.map(|response| JsValue::into_serde::<Vec<ScheduledRun>>(&response)) {
+          Ok(Ok(runs)) => {
+            preview.set(Some(runs));
+            error.set(None);
+          }
+          Ok(Err(e)) => error.set(Some(e.to_string())),
+          Err(e) => error.set(Some(e.as_string().unwrap_or_else(|| String::from("Previewing the schedule failed")))),
+        }
+      });
+    })
+  };
+
+  let revert = {
+    let text = text.clone();
+    let preview = preview.clone();
+    let error = error.clone();
+    Callback::from(move |_| {
+      error.set(None);
+      preview.set(None);
+      load_schedule(text.clone());
+    })
+  };
+
+  let refresh = {
+    let status = status.clone();
+    Callback::from(move |_| load_status(status.clone()))
+  };
+
+  html!{
+    <div class="schedule">
+      <table class="schedule__status">
+        {
+          for status.iter().map(|entry| {
+            let (class, state) = if let Some(error) = &entry.error {
+              ("schedule__broken", error.clone())
+            } else if !entry.enabled {
+              ("schedule__disabled", String::from("disabled"))
+            } else {
+              ("", entry.next.map_or_else(|| String::from("no more runs"), |next| format!("next {}", format_date(next))))
+            };
+            html!{
+              <tr class={class}>
+                <td>{&entry.name}</td>
+                <td>{state}</td>
+                <td>{entry.lastRun.map(|lastRun| format!("last {}", format_date(lastRun))).unwrap_or_default()}</td>
+              </tr>
+            }
+          })
+        }
+      </table>
+      <textarea class="schedule__editor" rows="12" spellcheck="false" value={(*text).clone()} oninput={onInput} />
+      <div class="schedule__controls">
+        <button type="button" onclick={save}>{"SAVE SCHEDULE"}</button>
+        <button type="button" onclick={dryRun}>{"PREVIEW"}</button>
+        <button type="button" onclick={revert}>{"REVERT"}</button>
+        <button type="button" onclick={refresh}>{"REFRESH"}</button>
+      </div>
+      if let Some(error) = (*error).as_ref() {
+        <p class="schedule__error">{error}</p>
+      }
+      if let Some(runs) = (*preview).as_ref() {
+        <div class="schedule__preview">
+          if runs.is_empty() {
+            <p>{format!("Nothing runs in the next {} hours", PREVIEW_HOURS)}</p>
+          }
+          {
+            for runs.iter().map(|run| {
+              html!{
+                <div>
+                  <p class="schedule__run">{format!("{} {}", format_date(run.at), &run.name)}</p>
+                  { for run.steps.iter().map(|step| html!{ <p class="schedule__step">{step}</p> }) }
+                </div>
+              }
+            })
+          }
+        </div>
+      }
+    </div>
+  }
+}
@@ -0,0 +1,222 @@
+use std::rc::Rc;
+
+use common::scripts::{OutputKind, ScriptInfo, ScriptOutput};
+use wasm_bindgen::{prelude::*, JsCast};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{Element, KeyboardEvent};
+use yew::prelude::*;
+
+use crate::{external::{invoke, listen, log, try_invoke}, includes::{commands::{RunScriptArgs, ScriptArgs}, events::ScriptOutputEvent}};
+
+/// Output lines kept in the panel, the backend keeps its own
+const OUTPUT_LIMIT: usize = 200;
+
+struct OutputState {
+  lines: Vec<ScriptOutput>,
+}
+
+enum OutputAction {
+  APPEND(ScriptOutput),
+  REPLACE(Vec<ScriptOutput>),
+}
+
+impl Default for OutputState {
+  fn default() -> Self {
+      Self { lines: vec![] }
+  }
+}
+
+impl Reducible for OutputState {
+  type Action = OutputAction;
+
+  fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+      let lines = match action {
+        OutputAction::APPEND(line) => {
+          let skip = (self.lines.len() + 1).saturating_sub(OUTPUT_LIMIT);
+          let mut lines: Vec<ScriptOutput> = self.lines.iter().skip(skip).cloned().collect();
+          lines.push(line);
+          lines
+        }
+        OutputAction::REPLACE(lines) => {
+          let skip = lines.len().saturating_sub(OUTPUT_LIMIT);
+          lines.into_iter().skip(skip).collect()
+        }
+      };
+      Self { lines }.into()
+  }
+}
+
+fn format_time(timestamp: u64) -> String {
+  let date = js_sys::Date::new(&JsValue::from_f64(timestamp as f64));
+  format!("{:02}:{:02}:{:02}", date.get_hours(), date.get_minutes(), date.get_seconds())
+}
+
+/// The pressed key as written in the config, e.g. `Ctrl+Shift+F5`
+fn hotkey(e: &KeyboardEvent) -> String {
+  let mut keys = vec![];
+  if e.ctrl_key() { keys.push(String::from("Ctrl")); }
+  if e.alt_key() { keys.push(String::from("Alt")); }
+  if e.shift_key() { keys.push(String::from("Shift")); }
+  if e.meta_key() { keys.push(String::from("Meta")); }
+  keys.push(match e.key().as_str() {
+    " " => String::from("Space"),
+    key => String::from(key),
+  });
+  keys.join("+")
+}
+
+fn load_scripts(scripts: UseStateHandle<Vec<ScriptInfo>>, error: UseStateHandle<Option<String>>) {
+  spawn_local(async move {
+    let response = try_invoke("listScripts", JsValue::default()).await;
+    match response.map(|response| JsValue::into_serde::<Vec<ScriptInfo>>(&response)) {
+      Ok(Ok(next)) => scripts.set(next),
+      Ok(Err(e)) => error.set(Some(e.to_string())),
+      Err(e) => error.set(Some(e.as_string().unwrap_or_else(|| String::from("Listing the scripts failed")))),
+    }
+  });
+}
+
+fn run(name: String, hotkey: Option<String>, error: UseStateHandle<Option<String>>) {
+  spawn_local(async move {
+    let response = try_invoke(
+        "runScript",
+        JsValue::from_serde(&RunScriptArgs { name, hotkey }).unwrap(),
+    )
+    .await;
+    match response {
+      Ok(_) => error.set(None),
+      Err(e) => error.set(Some(e.as_string().unwrap_or_else(|| String::from("Starting the script failed")))),
+    }
+  });
+}
+
+/// Scripts from the scripts folder with their output, run by button or hotkey
+#[function_component(Scripts)]
+pub fn scripts() -> Html {
+  let scripts = use_state(Vec::<ScriptInfo>::new);
+  let error = use_state(|| Option::<String>::None);
+  let output = use_reducer(OutputState::default);
+
+  let handler_output = output.clone();
+  let handler_scripts = scripts.clone();
+  let handler_error = error.clone();
+  let output_handler_ref = use_ref(|| Closure::new(move |ev: JsValue| {
+      match JsValue::into_serde::<ScriptOutputEvent>(&ev) {
+        Ok(output_event) => {
+          if matches!(output_event.payload.kind, OutputKind::STARTED | OutputKind::FINISHED) {
+            load_scripts(handler_scripts.clone(), handler_error.clone());
+          }
+          handler_output.dispatch(OutputAction::APPEND(output_event.payload));
+        }
+        Err(e) => log(format!("Bad script output :: {}", e).as_str()),
+      }
+  }));
+
+  {
+    let scripts = scripts.clone();
+    let error = error.clone();
+    let output = output.clone();
+    use_effect_with_deps(move |_| {
+        spawn_local(async move {
+            listen("script::output", &output_handler_ref).await;
+            let response = invoke("getScriptOutput", JsValue::default()).await;
+            match JsValue::into_serde::<Vec<ScriptOutput>>(&response) {
+              Ok(lines) => output.dispatch(OutputAction::REPLACE(lines)),
+              Err(e) => log(format!("Script output failed :: {}", e).as_str()),
+            }
+        });
+        load_scripts(scripts, error);
+
+        || {}
+    }, 0);
+  }
+
+  {
+    let error = error.clone();
+    use_effect_with_deps(move |scripts: &Vec<ScriptInfo>| {
+        let scripts = scripts.clone();
+        let listener = Closure::<dyn Fn(KeyboardEvent)>::new(move |e: KeyboardEvent| {
+          // Keys typed into the rules editor or any other field aren't hotkeys
+          let typing = e.target()
+            .map(|target| target.unchecked_into::<Element>().tag_name())
+            .map_or(false, |tag| matches!(tag.as_str(), "INPUT" | "TEXTAREA" | "SELECT"));
+          if typing || e.repeat() {
+            return;
+          }
+          let pressed = hotkey(&e);
+          let script = scripts.iter().find(|script| {
+            script.hotkey.as_ref().map_or(false, |key| key.eq_ignore_ascii_case(&pressed))
+          });
+          if let Some(script) = script {
+            e.prevent_default();
+            run(script.name.clone(), Some(pressed), error.clone());
+          }
+        });
+        let window = web_sys::window().unwrap();
+        window.add_event_listener_with_callback("keydown", listener.as_ref().unchecked_ref()).unwrap();
+
+        move || {
+          window.remove_event_listener_with_callback("keydown", listener.as_ref().unchecked_ref()).unwrap();
+        }
+    }, (*scripts).clone());
+  }
+
+  let refresh = {
+    let scripts = scripts.clone();
+    let error = error.clone();
+    Callback::from(move |_| load_scripts(scripts.clone(), error.clone()))
+  };
+
+  html!{
+    <div class="scripts">
+      <div class="scripts__list">
+        {
+          for scripts.iter().map(|script| {
+            let name = script.name.clone();
+            let error = error.clone();
+            let onclick = if script.running {
+              Callback::from(move |_| {
+                let name = name.clone();
+                let error = error.clone();
+                spawn_local(async move {
+                  if let Err(e) = try_invoke("stopScript", JsValue::from_serde(&ScriptArgs { name }).unwrap()).await {
+                    error.set(e.as_string());
+                  }
+                });
+              })
+            } else {
+              Callback::from(move |_| run(name.clone(), None, error.clone()))
+            };
+            html!{
+              <button type="button" class={if script.running { "scripts__running" } else { "" }} onclick={onclick}>
+                {if script.running { format!("STOP {}", script.name) } else { script.name.clone() }}
+                if let Some(hotkey) = &script.hotkey {
+                  <span class="scripts__hotkey">{hotkey}</span>
+                }
+              </button>
+            }
+          })
+        }
+        <button type="button" onclick={refresh}>{"REFRESH"}</button>
+      </div>
+      if let Some(error) = (*error).as_ref() {
+        <p class="scripts__error">{error}</p>
+      }
+      <div class="scripts__output">
+        {
+          for output.lines.iter().rev().map(|line| {
+            let class = match line.kind {
+              OutputKind::ALERT => "scripts__alert",
+              OutputKind::ERROR => "scripts__error",
+              OutputKind::STARTED | OutputKind::FINISHED => "scripts__status",
+              OutputKind::PRINT => "",
+            };
+            html!{
+              <p class={class}>{format!("{} {} #{} :: {}", format_time(line.timestamp), &line.script, line.run, &line.message)}</p>
+            }
+          })
+        }
+      </div>
+    </div>
+  }
+}
@@ -1,11 +1,11 @@
 use std::{collections::HashMap, rc::Rc, cmp::Ordering};
 
-use common::{Fader, DB, DeskInfo, AudioWidth};
+use common::{Fader, DB, DeskInfo, AudioWidth, link::LinkStatus};
 use yew::prelude::*;
 use wasm_bindgen_futures::spawn_local;
 use wasm_bindgen::prelude::*;
 
-use crate::{external::{log, listen, invoke}, includes::events::FaderChangedEvent};
+use crate::{external::{log, listen, invoke}, includes::{commands::ConsoleArgs, events::{DeskChangedEvent, FaderChangedEvent, LinkChangedEvent}}};
 
 #[derive(Properties, PartialEq)]
 pub struct AgentProps {
@@ -13,135 +13,148 @@ pub struct AgentProps {
     pub children: Children,
 }
 
-struct FadersState {
+/// Everything known about one console
+#[derive(Clone)]
+struct ConsoleState {
   faders: HashMap<u16, Fader>,
+  deskInfo: DeskInfo,
+  link: LinkStatus,
 }
 
-enum FaderAction {
-  INSERT(u16, Fader),
-  INSERT_BULK(Vec<Fader>),
-}
-
-impl Default for FadersState {
+impl Default for ConsoleState {
   fn default() -> Self {
-      Self { faders: HashMap::default() }
+      Self { faders: HashMap::default(), deskInfo: DeskInfo::default(), link: LinkStatus::default() }
   }
 }
 
-impl Reducible for FadersState {
-  /// Reducer Action Type
-  type Action = FaderAction;
-
-  /// Reducer Function
-  fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
-      let next_faders = match action {
-        FaderAction::INSERT(id, fader) => {
-          let mut nextFaders: HashMap<u16, Fader> = HashMap::default();
-
-          for (id, fader) in self.faders.iter() {
-            nextFaders.insert(id.clone(), fader.clone());
-          }
-
-          nextFaders.insert(id, fader);
-          nextFaders
-        },
-        FaderAction::INSERT_BULK(faders) => {
-          let mut nextFaders: HashMap<u16, Fader> = HashMap::default();
-          
-          for fader in faders.iter() {
-            nextFaders.insert(fader.index, fader.clone());
-          }
-
-          nextFaders
-        }
-      };
-
-      Self { faders: next_faders }.into()
-  }
-}
-
-
-struct DeskInfoState {
-  deskInfo: DeskInfo,
-}
-
-enum DeskInfoAction {
-  INSERT(DeskInfo),
+#[derive(Default)]
+struct ConsolesState {
+  consoles: HashMap<String, ConsoleState>,
 }
 
-impl Default for DeskInfoState {
-  fn default() -> Self {
-      Self { deskInfo: DeskInfo::default() }
-  }
+enum ConsoleAction {
+  INSERT_FADER(String, Fader),
+  INSERT_DB(String, DB),
+  INSERT_DESK_INFO(String, DeskInfo),
+  INSERT_LINK(String, LinkStatus),
 }
 
-impl Reducible for DeskInfoState {
+impl Reducible for ConsolesState {
   /// Reducer Action Type
-  type Action = DeskInfoAction;
+  type Action = ConsoleAction;
 
   /// Reducer Function
   fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
-      let next_desk_info = match action {
-        DeskInfoAction::INSERT(deskInfo) => {
-          deskInfo
-        }
+      let mut nextConsoles = self.consoles.clone();
+
+      match action {
+        ConsoleAction::INSERT_FADER(console, fader) => {
+          nextConsoles.entry(console).or_default().faders.insert(fader.index, fader);
+        },
+        ConsoleAction::INSERT_DB(console, db) => {
+          let state = nextConsoles.entry(console).or_default();
+          state.faders = db.faders.into_iter().map(|fader| (fader.index, fader)).collect();
+          state.deskInfo = db.deskInfo;
+          state.link = db.link;
+        },
+        ConsoleAction::INSERT_DESK_INFO(console, deskInfo) => {
+          nextConsoles.entry(console).or_default().deskInfo = deskInfo;
+        },
+        ConsoleAction::INSERT_LINK(console, link) => {
+          nextConsoles.entry(console).or_default().link = link;
+        },
       };
 
-      Self { deskInfo: next_desk_info }.into()
+      Self { consoles: nextConsoles }.into()
   }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct StateContext {
+    /// Ids of the configured consoles, in config order
+    pub consoles: Vec<String>,
+    /// Console the panels show and control
+    pub console: String,
     pub faders: Vec<Fader>,
     pub deskInfo: DeskInfo,
+    pub link: LinkStatus,
+    pub selectConsole: Callback<String>,
 }
 
 #[function_component(StateManager)]
 pub fn stateManager(props: &AgentProps) -> Html {
-  let faders = use_reducer(FadersState::default);
-  let deskInfo = use_reducer(DeskInfoState::default);
+  let consoles = use_reducer(ConsolesState::default);
+  let consoleIds = use_state(Vec::<String>::new);
+  let selected = use_state(String::new);
 
-  let handler_faders = faders.clone();
+  let handler_consoles = consoles.clone();
   let fader_changed_handler_ref = use_ref(|| Closure::new(move |ev: JsValue| {
       let fader_event: FaderChangedEvent = JsValue::into_serde(&ev).unwrap();
-      log(format!("Fader event :: {} faderNum={} level={}", fader_event.event, fader_event.payload.index, fader_event.payload.level).to_string().as_str());
+      log(format!("Fader event :: {} console={} faderNum={} level={}", fader_event.event, fader_event.payload.console, fader_event.payload.data.index, fader_event.payload.data.level).to_string().as_str());
 
-      handler_faders.dispatch(FaderAction::INSERT(fader_event.payload.index, fader_event.payload));
+      handler_consoles.dispatch(ConsoleAction::INSERT_FADER(fader_event.payload.console, fader_event.payload.data));
+  }));
+
+  let handler_consoles = consoles.clone();
+  let desk_changed_handler_ref = use_ref(|| Closure::new(move |ev: JsValue| {
+      let desk_event: DeskChangedEvent = JsValue::into_serde(&ev).unwrap();
+      log(format!("Desk event :: {} console={} name={}", desk_event.event, desk_event.payload.console, desk_event.payload.data.name).as_str());
+
+      handler_consoles.dispatch(ConsoleAction::INSERT_DESK_INFO(desk_event.payload.console, desk_event.payload.data));
+  }));
+
+  let handler_consoles = consoles.clone();
+  let link_changed_handler_ref = use_ref(|| Closure::new(move |ev: JsValue| {
+      let link_event: LinkChangedEvent = JsValue::into_serde(&ev).unwrap();
+      log(format!("Link event :: {} console={} endpoint={} connected={}", link_event.event, link_event.payload.console, link_event.payload.data.endpoint, link_event.payload.data.connected).as_str());
+
+      handler_consoles.dispatch(ConsoleAction::INSERT_LINK(link_event.payload.console, link_event.payload.data));
   }));
 
   {
-    let deskInfo = deskInfo.clone();
-    let faders = faders.clone();
+    let consoles = consoles.clone();
+    let consoleIds = consoleIds.clone();
+    let selected = selected.clone();
     use_effect_with_deps(move |_| {
-      let deskInfo = deskInfo.clone();
-      let faders = faders.clone();
         spawn_local(async move {
             listen("fader::changed", &fader_changed_handler_ref).await;
+            listen("desk::changed", &desk_changed_handler_ref).await;
+            listen("link::changed", &link_changed_handler_ref).await;
         });
 
         spawn_local(async move {
-          log("Send get DB");
-          let new_msg = invoke(
-              "getDatabase",
-              JsValue::default(),
-          )
-          .await;
-          let db: DB = JsValue::into_serde(&new_msg).unwrap();
-          // log(&db.faders.len().to_string());
-
-          log("Set new desk info");
-          deskInfo.dispatch(DeskInfoAction::INSERT(db.deskInfo));
-
-          log("Set new faders");
-          faders.dispatch(FaderAction::INSERT_BULK(db.faders));
+          let ids = invoke("listConsoles", JsValue::default()).await;
+          let ids: Vec<String> = JsValue::into_serde(&ids).unwrap();
+          if let Some(first) = ids.first() {
+            selected.set(first.clone());
+          }
+          consoleIds.set(ids.clone());
+
+          for console in ids {
+            log(format!("Send get DB console={}", console).as_str());
+            let new_msg = invoke(
+                "getDatabase",
+                JsValue::from_serde(&ConsoleArgs { console: console.clone() }).unwrap(),
+            )
+            .await;
+            let db: DB = JsValue::into_serde(&new_msg).unwrap();
+
+            log("Set new console state");
+            consoles.dispatch(ConsoleAction::INSERT_DB(console, db));
+          }
         });
 
         || {}
     }, 0);
   }
 
-  let mut faders: Vec<Fader> = faders.faders
+  let selectConsole = {
+    let selected = selected.clone();
+    Callback::from(move |console: String| selected.set(console))
+  };
+
+  let current = consoles.consoles.get(&*selected).cloned().unwrap_or_default();
+  let mut faders: Vec<Fader> = current.faders
     .iter()
     .filter(|(_, fader)| !matches!(fader.format, AudioWidth::NP))
     .map(|(_, fader)| fader.clone())
@@ -156,7 +169,14 @@ pub fn stateManager(props: &AgentProps) -> Html {
           Ordering::Greater
       }
   });
-  let state = StateContext { deskInfo: deskInfo.deskInfo.clone(), faders };
+  let state = StateContext {
+    consoles: (*consoleIds).clone(),
+    console: (*selected).clone(),
+    faders,
+    deskInfo: current.deskInfo,
+    link: current.link,
+    selectConsole,
+  };
 
   html! {
     <ContextProvider<StateContext> context={state.clone()}>
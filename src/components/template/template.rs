@@ -0,0 +1,117 @@
+use common::template::{CheckResult, TemplateReport};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+
+use crate::{components::state::stateManager::StateContext, external::{open, try_invoke}, includes::commands::{DialogFilter, OpenDialogOptions, VerifyTemplateArgs}};
+
+fn verify(console: String, path: String, report: UseStateHandle<Option<TemplateReport>>, error: UseStateHandle<Option<String>>) {
+  spawn_local(async move {
+    let response = try_invoke(
+        "verifyTemplate",
+        JsValue::from_serde(&VerifyTemplateArgs { console, path }).unwrap(),
+    )
+    .await;
+    match response.map(|response| JsValue::into_serde::<TemplateReport>(&response)) {
+      Ok(Ok(next)) => {
+        report.set(Some(next));
+        error.set(None);
+      }
+      Ok(Err(e)) => error.set(Some(e.to_string())),
+      Err(e) => error.set(Some(e.as_string().unwrap_or_else(|| String::from("Verification failed")))),
+    }
+  });
+}
+
+/// Pre-show check of the selected console against a show template file
+#[function_component(ShowCheck)]
+pub fn show_check() -> Html {
+  let state = use_context::<StateContext>().expect("no state context found");
+  let path = use_state(|| Option::<String>::None);
+  let report = use_state(|| Option::<TemplateReport>::None);
+  let error = use_state(|| Option::<String>::None);
+
+  let load = {
+    let console = state.console.clone();
+    let path = path.clone();
+    let report = report.clone();
+    let error = error.clone();
+    Callback::from(move |_| {
+      let console = console.clone();
+      let path = path.clone();
+      let report = report.clone();
+      let error = error.clone();
+      spawn_local(async move {
+        let options = OpenDialogOptions {
+          multiple: false,
+          filters: vec![DialogFilter { name: String::from("Show template"), extensions: vec![String::from("json")] }],
+        };
+        let selected = open(JsValue::from_serde(&options).unwrap()).await;
+        if let Some(selected) = selected.as_string() {
+          path.set(Some(selected.clone()));
+          verify(console, selected, report, error);
+        }
+      });
+    })
+  };
+
+  let recheck = {
+    let console = state.console.clone();
+    let path = path.clone();
+    let report = report.clone();
+    let error = error.clone();
+    Callback::from(move |_| {
+      if let Some(path) = (*path).clone() {
+        verify(console.clone(), path, report.clone(), error.clone());
+      }
+    })
+  };
+
+  html!{
+    <div class="template">
+      <div class="template__controls">
+        <button type="button" onclick={load}>{"LOAD TEMPLATE"}</button>
+        if path.is_some() {
+          <button type="button" onclick={recheck}>{"CHECK AGAIN"}</button>
+        }
+      </div>
+      if let Some(error) = (*error).as_ref() {
+        <p class="template__error">{error}</p>
+      }
+      if let Some(report) = (*report).as_ref() {
+        <p class={if report.passed() { "template__passed" } else { "template__failed" }}>
+          {format!(
+            "{} {} of {} checks ok, {} mismatched, {} missing, {} not reported",
+            &report.name,
+            report.count(CheckResult::PASS),
+            report.checks.len(),
+            report.count(CheckResult::MISMATCH),
+            report.count(CheckResult::MISSING),
+            report.count(CheckResult::UNKNOWN),
+          )}
+        </p>
+        <table class="template__checks">
+          {
+            for report.checks.iter().map(|check| {
+              let class = match check.result {
+                CheckResult::PASS => "",
+                CheckResult::MISMATCH | CheckResult::MISSING => "template__mismatch",
+                CheckResult::UNKNOWN => "template__unknown",
+              };
+              html!{
+                <tr class={class}>
+                  <td>{format!("F{}", check.index + 1)}</td>
+                  <td>{&check.label}</td>
+                  <td>{&check.field}</td>
+                  <td>{&check.expected}</td>
+                  <td>{&check.actual}</td>
+                  <td>{format!("{:?}", check.result)}</td>
+                </tr>
+              }
+            })
+          }
+        </table>
+      }
+    </div>
+  }
+}
@@ -0,0 +1,207 @@
+use common::timecode::{TimecodeCue, TimecodeSource, TimecodeStatus, Transport};
+use wasm_bindgen::{prelude::*, JsCast};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{HtmlInputElement, HtmlTextAreaElement};
+use yew::prelude::*;
+
+use crate::{external::{invoke, listen, log, try_invoke}, includes::{commands::{SaveCuesArgs, TimecodeTransportArgs}, events::TimecodeStatusEvent}};
+
+/// Cues as indented JSON for editing
+fn to_text(cues: &JsValue) -> String {
+  js_sys::JSON::stringify_with_replacer_and_space(cues, &JsValue::NULL, &JsValue::from(2))
+    .ok()
+    .and_then(|text| text.as_string())
+    .unwrap_or_default()
+}
+
+fn parse(text: &str) -> Result<Vec<TimecodeCue>, String> {
+  let value = js_sys::JSON::parse(text)
+    .map_err(|e| js_sys::Error::from(e).message().as_string().unwrap_or_else(|| String::from("Not JSON")))?;
+  JsValue::into_serde::<Vec<TimecodeCue>>(&value).map_err(|e| e.to_string())
+}
+
+fn load_cues(text: UseStateHandle<String>) {
+  spawn_local(async move {
+    let response = invoke("getCues", JsValue::default()).await;
+    text.set(to_text(&response));
+  });
+}
+
+fn load_status(status: UseStateHandle<Option<TimecodeStatus>>) {
+  spawn_local(async move {
+    let response = invoke("getTimecodeStatus", JsValue::default()).await;
+    match JsValue::into_serde::<TimecodeStatus>(&response) {
+      Ok(next) => status.set(Some(next)),
+      Err(e) => log(format!("Timecode status failed :: {}", e).as_str()),
+    }
+  });
+}
+
+fn send_transport(transport: Transport, error: UseStateHandle<Option<String>>) {
+  spawn_local(async move {
+    let response = try_invoke(
+        "timecodeTransport",
+        JsValue::from_serde(&TimecodeTransportArgs { transport }).unwrap(),
+    )
+    .await;
+    match response {
+      Ok(_) => error.set(None),
+      Err(e) => error.set(Some(e.as_string().unwrap_or_else(|| String::from("Moving the clock failed")))),
+    }
+  });
+}
+
+/// Where the timecode is, the cues it fires and the transport of the local clock
+#[function_component(Timecode)]
+pub fn timecode() -> Html {
+  let text = use_state(String::new);
+  let status = use_state(|| Option::<TimecodeStatus>::None);
+  let locateTo = use_state(|| String::from("00:00:00:00"));
+  let error = use_state(|| Option::<String>::None);
+
+  let handler_status = status.clone();
+  let status_handler_ref = use_ref(|| Closure::new(move |ev: JsValue| {
+      match JsValue::into_serde::<TimecodeStatusEvent>(&ev) {
+        Ok(status_event) => handler_status.set(Some(status_event.payload)),
+        Err(e) => log(format!("Bad timecode status :: {}", e).as_str()),
+      }
+  }));
+
+  {
+    let text = text.clone();
+    let status = status.clone();
+    use_effect_with_deps(move |_| {
+        spawn_local(async move {
+            listen("timecode::status", &status_handler_ref).await;
+        });
+        load_cues(text);
+        load_status(status);
+
+        || {}
+    }, 0);
+  }
+
+  let onInput = {
+    let text = text.clone();
+    Callback::from(move |e: InputEvent| {
+      let input: HtmlTextAreaElement = e.target().unwrap().unchecked_into();
+      text.set(input.value());
+    })
+  };
+
+  let onLocateInput = {
+    let locateTo = locateTo.clone();
+    Callback::from(move |e: InputEvent| {
+      let input: HtmlInputElement = e.target().unwrap().unchecked_into();
+      locateTo.set(input.value());
+    })
+  };
+
+  let save = {
+    let text = text.clone();
+    let status = status.clone();
+    let error = error.clone();
+    Callback::from(move |_| {
+      let cues = match parse(&text) {
+        Ok(cues) => cues,
+        Err(e) => return error.set(Some(e)),
+      };
+      let status = status.clone();
+      let error = error.clone();
+      spawn_local(async move {
+        let response = try_invoke(
+            "saveCues",
+            JsValue::from_serde(&SaveCuesArgs { cues }).unwrap(),
+        )
+        .await;
+        match response.map(|response| JsValue::into_serde::<TimecodeStatus>(&response)) {
+          Ok(Ok(next)) => {
+            status.set(Some(next));
+            error.set(None);
+          }
+          Ok(Err(e)) => error.set(Some(e.to_string())),
+          Err(e) => error.set(Some(e.as_string().unwrap_or_else(|| String::from("Saving the cues failed")))),
+        }
+      });
+    })
+  };
+
+  let revert = {
+    let text = text.clone();
+    let error = error.clone();
+    Callback::from(move |_| {
+      error.set(None);
+      load_cues(text.clone());
+    })
+  };
+
+  let play = {
+    let error = error.clone();
+    Callback::from(move |_| send_transport(Transport::PLAY, error.clone()))
+  };
+
+  let stop = {
+    let error = error.clone();
+    Callback::from(move |_| send_transport(Transport::STOP, error.clone()))
+  };
+
+  let locate = {
+    let locateTo = locateTo.clone();
+    let error = error.clone();
+    Callback::from(move |_| send_transport(Transport::LOCATE { position: (*locateTo).clone() }, error.clone()))
+  };
+
+  html!{
+    <div class="timecode">
+      if let Some(status) = (*status).as_ref() {
+        <p>
+          <span class={format!("timecode__position timecode__{:?}", status.state)}>
+            {status.position.clone().unwrap_or_else(|| String::from("--:--:--:--"))}
+          </span>
+          {format!(" {:?} {:?} {:?}", status.state, status.source, status.rate)}
+        </p>
+        if let Some(sourceError) = &status.error {
+          <p class="timecode__error">{sourceError}</p>
+        }
+        if status.source == TimecodeSource::LOCAL && status.error.is_none() {
+          <div class="timecode__controls">
+            <button type="button" onclick={play}>{"PLAY"}</button>
+            <button type="button" onclick={stop}>{"STOP"}</button>
+            <input type="text" value={(*locateTo).clone()} oninput={onLocateInput} />
+            <button type="button" onclick={locate}>{"LOCATE"}</button>
+          </div>
+        }
+        <table class="timecode__cues">
+          {
+            for status.cues.iter().map(|cue| {
+              let (class, state) = if let Some(cueError) = &cue.error {
+                ("timecode__broken", cueError.clone())
+              } else if !cue.enabled {
+                ("timecode__disabled", String::from("disabled"))
+              } else if cue.passed {
+                ("timecode__passed", String::from("passed"))
+              } else {
+                ("", String::new())
+              };
+              html!{
+                <tr class={class}>
+                  <td>{&cue.at}</td>
+                  <td>{&cue.name}</td>
+                  <td>{state}</td>
+                </tr>
+              }
+            })
+          }
+        </table>
+      }
+      <textarea class="timecode__editor" rows="12" spellcheck="false" value={(*text).clone()} oninput={onInput} />
+      <div class="timecode__controls">
+        <button type="button" onclick={save}>{"SAVE CUES"}</button>
+        <button type="button" onclick={revert}>{"REVERT"}</button>
+      </div>
+      if let Some(error) = (*error).as_ref() {
+        <p class="timecode__error">{error}</p>
+      }
+    </div>
+  }
+}
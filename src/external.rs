@@ -5,9 +5,19 @@ extern "C" {
     #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "tauri"])]
     pub async fn invoke(cmd: &str, args: JsValue) -> JsValue;
 
+    /// `invoke` that hands back the command's error instead of throwing
+    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "tauri"], js_name = invoke, catch)]
+    pub async fn try_invoke(cmd: &str, args: JsValue) -> Result<JsValue, JsValue>;
+
     #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "event"])]
     pub async fn listen(cmd: &str, callback: &Closure<dyn FnMut(JsValue)>) -> JsValue;
 
+    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "dialog"])]
+    pub async fn save(options: JsValue) -> JsValue;
+
+    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "dialog"])]
+    pub async fn open(options: JsValue) -> JsValue;
+
     #[wasm_bindgen(js_namespace = console)]
     pub fn log(s: &str);
 }
@@ -1,19 +1,132 @@
+use common::{audit::AuditQuery, midi::{MidiMapping, MidiTarget}, rules::Rule, schedule::ScheduleEntry, timecode::{TimecodeCue, Transport}};
 use serde::{Deserialize, Serialize};
 
+#[derive(Serialize, Deserialize)]
+pub struct ConsoleArgs {
+    pub console: String,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct SetFaderLevelArgs {
+    pub console: String,
     pub index: u16,
     pub level: u16,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct SetFaderCutArgs {
+    pub console: String,
     pub index: u16,
     pub isCut: bool,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct SetFaderPflArgs {
+    pub console: String,
     pub index: u16,
     pub isPfl: bool,
 }
+
+#[derive(Serialize, Deserialize)]
+pub struct QueryAuditLogArgs {
+    pub query: AuditQuery,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportAuditLogArgs {
+    pub query: AuditQuery,
+    pub path: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveDialogOptions {
+    pub defaultPath: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DialogFilter {
+    pub name: String,
+    pub extensions: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OpenDialogOptions {
+    pub multiple: bool,
+    pub filters: Vec<DialogFilter>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OnAirWindowArgs {
+    pub from: u64,
+    pub to: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportOnAirArgs {
+    pub from: u64,
+    pub to: u64,
+    pub path: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetInspectorFramesArgs {
+    pub command: Option<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportCatalogueArgs {
+    pub path: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct VerifyTemplateArgs {
+    pub console: String,
+    pub path: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveRulesArgs {
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ScriptArgs {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RunScriptArgs {
+    pub name: String,
+    pub hotkey: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveScheduleArgs {
+    pub entries: Vec<ScheduleEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PreviewScheduleArgs {
+    pub entries: Vec<ScheduleEntry>,
+    pub hours: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveCuesArgs {
+    pub cues: Vec<TimecodeCue>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TimecodeTransportArgs {
+    pub transport: Transport,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveMidiMappingsArgs {
+    pub mappings: Vec<MidiMapping>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LearnMidiArgs {
+    pub target: MidiTarget,
+}
@@ -1,8 +1,44 @@
-use common::Fader;
+use common::{DeskInfo, Fader, console::OnConsole, inspector::CapturedFrame, link::LinkStatus, rules::RuleAlert, scripts::ScriptOutput, timecode::TimecodeStatus};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize)]
 pub struct FaderChangedEvent {
     pub event: String,
-    pub payload: Fader,
+    pub payload: OnConsole<Fader>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct InspectorFrameEvent {
+    pub event: String,
+    pub payload: CapturedFrame,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DeskChangedEvent {
+    pub event: String,
+    pub payload: OnConsole<DeskInfo>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LinkChangedEvent {
+    pub event: String,
+    pub payload: OnConsole<LinkStatus>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RuleAlertEvent {
+    pub event: String,
+    pub payload: RuleAlert,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ScriptOutputEvent {
+    pub event: String,
+    pub payload: ScriptOutput,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TimecodeStatusEvent {
+    pub event: String,
+    pub payload: TimecodeStatus,
 }